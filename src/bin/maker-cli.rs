@@ -2,18 +2,12 @@ use clap::Parser;
 use coinswap::{
     maker::{
         error::MakerError,
-        rpc::{read_rpc_message, RpcMsgReq},
+        rpc::{read_rpc_message, RpcMsgReq, RpcMsgResp},
     },
     utill::{send_message, setup_logger},
 };
-use serde::{Deserialize, Serialize};
 use tokio::{io::BufReader, net::TcpStream};
 
-#[derive(Serialize, Deserialize, Debug)]
-enum Message {
-    Hello,
-}
-
 /// maker-cli is a command line app to send RPC messages to maker server.
 #[derive(Parser, Debug)]
 #[clap(author, version, about, long_about = None)]
@@ -43,6 +37,10 @@ enum Commands {
     ContractBalance,
     /// Returns the total fidelity balance
     FidelityBalance,
+    /// Returns the balances, active swap count, fidelity bond and uptime of the maker
+    Status,
+    /// Returns the swaps in progress
+    ListSwaps,
 }
 
 #[tokio::main]
@@ -78,6 +76,12 @@ async fn main() -> Result<(), MakerError> {
         Commands::SwapUtxo => {
             send_rpc_req(&RpcMsgReq::SwapUtxo).await?;
         }
+        Commands::Status => {
+            send_rpc_req(&RpcMsgReq::Status).await?;
+        }
+        Commands::ListSwaps => {
+            send_rpc_req(&RpcMsgReq::ListSwaps).await?;
+        }
     }
 
     Ok(())
@@ -94,7 +98,7 @@ async fn send_rpc_req(req: &RpcMsgReq) -> Result<(), MakerError> {
 
     let mut read_half = BufReader::new(read_half);
 
-    if let Some(rpc_resp) = read_rpc_message(&mut read_half).await? {
+    if let Some(rpc_resp) = read_rpc_message::<RpcMsgResp>(&mut read_half).await? {
         println!("{:?}", rpc_resp);
     } else {
         log::error!("No RPC response received");
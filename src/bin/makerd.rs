@@ -1,13 +1,14 @@
 use bitcoind::bitcoincore_rpc::Auth;
 use clap::Parser;
 use coinswap::{
-    maker::{start_maker_server, Maker, MakerBehavior},
+    maker::{config::MakerConfig, start_maker_server, Maker, MakerBehavior},
     utill::{
-        parse_proxy_auth, read_bitcoin_network_string, read_connection_network_string, setup_logger,
+        get_maker_dir, parse_proxy_auth, parse_signer_paths, read_bitcoin_network_string,
+        read_connection_network_string, setup_logger,
     },
-    wallet::RPCConfig,
+    wallet::{serve_sign_requests, LocalSigner, RPCConfig, RemoteSigner, StreamSigner},
 };
-use std::{path::PathBuf, sync::Arc};
+use std::{path::PathBuf, process::ExitCode, sync::Arc};
 
 /// The Maker Server.
 ///
@@ -44,22 +45,63 @@ struct Cli {
         name = "NETWORK",
         long,
         short = 'n',
-        default_value = "regtest", possible_values = &["regtest", "signet", "mainnet"]
+        default_value = "regtest", possible_values = &["regtest", "testnet", "testnet4", "signet", "mainnet"]
     )]
     pub rpc_network: String,
     /// Sets the maker wallet's name. If the wallet file already exists at data-directory, it will load that wallet.
     #[clap(name = "WALLET", long, short = 'w', default_value = "maker")]
     pub wallet_name: String,
+    /// Fail on invalid config values, instead of falling back to their defaults.
+    #[clap(long)]
+    pub strict_config: bool,
+    /// Run as the offline signer of the wallet: answer the sign requests read on stdin on stdout,
+    /// with the keys of the wallet file at data-directory. Creates the wallet file if it doesn't exist.
+    #[clap(long, conflicts_with = "remote-signer")]
+    pub offline: bool,
+    /// Hold no keys, and ask the signer for all the private key operations. The signer requests are
+    /// written to REQUESTS and its responses read from RESPONSES, e.g two named pipes to a `--offline` makerd.
+    #[clap(long, value_name = "REQUESTS:RESPONSES", value_parser = parse_signer_paths)]
+    pub remote_signer: Option<(PathBuf, PathBuf)>,
 }
 
-fn main() -> std::io::Result<()> {
-    setup_logger();
+fn main() -> ExitCode {
+    match run(Cli::parse()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
 
-    let args = Cli::parse();
+fn run(args: Cli) -> Result<(), String> {
+    let rpc_network = read_bitcoin_network_string(&args.rpc_network).map_err(|e| e.to_string())?;
 
-    let rpc_network = read_bitcoin_network_string(&args.rpc_network).unwrap();
+    if args.offline {
+        // The responses are written to stdout, no logger here.
+        let data_dir = args.data_directory.unwrap_or(get_maker_dir());
+        let wallet_path = data_dir.join("wallets").join(&args.wallet_name);
+        // The config holds the passphrase of an encrypted wallet file.
+        let config_path = data_dir.join("config.toml");
+        let config = if args.strict_config {
+            MakerConfig::new_strict(Some(&config_path))
+        } else {
+            MakerConfig::new(Some(&config_path))
+        }
+        .map_err(|e| format!("Failed to load the config {:?}: {:?}", config_path, e))?;
+        let signer = LocalSigner::from_wallet_file(
+            &wallet_path,
+            rpc_network,
+            config.wallet_encryption_passphrase.as_deref(),
+        )
+        .map_err(|e| format!("Failed to load the wallet file {:?}: {:?}", wallet_path, e))?;
+        return serve_sign_requests(&signer, std::io::stdin().lock(), std::io::stdout())
+            .map_err(|e| format!("Failed to serve the sign requests: {:?}", e));
+    }
 
-    let conn_type = read_connection_network_string(&args.network).unwrap();
+    setup_logger();
+
+    let conn_type = read_connection_network_string(&args.network)?;
 
     let rpc_config = RPCConfig {
         url: args.rpc,
@@ -68,21 +110,27 @@ fn main() -> std::io::Result<()> {
         wallet_name: args.wallet_name.clone(),
     };
 
-    let maker = Arc::new(
-        Maker::init(
-            args.data_directory,
-            Some(args.wallet_name),
-            Some(rpc_config),
-            None,
-            None,
-            None,
-            Some(conn_type),
-            MakerBehavior::Normal,
-        )
-        .unwrap(),
-    );
+    let signer = match args.remote_signer {
+        Some((requests, responses)) => Some(Box::new(
+            StreamSigner::open(&requests, &responses)
+                .map_err(|e| format!("Failed to open the remote signer: {:?}", e))?,
+        ) as Box<dyn RemoteSigner + Send + Sync>),
+        None => None,
+    };
 
-    start_maker_server(maker).unwrap();
+    let maker = Maker::init_with_signer(
+        args.data_directory,
+        Some(args.wallet_name),
+        Some(rpc_config),
+        None,
+        None,
+        None,
+        Some(conn_type),
+        MakerBehavior::Normal,
+        args.strict_config,
+        signer,
+    )
+    .map_err(|e| format!("Failed to initialize the maker: {:?}", e))?;
 
-    Ok(())
+    start_maker_server(Arc::new(maker)).map_err(|e| format!("Maker server failed: {:?}", e))
 }
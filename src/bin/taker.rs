@@ -1,3 +1,140 @@
-fn main() {
-    unimplemented!("Implement the taker app")
+use bitcoind::bitcoincore_rpc::Auth;
+use clap::Parser;
+use coinswap::{
+    taker::{Taker, TakerBehavior, TakerConfig},
+    utill::{
+        get_taker_dir, parse_proxy_auth, parse_signer_paths, read_bitcoin_network_string,
+        read_connection_network_string, setup_logger,
+    },
+    wallet::{serve_sign_requests, LocalSigner, RPCConfig, RemoteSigner, StreamSigner},
+};
+use std::{path::PathBuf, process::ExitCode};
+
+/// The Taker app.
+///
+/// This app loads the Taker, its config and wallet, and syncs the wallet. Swaps aren't driven from
+/// here yet.
+#[derive(Parser)]
+#[clap(version = option_env ! ("CARGO_PKG_VERSION").unwrap_or("unknown"),
+author = option_env ! ("CARGO_PKG_AUTHORS").unwrap_or(""))]
+struct Cli {
+    /// Optional Connection Network Type
+    #[clap(long, default_value = "clearnet", possible_values = &["tor", "clearnet"])]
+    network: String,
+    /// Optional data directory. Default value : "~/.coinswap/taker"
+    #[clap(long, short = 'd')]
+    data_directory: Option<PathBuf>,
+    /// Sets the full node address for rpc connection.
+    #[clap(
+        name = "ADDRESS:PORT",
+        long,
+        short = 'r',
+        default_value = "127.0.0.1:18443"
+    )]
+    pub rpc: String,
+    /// Sets the rpc basic authentication.
+    #[clap(
+        name = "USER:PASSWD",
+        short = 'a',
+        long,
+        value_parser = parse_proxy_auth,
+        default_value = "user:password",
+    )]
+    pub auth: (String, String),
+    /// Sets the full node network, this should match with the network of the running node.
+    #[clap(
+        name = "NETWORK",
+        long,
+        short = 'n',
+        default_value = "regtest", possible_values = &["regtest", "testnet", "testnet4", "signet", "mainnet"]
+    )]
+    pub rpc_network: String,
+    /// Sets the taker wallet's name. If the wallet file already exists at data-directory, it will load that wallet.
+    #[clap(name = "WALLET", long, short = 'w', default_value = "taker")]
+    pub wallet_name: String,
+    /// Fail on invalid config values, instead of falling back to their defaults.
+    #[clap(long)]
+    pub strict_config: bool,
+    /// Run as the offline signer of the wallet: answer the sign requests read on stdin on stdout,
+    /// with the keys of the wallet file at data-directory. Creates the wallet file if it doesn't exist.
+    #[clap(long, conflicts_with = "remote-signer")]
+    pub offline: bool,
+    /// Hold no keys, and ask the signer for all the private key operations. The signer requests are
+    /// written to REQUESTS and its responses read from RESPONSES, e.g two named pipes to a `--offline` taker.
+    #[clap(long, value_name = "REQUESTS:RESPONSES", value_parser = parse_signer_paths)]
+    pub remote_signer: Option<(PathBuf, PathBuf)>,
+}
+
+fn main() -> ExitCode {
+    match run(Cli::parse()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(args: Cli) -> Result<(), String> {
+    let rpc_network = read_bitcoin_network_string(&args.rpc_network).map_err(|e| e.to_string())?;
+
+    if args.offline {
+        // The responses are written to stdout, no logger here.
+        let data_dir = args.data_directory.unwrap_or(get_taker_dir());
+        let wallet_path = data_dir.join("wallets").join(&args.wallet_name);
+        // The config holds the passphrase of an encrypted wallet file.
+        let config_path = data_dir.join("config.toml");
+        let config = if args.strict_config {
+            TakerConfig::new_strict(Some(&config_path))
+        } else {
+            TakerConfig::new(Some(&config_path))
+        }
+        .map_err(|e| format!("Failed to load the config {:?}: {:?}", config_path, e))?;
+        let signer = LocalSigner::from_wallet_file(
+            &wallet_path,
+            rpc_network,
+            config.wallet_encryption_passphrase.as_deref(),
+        )
+        .map_err(|e| format!("Failed to load the wallet file {:?}: {:?}", wallet_path, e))?;
+        return serve_sign_requests(&signer, std::io::stdin().lock(), std::io::stdout())
+            .map_err(|e| format!("Failed to serve the sign requests: {:?}", e));
+    }
+
+    setup_logger();
+
+    let conn_type = read_connection_network_string(&args.network)?;
+
+    let rpc_config = RPCConfig {
+        url: args.rpc,
+        auth: Auth::UserPass(args.auth.0, args.auth.1),
+        network: rpc_network,
+        wallet_name: args.wallet_name.clone(),
+    };
+
+    let signer = match args.remote_signer {
+        Some((requests, responses)) => Some(Box::new(
+            StreamSigner::open(&requests, &responses)
+                .map_err(|e| format!("Failed to open the remote signer: {:?}", e))?,
+        ) as Box<dyn RemoteSigner + Send + Sync>),
+        None => None,
+    };
+
+    let taker = Taker::init_with_signer(
+        args.data_directory,
+        Some(args.wallet_name),
+        Some(rpc_config),
+        TakerBehavior::Normal,
+        Some(conn_type),
+        args.strict_config,
+        signer,
+    )
+    .map_err(|e| format!("Failed to initialize the taker: {:?}", e))?;
+
+    let balance = taker
+        .get_wallet()
+        .balance()
+        .map_err(|e| format!("Failed to read the wallet balance: {:?}", e))?;
+    log::info!("Taker wallet balance: {}", balance);
+
+    Ok(())
 }
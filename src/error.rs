@@ -1,8 +1,10 @@
 //! High-level network and protocol errors.
 
-use bitcoin::Amount;
+use std::path::PathBuf;
 
-use crate::protocol::error::ContractError;
+use bitcoin::{Amount, OutPoint};
+
+use crate::protocol::{error::ContractError, Hash160};
 
 /// Includes all network-related errors.
 #[derive(Debug)]
@@ -25,15 +27,161 @@ impl From<serde_cbor::Error> for NetError {
     }
 }
 
+/// Errors reported by strict config loading, instead of silently using the defaults.
+#[derive(Debug)]
+pub enum ConfigError {
+    /// The config file doesn't exist.
+    MissingFile(PathBuf),
+    /// The config file doesn't have the expected section.
+    MissingSection(String),
+    /// A field set to a value that doesn't parse.
+    InvalidField {
+        field: String,
+        value: String,
+        expected_type: String,
+    },
+    /// The contract spend fee rate isn't above the funding fee rate.
+    ContractFeeRateTooLow {
+        contract_fee_rate: u64,
+        funding_fee_rate: u64,
+    },
+    IO(std::io::Error),
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::MissingFile(path) => write!(f, "Config file not found: {}", path.display()),
+            Self::MissingSection(section) => write!(f, "Missing config section [{}]", section),
+            Self::InvalidField {
+                field,
+                value,
+                expected_type,
+            } => write!(
+                f,
+                "Invalid config value {:?} for {}, expected {}",
+                value, field, expected_type
+            ),
+            Self::ContractFeeRateTooLow {
+                contract_fee_rate,
+                funding_fee_rate,
+            } => write!(
+                f,
+                "Contract fee rate {} sats/vbyte must be above the funding fee rate {} sats/vbyte",
+                contract_fee_rate, funding_fee_rate
+            ),
+            Self::IO(e) => write!(f, "Failed to read config: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl From<std::io::Error> for ConfigError {
+    fn from(value: std::io::Error) -> Self {
+        Self::IO(value)
+    }
+}
+
+impl From<ConfigError> for std::io::Error {
+    fn from(value: ConfigError) -> Self {
+        match value {
+            ConfigError::IO(e) => e,
+            ConfigError::MissingFile(_) => Self::new(std::io::ErrorKind::NotFound, value),
+            _ => Self::new(std::io::ErrorKind::InvalidData, value),
+        }
+    }
+}
+
+/// A descriptor that can't be parsed for its HD path.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DescriptorParseError {
+    /// Not a `function(...)` descriptor expression.
+    NotAnExpression(String),
+    /// A key origin with unbalanced brackets.
+    UnclosedKeyOrigin(String),
+    /// A key origin path component that isn't a number.
+    InvalidPathComponent(String),
+}
+
+impl std::fmt::Display for DescriptorParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::NotAnExpression(descriptor) => {
+                write!(f, "Not a descriptor expression: {}", descriptor)
+            }
+            Self::UnclosedKeyOrigin(descriptor) => {
+                write!(f, "Unclosed key origin in descriptor: {}", descriptor)
+            }
+            Self::InvalidPathComponent(descriptor) => {
+                write!(f, "Invalid key origin path in descriptor: {}", descriptor)
+            }
+        }
+    }
+}
+
+impl std::error::Error for DescriptorParseError {}
+
+/// A Bitcoin network name the CLI apps don't know, see [read_bitcoin_network_string](crate::utill::read_bitcoin_network_string).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownNetworkError(pub String);
+
+impl std::fmt::Display for UnknownNetworkError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "Unknown Bitcoin network {:?}, expected one of regtest, testnet, testnet4, signet, mainnet",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for UnknownNetworkError {}
+
 /// Includes all Protocol-level errors.
 #[derive(Debug)]
 pub enum ProtocolError {
-    WrongMessage { expected: String, received: String },
-    WrongNumOfSigs { expected: usize, received: usize },
-    WrongNumOfContractTxs { expected: usize, received: usize },
-    WrongNumOfPrivkeys { expected: usize, received: usize },
-    IncorrectFundingAmount { expected: Amount, found: Amount },
+    WrongMessage {
+        expected: String,
+        received: String,
+    },
+    WrongNumOfSigs {
+        expected: usize,
+        received: usize,
+    },
+    WrongNumOfContractTxs {
+        expected: usize,
+        received: usize,
+    },
+    WrongNumOfPrivkeys {
+        expected: usize,
+        received: usize,
+    },
+    IncorrectFundingAmount {
+        expected: Amount,
+        found: Amount,
+    },
     Contract(ContractError),
+    /// A contract transaction with unexpected version, locktime or sequence values.
+    InvalidContractFields(String),
+    /// A contract transaction not spending the expected funding outpoint.
+    WrongContractPrevout {
+        expected: OutPoint,
+        found: OutPoint,
+    },
+    /// The counterparty didn't complete the protocol step in time.
+    Timeout {
+        step: String,
+    },
+    /// The Taker's and the Maker's supported protocol version ranges don't overlap.
+    NoCompatibleVersion {
+        taker_range: (u32, u32),
+        maker_range: (u32, u32),
+    },
+    /// A hash preimage that doesn't hash to the contract's hashvalue.
+    InvalidPreimage {
+        expected: Hash160,
+    },
 }
 
 impl From<ContractError> for ProtocolError {
@@ -15,12 +15,7 @@ use std::{
 };
 
 use bip39::Mnemonic;
-use bitcoin::{
-    absolute::LockTime,
-    ecdsa::Signature,
-    secp256k1::{self, Secp256k1},
-    Amount, OutPoint, PublicKey, ScriptBuf, Transaction,
-};
+use bitcoin::{absolute::LockTime, ecdsa::Signature, Amount, OutPoint, ScriptBuf, Transaction};
 use bitcoind::bitcoincore_rpc::RpcApi;
 use std::time::Duration;
 
@@ -28,10 +23,11 @@ use crate::{
     protocol::{
         contract::check_hashvalues_are_equal,
         messages::{FidelityProof, ReqContractSigsForSender},
-        Hash160,
+        ActiveSwap, Hash160,
     },
     utill::{
-        get_maker_dir, redeemscript_to_scriptpubkey, seed_phrase_to_unique_id, ConnectionType,
+        get_maker_dir, recovery_poll_interval, redeemscript_to_scriptpubkey,
+        seed_phrase_to_unique_id, ConnectionType,
     },
     wallet::{RPCConfig, SwapCoin, WalletSwapCoin},
 };
@@ -44,10 +40,13 @@ use crate::{
         },
         messages::ProofOfFunding,
     },
-    wallet::{IncomingSwapCoin, OutgoingSwapCoin, Wallet, WalletError},
+    wallet::{
+        sign_contract_tx_with_key, IncomingSwapCoin, OutgoingSwapCoin, RemoteSigner, SignerKey,
+        Wallet, WalletError,
+    },
 };
 
-use super::{config::MakerConfig, error::MakerError};
+use super::{config::MakerConfig, error::MakerError, rpc::MakerStatus};
 
 /// Used to configure the maker for testing purposes.
 #[derive(Debug, Clone, Copy)]
@@ -85,12 +84,56 @@ pub struct ConnectionState {
     pub incoming_swapcoins: Vec<IncomingSwapCoin>,
     pub outgoing_swapcoins: Vec<OutgoingSwapCoin>,
     pub pending_funding_txes: Vec<Transaction>,
+    /// The protocol version agreed with the Taker at the hello.
+    pub protocol_version: u32,
+}
+
+impl ConnectionState {
+    /// The id of the swap of this connection, the hashvalue of its contracts. None if no contract is set up yet.
+    pub(crate) fn swap_id(&self) -> Option<Hash160> {
+        self.incoming_swapcoins
+            .first()
+            .map(|swapcoin| swapcoin.get_hashvalue())
+            .or_else(|| {
+                self.outgoing_swapcoins
+                    .first()
+                    .map(|swapcoin| swapcoin.get_hashvalue())
+            })
+    }
+
+    /// Summarize the swap of this connection. Returns None if no contract is set up yet.
+    fn active_swap(&self, ip: &IpAddr) -> Option<ActiveSwap> {
+        let id = self.swap_id()?;
+
+        let earliest_locktime = self
+            .outgoing_swapcoins
+            .iter()
+            .map(|swapcoin| swapcoin.get_timelock())
+            .chain(
+                self.incoming_swapcoins
+                    .iter()
+                    .map(|swapcoin| swapcoin.get_timelock()),
+            )
+            .min();
+
+        Some(ActiveSwap {
+            id,
+            step: format!("Waiting for {:?}", self.allowed_message),
+            counterparties: vec![ip.to_string()],
+            committed_amount: self
+                .outgoing_swapcoins
+                .iter()
+                .map(|swapcoin| swapcoin.funding_amount)
+                .sum(),
+            earliest_locktime,
+        })
+    }
 }
 
 /// Represents the maker in the swap protocol.
 pub struct Maker {
     /// Defines special maker behavior, only applicable for testing
-    pub behavior: MakerBehavior,
+    pub behavior: RwLock<MakerBehavior>,
     /// Maker configurations
     pub config: MakerConfig,
     /// Maker's underlying wallet
@@ -99,10 +142,19 @@ pub struct Maker {
     pub shutdown: RwLock<bool>,
     /// Map of IP address to Connection State + last Connected instant
     pub connection_state: Mutex<HashMap<IpAddr, (ConnectionState, Instant)>>,
+    /// Map of swap id to the instant its contracts time out, for the swaps counted against
+    /// [MakerConfig::max_concurrent_swaps]
+    pub swaps_in_flight: Mutex<HashMap<Hash160, Instant>>,
     /// Highest Value Fidelity Proof
     pub highest_fidelity_proof: RwLock<Option<FidelityProof>>,
     /// Is setup complete
     pub is_setup_complete: RwLock<bool>,
+    /// When the Maker was initialized
+    pub start_time: Instant,
+    /// The port announced to the directory, if not the listening port. Only set in tests, to route
+    /// Takers through a proxy.
+    #[cfg(feature = "integration-test")]
+    pub(crate) advertised_port: RwLock<Option<u16>>,
 }
 
 #[allow(clippy::too_many_arguments)]
@@ -118,6 +170,8 @@ impl Maker {
     /// rpc_conf: None = Use the default [RPCConfig].
     ///
     /// behavior: Defines special Maker behavior. Only applicable in integration-tests.
+    ///
+    /// strict_config: Fail on invalid config values, instead of falling back to their defaults. See [MakerConfig::new_strict].
     pub fn init(
         data_dir: Option<PathBuf>,
         wallet_file_name: Option<String>,
@@ -127,6 +181,36 @@ impl Maker {
         socks_port: Option<u16>,
         connection_type: Option<ConnectionType>,
         behavior: MakerBehavior,
+        strict_config: bool,
+    ) -> Result<Self, MakerError> {
+        Self::init_with_signer(
+            data_dir,
+            wallet_file_name,
+            rpc_config,
+            port,
+            rpc_port,
+            socks_port,
+            connection_type,
+            behavior,
+            strict_config,
+            None,
+        )
+    }
+
+    /// Same as [Maker::init], with the wallet keys held by `signer`. See [Wallet::init_with_signer].
+    ///
+    /// signer: None = The keys are held in the wallet file.
+    pub fn init_with_signer(
+        data_dir: Option<PathBuf>,
+        wallet_file_name: Option<String>,
+        rpc_config: Option<RPCConfig>,
+        port: Option<u16>,
+        rpc_port: Option<u16>,
+        socks_port: Option<u16>,
+        connection_type: Option<ConnectionType>,
+        behavior: MakerBehavior,
+        strict_config: bool,
+        signer: Option<Box<dyn RemoteSigner + Send + Sync>>,
     ) -> Result<Self, MakerError> {
         // Only allow MakerBehavior in functional tests
         let behavior = if cfg!(feature = "integration-test") {
@@ -150,13 +234,50 @@ impl Maker {
 
         let mut rpc_config = rpc_config.unwrap_or_default();
 
+        // If config file doesn't exist, default config will be loaded.
+        let config_path = data_dir.join("config.toml");
+        let mut config = if strict_config {
+            MakerConfig::new_strict(Some(&config_path))?
+        } else {
+            MakerConfig::new(Some(&config_path))?
+        };
+
+        let encryption_passphrase = config.wallet_encryption_passphrase.as_deref();
+
         // Load/Create wallet depending on if a wallet with wallet_file_name exists.
-        let mut wallet = if let Some(file_name) = wallet_file_name {
+        let mut wallet = if let Some(signer) = signer {
+            // The keys are held by the signer, the default file name is its master fingerprint.
+            let file_name = match wallet_file_name {
+                Some(file_name) => file_name,
+                None => format!("{}-maker", signer.wallet_pubkeys()?.master_fingerprint),
+            };
+            let wallet_path = wallet_dir.join(&file_name);
+            rpc_config.wallet_name = file_name;
+            if wallet_path.exists() {
+                let wallet = Wallet::load_with_signer(
+                    &rpc_config,
+                    &wallet_path,
+                    signer,
+                    encryption_passphrase,
+                )?;
+                log::info!("Wallet file at {:?} successfully loaded.", wallet_path);
+                wallet
+            } else {
+                let wallet = Wallet::init_with_signer(
+                    &wallet_path,
+                    &rpc_config,
+                    signer,
+                    encryption_passphrase,
+                )?;
+                log::info!("New Wallet created at : {:?}", wallet_path);
+                wallet
+            }
+        } else if let Some(file_name) = wallet_file_name {
             let wallet_path = wallet_dir.join(&file_name);
             rpc_config.wallet_name = file_name;
             if wallet_path.exists() {
                 // Try loading wallet
-                let wallet = Wallet::load(&rpc_config, &wallet_path)?;
+                let wallet = Wallet::load(&rpc_config, &wallet_path, encryption_passphrase)?;
                 log::info!("Wallet file at {:?} successfully loaded.", wallet_path);
                 wallet
             } else {
@@ -164,7 +285,13 @@ impl Maker {
                 let mnemonic = Mnemonic::generate(12).map_err(WalletError::BIP39)?;
                 let seedphrase = mnemonic.to_string();
 
-                let wallet = Wallet::init(&wallet_path, &rpc_config, seedphrase, "".to_string())?;
+                let wallet = Wallet::init(
+                    &wallet_path,
+                    &rpc_config,
+                    seedphrase,
+                    "".to_string(),
+                    encryption_passphrase,
+                )?;
                 log::info!("New Wallet created at : {:?}", wallet_path);
                 wallet
             }
@@ -179,14 +306,17 @@ impl Maker {
             let wallet_path = wallet_dir.join(&file_name);
             rpc_config.wallet_name = file_name;
 
-            let wallet = Wallet::init(&wallet_path, &rpc_config, seedphrase, "".to_string())?;
+            let wallet = Wallet::init(
+                &wallet_path,
+                &rpc_config,
+                seedphrase,
+                "".to_string(),
+                encryption_passphrase,
+            )?;
             log::info!("New Wallet created at : {:?}", wallet_path);
             wallet
         };
 
-        // If config file doesn't exist, default config will be loaded.
-        let mut config = MakerConfig::new(Some(&data_dir.join("config.toml")))?;
-
         if let Some(port) = port {
             config.port = port;
         }
@@ -210,13 +340,17 @@ impl Maker {
         log::info!("Completed wallet sync");
 
         Ok(Self {
-            behavior,
+            behavior: RwLock::new(behavior),
             config,
             wallet: RwLock::new(wallet),
             shutdown: RwLock::new(false),
             connection_state: Mutex::new(HashMap::new()),
+            swaps_in_flight: Mutex::new(HashMap::new()),
             highest_fidelity_proof: RwLock::new(None),
             is_setup_complete: RwLock::new(false),
+            start_time: Instant::now(),
+            #[cfg(feature = "integration-test")]
+            advertised_port: RwLock::new(None),
         })
     }
 
@@ -232,6 +366,27 @@ impl Maker {
         Ok(())
     }
 
+    /// Changes the special behavior of a running Maker, for the next swaps.
+    /// Fails if a swap is in progress.
+    #[cfg(feature = "integration-test")]
+    pub fn set_behavior(&self, behavior: MakerBehavior) -> Result<(), MakerError> {
+        if !self.connection_state.lock()?.is_empty() {
+            return Err(MakerError::General(
+                "Can't change the behavior during a swap",
+            ));
+        }
+        *self.behavior.write()? = behavior;
+        Ok(())
+    }
+
+    /// Announces another port than the listening one to the directory, for the Takers to connect to.
+    /// Must be set before the server starts.
+    #[cfg(feature = "integration-test")]
+    pub fn set_advertised_port(&self, port: u16) -> Result<(), MakerError> {
+        *self.advertised_port.write()? = Some(port);
+        Ok(())
+    }
+
     /// Triggers a setup complete event for the Maker.
     pub fn setup_complete(&self) -> Result<(), MakerError> {
         let mut flag = self.is_setup_complete.write()?;
@@ -239,6 +394,77 @@ impl Maker {
         Ok(())
     }
 
+    /// Releases the coins reserved for the swap of a Taker connection that went idle.
+    ///
+    /// The Maker's funding transactions are created at the proof of funding, locking their inputs, and
+    /// only broadcasted once the Taker sends the contract signatures. If the Taker stalls in between, the
+    /// inputs are unlocked, and the unfunded outgoing swapcoins and the incoming ones are removed, so the
+    /// swap is forgotten. The Taker refunds its own funding with its contracts. The contracts of a funded
+    /// swap are left for [check_for_idle_states] to recover.
+    pub fn release_idle_connection(
+        &self,
+        ip: IpAddr,
+        connection_state: &ConnectionState,
+    ) -> Result<(), MakerError> {
+        if connection_state.allowed_message
+            != ExpectedMessage::ProofOfFundingORContractSigsForRecvrAndSender
+            || connection_state.pending_funding_txes.is_empty()
+        {
+            return Ok(());
+        }
+        let reserved = connection_state
+            .pending_funding_txes
+            .iter()
+            .flat_map(|tx| tx.input.iter().map(|txin| txin.previous_output))
+            .collect::<Vec<OutPoint>>();
+        {
+            let mut wallet = self.wallet.write()?;
+            wallet.release_in_flight(&reserved)?;
+            for outgoing_swapcoin in &connection_state.outgoing_swapcoins {
+                wallet.remove_outgoing_swapcoin(&outgoing_swapcoin.get_multisig_redeemscript())?;
+            }
+            for incoming_swapcoin in &connection_state.incoming_swapcoins {
+                wallet.remove_incoming_swapcoin(&incoming_swapcoin.get_multisig_redeemscript())?;
+            }
+            wallet.save_to_disk()?;
+        }
+        if let Some(swap_id) = connection_state.swap_id() {
+            self.swaps_in_flight.lock()?.remove(&swap_id);
+        }
+        self.connection_state.lock()?.remove(&ip);
+        log::info!(
+            "[{}] Released {} coins reserved for the idle Taker {}",
+            self.config.port,
+            reserved.len(),
+            ip
+        );
+        Ok(())
+    }
+
+    /// List the swaps currently in flight, one for each Taker connection with contracts set up.
+    pub fn list_active_swaps(&self) -> Result<Vec<ActiveSwap>, MakerError> {
+        Ok(self
+            .connection_state
+            .lock()?
+            .iter()
+            .filter_map(|(ip, (state, _))| state.active_swap(ip))
+            .collect())
+    }
+
+    /// A snapshot of the Maker's live state, for the operator.
+    pub fn status(&self) -> Result<MakerStatus, MakerError> {
+        Ok(MakerStatus {
+            balance: self.wallet.read()?.balance_breakdown()?,
+            active_swaps: self.list_active_swaps()?.len(),
+            fidelity_bond: self
+                .highest_fidelity_proof
+                .read()?
+                .as_ref()
+                .map(|proof| proof.bond.clone()),
+            uptime_secs: self.start_time.elapsed().as_secs(),
+        })
+    }
+
     /// Returns a reference to the Maker's wallet.
     pub fn get_wallet(&self) -> &RwLock<Wallet> {
         &self.wallet
@@ -305,7 +531,7 @@ impl Maker {
 
             check_reedemscript_is_multisig(&funding_info.multisig_redeemscript)?;
 
-            let (_, tweabale_pubkey) = self.wallet.read()?.get_tweakable_keypair();
+            let tweabale_pubkey = self.wallet.read()?.get_tweakable_pubkey();
 
             check_multisig_has_pubkey(
                 &funding_info.multisig_redeemscript,
@@ -363,7 +589,7 @@ impl Maker {
                 ));
             }
 
-            let (tweakable_privkey, tweakable_pubkey) = self.wallet.read()?.get_tweakable_keypair();
+            let tweakable_pubkey = self.wallet.read()?.get_tweakable_pubkey();
 
             check_multisig_has_pubkey(
                 &txinfo.multisig_redeemscript,
@@ -371,14 +597,13 @@ impl Maker {
                 &txinfo.multisig_nonce,
             )?;
 
-            let secp = Secp256k1::new();
-
-            let hashlock_privkey = tweakable_privkey.add_tweak(&txinfo.hashlock_nonce.into())?;
-
-            let hashlock_pubkey = PublicKey {
-                compressed: true,
-                inner: secp256k1::PublicKey::from_secret_key(&secp, &hashlock_privkey),
-            };
+            let hashlock_pubkey = self
+                .wallet
+                .read()?
+                .signer()
+                .public_key(&SignerKey::Tweaked {
+                    nonce: txinfo.hashlock_nonce,
+                })?;
 
             crate::protocol::contract::is_contract_out_valid(
                 &txinfo.senders_contract_tx.output[0],
@@ -394,13 +619,17 @@ impl Maker {
                 txinfo.senders_contract_tx.output[0].script_pubkey.clone(),
             )?;
 
-            let multisig_privkey = tweakable_privkey.add_tweak(&txinfo.multisig_nonce.into())?;
-
-            let sig = crate::protocol::contract::sign_contract_tx(
+            let wallet = self.wallet.read()?;
+            let multisig_key = SignerKey::Tweaked {
+                nonce: txinfo.multisig_nonce,
+            };
+            let sig = sign_contract_tx_with_key(
                 &txinfo.senders_contract_tx,
                 &txinfo.multisig_redeemscript,
                 txinfo.funding_input_value,
-                &multisig_privkey,
+                &multisig_key,
+                &wallet.signer().public_key(&multisig_key)?,
+                wallet.signer(),
             )?;
             sigs.push(sig);
         }
@@ -460,17 +689,20 @@ pub fn check_for_broadcasted_contracts(maker: Arc<Maker>) -> Result<(), MakerErr
                             .zip(connection_state.incoming_swapcoins.iter())
                         {
                             let contract_timelock = og_sc.get_timelock();
-                            let next_internal_address =
-                                &maker.wallet.read()?.get_next_internal_addresses(1)?[0];
-                            let time_lock_spend =
-                                og_sc.create_timelock_spend(next_internal_address);
+                            let wallet = maker.wallet.read()?;
+                            let next_internal_address = &wallet.get_next_internal_addresses(1)?[0];
+                            let time_lock_spend = og_sc.create_timelock_spend(
+                                next_internal_address,
+                                maker.config.contract_fee_rate,
+                                wallet.signer(),
+                            )?;
 
                             // Sometimes we might not have other's contact signatures.
                             // This means the protocol have been stopped abruptly.
                             // This needs more careful consideration as this should not happen
                             // after funding transactions have been broadcasted for outgoing contracts.
                             // For incomings, its less lethal as thats mostly the other party's burden.
-                            if let Ok(tx) = og_sc.get_fully_signed_contract_tx() {
+                            if let Ok(tx) = og_sc.get_fully_signed_contract_tx(wallet.signer()) {
                                 outgoings.push((
                                     (og_sc.get_multisig_redeemscript(), tx),
                                     (contract_timelock, time_lock_spend),
@@ -481,7 +713,7 @@ pub fn check_for_broadcasted_contracts(maker: Arc<Maker>) -> Result<(), MakerErr
                                     maker.config.port
                                 );
                             }
-                            if let Ok(tx) = ic_sc.get_fully_signed_contract_tx() {
+                            if let Ok(tx) = ic_sc.get_fully_signed_contract_tx(wallet.signer()) {
                                 incomings.push((ic_sc.get_multisig_redeemscript(), tx));
                             } else {
                                 log::warn!(
@@ -491,6 +723,9 @@ pub fn check_for_broadcasted_contracts(maker: Arc<Maker>) -> Result<(), MakerErr
                             }
                         }
                         failed_swap_ip.push(*ip);
+                        if let Some(swap_id) = connection_state.swap_id() {
+                            maker.swaps_in_flight.lock()?.remove(&swap_id);
+                        }
 
                         // Spawn a separate thread to wait for contract maturity and broadcasting timelocked.
                         let maker_clone = maker.clone();
@@ -514,7 +749,7 @@ pub fn check_for_broadcasted_contracts(maker: Arc<Maker>) -> Result<(), MakerErr
             }
         } // All locks are cleared here.
 
-        std::thread::sleep(Duration::from_secs(maker.config.heart_beat_interval_secs));
+        std::thread::sleep(Duration::from_secs(maker.config.poll_interval_secs));
     }
 
     Ok(())
@@ -561,18 +796,26 @@ pub fn check_for_idle_states(maker: Arc<Maker>) -> Result<(), MakerError> {
                         .zip(state.incoming_swapcoins.iter())
                     {
                         let contract_timelock = og_sc.get_timelock();
-                        let contract = og_sc.get_fully_signed_contract_tx()?;
-                        let next_internal_address =
-                            &maker.wallet.read()?.get_next_internal_addresses(1)?[0];
-                        let time_lock_spend = og_sc.create_timelock_spend(next_internal_address);
+                        let wallet = maker.wallet.read()?;
+                        let contract = og_sc.get_fully_signed_contract_tx(wallet.signer())?;
+                        let next_internal_address = &wallet.get_next_internal_addresses(1)?[0];
+                        let time_lock_spend = og_sc.create_timelock_spend(
+                            next_internal_address,
+                            maker.config.contract_fee_rate,
+                            wallet.signer(),
+                        )?;
                         outgoings.push((
                             (og_sc.get_multisig_redeemscript(), contract),
                             (contract_timelock, time_lock_spend),
                         ));
-                        let incoming_contract = ic_sc.get_fully_signed_contract_tx()?;
+                        let incoming_contract =
+                            ic_sc.get_fully_signed_contract_tx(wallet.signer())?;
                         incomings.push((ic_sc.get_multisig_redeemscript(), incoming_contract));
                     }
                     bad_ip.push(*ip);
+                    if let Some(swap_id) = state.swap_id() {
+                        maker.swaps_in_flight.lock()?.remove(&swap_id);
+                    }
                     // Spawn a separate thread to wait for contract maturity and broadcasting timelocked.
                     let maker_clone = maker.clone();
                     log::info!(
@@ -754,11 +997,8 @@ pub fn recover_from_swap(
             return Ok(());
         }
         // Sleep before next blockchain scan
-        let block_lookup_interval = if cfg!(feature = "integration-test") {
-            Duration::from_secs(10)
-        } else {
-            Duration::from_secs(10 * 60)
-        };
-        std::thread::sleep(block_lookup_interval);
+        std::thread::sleep(recovery_poll_interval(Duration::from_secs(
+            maker.config.poll_interval_secs,
+        )));
     }
 }
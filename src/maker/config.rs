@@ -1,10 +1,20 @@
 //! Maker Configuration. Controlling various behaviors.
 
-use std::{io, path::PathBuf};
+use std::{
+    collections::HashMap,
+    io,
+    path::{Path, PathBuf},
+};
 
 use bitcoin::Amount;
 
-use crate::utill::{get_maker_dir, parse_field, parse_toml, write_default_config, ConnectionType};
+use crate::{
+    error::ConfigError,
+    utill::{
+        default_poll_interval_secs, get_maker_dir, parse_config_field, parse_toml,
+        write_default_config, ConnectionType, MIN_FEE_RATE,
+    },
+};
 
 /// Maker Configuration, controlling various maker behavior.
 #[derive(Debug, Clone, PartialEq)]
@@ -29,10 +39,19 @@ pub struct MakerConfig {
     pub time_relative_fee_ppb: Amount,
     /// No of confirmation required for funding transaction
     pub required_confirms: u64,
+    /// Time interval between backend polls, while waiting for confirmations and watching for contract broadcasts
+    pub poll_interval_secs: u64,
+    /// Fee rate in sats/vbyte for contract spends, i.e the timelock recovery transactions.
+    /// Kept higher than the funding fee rate, so recoveries confirm in time during fee spikes.
+    pub contract_fee_rate: u64,
     // Minimum timelock difference between contract transaction of two hops
     pub min_contract_reaction_time: u16,
     /// Minimum coinswap amount size in sats
     pub min_size: u64,
+    /// Maximum coinswap amount size in sats. Can't be below `min_size`.
+    pub max_size: u64,
+    /// Maximum number of swaps in flight. New swaps are refused beyond it.
+    pub max_concurrent_swaps: u32,
     /// Socks port
     pub socks_port: u16,
     /// Directory server onion address
@@ -45,6 +64,11 @@ pub struct MakerConfig {
     pub fidelity_timelock: u32,
     /// Connection type
     pub connection_type: ConnectionType,
+    /// Free text advertised in the offer, like contact info or terms
+    pub announcement: Option<String>,
+    /// Passphrase the wallet file is encrypted with, see [Wallet::init](crate::wallet::Wallet::init).
+    /// Unencrypted if not set. Keep the config file apart from the backups of the wallet file.
+    pub wallet_encryption_passphrase: Option<String>,
 }
 
 impl Default for MakerConfig {
@@ -60,14 +84,20 @@ impl Default for MakerConfig {
             amount_relative_fee_ppb: Amount::from_sat(10_000_000),
             time_relative_fee_ppb: Amount::from_sat(100_000),
             required_confirms: 1,
+            poll_interval_secs: default_poll_interval_secs(1),
+            contract_fee_rate: 10,
             min_contract_reaction_time: 48,
             min_size: 10_000,
+            max_size: u64::MAX,
+            max_concurrent_swaps: 10,
             socks_port: 19050,
             directory_server_onion_address: "directoryhiddenserviceaddress.onion:8080".to_string(),
             directory_server_clearnet_address: "127.0.0.1:8080".to_string(),
             fidelity_value: 5_000_000, // 5 million  sats
             fidelity_timelock: 26_000, // Approx 6 months of blocks
             connection_type: ConnectionType::TOR,
+            announcement: None,
+            wallet_encryption_passphrase: None,
         }
     }
 }
@@ -84,8 +114,16 @@ impl MakerConfig {
     /// Default data-dir for linux: `~/.coinswap/`
     /// Default config locations, for taker for ex: `~/.coinswap/taker/config.toml`.
     pub fn new(config_path: Option<&PathBuf>) -> io::Result<Self> {
-        let default_config = Self::default();
+        Self::load(config_path, false)
+    }
+
+    /// Like [MakerConfig::new], but fields set to invalid values error with a
+    /// [ConfigError], instead of silently using their defaults.
+    pub fn new_strict(config_path: Option<&PathBuf>) -> io::Result<Self> {
+        Self::load(config_path, true)
+    }
 
+    fn load(config_path: Option<&PathBuf>, strict: bool) -> io::Result<Self> {
         let default_config_path = get_maker_dir().join("maker.toml");
         let config_path = config_path.unwrap_or(&default_config_path);
 
@@ -105,69 +143,157 @@ impl MakerConfig {
 
         let maker_config_section = section.get("maker_config").cloned().unwrap_or_default();
 
-        Ok(MakerConfig {
-            port: parse_field(maker_config_section.get("port"), default_config.port)
-                .unwrap_or(default_config.port),
-            rpc_port: parse_field(
-                maker_config_section.get("rpc_port"),
+        Ok(Self::from_section(&maker_config_section, strict)?)
+    }
+
+    /// Strictly loads a [MakerConfig] from the file at `config_path`.
+    ///
+    /// Unlike [MakerConfig::new_strict], a missing file or a missing `[maker_config]` section is
+    /// an error, and no default file is written. Fields absent from the section still take their
+    /// defaults.
+    pub fn from_file_strict(config_path: &Path) -> Result<Self, ConfigError> {
+        if !config_path.exists() {
+            return Err(ConfigError::MissingFile(config_path.to_path_buf()));
+        }
+
+        let section = parse_toml(&config_path.to_path_buf())?;
+        let maker_config_section = section
+            .get("maker_config")
+            .ok_or_else(|| ConfigError::MissingSection("maker_config".to_string()))?;
+
+        Self::from_section(maker_config_section, true)
+    }
+
+    /// Parses the fields of a config section. Invalid values error if `strict`, and otherwise
+    /// take their defaults.
+    ///
+    /// Whatever `strict`, a contract fee rate not above the minimum relay fee rate errors, as the
+    /// funding transactions of the Maker pay at least that.
+    fn from_section(
+        maker_config_section: &HashMap<String, String>,
+        strict: bool,
+    ) -> Result<Self, ConfigError> {
+        let default_config = Self::default();
+
+        // The default polling interval depends on the confirmations we wait for.
+        let required_confirms = parse_config_field(
+            maker_config_section,
+            "required_confirms",
+            default_config.required_confirms,
+            strict,
+        )?;
+
+        // The maximum swap size can't be below the minimum.
+        let min_size = parse_config_field(
+            maker_config_section,
+            "min_size",
+            default_config.min_size,
+            strict,
+        )?;
+        let max_size = parse_config_field(
+            maker_config_section,
+            "max_size",
+            default_config.max_size,
+            strict,
+        )?;
+        let max_size = if max_size < min_size {
+            let e = ConfigError::InvalidField {
+                field: "max_size".to_string(),
+                value: max_size.to_string(),
+                expected_type: format!("u64 not below min_size {}", min_size),
+            };
+            if strict {
+                return Err(e);
+            }
+            log::warn!("{}, using the default", e);
+            default_config.max_size
+        } else {
+            max_size
+        };
+
+        let config = MakerConfig {
+            port: parse_config_field(maker_config_section, "port", default_config.port, strict)?,
+            rpc_port: parse_config_field(
+                maker_config_section,
+                "rpc_port",
                 default_config.rpc_port,
-            )
-            .unwrap_or(default_config.rpc_port),
-            heart_beat_interval_secs: parse_field(
-                maker_config_section.get("heart_beat_interval_secs"),
+                strict,
+            )?,
+            heart_beat_interval_secs: parse_config_field(
+                maker_config_section,
+                "heart_beat_interval_secs",
                 default_config.heart_beat_interval_secs,
-            )
-            .unwrap_or(default_config.heart_beat_interval_secs),
-            rpc_ping_interval_secs: parse_field(
-                maker_config_section.get("rpc_ping_interval_secs"),
+                strict,
+            )?,
+            rpc_ping_interval_secs: parse_config_field(
+                maker_config_section,
+                "rpc_ping_interval_secs",
                 default_config.rpc_ping_interval_secs,
-            )
-            .unwrap_or(default_config.rpc_ping_interval_secs),
-            directory_servers_refresh_interval_secs: parse_field(
-                maker_config_section.get("directory_servers_refresh_interval_secs"),
+                strict,
+            )?,
+            directory_servers_refresh_interval_secs: parse_config_field(
+                maker_config_section,
+                "directory_servers_refresh_interval_secs",
                 default_config.directory_servers_refresh_interval_secs,
-            )
-            .unwrap_or(default_config.directory_servers_refresh_interval_secs),
-            idle_connection_timeout: parse_field(
-                maker_config_section.get("idle_connection_timeout"),
+                strict,
+            )?,
+            idle_connection_timeout: parse_config_field(
+                maker_config_section,
+                "idle_connection_timeout",
                 default_config.idle_connection_timeout,
-            )
-            .unwrap_or(default_config.idle_connection_timeout),
-            absolute_fee_sats: parse_field(
-                maker_config_section.get("absolute_fee_sats"),
+                strict,
+            )?,
+            absolute_fee_sats: parse_config_field(
+                maker_config_section,
+                "absolute_fee_sats",
                 default_config.absolute_fee_sats,
-            )
-            .unwrap_or(default_config.absolute_fee_sats),
-            amount_relative_fee_ppb: parse_field(
-                maker_config_section.get("amount_relative_fee_ppb"),
+                strict,
+            )?,
+            amount_relative_fee_ppb: parse_config_field(
+                maker_config_section,
+                "amount_relative_fee_ppb",
                 default_config.amount_relative_fee_ppb,
-            )
-            .unwrap_or(default_config.amount_relative_fee_ppb),
-            time_relative_fee_ppb: parse_field(
-                maker_config_section.get("time_relative_fee_ppb"),
+                strict,
+            )?,
+            time_relative_fee_ppb: parse_config_field(
+                maker_config_section,
+                "time_relative_fee_ppb",
                 default_config.time_relative_fee_ppb,
-            )
-            .unwrap_or(default_config.time_relative_fee_ppb),
-            required_confirms: parse_field(
-                maker_config_section.get("required_confirms"),
-                default_config.required_confirms,
-            )
-            .unwrap_or(default_config.required_confirms),
-            min_contract_reaction_time: parse_field(
-                maker_config_section.get("min_contract_reaction_time"),
+                strict,
+            )?,
+            required_confirms,
+            poll_interval_secs: parse_config_field(
+                maker_config_section,
+                "poll_interval_secs",
+                default_poll_interval_secs(required_confirms),
+                strict,
+            )?,
+            contract_fee_rate: parse_config_field(
+                maker_config_section,
+                "contract_fee_rate",
+                default_config.contract_fee_rate,
+                strict,
+            )?,
+            min_contract_reaction_time: parse_config_field(
+                maker_config_section,
+                "min_contract_reaction_time",
                 default_config.min_contract_reaction_time,
-            )
-            .unwrap_or(default_config.min_contract_reaction_time),
-            min_size: parse_field(
-                maker_config_section.get("min_size"),
-                default_config.min_size,
-            )
-            .unwrap_or(default_config.min_size),
-            socks_port: parse_field(
-                maker_config_section.get("socks_port"),
+                strict,
+            )?,
+            min_size,
+            max_size,
+            max_concurrent_swaps: parse_config_field(
+                maker_config_section,
+                "max_concurrent_swaps",
+                default_config.max_concurrent_swaps,
+                strict,
+            )?,
+            socks_port: parse_config_field(
+                maker_config_section,
+                "socks_port",
                 default_config.socks_port,
-            )
-            .unwrap_or(default_config.socks_port),
+                strict,
+            )?,
             directory_server_onion_address: maker_config_section
                 .get("directory_server_onion_address")
                 .map(|s| s.to_string())
@@ -176,22 +302,42 @@ impl MakerConfig {
                 .get("directory_server_clearnet_address")
                 .map(|s| s.to_string())
                 .unwrap_or(default_config.directory_server_clearnet_address),
-            fidelity_value: parse_field(
-                maker_config_section.get("fidelity_value"),
+            fidelity_value: parse_config_field(
+                maker_config_section,
+                "fidelity_value",
                 default_config.fidelity_value,
-            )
-            .unwrap_or(default_config.fidelity_value),
-            fidelity_timelock: parse_field(
-                maker_config_section.get("fidelity_timelock"),
+                strict,
+            )?,
+            fidelity_timelock: parse_config_field(
+                maker_config_section,
+                "fidelity_timelock",
                 default_config.fidelity_timelock,
-            )
-            .unwrap_or(default_config.fidelity_timelock),
-            connection_type: parse_field(
-                maker_config_section.get("connection_type"),
+                strict,
+            )?,
+            connection_type: parse_config_field(
+                maker_config_section,
+                "connection_type",
                 default_config.connection_type,
-            )
-            .unwrap_or(default_config.connection_type),
-        })
+                strict,
+            )?,
+            announcement: maker_config_section
+                .get("announcement")
+                .map(|s| s.to_string())
+                .or(default_config.announcement),
+            wallet_encryption_passphrase: maker_config_section
+                .get("wallet_encryption_passphrase")
+                .map(|s| s.to_string())
+                .or(default_config.wallet_encryption_passphrase),
+        };
+
+        if config.contract_fee_rate <= MIN_FEE_RATE {
+            return Err(ConfigError::ContractFeeRateTooLow {
+                contract_fee_rate: config.contract_fee_rate,
+                funding_fee_rate: MIN_FEE_RATE,
+            });
+        }
+
+        Ok(config)
     }
 }
 
@@ -210,6 +356,8 @@ fn write_default_maker_config(config_path: &PathBuf) {
             amount_relative_fee_ppb = 10000000\n\
             time_relative_fee_ppb = 100000\n\
             required_confirms = 1\n\
+            poll_interval_secs = 2\n\
+            contract_fee_rate = 10\n\
             min_contract_reaction_time = 48\n\
             min_size = 10000\n\
             socks_port = 19050\n\
@@ -288,6 +436,39 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_swap_limits() {
+        let contents = r#"
+            [maker_config]
+            min_size = 20000
+            max_size = 5000000
+            max_concurrent_swaps = 2
+        "#;
+        let config_path = create_temp_config(contents, "swap_limits_maker_config.toml");
+        let config = MakerConfig::new(Some(&config_path)).unwrap();
+        remove_temp_config(&config_path);
+        assert_eq!(config.min_size, 20_000);
+        assert_eq!(config.max_size, 5_000_000);
+        assert_eq!(config.max_concurrent_swaps, 2);
+
+        // A maximum size below the minimum is defaulted, or errors in strict mode.
+        let contents = r#"
+            [maker_config]
+            min_size = 20000
+            max_size = 10000
+        "#;
+        let config_path = create_temp_config(contents, "inverted_swap_limits_maker_config.toml");
+        let lenient = MakerConfig::new(Some(&config_path)).unwrap();
+        let strict = MakerConfig::from_file_strict(&config_path);
+        remove_temp_config(&config_path);
+        assert_eq!(lenient.max_size, MakerConfig::default().max_size);
+        assert!(matches!(
+            strict,
+            Err(ConfigError::InvalidField { field, value, .. })
+                if field == "max_size" && value == "10000"
+        ));
+    }
+
     #[test]
     fn test_incorrect_data_type() {
         let contents = r#"
@@ -301,6 +482,126 @@ mod tests {
         assert_eq!(config, MakerConfig::default());
     }
 
+    #[test]
+    fn test_strict_config() {
+        let contents = r#"
+            [maker_config]
+            min_size = 100000x
+        "#;
+        let config_path = create_temp_config(contents, "strict_maker_config.toml");
+        let lenient = MakerConfig::new(Some(&config_path));
+        let strict = MakerConfig::new_strict(Some(&config_path));
+        remove_temp_config(&config_path);
+
+        // Lenient parsing defaults, strict parsing reports the field.
+        assert_eq!(lenient.unwrap(), MakerConfig::default());
+        let error = strict.unwrap_err();
+        assert!(matches!(
+            error.get_ref().unwrap().downcast_ref::<ConfigError>(),
+            Some(ConfigError::InvalidField { field, value, expected_type })
+                if field == "min_size" && value == "100000x" && expected_type == "u64"
+        ));
+    }
+
+    #[test]
+    fn test_from_file_strict() {
+        // A missing file errors, and isn't created.
+        let config_path = PathBuf::from("missing_strict_maker_config.toml");
+        assert!(matches!(
+            MakerConfig::from_file_strict(&config_path),
+            Err(ConfigError::MissingFile(path)) if path == config_path
+        ));
+        assert!(!config_path.exists());
+
+        // A missing section errors.
+        let contents = r#"
+            [taker_config]
+            port = 6102
+        "#;
+        let config_path = create_temp_config(contents, "missing_section_maker_config.toml");
+        let missing_section = MakerConfig::from_file_strict(&config_path);
+        remove_temp_config(&config_path);
+        assert!(matches!(
+            missing_section,
+            Err(ConfigError::MissingSection(section)) if section == "maker_config"
+        ));
+
+        // An invalid field errors, naming the key.
+        let contents = r#"
+            [maker_config]
+            port = 6102
+            fidelity_timelock = soon
+        "#;
+        let config_path = create_temp_config(contents, "invalid_field_maker_config.toml");
+        let invalid_field = MakerConfig::from_file_strict(&config_path);
+        remove_temp_config(&config_path);
+        assert!(matches!(
+            invalid_field,
+            Err(ConfigError::InvalidField { field, .. }) if field == "fidelity_timelock"
+        ));
+
+        // A valid file loads, with the absent fields defaulted.
+        let contents = r#"
+            [maker_config]
+            port = 6102
+        "#;
+        let config_path = create_temp_config(contents, "valid_strict_maker_config.toml");
+        let config = MakerConfig::from_file_strict(&config_path).unwrap();
+        remove_temp_config(&config_path);
+        assert_eq!(
+            config,
+            MakerConfig {
+                port: 6102,
+                ..MakerConfig::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_poll_interval() {
+        // Follows the required confirmations, if not set.
+        let contents = r#"
+            [maker_config]
+            required_confirms = 6
+        "#;
+        let config_path = create_temp_config(contents, "poll_interval_default_maker_config.toml");
+        let config = MakerConfig::new(Some(&config_path)).unwrap();
+        remove_temp_config(&config_path);
+        assert_eq!(config.poll_interval_secs, default_poll_interval_secs(6));
+        assert!(config.poll_interval_secs > MakerConfig::default().poll_interval_secs);
+
+        // Explicit value takes precedence.
+        let contents = r#"
+            [maker_config]
+            required_confirms = 6
+            poll_interval_secs = 5
+        "#;
+        let config_path = create_temp_config(contents, "poll_interval_maker_config.toml");
+        let config = MakerConfig::new(Some(&config_path)).unwrap();
+        remove_temp_config(&config_path);
+        assert_eq!(config.poll_interval_secs, 5);
+    }
+
+    #[test]
+    fn test_contract_fee_rate_above_relay() {
+        let contents = r#"
+            [maker_config]
+            contract_fee_rate = 1
+        "#;
+        let config_path = create_temp_config(contents, "low_contract_fee_maker_config.toml");
+        let config = MakerConfig::from_file_strict(&config_path);
+        remove_temp_config(&config_path);
+        assert!(matches!(
+            config,
+            Err(ConfigError::ContractFeeRateTooLow {
+                contract_fee_rate: 1,
+                funding_fee_rate: 1
+            })
+        ));
+
+        assert!(MakerConfig::default().contract_fee_rate > MIN_FEE_RATE);
+    }
+
     #[test]
     fn test_missing_file() {
         let config_path = get_maker_dir().join("maker.toml");
@@ -4,19 +4,24 @@ use std::sync::{MutexGuard, PoisonError, RwLockReadGuard, RwLockWriteGuard};
 
 use bitcoin::secp256k1;
 
-use crate::{protocol::error::ContractError, wallet::WalletError};
+use crate::{error::ProtocolError, protocol::error::ContractError, wallet::WalletError};
 
 /// Enum to handle Maker related errors.
 #[derive(Debug)]
 pub enum MakerError {
     IO(std::io::Error),
-    UnexpectedMessage { expected: String, got: String },
+    UnexpectedMessage {
+        expected: String,
+        got: String,
+    },
     General(&'static str),
     MutexPossion,
     Secp(secp256k1::Error),
     ContractError(ContractError),
     Wallet(WalletError),
     Deserialize(serde_cbor::Error),
+    /// The Taker doesn't follow the protocol. Only its connection is dropped.
+    Protocol(ProtocolError),
 }
 
 impl From<std::io::Error> for MakerError {
@@ -25,6 +30,12 @@ impl From<std::io::Error> for MakerError {
     }
 }
 
+impl From<ProtocolError> for MakerError {
+    fn from(value: ProtocolError) -> Self {
+        Self::Protocol(value)
+    }
+}
+
 impl From<serde_cbor::Error> for MakerError {
     fn from(value: serde_cbor::Error) -> Self {
         Self::Deserialize(value)
@@ -7,16 +7,17 @@
 //! The file includes functions to validate and sign contract transactions, verify proof of funding, and handle unexpected recovery scenarios.
 //! Implements the core functionality for a Maker in a Bitcoin coinswap protocol.
 
-use std::{net::IpAddr, sync::Arc, time::Instant};
-
-use bitcoin::{
-    hashes::Hash,
-    secp256k1::{self, Secp256k1},
-    Amount, OutPoint, PublicKey, Transaction, Txid,
+use std::{
+    net::IpAddr,
+    sync::Arc,
+    time::{Duration, Instant},
 };
+
+use bitcoin::{hashes::Hash, Amount, OutPoint, PublicKey, Transaction, Txid};
 use bitcoind::bitcoincore_rpc::RpcApi;
 
 use crate::{
+    error::ProtocolError,
     maker::api::recover_from_swap,
     protocol::{
         messages::{MultisigPrivkey, PrivKeyHandover},
@@ -34,16 +35,17 @@ use crate::{
         contract::{
             calculate_coinswap_fee, create_receivers_contract_tx, find_funding_output_index,
             read_contract_locktime, read_hashvalue_from_contract,
-            read_pubkeys_from_multisig_redeemscript, FUNDING_TX_VBYTE_SIZE,
+            read_pubkeys_from_multisig_redeemscript, verify_preimage, FUNDING_TX_VBYTE_SIZE,
         },
         messages::{
-            ContractSigsAsRecvrAndSender, ContractSigsForRecvr, ContractSigsForRecvrAndSender,
-            ContractSigsForSender, HashPreimage, MakerToTakerMessage, Offer, ProofOfFunding,
-            ReqContractSigsForRecvr, ReqContractSigsForSender, SenderContractTxInfo,
-            TakerToMakerMessage,
+            negotiate_version, sanitize_announcement, ContractSigsAsRecvrAndSender,
+            ContractSigsForRecvr, ContractSigsForRecvrAndSender, ContractSigsForSender,
+            HashPreimage, MakerToTakerMessage, Offer, ProofOfFunding, ReqContractSigsForRecvr,
+            ReqContractSigsForSender, SenderContractTxInfo, SwapRejection, TakerToMakerMessage,
+            PROTOCOL_VERSION_MAX, PROTOCOL_VERSION_MIN,
         },
     },
-    wallet::{IncomingSwapCoin, SwapCoin},
+    wallet::{IncomingSwapCoin, SignerKey, SwapCoin},
 };
 
 /// The Global Handle Message function. Takes in a [`Arc<Maker>`] and handle messages
@@ -56,7 +58,11 @@ pub async fn handle_message(
 ) -> Result<Option<MakerToTakerMessage>, MakerError> {
     let outgoing_message = match connection_state.allowed_message {
         ExpectedMessage::TakerHello => {
-            if let TakerToMakerMessage::TakerHello(_) = message {
+            if let TakerToMakerMessage::TakerHello(hello) = message {
+                connection_state.protocol_version = negotiate_version(
+                    (hello.protocol_version_min, hello.protocol_version_max),
+                    (PROTOCOL_VERSION_MIN, PROTOCOL_VERSION_MAX),
+                )?;
                 connection_state.allowed_message = ExpectedMessage::NewlyConnectedTaker;
                 None
             } else {
@@ -71,7 +77,7 @@ pub async fn handle_message(
                 let (tweakable_point, max_size) = {
                     let wallet_reader = maker.wallet.read()?;
                     let max_size = wallet_reader.store.offer_maxsize;
-                    let tweakable_point = wallet_reader.get_tweakable_keypair().1;
+                    let tweakable_point = wallet_reader.get_tweakable_pubkey();
                     (tweakable_point, max_size)
                 };
                 connection_state.allowed_message = ExpectedMessage::ReqContractSigsForSender;
@@ -83,15 +89,24 @@ pub async fn handle_message(
                     time_relative_fee_ppb: maker.config.time_relative_fee_ppb,
                     required_confirms: maker.config.required_confirms,
                     minimum_locktime: maker.config.min_contract_reaction_time,
-                    max_size,
+                    max_size: max_size.min(maker.config.max_size),
                     min_size: maker.config.min_size,
                     tweakable_point,
                     fidelity: fidelity.clone(),
+                    announcement: maker
+                        .config
+                        .announcement
+                        .as_deref()
+                        .and_then(sanitize_announcement),
                 })))
             }
             TakerToMakerMessage::ReqContractSigsForSender(message) => {
-                connection_state.allowed_message = ExpectedMessage::ProofOfFunding;
-                Some(maker.handle_req_contract_sigs_for_sender(message)?)
+                let response = maker.handle_req_contract_sigs_for_sender(message)?;
+                // A refused swap doesn't move on to the funding.
+                if let MakerToTakerMessage::RespContractSigsForSender(_) = response {
+                    connection_state.allowed_message = ExpectedMessage::ProofOfFunding;
+                }
+                Some(response)
             }
             TakerToMakerMessage::RespProofOfFunding(proof) => {
                 connection_state.allowed_message =
@@ -115,8 +130,11 @@ pub async fn handle_message(
         },
         ExpectedMessage::ReqContractSigsForSender => {
             if let TakerToMakerMessage::ReqContractSigsForSender(message) = message {
-                connection_state.allowed_message = ExpectedMessage::ProofOfFunding;
-                Some(maker.handle_req_contract_sigs_for_sender(message)?)
+                let response = maker.handle_req_contract_sigs_for_sender(message)?;
+                if let MakerToTakerMessage::RespContractSigsForSender(_) = response {
+                    connection_state.allowed_message = ExpectedMessage::ProofOfFunding;
+                }
+                Some(response)
             } else {
                 return Err(MakerError::UnexpectedMessage {
                     expected: "ReqContractSigsForSender".to_string(),
@@ -149,7 +167,7 @@ pub async fn handle_message(
                     maker
                         .handle_contract_sigs_for_recvr_and_sender(connection_state, message, ip)
                         .await?;
-                    if let MakerBehavior::BroadcastContractAfterSetup = maker.behavior {
+                    if let MakerBehavior::BroadcastContractAfterSetup = *maker.behavior.read()? {
                         unexpected_recovery(maker.clone())?;
                         return Err(MakerError::General(
                             "Special Maker Behavior BroadcastContractAfterSetup",
@@ -202,16 +220,25 @@ impl Maker {
     /// checks the validity of contract transactions, and provide's the signature for the sender side.
     /// This will fail if the maker doesn't have enough utxos to fund the next coinswap hop, or the contract
     /// transaction isn't valid.
+    ///
+    /// Swaps above [MakerConfig::max_size](crate::maker::config::MakerConfig::max_size), or beyond
+    /// [MakerConfig::max_concurrent_swaps](crate::maker::config::MakerConfig::max_concurrent_swaps), are
+    /// refused with a [MakerToTakerMessage::RespSwapRejected].
     pub fn handle_req_contract_sigs_for_sender(
         &self,
         message: ReqContractSigsForSender,
     ) -> Result<MakerToTakerMessage, MakerError> {
-        if let MakerBehavior::CloseAtReqContractSigsForSender = self.behavior {
+        if let MakerBehavior::CloseAtReqContractSigsForSender = *self.behavior.read()? {
             return Err(MakerError::General(
                 "Special Behavior: CloseAtReqContractSigsForSender",
             ));
         }
 
+        if let Some(rejection) = self.check_swap_limits(&message)? {
+            log::warn!("[{}] Rejecting swap: {}", self.config.port, rejection);
+            return Ok(MakerToTakerMessage::RespSwapRejected(rejection));
+        }
+
         // Verify and sign the contract transaction, check function definition for all the checks.
         let sigs = self.verify_and_sign_contract_tx(&message)?;
 
@@ -234,6 +261,12 @@ impl Maker {
                 Amount::from_sat(total_funding_amount),
                 funding_txids
             );
+            // The swap holds one of the concurrent swap slots from now on, until it completes, is
+            // released or recovered, or its contracts time out.
+            let expiry = Instant::now() + Duration::from_secs(message.locktime as u64 * 10 * 60);
+            self.swaps_in_flight
+                .lock()?
+                .insert(message.hashvalue, expiry);
             Ok(MakerToTakerMessage::RespContractSigsForSender(
                 ContractSigsForSender { sigs },
             ))
@@ -242,6 +275,40 @@ impl Maker {
         }
     }
 
+    /// Checks a new swap against the configured size and concurrency limits.
+    /// Returns the reason to refuse it, if any.
+    fn check_swap_limits(
+        &self,
+        message: &ReqContractSigsForSender,
+    ) -> Result<Option<SwapRejection>, MakerError> {
+        let amount = message
+            .txs_info
+            .iter()
+            .try_fold(0u64, |acc, txinfo| {
+                acc.checked_add(txinfo.funding_input_value.to_sat())
+            })
+            .ok_or(MakerError::General("Total funding amount overflows"))?;
+        if amount > self.config.max_size {
+            return Ok(Some(SwapRejection::AmountTooLarge {
+                amount,
+                max_size: self.config.max_size,
+            }));
+        }
+
+        // Swaps in flight are tracked by swap id. A swap asking again isn't counted twice.
+        let mut swaps_in_flight = self.swaps_in_flight.lock()?;
+        let now = Instant::now();
+        swaps_in_flight.retain(|_, expiry| *expiry > now);
+        if !swaps_in_flight.contains_key(&message.hashvalue)
+            && swaps_in_flight.len() >= self.config.max_concurrent_swaps as usize
+        {
+            return Ok(Some(SwapRejection::TooManySwaps {
+                max_concurrent_swaps: self.config.max_concurrent_swaps,
+            }));
+        }
+        Ok(None)
+    }
+
     /// Validates the [ProofOfFunding] message, initiate the next hop,
     /// and create the `[ReqContractSigsAsRecvrAndSender`\] message.
     pub fn handle_proof_of_funding(
@@ -250,7 +317,7 @@ impl Maker {
         message: ProofOfFunding,
         ip: IpAddr,
     ) -> Result<MakerToTakerMessage, MakerError> {
-        if let MakerBehavior::CloseAtProofOfFunding = self.behavior {
+        if let MakerBehavior::CloseAtProofOfFunding = *self.behavior.read()? {
             return Err(MakerError::General(
                 "Special Behavior: CloseAtProofOfFunding",
             ));
@@ -288,14 +355,11 @@ impl Maker {
                 &funding_info.contract_redeemscript,
             );
 
-            let (tweakable_privkey, _) = self.wallet.read()?.get_tweakable_keypair();
-            let multisig_privkey =
-                tweakable_privkey.add_tweak(&funding_info.multisig_nonce.into())?;
-
-            let multisig_pubkey = PublicKey {
-                compressed: true,
-                inner: secp256k1::PublicKey::from_secret_key(&Secp256k1::new(), &multisig_privkey),
+            let wallet_read = self.wallet.read()?;
+            let multisig_key = SignerKey::Tweaked {
+                nonce: funding_info.multisig_nonce,
             };
+            let multisig_pubkey = wallet_read.signer().public_key(&multisig_key)?;
 
             let other_pubkey = if multisig_pubkey == pubkey1 {
                 pubkey2
@@ -303,19 +367,20 @@ impl Maker {
                 pubkey1
             };
 
-            let hashlock_privkey =
-                tweakable_privkey.add_tweak(&funding_info.hashlock_nonce.into())?;
-
             // Taker can send same funding transactions twice. Happens when one maker in the
             // path fails. Only add it if it din't already existed.
             let incoming_swapcoin = IncomingSwapCoin::new(
-                multisig_privkey,
+                multisig_key,
                 other_pubkey,
                 receiver_contract_tx.clone(),
                 funding_info.contract_redeemscript.clone(),
-                hashlock_privkey,
+                SignerKey::Tweaked {
+                    nonce: funding_info.hashlock_nonce,
+                },
                 funding_output.value,
-            );
+                wallet_read.signer(),
+            )?;
+            drop(wallet_read);
             if !connection_state
                 .incoming_swapcoins
                 .contains(&incoming_swapcoin)
@@ -422,7 +487,7 @@ impl Maker {
                 wallet_writer.add_incoming_swapcoin(incoming_sc);
                 wallet_writer.add_outgoing_swapcoin(outgoing_sc);
             }
-            wallet_writer.save_to_disk()?;
+            wallet_writer.commit()?;
         }
 
         // Craft ReqContractSigsAsRecvrAndSender message to send to the Taker.
@@ -463,7 +528,7 @@ impl Maker {
         message: ContractSigsForRecvrAndSender,
         ip: IpAddr,
     ) -> Result<(), MakerError> {
-        if let MakerBehavior::CloseAtContractSigsForRecvrAndSender = self.behavior {
+        if let MakerBehavior::CloseAtContractSigsForRecvrAndSender = *self.behavior.read()? {
             return Err(MakerError::General(
                 "Special Behavior: CloseAtContractSigsForRecvrAndSender",
             ));
@@ -524,7 +589,7 @@ impl Maker {
                 wallet_writer.add_incoming_swapcoin(incoming_sc);
                 wallet_writer.add_outgoing_swapcoin(outgoing_sc);
             }
-            wallet_writer.save_to_disk()?;
+            wallet_writer.commit()?;
         }
 
         // Update the connection state.
@@ -540,7 +605,7 @@ impl Maker {
         &self,
         message: ReqContractSigsForRecvr,
     ) -> Result<MakerToTakerMessage, MakerError> {
-        if let MakerBehavior::CloseAtContractSigsForRecvr = self.behavior {
+        if let MakerBehavior::CloseAtContractSigsForRecvr = *self.behavior.read()? {
             return Err(MakerError::General(
                 "Speacial Behavior: CloseAtContractSigsForRecvr",
             ));
@@ -550,12 +615,11 @@ impl Maker {
             .txs
             .iter()
             .map(|txinfo| {
-                Ok(self
-                    .wallet
-                    .read()?
+                let wallet_read = self.wallet.read()?;
+                Ok(wallet_read
                     .find_outgoing_swapcoin(&txinfo.multisig_redeemscript)
                     .expect("Outgoing Swapcoin expected")
-                    .sign_contract_tx_with_my_privkey(&txinfo.contract_tx)?)
+                    .sign_contract_tx_with_my_key(&txinfo.contract_tx, wallet_read.signer())?)
             })
             .collect::<Result<Vec<_>, MakerError>>()?;
 
@@ -569,7 +633,7 @@ impl Maker {
         &self,
         message: HashPreimage,
     ) -> Result<MakerToTakerMessage, MakerError> {
-        if let MakerBehavior::CloseAtHashPreimage = self.behavior {
+        if let MakerBehavior::CloseAtHashPreimage = *self.behavior.read()? {
             return Err(MakerError::General("Special Behavior: CloseAtHashPreimage"));
         }
 
@@ -579,12 +643,13 @@ impl Maker {
             let incoming_swapcoin = wallet_write
                 .find_incoming_swapcoin_mut(multisig_redeemscript)
                 .expect("Incoming swampcoin expected");
-            if read_hashvalue_from_contract(&incoming_swapcoin.contract_redeemscript)? != hashvalue
-            {
-                return Err(MakerError::General("not correct hash preimage"));
+            let expected = read_hashvalue_from_contract(&incoming_swapcoin.contract_redeemscript)?;
+            if !verify_preimage(&message.preimage, expected) {
+                return Err(ProtocolError::InvalidPreimage { expected }.into());
             }
             incoming_swapcoin.hash_preimage = Some(message.preimage);
         }
+        self.wallet.write()?.add_swap_preimage(message.preimage);
 
         log::info!(
             "[{}] received preimage for hashvalue={}",
@@ -598,14 +663,16 @@ impl Maker {
             let outgoing_swapcoin = wallet_read
                 .find_outgoing_swapcoin(multisig_redeemscript)
                 .expect("outgoing swapcoin expected");
-            if read_hashvalue_from_contract(&outgoing_swapcoin.contract_redeemscript)? != hashvalue
-            {
-                return Err(MakerError::General("not correct hash preimage"));
+            let expected = read_hashvalue_from_contract(&outgoing_swapcoin.contract_redeemscript)?;
+            if !verify_preimage(&message.preimage, expected) {
+                return Err(ProtocolError::InvalidPreimage { expected }.into());
             }
 
             swapcoin_private_keys.push(MultisigPrivkey {
                 multisig_redeemscript: multisig_redeemscript.clone(),
-                key: outgoing_swapcoin.my_privkey,
+                key: wallet_read
+                    .signer()
+                    .handover_key(&outgoing_swapcoin.my_key)?,
             });
         }
 
@@ -619,11 +686,14 @@ impl Maker {
     /// This is the last step of completing a coinswap round.
     pub fn handle_private_key_handover(&self, message: PrivKeyHandover) -> Result<(), MakerError> {
         for swapcoin_private_key in &message.multisig_privkeys {
-            self.wallet
-                .write()?
+            let mut wallet = self.wallet.write()?;
+            let incoming_swapcoin = wallet
                 .find_incoming_swapcoin_mut(&swapcoin_private_key.multisig_redeemscript)
-                .expect("incoming swapcoin not found")
-                .apply_privkey(swapcoin_private_key.key)?;
+                .expect("incoming swapcoin not found");
+            incoming_swapcoin.apply_privkey(swapcoin_private_key.key)?;
+            self.swaps_in_flight
+                .lock()?
+                .remove(&incoming_swapcoin.get_hashvalue());
         }
         log::info!("initializing Wallet Sync.");
         {
@@ -639,6 +709,7 @@ impl Maker {
 
 fn unexpected_recovery(maker: Arc<Maker>) -> Result<(), MakerError> {
     let mut lock_on_state = maker.connection_state.lock()?;
+    let wallet = maker.wallet.read()?;
     for (_, (state, _)) in lock_on_state.iter_mut() {
         let mut outgoings = Vec::new();
         let mut incomings = Vec::new();
@@ -650,21 +721,23 @@ fn unexpected_recovery(maker: Arc<Maker>) -> Result<(), MakerError> {
             .zip(state.incoming_swapcoins.iter())
         {
             let contract_timelock = og_sc.get_timelock();
-            let contract = og_sc.get_fully_signed_contract_tx()?;
-            let next_internal_address = &maker
-                .wallet
-                .read()
-                .unwrap()
-                .get_next_internal_addresses(1)
-                .unwrap()[0];
-            let time_lock_spend = og_sc.create_timelock_spend(next_internal_address);
+            let contract = og_sc.get_fully_signed_contract_tx(wallet.signer())?;
+            let next_internal_address = &wallet.get_next_internal_addresses(1).unwrap()[0];
+            let time_lock_spend = og_sc.create_timelock_spend(
+                next_internal_address,
+                maker.config.contract_fee_rate,
+                wallet.signer(),
+            )?;
             outgoings.push((
                 (og_sc.get_multisig_redeemscript(), contract),
                 (contract_timelock, time_lock_spend),
             ));
-            let incoming_contract = ic_sc.get_fully_signed_contract_tx().unwrap();
+            let incoming_contract = ic_sc.get_fully_signed_contract_tx(wallet.signer()).unwrap();
             incomings.push((ic_sc.get_multisig_redeemscript(), incoming_contract));
         }
+        if let Some(swap_id) = state.swap_id() {
+            maker.swaps_in_flight.lock()?.remove(&swap_id);
+        }
         // Spawn a separate thread to wait for contract maturity and broadcasting timelocked.
         let maker_clone = maker.clone();
         std::thread::spawn(move || {
@@ -22,7 +22,6 @@ use std::{
 use bitcoin::{absolute::LockTime, Amount};
 use bitcoind::bitcoincore_rpc::RpcApi;
 
-use serde::{Deserialize, Serialize};
 use tokio::{
     io::{AsyncReadExt, BufReader},
     net::{tcp::ReadHalf, TcpListener, TcpStream},
@@ -37,25 +36,84 @@ use std::io::Read;
 use tokio::io::AsyncWriteExt;
 use tokio_socks::tcp::Socks5Stream;
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
-struct OnionAddress {
-    port: String,
-    onion_addr: String,
-}
-
 use crate::{
     maker::{
         api::{check_for_broadcasted_contracts, check_for_idle_states, ConnectionState},
         handlers::handle_message,
         rpc::start_rpc_server_thread,
     },
-    protocol::messages::{MakerHello, MakerToTakerMessage, TakerToMakerMessage},
+    protocol::messages::{
+        MakerHello, MakerToTakerMessage, TakerToMakerMessage, PROTOCOL_VERSION_MAX,
+        PROTOCOL_VERSION_MIN,
+    },
     utill::{monitor_log_for_completion, send_message, ConnectionType},
-    wallet::WalletError,
+    wallet::{FidelityError, WalletError},
 };
 
 use crate::maker::error::MakerError;
 
+/// Creates a fidelity bond of the configured value.
+///
+/// If the wallet doesn't have enough funds yet, waits for them to arrive, checking back every
+/// poll interval. Returns None if the Maker was shut down meanwhile.
+fn create_fidelity_bond(maker: &Maker) -> Result<Option<u32>, MakerError> {
+    let amount = Amount::from_sat(maker.config.fidelity_value);
+    let mut funding_address = None;
+
+    loop {
+        // An extra scope to release the wallet lock while waiting.
+        {
+            let mut wallet = maker.wallet.write()?;
+            let current_height = wallet.rpc.get_block_count().map_err(WalletError::Rpc)? as u32;
+
+            // Set 100 blocks locktime for test
+            let locktime = if cfg!(feature = "integration-test") {
+                LockTime::from_height(current_height + 100).unwrap()
+            } else {
+                LockTime::from_height(maker.config.fidelity_timelock + current_height).unwrap()
+            };
+
+            match wallet.create_fidelity(amount, locktime) {
+                Ok(i) => {
+                    log::info!("[{}] Successfully created fidelity bond", maker.config.port);
+                    return Ok(Some(i));
+                }
+                Err(WalletError::Fidelity(FidelityError::InsufficientFund {
+                    available,
+                    required,
+                })) => {
+                    let address = match &funding_address {
+                        Some(address) => address,
+                        None => funding_address.insert(wallet.get_next_external_address()?),
+                    };
+                    log::warn!(
+                        "[{}] Not enough funds for the fidelity bond. Available: {} sats, Required: {} sats. Send funds to {} to continue. Waiting...",
+                        maker.config.port,
+                        available,
+                        required,
+                        address
+                    );
+                }
+                // Hard error if we cant create fidelity. As without this Maker can't send a valid
+                // Offer to taker.
+                Err(e) => {
+                    log::error!(
+                        "[{}] Fidelity Bond Creation failed: {:?}. Shutting Down Maker server",
+                        maker.config.port,
+                        e
+                    );
+                    return Err(e.into());
+                }
+            }
+        }
+
+        if *maker.shutdown.read()? {
+            return Ok(None);
+        }
+        thread::sleep(Duration::from_secs(maker.config.poll_interval_secs));
+    }
+}
+
 /// Initializes and starts the Maker server, handling connections and various
 /// aspects of the Maker's behavior.
 #[tokio::main]
@@ -64,7 +122,11 @@ pub async fn start_maker_server(maker: Arc<Maker>) -> Result<(), MakerError> {
 
     let mut handle = None;
 
-    let mut maker_address = format!("127.0.0.1:{}", maker_port);
+    #[cfg(feature = "integration-test")]
+    let advertised_port = maker.advertised_port.read()?.unwrap_or(maker_port);
+    #[cfg(not(feature = "integration-test"))]
+    let advertised_port = maker_port;
+    let mut maker_address = format!("127.0.0.1:{}", advertised_port);
 
     match maker.config.connection_type {
         ConnectionType::CLEARNET => {
@@ -246,45 +308,32 @@ pub async fn start_maker_server(maker: Arc<Maker>) -> Result<(), MakerError> {
     });
 
     // Get the highest value fidelity bond from the wallet.
-    {
-        let mut wallet = maker.wallet.write()?;
-        if let Some(i) = wallet.get_highest_fidelity_index()? {
-            let highest_proof = wallet.generate_fidelity_proof(i, maker.config.port.to_string())?;
-            let mut proof = maker.highest_fidelity_proof.write()?;
-            *proof = Some(highest_proof);
-        } else {
-            // No bond in the wallet. Lets attempt to create one.
-            let amount = Amount::from_sat(maker.config.fidelity_value);
-            let current_height = wallet.rpc.get_block_count().map_err(WalletError::Rpc)? as u32;
-
-            // Set 100 blocks locktime for test
-            let locktime = if cfg!(feature = "integration-test") {
-                LockTime::from_height(current_height + 100).unwrap()
-            } else {
-                LockTime::from_height(maker.config.fidelity_timelock + current_height).unwrap()
-            };
-
-            match wallet.create_fidelity(amount, locktime) {
-                // Hard error if we cant create fidelity. As without this Maker can't send a valid
-                // Offer to taker.
-                Err(e) => {
-                    log::error!(
-                        "[{}] Fidelity Bond Creation failed: {:?}. Shutting Down Maker server",
-                        maker.config.port,
-                        e
-                    );
-                    return Err(e.into());
-                }
-                Ok(i) => {
-                    log::info!("[{}] Successfully created fidelity bond", maker.config.port);
-
-                    let address_string = maker_address.clone();
-                    let highest_proof = wallet.generate_fidelity_proof(i, address_string)?;
-                    let mut proof = maker.highest_fidelity_proof.write()?;
-                    *proof = Some(highest_proof);
-                }
+    let highest_index = maker.wallet.read()?.get_highest_fidelity_index()?;
+    let highest_proof = if let Some(i) = highest_index {
+        maker
+            .wallet
+            .read()?
+            .generate_fidelity_proof(i, maker.config.port.to_string())?
+    } else {
+        // No bond in the wallet. Lets attempt to create one.
+        match create_fidelity_bond(&maker)? {
+            Some(i) => maker
+                .wallet
+                .read()?
+                .generate_fidelity_proof(i, maker_address.clone())?,
+            None => {
+                log::warn!(
+                    "[{}] Maker shut down while waiting for fidelity bond funds",
+                    maker.config.port
+                );
+                return Ok(());
             }
         }
+    };
+    *maker.highest_fidelity_proof.write()? = Some(highest_proof);
+
+    {
+        let mut wallet = maker.wallet.write()?;
         log::info!("[{}] Syncing and saving wallet data", maker.config.port);
         wallet.sync()?;
         wallet.save_to_disk()?;
@@ -322,6 +371,11 @@ pub async fn start_maker_server(maker: Arc<Maker>) -> Result<(), MakerError> {
                         }
                         continue;
                     },
+                    MakerError::Protocol(e) => {
+                        // The misbehaving connection is already closed, keep serving the others.
+                        log::warn!("[{}] Dropped a Taker connection : {:?}", maker.config.port, e);
+                        continue;
+                    },
                     _ => {
                         log::error!("[{}] Maker Handling Error : {:?}", maker.config.port, client_err.unwrap());
                         // Either in special Maker behavior, or something went worng.
@@ -375,8 +429,8 @@ pub async fn start_maker_server(maker: Arc<Maker>) -> Result<(), MakerError> {
             if let Err(e) = send_message(
                 &mut socket_writer,
                 &MakerToTakerMessage::MakerHello(MakerHello {
-                    protocol_version_min: 0,
-                    protocol_version_max: 0,
+                    protocol_version_min: PROTOCOL_VERSION_MIN,
+                    protocol_version_max: PROTOCOL_VERSION_MAX,
                 }),
             )
             .await
@@ -403,6 +457,9 @@ pub async fn start_maker_server(maker: Arc<Maker>) -> Result<(), MakerError> {
                     },
                     _ = sleep(Duration::from_secs(maker_clone.config.idle_connection_timeout)) => {
                         log::info!("[{}] Idle connection closed", addr.port());
+                        if let Err(e) = maker_clone.release_idle_connection(addr.ip(), &connection_state) {
+                            log::error!("[{}] Failed to release the idle swap: {:?}", maker_clone.config.port, e);
+                        }
                         break;
                     },
                 };
@@ -1,6 +1,21 @@
 use bitcoind::bitcoincore_rpc::json::ListUnspentResultEntry;
 use serde::{Deserialize, Serialize};
 
+use crate::{
+    protocol::ActiveSwap,
+    wallet::{BalanceBreakdown, FidelityBond},
+};
+
+/// A snapshot of a running Maker, answering [RpcMsgReq::Status].
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct MakerStatus {
+    pub balance: BalanceBreakdown,
+    pub active_swaps: usize,
+    /// The bond advertised in the Maker's offers, if it's set up yet.
+    pub fidelity_bond: Option<FidelityBond>,
+    pub uptime_secs: u64,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub enum RpcMsgReq {
     Ping,
@@ -12,6 +27,8 @@ pub enum RpcMsgReq {
     SwapBalance,
     ContractBalance,
     FidelityBalance,
+    Status,
+    ListSwaps,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -25,4 +42,6 @@ pub enum RpcMsgResp {
     SwapBalanceResp(u64),
     ContractBalanceResp(u64),
     FidleityBalanceResp(u64),
+    StatusResp(MakerStatus),
+    ListSwapsResp(Vec<ActiveSwap>),
 }
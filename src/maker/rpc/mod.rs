@@ -1,5 +1,5 @@
 mod messages;
 mod server;
 
-pub use messages::RpcMsgReq;
+pub use messages::{MakerStatus, RpcMsgReq, RpcMsgResp};
 pub use server::{read_rpc_message, start_rpc_server_thread};
@@ -1,8 +1,11 @@
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
+use serde::de::DeserializeOwned;
 use tokio::{
     io::{AsyncReadExt, BufReader},
     net::{tcp::ReadHalf, TcpListener, TcpStream},
+    select,
+    time::sleep,
 };
 
 use crate::{
@@ -12,10 +15,10 @@ use crate::{
 
 use super::messages::RpcMsgReq;
 
-/// Reads a RPC Message.
-pub async fn read_rpc_message(
+/// Reads a RPC Message. Requests are read as [RpcMsgReq], responses as [RpcMsgResp].
+pub async fn read_rpc_message<T: DeserializeOwned>(
     reader: &mut BufReader<ReadHalf<'_>>,
-) -> Result<Option<RpcMsgReq>, MakerError> {
+) -> Result<Option<T>, MakerError> {
     let read_result = reader.read_u32().await;
     // If its EOF, return None
     if read_result
@@ -30,7 +33,7 @@ pub async fn read_rpc_message(
     }
     let mut buffer = vec![0; length as usize];
     reader.read_exact(&mut buffer).await?;
-    let message: RpcMsgReq = serde_cbor::from_slice(&buffer)?;
+    let message: T = serde_cbor::from_slice(&buffer)?;
     Ok(Some(message))
 }
 
@@ -38,7 +41,7 @@ async fn handle_request(maker: &Arc<Maker>, mut socket: TcpStream) -> Result<(),
     let (socket_reader, mut socket_writer) = socket.split();
     let mut reader = BufReader::new(socket_reader);
 
-    if let Some(rpc_request) = read_rpc_message(&mut reader).await? {
+    if let Some(rpc_request) = read_rpc_message::<RpcMsgReq>(&mut reader).await? {
         match rpc_request {
             RpcMsgReq::Ping => {
                 log::info!("RPC request received: {:?}", rpc_request);
@@ -126,6 +129,18 @@ async fn handle_request(maker: &Arc<Maker>, mut socket: TcpStream) -> Result<(),
                     log::info!("Error sending RPC response {:?}", e);
                 };
             }
+            RpcMsgReq::Status => {
+                let resp = RpcMsgResp::StatusResp(maker.status()?);
+                if let Err(e) = send_message(&mut socket_writer, &resp).await {
+                    log::info!("Error sending RPC response {:?}", e);
+                };
+            }
+            RpcMsgReq::ListSwaps => {
+                let resp = RpcMsgResp::ListSwapsResp(maker.list_active_swaps()?);
+                if let Err(e) = send_message(&mut socket_writer, &resp).await {
+                    log::info!("Error sending RPC response {:?}", e);
+                };
+            }
         }
     }
 
@@ -143,9 +158,27 @@ pub async fn start_rpc_server_thread(maker: Arc<Maker>) {
     );
     tokio::spawn(async move {
         loop {
-            let (socket, addrs) = listener.accept().await.unwrap();
+            let (socket, addrs) = select! {
+                accepted = listener.accept() => match accepted {
+                    Ok(accepted) => accepted,
+                    Err(e) => {
+                        log::error!("Error accepting RPC connection: {:?}", e);
+                        continue;
+                    }
+                },
+                // Check the shutdown flag on every heart beat.
+                _ = sleep(Duration::from_secs(maker.config.heart_beat_interval_secs)) => {
+                    if *maker.shutdown.read().unwrap() {
+                        log::info!("[{}] RPC server shut down", maker.config.port);
+                        return;
+                    }
+                    continue;
+                },
+            };
             log::info!("Got RPC request from: {}", addrs);
-            handle_request(&maker, socket).await.unwrap();
+            if let Err(e) = handle_request(&maker, socket).await {
+                log::error!("Error handling RPC request: {:?}", e);
+            }
         }
     });
 }
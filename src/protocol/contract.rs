@@ -12,22 +12,24 @@ use bitcoin::{
     },
     ecdsa::Signature,
     hashes::Hash,
-    secp256k1::{
-        rand::{rngs::OsRng, RngCore},
-        Message, Secp256k1, SecretKey,
-    },
+    secp256k1::{rand::rngs::OsRng, All, Message, Secp256k1, SecretKey},
     sighash::{EcdsaSighashType, SighashCache},
+    taproot::{TaprootBuilder, TaprootSpendInfo},
     transaction::Version,
     Amount, OutPoint, PublicKey, ScriptBuf, Sequence, Transaction, TxIn, TxOut, Witness,
+    XOnlyPublicKey,
 };
 
 pub use bitcoin::hashes::hash160::Hash as Hash160;
 
-use crate::utill::redeemscript_to_scriptpubkey;
+use crate::{
+    error::ProtocolError,
+    utill::{generate_keypair_with, redeemscript_to_scriptpubkey},
+};
 
 use super::{
     error::ContractError,
-    messages::{FundingTxInfo, ProofOfFunding},
+    messages::{FundingTxInfo, Preimage, ProofOfFunding},
 };
 
 // relatively simple handling of miner fees for now, each funding transaction is considered
@@ -46,6 +48,12 @@ use super::{
 
 /// Constant representing the virtual byte size of a funding transaction.
 pub const FUNDING_TX_VBYTE_SIZE: u64 = 372;
+
+/// The miner fee of one funding transaction at `fee_rate` sats/kvbyte, over [FUNDING_TX_VBYTE_SIZE].
+/// Takers and Makers both pay this for each of their funding transactions.
+pub fn funding_tx_fee(fee_rate: Amount) -> Amount {
+    Amount::from_sat(FUNDING_TX_VBYTE_SIZE * fee_rate.to_sat() / 1000)
+}
 const MIN_HASHV_LEN: usize = 25;
 
 // Used in read_pubkeys_from_multisig_redeemscript() function.
@@ -102,12 +110,14 @@ pub fn create_multisig_redeemscript(key1: &PublicKey, key2: &PublicKey) -> Scrip
 
 /// Derive the maker's public key and nonce from a tweakable point.
 pub fn derive_maker_pubkey_and_nonce(
+    secp: &Secp256k1<All>,
     tweakable_point: &PublicKey,
 ) -> Result<(PublicKey, SecretKey), ContractError> {
-    let mut nonce_bytes = [0u8; 32];
-    OsRng.fill_bytes(&mut nonce_bytes);
-    let nonce = SecretKey::from_slice(&nonce_bytes)?;
-    let maker_pubkey = calculate_pubkey_from_nonce(tweakable_point, &nonce)?;
+    let (nonce_point, nonce) = generate_keypair_with(secp, &mut OsRng);
+    let maker_pubkey = PublicKey {
+        compressed: true,
+        inner: tweakable_point.inner.combine(&nonce_point.inner)?,
+    };
     Ok((maker_pubkey, nonce))
 }
 
@@ -275,6 +285,66 @@ pub fn create_contract_redeemscript(
         .into_script()
 }
 
+/// The hashlock leaf of a taproot contract.
+///
+/// Same conditions as the hashlock branch of [create_contract_redeemscript]: a 32 bytes preimage of
+/// `hashvalue`, a signature of `pub_hashlock`, and 1 block of relative locktime against pinning.
+/// Spent with the witness `<hashlock_signature> <preimage>`.
+fn create_taproot_hashlock_script(pub_hashlock: &PublicKey, hashvalue: &Hash160) -> ScriptBuf {
+    Builder::new()
+        .push_opcode(opcodes::all::OP_SIZE)
+        .push_int(32)
+        .push_opcode(opcodes::all::OP_EQUALVERIFY)
+        .push_opcode(opcodes::all::OP_HASH160)
+        .push_slice(hashvalue.to_byte_array())
+        .push_opcode(opcodes::all::OP_EQUALVERIFY)
+        .push_int(1)
+        .push_opcode(opcodes::all::OP_CSV)
+        .push_opcode(opcodes::all::OP_DROP)
+        .push_x_only_key(&pub_hashlock.inner.x_only_public_key().0)
+        .push_opcode(opcodes::all::OP_CHECKSIG)
+        .into_script()
+}
+
+/// The timelock leaf of a taproot contract.
+///
+/// Same conditions as the timelock branch of [create_contract_redeemscript]: a signature of
+/// `pub_timelock`, after `locktime` blocks of relative locktime. Spent with the witness
+/// `<timelock_signature>`.
+fn create_taproot_timelock_script(pub_timelock: &PublicKey, locktime: &u16) -> ScriptBuf {
+    Builder::new()
+        .push_int(*locktime as i64)
+        .push_opcode(opcodes::all::OP_CSV)
+        .push_opcode(opcodes::all::OP_DROP)
+        .push_x_only_key(&pub_timelock.inner.x_only_public_key().0)
+        .push_opcode(opcodes::all::OP_CHECKSIG)
+        .into_script()
+}
+
+/// Create a taproot contract for a coinswap transaction, with the spending conditions of
+/// [create_contract_redeemscript].
+///
+/// The hashlock and timelock cases are two leaves of the script tree, both at depth 1, committed to
+/// `internal_key`.
+pub fn create_taproot_contract(
+    pub_hashlock: &PublicKey,
+    pub_timelock: &PublicKey,
+    hashvalue: &Hash160,
+    locktime: &u16,
+    internal_key: XOnlyPublicKey,
+) -> TaprootSpendInfo {
+    let secp = Secp256k1::verification_only();
+    TaprootBuilder::new()
+        .add_leaf(1, create_taproot_hashlock_script(pub_hashlock, hashvalue))
+        .and_then(|builder| {
+            builder.add_leaf(1, create_taproot_timelock_script(pub_timelock, locktime))
+        })
+        // Unwrap Safety: two leaves at depth 1 is a complete tree.
+        .expect("valid taproot tree")
+        .finalize(&secp, internal_key)
+        .expect("complete taproot tree")
+}
+
 /// Read the hash value from a contract redeem script.
 pub fn read_hashvalue_from_contract(redeemscript: &Script) -> Result<Hash160, ContractError> {
     if redeemscript.to_bytes().len() < MIN_HASHV_LEN {
@@ -292,6 +362,11 @@ pub fn read_hashvalue_from_contract(redeemscript: &Script) -> Result<Hash160, Co
     Ok(Hash160::from_slice(hash_b.as_bytes())?)
 }
 
+/// Check that a hash preimage hashes to the `expected` hashvalue of a contract.
+pub fn verify_preimage(preimage: &Preimage, expected: Hash160) -> bool {
+    Hash160::hash(preimage) == expected
+}
+
 /// Check that all the contract redeemscripts involve the same hashvalue.
 pub fn check_hashvalues_are_equal(message: &ProofOfFunding) -> Result<Hash160, ContractError> {
     let hashvalues = message
@@ -342,6 +417,34 @@ pub fn read_contract_locktime(redeemscript: &Script) -> Result<u16, ContractErro
     }
 }
 
+/// A relative locktime, as enforced by `OP_CHECKSEQUENCEVERIFY`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RelativeLocktime {
+    /// Number of blocks.
+    Blocks(u32),
+    /// Number of seconds. Rounded up to the 512 seconds granularity of BIP68.
+    Seconds(u32),
+}
+
+/// Encode a relative locktime into a BIP68 input [Sequence].
+///
+/// The disable flag (bit 31) is always cleared and the type flag (bit 22) is set for time based locktimes.
+/// Errors if the value doesn't fit into the 16 bits value field, i.e above 0xFFFF blocks or 0xFFFF * 512 seconds.
+pub fn relative_locktime_to_sequence(
+    blocks_or_time: RelativeLocktime,
+) -> Result<Sequence, ContractError> {
+    match blocks_or_time {
+        RelativeLocktime::Blocks(blocks) => {
+            let blocks: u16 = blocks
+                .try_into()
+                .map_err(|_| ContractError::Protocol("Relative locktime above 0xFFFF blocks"))?;
+            Ok(Sequence::from_height(blocks))
+        }
+        RelativeLocktime::Seconds(seconds) => Sequence::from_seconds_ceil(seconds)
+            .map_err(|_| ContractError::Protocol("Relative locktime above 0xFFFF * 512 seconds")),
+    }
+}
+
 /// Read the hashlock pubkey from a contract redeem script.
 pub fn read_hashlock_pubkey_from_contract(
     redeemscript: &Script,
@@ -458,6 +561,94 @@ pub fn validate_contract_tx(
     Ok(())
 }
 
+/// Check a contract transaction spends exactly the `expected` funding outpoint, and nothing else.
+pub fn validate_contract_prevout(
+    contract_tx: &Transaction,
+    expected: &OutPoint,
+) -> Result<(), ProtocolError> {
+    if contract_tx.input.len() != 1 {
+        return Err(ProtocolError::InvalidContractFields(format!(
+            "{} inputs",
+            contract_tx.input.len()
+        )));
+    }
+    let found = contract_tx.input[0].previous_output;
+    if found != *expected {
+        return Err(ProtocolError::WrongContractPrevout {
+            expected: *expected,
+            found,
+        });
+    }
+    Ok(())
+}
+
+/// Check each declared funding outpoint is an output of the given funding transactions paying to
+/// its multisig redeemscript, and each contract transaction spends exactly its funding outpoint.
+pub fn validate_contract_prevouts_against_funding(
+    contract_txs: &[Transaction],
+    multisig_redeemscripts: &[ScriptBuf],
+    funding_outpoints: &[OutPoint],
+    funding_txs: &[Transaction],
+) -> Result<(), ProtocolError> {
+    if contract_txs.len() != multisig_redeemscripts.len()
+        || contract_txs.len() != funding_outpoints.len()
+    {
+        return Err(ContractError::Protocol(
+            "contracts, multisigs and funding outpoints count mismatch",
+        )
+        .into());
+    }
+    for ((contract_tx, multisig_redeemscript), funding_outpoint) in contract_txs
+        .iter()
+        .zip(multisig_redeemscripts)
+        .zip(funding_outpoints)
+    {
+        let multisig_spk = redeemscript_to_scriptpubkey(multisig_redeemscript);
+        let pays_to_multisig = funding_txs
+            .iter()
+            .find(|tx| tx.compute_txid() == funding_outpoint.txid)
+            .and_then(|funding_tx| funding_tx.output.get(funding_outpoint.vout as usize))
+            .is_some_and(|output| output.script_pubkey == multisig_spk);
+        if !pays_to_multisig {
+            return Err(ContractError::Protocol(
+                "funding outpoint isn't a multisig output of the funding transactions",
+            )
+            .into());
+        }
+        validate_contract_prevout(contract_tx, funding_outpoint)?;
+    }
+    Ok(())
+}
+
+/// Check the version, locktime and sequence of a contract transaction are exactly the ones set by
+/// [create_senders_contract_tx]. Unusual values could enable fee-sniping, or make the contract
+/// unspendable by the expected path.
+pub fn validate_contract_tx_fields(contract_tx: &Transaction) -> Result<(), ProtocolError> {
+    if contract_tx.version != Version::TWO {
+        return Err(ProtocolError::InvalidContractFields(format!(
+            "version {}",
+            contract_tx.version
+        )));
+    }
+    if contract_tx.lock_time != LockTime::ZERO {
+        return Err(ProtocolError::InvalidContractFields(format!(
+            "locktime {}",
+            contract_tx.lock_time
+        )));
+    }
+    if let Some(input) = contract_tx
+        .input
+        .iter()
+        .find(|input| input.sequence != Sequence::ZERO)
+    {
+        return Err(ProtocolError::InvalidContractFields(format!(
+            "sequence {}",
+            input.sequence
+        )));
+    }
+    Ok(())
+}
+
 /// Sign a contract transaction.
 pub fn sign_contract_tx(
     contract_tx: &Transaction,
@@ -533,6 +724,43 @@ mod test {
         Ok((hashpub, timepub))
     }
 
+    #[test]
+    fn test_relative_locktime_to_sequence() {
+        // Block based: the value as is, no flag set.
+        for (blocks, expected) in [
+            (0, 0x0000_0000),
+            (1, 0x0000_0001),
+            (144, 0x0000_0090),
+            (0xFFFF, 0x0000_FFFF),
+        ] {
+            let sequence = relative_locktime_to_sequence(RelativeLocktime::Blocks(blocks)).unwrap();
+            assert_eq!(sequence, Sequence(expected));
+            assert!(sequence.is_relative_lock_time());
+            assert!(sequence.is_height_locked());
+        }
+        // One above the 16 bits value field.
+        assert!(relative_locktime_to_sequence(RelativeLocktime::Blocks(0x1_0000)).is_err());
+        assert!(relative_locktime_to_sequence(RelativeLocktime::Blocks(u32::MAX)).is_err());
+
+        // Time based: type flag set, 512 seconds granularity rounded up.
+        for (seconds, expected) in [
+            (0, 0x0040_0000),
+            (1, 0x0040_0001),
+            (512, 0x0040_0001),
+            (513, 0x0040_0002),
+            (0xFFFF * 512, 0x0040_FFFF),
+        ] {
+            let sequence =
+                relative_locktime_to_sequence(RelativeLocktime::Seconds(seconds)).unwrap();
+            assert_eq!(sequence, Sequence(expected));
+            assert!(sequence.is_relative_lock_time());
+            assert!(sequence.is_time_locked());
+        }
+        assert!(
+            relative_locktime_to_sequence(RelativeLocktime::Seconds(0xFFFF * 512 + 1)).is_err()
+        );
+    }
+
     #[test]
     fn test_maker_pubkey_computation() {
         let secp = Secp256k1::new();
@@ -554,7 +782,7 @@ mod test {
         let privkey_org =
             PrivateKey::from_wif("cVt4o7BGAig1UXywgGSmARhxMdzP5qvQsxKkSsc1XEkw3tDTQFpy").unwrap();
         let pubkey_org = privkey_org.public_key(&secp);
-        let (pubkey_derived, nonce) = derive_maker_pubkey_and_nonce(&pubkey_org).unwrap();
+        let (pubkey_derived, nonce) = derive_maker_pubkey_and_nonce(&secp, &pubkey_org).unwrap();
         let nonce_point = secp256k1::PublicKey::from_secret_key(&secp, &nonce);
         let expected_derivation = PublicKey {
             compressed: true,
@@ -609,6 +837,86 @@ mod test {
         assert_eq!(read_contract_locktime(&contract_script).unwrap(), locktime);
     }
 
+    #[test]
+    fn test_taproot_contract_generation() {
+        use bitcoin::taproot::LeafVersion;
+
+        let hashvalue = Hash160::from_str("a5d5e5b258a8231de2b9d0b374d1d6d3fd0ff3d6").unwrap();
+        let pub_hashlock = PublicKey::from_str(
+            "032e58afe51f9ed8ad3cc7897f634d881fdbe49a81564629ded8156bebd2ffd1af",
+        )
+        .unwrap();
+        let pub_timelock = PublicKey::from_str(
+            "039b6347398505f5ec93826dc61c19f47c66c0283ee9be980e29ce325a0f4679ef",
+        )
+        .unwrap();
+        let internal_key = XOnlyPublicKey::from_str(
+            "bf98c86c3d536136378cf43ac42861ece609de87f5a44e19b730e8e9bd791938",
+        )
+        .unwrap();
+        let locktime = 20;
+
+        let spend_info = create_taproot_contract(
+            &pub_hashlock,
+            &pub_timelock,
+            &hashvalue,
+            &locktime,
+            internal_key,
+        );
+
+        // Below are hand made leaf scripts that should be expected
+        let hashlock_script = ScriptBuf::from_hex(
+            &("82012088a914".to_owned()
+                + &hashvalue.to_string()
+                + "8851b27520"
+                + &pub_hashlock.to_string()[2..]
+                + "ac"),
+        )
+        .unwrap();
+        let timelock_script =
+            ScriptBuf::from_hex(&("0114b27520".to_owned() + &pub_timelock.to_string()[2..] + "ac"))
+                .unwrap();
+        assert_eq!(spend_info.script_map().len(), 2);
+        let secp = Secp256k1::verification_only();
+        for script in [hashlock_script, timelock_script] {
+            let control_block = spend_info
+                .control_block(&(script.clone(), LeafVersion::TapScript))
+                .unwrap();
+            assert!(control_block.verify_taproot_commitment(
+                &secp,
+                spend_info.output_key().to_x_only_public_key(),
+                &script
+            ));
+        }
+
+        // The tree commitment is stable.
+        assert_eq!(spend_info.internal_key(), internal_key);
+        assert_eq!(
+            spend_info.merkle_root().unwrap().to_string(),
+            "7b0d0bb08b43d7386b7f539ac54e3d8518b6d539500408b99159b8452bb2d34f"
+        );
+        assert_eq!(
+            create_taproot_contract(
+                &pub_hashlock,
+                &pub_timelock,
+                &hashvalue,
+                &locktime,
+                internal_key
+            ),
+            spend_info
+        );
+    }
+
+    #[test]
+    fn test_verify_preimage() {
+        let preimage = [7u8; 32];
+        let hashvalue = Hash160::hash(&preimage);
+        assert!(verify_preimage(&preimage, hashvalue));
+        // A different preimage, or an all-zero one, doesn't match.
+        assert!(!verify_preimage(&[8u8; 32], hashvalue));
+        assert!(!verify_preimage(&[0u8; 32], hashvalue));
+    }
+
     #[test]
     fn test_pubkey_extraction_from_2of2_multisig() {
         // Create pubkeys to contruct 2of2 multi
@@ -805,6 +1113,132 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_contract_prevout_validation() {
+        let contract_script = ScriptBuf::from(
+            Vec::from_hex(
+                "827ca91414cdf8fe0b7b2db2bd976f27fb6f3cd5f9228633876321038cc778b555c3fe2b01d1b550a07\
+            d26e38c026c4c4e1dee2a41f0431283230ee0012051672102b6b9ab72d42fb625a24598a792fa5346aa\
+            64d728b446f7560f4ce1c29378b22c00012868b2757b88ac"
+            ).unwrap()
+        );
+        let multisig_redeemscript = ScriptBuf::from(
+            Vec::from_hex(
+                "5221032e58afe51f9ed8ad3cc7897f634d881fdbe49a81564629ded8156bebd2ffd1af21039b6347398505f5ec93826dc61c19f47c66c0283ee9be980e29ce325a0f4679ef52ae"
+            ).unwrap()
+        );
+        let funding_tx = Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: vec![],
+            output: vec![
+                TxOut {
+                    value: Amount::from_sat(1000),
+                    script_pubkey: ScriptBuf::new(),
+                },
+                TxOut {
+                    value: Amount::from_sat(30000),
+                    script_pubkey: redeemscript_to_scriptpubkey(&multisig_redeemscript),
+                },
+            ],
+        };
+        let funding_outpoint = OutPoint::new(funding_tx.compute_txid(), 1);
+
+        let contract_tx =
+            create_senders_contract_tx(funding_outpoint, Amount::from_sat(30000), &contract_script);
+        assert!(validate_contract_prevout(&contract_tx, &funding_outpoint).is_ok());
+        assert!(validate_contract_prevouts_against_funding(
+            std::slice::from_ref(&contract_tx),
+            std::slice::from_ref(&multisig_redeemscript),
+            &[funding_outpoint],
+            std::slice::from_ref(&funding_tx)
+        )
+        .is_ok());
+
+        // A contract substituting another output of the funding tx.
+        let wrong_outpoint = OutPoint::new(funding_tx.compute_txid(), 0);
+        let wrong_contract_tx =
+            create_senders_contract_tx(wrong_outpoint, Amount::from_sat(30000), &contract_script);
+        match validate_contract_prevout(&wrong_contract_tx, &funding_outpoint) {
+            Err(ProtocolError::WrongContractPrevout { expected, found }) => {
+                assert_eq!(expected, funding_outpoint);
+                assert_eq!(found, wrong_outpoint);
+            }
+            other => panic!("expected WrongContractPrevout, got {:?}", other),
+        }
+        assert!(matches!(
+            validate_contract_prevouts_against_funding(
+                std::slice::from_ref(&wrong_contract_tx),
+                std::slice::from_ref(&multisig_redeemscript),
+                &[funding_outpoint],
+                std::slice::from_ref(&funding_tx)
+            ),
+            Err(ProtocolError::WrongContractPrevout { .. })
+        ));
+
+        // A declared funding outpoint that isn't the multisig output, even if the contract spends it.
+        assert!(validate_contract_prevouts_against_funding(
+            &[wrong_contract_tx],
+            std::slice::from_ref(&multisig_redeemscript),
+            &[wrong_outpoint],
+            std::slice::from_ref(&funding_tx)
+        )
+        .is_err());
+
+        // A funding outpoint outside the funding txs.
+        assert!(validate_contract_prevouts_against_funding(
+            &[contract_tx],
+            &[multisig_redeemscript],
+            &[funding_outpoint],
+            &[]
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_contract_tx_fields_validation() {
+        let contract_script = ScriptBuf::from(
+            Vec::from_hex(
+                "827ca91414cdf8fe0b7b2db2bd976f27fb6f3cd5f9228633876321038cc778b555c3fe2b01d1b550a07\
+            d26e38c026c4c4e1dee2a41f0431283230ee0012051672102b6b9ab72d42fb625a24598a792fa5346aa\
+            64d728b446f7560f4ce1c29378b22c00012868b2757b88ac"
+            ).unwrap()
+        );
+        let contract_tx = create_senders_contract_tx(
+            OutPoint::from_str(
+                "5df6e0e2761359d30a8275058e299fcc0381534545f55cf43e41983f5d4c9456:42",
+            )
+            .unwrap(),
+            Amount::from_sat(30000),
+            &contract_script,
+        );
+        assert!(validate_contract_tx_fields(&contract_tx).is_ok());
+
+        // Tampered sequence, enabling a relative timelock on the contract tx.
+        let mut tampered = contract_tx.clone();
+        tampered.input[0].sequence = Sequence::from_height(10);
+        assert!(matches!(
+            validate_contract_tx_fields(&tampered),
+            Err(ProtocolError::InvalidContractFields(_))
+        ));
+
+        // Tampered locktime.
+        let mut tampered = contract_tx.clone();
+        tampered.lock_time = LockTime::from_height(800_000).unwrap();
+        assert!(matches!(
+            validate_contract_tx_fields(&tampered),
+            Err(ProtocolError::InvalidContractFields(_))
+        ));
+
+        // Tampered version.
+        let mut tampered = contract_tx;
+        tampered.version = Version::ONE;
+        assert!(matches!(
+            validate_contract_tx_fields(&tampered),
+            Err(ProtocolError::InvalidContractFields(_))
+        ));
+    }
+
     #[test]
     fn test_contract_sig_validation() {
         // First create a funding transaction
@@ -904,8 +1338,8 @@ mod test {
         let pubkey_1 = privkey_1.public_key(&secp);
         let pubkey_2 = privkey_2.public_key(&secp);
 
-        let (pubkey_derived_1, nonce_1) = derive_maker_pubkey_and_nonce(&pubkey_1).unwrap();
-        let (pubkey_derived_2, nonce_2) = derive_maker_pubkey_and_nonce(&pubkey_2).unwrap();
+        let (pubkey_derived_1, nonce_1) = derive_maker_pubkey_and_nonce(&secp, &pubkey_1).unwrap();
+        let (pubkey_derived_2, nonce_2) = derive_maker_pubkey_and_nonce(&secp, &pubkey_2).unwrap();
 
         let script_value = create_multisig_redeemscript(&pubkey_derived_1, &pubkey_derived_2);
         let script = script_value.as_script();
@@ -930,7 +1364,7 @@ mod test {
         let private_key_1 =
             PrivateKey::from_wif("cVt4o7BGAig1UXywgGSmARhxMdzP5qvQsxKkSsc1XEkw3tDTQFpy").unwrap();
         let public_key_1 = private_key_1.public_key(&secp);
-        let (pub_hashlock, nonce) = derive_maker_pubkey_and_nonce(&public_key_1).unwrap();
+        let (pub_hashlock, nonce) = derive_maker_pubkey_and_nonce(&secp, &public_key_1).unwrap();
 
         let pub_timelock = PublicKey::from_str(
             "039b6347398505f5ec93826dc61c19f47c66c0283ee9be980e29ce325a0f4679ef",
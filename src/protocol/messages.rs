@@ -54,6 +54,12 @@
 //! Taker -> Maker2: [`TakerToMakerMessage::RespHashPreimage`] (for Maker2-Taker HTLC).
 //! Maker2 -> Taker: [`MakerToTakerMessage::RespPrivKeyHandover`] (For Maker2-Taker funding multisig).
 //! ```
+//!
+//! The connection starts with the [MakerHello] and [TakerHello] exchange, carrying each side's
+//! supported protocol version range. Both sides settle on the version picked by [negotiate_version].
+//!
+//! A Maker that can't take the swap answers [TakerToMakerMessage::ReqContractSigsForSender] with
+//! [MakerToTakerMessage::RespSwapRejected] instead, and the Taker moves on to another Maker.
 
 use std::fmt::Display;
 
@@ -66,7 +72,31 @@ use serde::{Deserialize, Serialize};
 
 use bitcoin::hashes::hash160::Hash as Hash160;
 
-use crate::wallet::FidelityBond;
+use crate::{error::ProtocolError, wallet::FidelityBond};
+
+/// Lowest protocol version supported by this implementation.
+pub const PROTOCOL_VERSION_MIN: u32 = 0;
+
+/// Highest protocol version supported by this implementation.
+pub const PROTOCOL_VERSION_MAX: u32 = 0;
+
+/// Pick the highest protocol version in both the Taker's and the Maker's inclusive (min, max) ranges.
+///
+/// Errors with [ProtocolError::NoCompatibleVersion] if the ranges don't overlap.
+pub fn negotiate_version(
+    taker_range: (u32, u32),
+    maker_range: (u32, u32),
+) -> Result<u32, ProtocolError> {
+    let min = taker_range.0.max(maker_range.0);
+    let max = taker_range.1.min(maker_range.1);
+    if min > max {
+        return Err(ProtocolError::NoCompatibleVersion {
+            taker_range,
+            maker_range,
+        });
+    }
+    Ok(max)
+}
 
 /// Defines the length of the Preimage.
 pub const PREIMAGE_LEN: usize = 32;
@@ -247,6 +277,29 @@ pub struct Offer {
     pub min_size: u64,
     pub tweakable_point: PublicKey,
     pub fidelity: FidelityProof,
+    /// Free text from the maker, like contact info or terms. Untrusted, only for display.
+    #[serde(default)]
+    pub announcement: Option<String>,
+}
+
+/// Maximum length of a maker announcement, in characters.
+pub const MAX_ANNOUNCEMENT_LEN: usize = 256;
+
+/// Sanitize a maker announcement for display. Line breaks and other whitespace become spaces, the
+/// other control characters are dropped, and the text is capped at [MAX_ANNOUNCEMENT_LEN] characters.
+/// Returns `None` if nothing is left.
+pub fn sanitize_announcement(announcement: &str) -> Option<String> {
+    let sanitized = announcement
+        .chars()
+        .filter_map(|c| match c {
+            c if c.is_whitespace() => Some(' '),
+            c if c.is_control() => None,
+            c => Some(c),
+        })
+        .take(MAX_ANNOUNCEMENT_LEN)
+        .collect::<String>();
+    let sanitized = sanitized.trim();
+    (!sanitized.is_empty()).then(|| sanitized.to_string())
 }
 
 /// Contract Tx signatures provided by a Sender of a Coinswap.
@@ -280,6 +333,34 @@ pub struct ContractSigsForRecvr {
     pub sigs: Vec<Signature>,
 }
 
+/// Reasons for a Maker to refuse a swap. The Taker can move on to another Maker.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub enum SwapRejection {
+    /// The swap amount is above the Maker's maximum size.
+    AmountTooLarge { amount: u64, max_size: u64 },
+    /// The Maker already has its maximum number of swaps in flight.
+    TooManySwaps { max_concurrent_swaps: u32 },
+}
+
+impl Display for SwapRejection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::AmountTooLarge { amount, max_size } => write!(
+                f,
+                "swap amount {} sats is above the maximum of {} sats",
+                amount, max_size
+            ),
+            Self::TooManySwaps {
+                max_concurrent_swaps,
+            } => write!(
+                f,
+                "already running the maximum of {} swaps",
+                max_concurrent_swaps
+            ),
+        }
+    }
+}
+
 /// All messages sent from Maker to Taker.
 #[derive(Debug, Serialize, Deserialize)]
 pub enum MakerToTakerMessage {
@@ -295,6 +376,8 @@ pub enum MakerToTakerMessage {
     RespContractSigsForRecvr(ContractSigsForRecvr),
     /// Send the multisig private keys of the swap, declaring completion of the contract.
     RespPrivKeyHandover(PrivKeyHandover),
+    /// Refuse to take part in the swap.
+    RespSwapRejected(SwapRejection),
 }
 
 impl Display for MakerToTakerMessage {
@@ -310,6 +393,38 @@ impl Display for MakerToTakerMessage {
                 write!(f, "RespContractSigsForRecvr")
             }
             Self::RespPrivKeyHandover(_) => write!(f, "RespPrivKeyHandover"),
+            Self::RespSwapRejected(_) => write!(f, "RespSwapRejected"),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_negotiate_version() {
+        // Overlapping ranges settle on the highest common version.
+        assert_eq!(negotiate_version((0, 3), (2, 5)).unwrap(), 3);
+        assert_eq!(negotiate_version((1, 4), (0, 2)).unwrap(), 2);
+        // Single point ranges.
+        assert_eq!(negotiate_version((1, 1), (0, 2)).unwrap(), 1);
+        assert_eq!(
+            negotiate_version(
+                (PROTOCOL_VERSION_MIN, PROTOCOL_VERSION_MAX),
+                (PROTOCOL_VERSION_MIN, PROTOCOL_VERSION_MAX)
+            )
+            .unwrap(),
+            PROTOCOL_VERSION_MAX
+        );
+        // Disjoint ranges.
+        assert!(matches!(
+            negotiate_version((0, 1), (2, 3)),
+            Err(ProtocolError::NoCompatibleVersion {
+                taker_range: (0, 1),
+                maker_range: (2, 3)
+            })
+        ));
+        assert!(negotiate_version((2, 2), (1, 1)).is_err());
+    }
+}
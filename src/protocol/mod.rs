@@ -5,3 +5,21 @@ pub mod error;
 pub mod messages;
 
 pub use contract::Hash160;
+
+use bitcoin::Amount;
+use serde::{Deserialize, Serialize};
+
+/// Summary of an in-flight swap, as seen by one of its participants.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ActiveSwap {
+    /// The swap id. This is the hashvalue locking all the contracts of the swap.
+    pub id: Hash160,
+    /// The current protocol step.
+    pub step: String,
+    /// Addresses of the counterparties we are swapping with.
+    pub counterparties: Vec<String>,
+    /// Total amount we have committed into outgoing contracts.
+    pub committed_amount: Amount,
+    /// The earliest contract locktime (in blocks) of the swap, if any contract is set up yet.
+    pub earliest_locktime: Option<u16>,
+}
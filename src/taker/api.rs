@@ -19,7 +19,7 @@ use std::{
 
 use bip39::Mnemonic;
 use bitcoind::bitcoincore_rpc::RpcApi;
-use tokio::{net::TcpStream, select, time::sleep};
+use tokio::{io::AsyncReadExt, select, time::sleep};
 
 use bitcoin::{
     consensus::encode::deserialize,
@@ -30,28 +30,31 @@ use bitcoin::{
     },
     Amount, BlockHash, Network, OutPoint, PublicKey, ScriptBuf, Transaction, Txid,
 };
-use tokio_socks::tcp::Socks5Stream;
 
 use super::{
     error::TakerError,
+    fees::{resolve_fee_rate, CoreFeeEstimator, FeeEstimator, FeeSource, StaticFeeEstimator},
     offers::{fetch_addresses_from_dns, fetch_offer_from_makers, MakerAddress, OfferAndAddress},
     routines::*,
 };
 use crate::{
-    error::{NetError, ProtocolError},
+    error::ProtocolError,
     protocol::{
+        contract::validate_contract_prevouts_against_funding,
         error::ContractError,
         messages::{
             ContractSigsAsRecvrAndSender, ContractSigsForRecvr, ContractSigsForRecvrAndSender,
             ContractSigsForSender, FundingTxInfo, MultisigPrivkey, Preimage, PrivKeyHandover,
             TakerToMakerMessage,
         },
+        ActiveSwap,
     },
     taker::{config::TakerConfig, offers::OfferBook},
     utill::*,
     wallet::{
-        IncomingSwapCoin, OutgoingSwapCoin, RPCConfig, SwapCoin, Wallet, WalletSwapCoin,
-        WatchOnlySwapCoin,
+        sign_contract_tx_with_key, ContractBroadcastDetected, IncomingSwapCoin, OutgoingSwapCoin,
+        RPCConfig, RemoteSigner, SignerKey, SwapCheckpoint, SwapCoin, Wallet, WalletError,
+        WalletSwapCoin, WatchOnlySwapCoin,
     },
 };
 
@@ -69,8 +72,122 @@ pub struct SwapParams {
     // TODO: Following two should be moved to TakerConfig as global configuration.
     /// Confirmation count required for funding txs.
     pub required_confirms: u64,
-    /// Fee rate for funding txs.
+    /// Fee rate for funding txs, in sats/kvbyte. [SwapParams::ESTIMATED_FEE_RATE] takes it from
+    /// [TakerConfig::fee_source].
     pub fee_rate: Amount,
+    /// Confirmation depth the received swap coins must reach before the swap returns.
+    /// `0` returns right after the private key handover.
+    pub wait_for_confirms: u32,
+    /// Overrides [TakerConfig::refund_locktime] for this swap. A longer locktime trades speed for safety.
+    pub refund_locktime: Option<u16>,
+    /// Overrides [TakerConfig::refund_locktime_step] for this swap. Makers requiring a longer reaction
+    /// time than the step are skipped.
+    pub refund_locktime_step: Option<u16>,
+}
+
+impl SwapParams {
+    /// Fee rate placeholder, resolved from [TakerConfig::fee_source] when the swap starts.
+    pub const ESTIMATED_FEE_RATE: Amount = Amount::MAX;
+
+    /// Check the parameters describe a possible swap, before any maker is contacted. The refund
+    /// locktimes not overridden are taken from `config`, and its contract fee rate must be above the
+    /// funding fee rate, once resolved.
+    pub fn validate(&self, config: &TakerConfig) -> Result<(), TakerError> {
+        if self.maker_count == 0 {
+            return Err(TakerError::NoMakersRequested);
+        }
+        if self.tx_count == 0 {
+            return Err(TakerError::NoFundingTxsRequested);
+        }
+        if self.send_amount == Amount::ZERO {
+            return Err(TakerError::SendAmountNotSet);
+        }
+        if self.fee_rate == Amount::ZERO {
+            return Err(TakerError::FeeRateNotSet);
+        }
+        // The recoveries must outbid the funding txs, whichever fee source the rate came from.
+        let funding_fee_rate = self.fee_rate.to_sat().div_ceil(1000);
+        if config.contract_fee_rate <= funding_fee_rate {
+            return Err(TakerError::ContractFeeRateTooLow {
+                contract_fee_rate: config.contract_fee_rate,
+                funding_fee_rate,
+            });
+        }
+        // The first hop has the highest locktime, all the others fit if it does.
+        let (refund_locktime, refund_locktime_step) = self.refund_locktimes(config);
+        refund_locktime_step
+            .checked_mul(self.maker_count)
+            .and_then(|steps| steps.checked_add(refund_locktime))
+            .ok_or(TakerError::LocktimeOverflow {
+                refund_locktime,
+                refund_locktime_step,
+                maker_count: self.maker_count,
+            })?;
+        Ok(())
+    }
+
+    /// The refund locktime and step of the swap, overrides first, else from `config`.
+    pub fn refund_locktimes(&self, config: &TakerConfig) -> (u16, u16) {
+        (
+            self.refund_locktime.unwrap_or(config.refund_locktime),
+            self.refund_locktime_step
+                .unwrap_or(config.refund_locktime_step),
+        )
+    }
+}
+
+/// Advisory warnings about a swap's privacy benefit being undermined. They don't block the swap.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PrivacyWarning {
+    /// The send amount is a round number, making the swap outputs trivially linkable by value.
+    RoundAmount(Amount),
+    /// Too few makers in the route. A single maker sees both ends of the swap.
+    TooFewHops { maker_count: u16 },
+    /// Several spendable coins sit on the same address. Funding the swap with them links them together.
+    ReusedAddress {
+        script_pubkey: ScriptBuf,
+        utxo_count: usize,
+    },
+}
+
+/// The fees a maker of the route takes, see [SwapCostEstimate].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MakerFeeEstimate {
+    pub address: MakerAddress,
+    /// Incoming amount of the maker's hop.
+    pub amount_in: Amount,
+    /// The maker's coinswap fee, from its offer.
+    pub coinswap_fee: Amount,
+    /// Miner fees of the maker's funding txs, paid by the Taker.
+    pub miner_fee: Amount,
+}
+
+/// Dry-run cost breakdown of a swap, see [Taker::estimate_swap_cost].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SwapCostEstimate {
+    /// The makers of the route, in hop order.
+    pub maker_fees: Vec<MakerFeeEstimate>,
+    /// Miner fees of the Taker's own funding txs, paid on top of the send amount.
+    pub own_miner_fee: Amount,
+    /// The amount the Taker receives from the last hop.
+    pub net_received: Amount,
+}
+
+impl SwapCostEstimate {
+    /// The coinswap fees of all the makers.
+    pub fn total_coinswap_fees(&self) -> Amount {
+        self.maker_fees.iter().map(|m| m.coinswap_fee).sum()
+    }
+
+    /// All the miner fees, the makers' and the Taker's own.
+    pub fn total_miner_fees(&self) -> Amount {
+        self.own_miner_fee + self.maker_fees.iter().map(|m| m.miner_fee).sum()
+    }
+
+    /// The total cost of the swap.
+    pub fn total_fees(&self) -> Amount {
+        self.total_coinswap_fees() + self.total_miner_fees()
+    }
 }
 
 // Defines the Taker's position in the current ongoing swap.
@@ -111,6 +228,54 @@ struct OngoingSwapState {
     pub taker_position: TakerPosition,
 }
 
+impl OngoingSwapState {
+    /// Summarize the ongoing swap round. Returns None if no swap has been initiated.
+    fn active_swap(&self) -> Option<ActiveSwap> {
+        if self.peer_infos.is_empty() {
+            return None;
+        }
+
+        let step = if self.outgoing_swapcoins.is_empty() {
+            "Initiating first hop".to_string()
+        } else if self.incoming_swapcoins.is_empty() {
+            format!(
+                "Setting up hop {} of {}",
+                self.peer_infos.len(),
+                self.swap_params.maker_count
+            )
+        } else {
+            "Settling swaps".to_string()
+        };
+
+        let earliest_locktime = self
+            .outgoing_swapcoins
+            .iter()
+            .map(|swapcoin| swapcoin.get_timelock())
+            .chain(
+                self.incoming_swapcoins
+                    .iter()
+                    .map(|swapcoin| swapcoin.get_timelock()),
+            )
+            .min();
+
+        Some(ActiveSwap {
+            id: Hash160::hash(&self.active_preimage),
+            step,
+            counterparties: self
+                .peer_infos
+                .iter()
+                .map(|peer_info| peer_info.peer.address.to_string())
+                .collect(),
+            committed_amount: self
+                .outgoing_swapcoins
+                .iter()
+                .map(|swapcoin| swapcoin.funding_amount)
+                .sum(),
+            earliest_locktime,
+        })
+    }
+}
+
 /// Information for the next maker in the hop.
 #[derive(Debug, Clone)]
 struct NextPeerInfo {
@@ -131,6 +296,20 @@ pub enum TakerBehavior {
     DropConnectionAfterFullSetup,
     /// Behavior to broadcast the contract after the full coinswap setup.
     BroadcastContractAfterFullSetup,
+    /// The taker goes silent after the first maker's reply to the proof of funding, keeping the
+    /// connection open until the maker closes it.
+    StallAfterProofOfFunding,
+}
+
+/// Outcome of [Taker::resume_swap] and [Taker::refund_unfinished_swap].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecoveredSwap {
+    /// The swap had already been settled before the Taker stopped.
+    Completed,
+    /// The swap had been set up in full before the Taker stopped, and got settled with its Makers.
+    Settled,
+    /// The swap was unfinished, its contracts were broadcasted and the outgoing coins refunded.
+    Refunded,
 }
 
 /// The Taker structure that performs bulk of the coinswap protocol. Taker connects
@@ -140,6 +319,7 @@ pub struct Taker {
     wallet: Wallet,
     pub config: TakerConfig,
     offerbook: OfferBook,
+    offerbook_path: PathBuf,
     ongoing_swap_state: OngoingSwapState,
     behavior: TakerBehavior,
 }
@@ -159,12 +339,38 @@ impl Taker {
     /// rpc_conf: None = Use the default [RPCConfig].
     ///
     /// behavior: Defines special Maker behavior. Only applicable in integration-tests.
+    ///
+    /// strict_config: Fail on invalid config values, instead of falling back to their defaults. See [TakerConfig::new_strict].
     pub fn init(
         data_dir: Option<PathBuf>,
         wallet_file_name: Option<String>,
         rpc_config: Option<RPCConfig>,
         behavior: TakerBehavior,
         connection_type: Option<ConnectionType>,
+        strict_config: bool,
+    ) -> Result<Taker, TakerError> {
+        Self::init_with_signer(
+            data_dir,
+            wallet_file_name,
+            rpc_config,
+            behavior,
+            connection_type,
+            strict_config,
+            None,
+        )
+    }
+
+    /// Same as [Taker::init], with the wallet keys held by `signer`. See [Wallet::init_with_signer].
+    ///
+    /// signer: None = The keys are held in the wallet file.
+    pub fn init_with_signer(
+        data_dir: Option<PathBuf>,
+        wallet_file_name: Option<String>,
+        rpc_config: Option<RPCConfig>,
+        behavior: TakerBehavior,
+        connection_type: Option<ConnectionType>,
+        strict_config: bool,
+        signer: Option<Box<dyn RemoteSigner + Send + Sync>>,
     ) -> Result<Taker, TakerError> {
         // Only allow Special Behavior in functional tests
         let behavior = if cfg!(feature = "integration-test") {
@@ -179,13 +385,50 @@ impl Taker {
 
         let mut rpc_config = rpc_config.unwrap_or_default();
 
+        // If config file doesn't exist, default config will be loaded.
+        let config_path = data_dir.join("config.toml");
+        let mut config = if strict_config {
+            TakerConfig::new_strict(Some(&config_path))?
+        } else {
+            TakerConfig::new(Some(&config_path))?
+        };
+
+        let encryption_passphrase = config.wallet_encryption_passphrase.as_deref();
+
         // Load/Create wallet depending on if a wallet with wallet_file_name exists.
-        let mut wallet = if let Some(file_name) = wallet_file_name {
+        let mut wallet = if let Some(signer) = signer {
+            // The keys are held by the signer, the default file name is its master fingerprint.
+            let file_name = match wallet_file_name {
+                Some(file_name) => file_name,
+                None => format!("{}-taker", signer.wallet_pubkeys()?.master_fingerprint),
+            };
+            let wallet_path = wallets_dir.join(&file_name);
+            rpc_config.wallet_name = file_name;
+            if wallet_path.exists() {
+                let wallet = Wallet::load_with_signer(
+                    &rpc_config,
+                    &wallet_path,
+                    signer,
+                    encryption_passphrase,
+                )?;
+                log::info!("Wallet file at {:?} successfully loaded.", wallet_path);
+                wallet
+            } else {
+                let wallet = Wallet::init_with_signer(
+                    &wallet_path,
+                    &rpc_config,
+                    signer,
+                    encryption_passphrase,
+                )?;
+                log::info!("New Wallet created at : {:?}", wallet_path);
+                wallet
+            }
+        } else if let Some(file_name) = wallet_file_name {
             let wallet_path = wallets_dir.join(&file_name);
             rpc_config.wallet_name = file_name;
             if wallet_path.exists() {
                 // Try loading wallet
-                let wallet = Wallet::load(&rpc_config, &wallet_path)?;
+                let wallet = Wallet::load(&rpc_config, &wallet_path, encryption_passphrase)?;
                 log::info!("Wallet file at {:?} successfully loaded.", wallet_path);
                 wallet
             } else {
@@ -193,7 +436,13 @@ impl Taker {
                 let mnemonic = Mnemonic::generate(12).unwrap();
                 let seedphrase = mnemonic.to_string();
 
-                let wallet = Wallet::init(&wallet_path, &rpc_config, seedphrase, "".to_string())?;
+                let wallet = Wallet::init(
+                    &wallet_path,
+                    &rpc_config,
+                    seedphrase,
+                    "".to_string(),
+                    encryption_passphrase,
+                )?;
                 log::info!("New Wallet created at : {:?}", wallet_path);
                 wallet
             }
@@ -208,14 +457,17 @@ impl Taker {
             let wallet_path = wallets_dir.join(&file_name);
             rpc_config.wallet_name = file_name;
 
-            let wallet = Wallet::init(&wallet_path, &rpc_config, seedphrase, "".to_string())?;
+            let wallet = Wallet::init(
+                &wallet_path,
+                &rpc_config,
+                seedphrase,
+                "".to_string(),
+                encryption_passphrase,
+            )?;
             log::info!("New Wallet created at : {:?}", wallet_path);
             wallet
         };
 
-        // If config file doesn't exist, default config will be loaded.
-        let mut config = TakerConfig::new(Some(&data_dir.join("config.toml")))?;
-
         if let Some(connection_type) = connection_type {
             config.connection_type = connection_type;
         }
@@ -224,10 +476,22 @@ impl Taker {
         wallet.sync()?;
         log::info!("Completed wallet sync");
 
+        // Reuse the offers and the bad makers of the previous runs.
+        let offerbook_path = data_dir.join("offerbook.json");
+        let offerbook = if offerbook_path.exists() {
+            OfferBook::load(&offerbook_path).unwrap_or_else(|e| {
+                log::warn!("Could not load the offerbook, starting afresh: {:?}", e);
+                OfferBook::default()
+            })
+        } else {
+            OfferBook::default()
+        };
+
         Ok(Self {
             wallet,
             config,
-            offerbook: OfferBook::default(),
+            offerbook,
+            offerbook_path,
             ongoing_swap_state: OngoingSwapState::default(),
             behavior,
         })
@@ -241,6 +505,24 @@ impl Taker {
         &mut self.wallet
     }
 
+    /// The backend polling interval. Configured by [TakerConfig::poll_interval_secs], or else follows
+    /// the required confirmations of the ongoing swap.
+    pub fn poll_interval(&self) -> Duration {
+        Duration::from_secs(self.config.poll_interval_secs.unwrap_or_else(|| {
+            default_poll_interval_secs(self.ongoing_swap_state.swap_params.required_confirms)
+        }))
+    }
+
+    /// The fee estimator selected by [TakerConfig::fee_source].
+    pub fn fee_estimator(&self) -> Box<dyn FeeEstimator + '_> {
+        match self.config.fee_source {
+            FeeSource::Core { conf_target } => {
+                Box::new(CoreFeeEstimator::new(&self.wallet.rpc, conf_target))
+            }
+            FeeSource::Static(fee_rate) => Box::new(StaticFeeEstimator(Amount::from_sat(fee_rate))),
+        }
+    }
+
     #[tokio::main]
     pub async fn do_coinswap(&mut self, swap_params: SwapParams) -> Result<(), TakerError> {
         let tor_log_dir = "/tmp/tor-rust-taker/log".to_string();
@@ -280,7 +562,10 @@ impl Taker {
             }
         }
 
-        self.send_coinswap(swap_params).await?;
+        let result = self.send_coinswap(swap_params).await;
+        // Remember the makers found bad during the swap.
+        self.save_offerbook();
+        result?;
 
         if self.config.connection_type == ConnectionType::TOR && cfg!(feature = "tor") {
             crate::tor::kill_tor_handles(handle.unwrap());
@@ -298,18 +583,33 @@ impl Taker {
     ///
     /// If that fails too. Open an issue at [our github](https://github.com/utxo-teleport/teleport-transactions/issues)
     pub async fn send_coinswap(&mut self, swap_params: SwapParams) -> Result<(), TakerError> {
+        let swap_params = resolve_fee_rate(swap_params, self.fee_estimator().as_ref())?;
+        swap_params.validate(&self.config)?;
+
+        // The last hop pays us with the base refund locktime. Make sure it leaves us enough time to react.
+        let (refund_locktime, _) = self.refund_locktimes(&swap_params);
+        check_self_reaction_margin(refund_locktime, self.config.min_self_reaction_blocks)?;
+
         log::info!("Syncing Offerbook");
         let network = self.wallet.store.network;
         let config = self.config.clone();
         self.sync_offerbook(network, &config, swap_params.maker_count)
             .await?;
 
+        // Don't go ahead if the fees eat up too much of the send amount.
+        self.check_swap_economics(&swap_params)?;
+
+        for warning in self.privacy_warnings(&swap_params) {
+            log::warn!("Privacy warning: {:?}", warning);
+        }
+
         // Generate new random preimage and initiate the first hop.
         let mut preimage = [0u8; 32];
         OsRng.fill_bytes(&mut preimage);
 
         self.ongoing_swap_state.active_preimage = preimage;
         self.ongoing_swap_state.swap_params = swap_params;
+        self.wallet.add_swap_preimage(preimage);
 
         // Try first hop. Abort if error happens.
         if let Err(e) = self.init_first_hop().await {
@@ -329,8 +629,10 @@ impl Taker {
             }
 
             // Refund lock time decreases by `refund_locktime_step` for each hop.
-            let maker_refund_locktime = self.config.refund_locktime
-                + self.config.refund_locktime_step
+            let (refund_locktime, refund_locktime_step) =
+                self.refund_locktimes(&self.ongoing_swap_state.swap_params);
+            let maker_refund_locktime = refund_locktime
+                + refund_locktime_step
                     * (self.ongoing_swap_state.swap_params.maker_count - maker_index - 1);
 
             let funding_tx_infos = self.funding_info_for_next_maker();
@@ -338,7 +640,7 @@ impl Taker {
             // Attempt to initiate the next hop of the swap. If anything goes wrong, abort immediately.
             // If succeeded, collect the funding_outpoints and multisig_reedemscripts of the next hop.
             // If error then aborts from current swap. Ban the Peer.
-            let (funding_outpoints, multisig_reedemscripts) = match self
+            let (funding_outpoints, multisig_reedemscripts, senders_contract_txs) = match self
                 .send_sigs_init_next_hop(maker_refund_locktime, &funding_tx_infos)
                 .await
             {
@@ -358,8 +660,19 @@ impl Taker {
                             senders_contract_tx_info.contract_tx.input[0].previous_output
                         })
                         .collect::<Vec<OutPoint>>();
+                    let senders_contract_txs = contract_sigs
+                        .senders_contract_txs_info
+                        .iter()
+                        .map(|senders_contract_tx_info| {
+                            senders_contract_tx_info.contract_tx.clone()
+                        })
+                        .collect::<Vec<_>>();
 
-                    (funding_outpoints, multisig_reedemscripts)
+                    (
+                        funding_outpoints,
+                        multisig_reedemscripts,
+                        senders_contract_txs,
+                    )
                 }
                 Err(e) => {
                     log::error!("Could not initiate next hop. Error : {:?}", e);
@@ -376,7 +689,25 @@ impl Taker {
             // For the timeout case also ban the Peer.
             let txids_to_watch = funding_outpoints.iter().map(|op| op.txid).collect();
             match self.watch_for_txs(&txids_to_watch).await {
-                Ok(r) => self.ongoing_swap_state.funding_txs.push(r),
+                Ok(r) => {
+                    // The funding outpoints must be the multisig outputs of the funding txs, and the
+                    // contracts must spend them, not any other outpoint picked by the Maker.
+                    if let Err(e) = validate_contract_prevouts_against_funding(
+                        &senders_contract_txs,
+                        &multisig_reedemscripts,
+                        &funding_outpoints,
+                        &r.0,
+                    ) {
+                        log::error!("Invalid contract transactions : {:?}", e);
+                        log::warn!("Starting recovery from existing swap");
+                        let bad_maker =
+                            &self.ongoing_swap_state.peer_infos[maker_index as usize].peer;
+                        self.offerbook.add_bad_maker(bad_maker);
+                        self.recover_from_swap()?;
+                        return Ok(());
+                    }
+                    self.ongoing_swap_state.funding_txs.push(r)
+                }
                 Err(e) => {
                     log::error!("Error: {:?}", e);
                     log::warn!("Starting recovery from existing swap");
@@ -397,7 +728,7 @@ impl Taker {
                 log::debug!("Incoming Swapcoins: {:?}", incoming_swapcoins);
                 self.ongoing_swap_state.incoming_swapcoins = incoming_swapcoins;
                 match self.request_sigs_for_incoming_swap().await {
-                    Ok(_) => (),
+                    Ok(_) => self.save_swap_checkpoint()?,
                     Err(e) => {
                         log::error!("Incoming SwapCoin Generation failed : {:?}", e);
                         log::warn!("Starting recovery from existing swap");
@@ -429,10 +760,23 @@ impl Taker {
             }
         }
 
+        let received_txids = self
+            .ongoing_swap_state
+            .incoming_swapcoins
+            .iter()
+            .map(|sc| sc.contract_tx.input[0].previous_output.txid)
+            .collect::<Vec<_>>();
+        let wait_for_confirms = self.ongoing_swap_state.swap_params.wait_for_confirms;
+
         log::info!("Initializing Sync and Save.");
         self.wallet.sync()?;
         self.save_and_reset_swap_round()?;
         log::info!("Completed Sync and Save.");
+
+        if wait_for_confirms > 0 {
+            self.wait_for_confirmations(&received_txids, wait_for_confirms)
+                .await?;
+        }
         log::info!("Successfully Completed Coinswap.");
         Ok(())
     }
@@ -448,8 +792,10 @@ impl Taker {
         self.ongoing_swap_state.taker_position = TakerPosition::FirstPeer;
 
         // Locktime to be used for this swap.
-        let swap_locktime = self.config.refund_locktime
-            + self.config.refund_locktime_step * self.ongoing_swap_state.swap_params.maker_count;
+        let (refund_locktime, refund_locktime_step) =
+            self.refund_locktimes(&self.ongoing_swap_state.swap_params);
+        let swap_locktime = refund_locktime
+            + refund_locktime_step * self.ongoing_swap_state.swap_params.maker_count;
 
         // Loop until we find a live maker who responded to our signature request.
         let (maker, funding_txs) = loop {
@@ -475,6 +821,11 @@ impl Taker {
                     swap_locktime,
                     self.ongoing_swap_state.swap_params.fee_rate,
                 )?;
+            // Given back to the wallet if this maker doesn't take the swap.
+            let funding_inputs = funding_txs
+                .iter()
+                .flat_map(|tx| tx.input.iter().map(|txin| txin.previous_output))
+                .collect::<Vec<_>>();
 
             let contract_reedemscripts = outgoing_swapcoins
                 .iter()
@@ -493,8 +844,20 @@ impl Taker {
                 .await
             {
                 Ok(contract_sigs) => contract_sigs,
+                Err(TakerError::SwapRejected(rejection)) => {
+                    // Not a bad maker, but it can't take this swap. Try next one.
+                    self.wallet.release_in_flight(&funding_inputs)?;
+                    self.offerbook.add_good_maker(&maker);
+                    log::warn!(
+                        "First maker {} rejected the swap: {}",
+                        maker.address,
+                        rejection
+                    );
+                    continue;
+                }
                 Err(e) => {
                     // Bad maker, mark it, and try next one.
+                    self.wallet.release_in_flight(&funding_inputs)?;
                     self.offerbook.add_bad_maker(&maker);
                     log::error!(
                         "Failed to obtain sender's contract signatures from first_maker {}: {:?}",
@@ -526,9 +889,10 @@ impl Taker {
             for outgoing_swapcoin in &outgoing_swapcoins {
                 self.wallet.add_outgoing_swapcoin(outgoing_swapcoin);
             }
-            self.wallet.save_to_disk()?;
+            self.wallet.commit()?;
 
             self.ongoing_swap_state.outgoing_swapcoins = outgoing_swapcoins;
+            self.save_swap_checkpoint()?;
 
             log::info!("Total Funding Txs Fees: {}", funding_fee);
 
@@ -611,16 +975,18 @@ impl Taker {
 
         let start_time = Instant::now();
 
-        loop {
+        poll_until(self.poll_interval(), || {
             // Abort if any of the contract transaction is broadcasted
             // TODO: Find the culprit Maker, and ban it's fidelity bond.
-            let contracts_broadcasted = self.check_for_broadcasted_contract_txes();
+            let contracts_broadcasted = self.check_for_broadcasted_contract_txes()?;
             if !contracts_broadcasted.is_empty() {
                 log::info!(
                     "Contract transactions were broadcasted : {:?}",
                     contracts_broadcasted
                 );
-                return Err(TakerError::ContractsBroadcasted(contracts_broadcasted));
+                return Err(TakerError::ContractsBroadcasted(
+                    contracts_broadcasted.iter().map(|c| c.txid).collect(),
+                ));
             }
 
             // Check for funding transactions
@@ -677,10 +1043,38 @@ impl Taker {
                             .map(|gettxoutproof_result| to_hex(&gettxoutproof_result))
                     })
                     .collect::<Result<Vec<String>, _>>()?;
-                return Ok((txes, merkleproofs));
+                return Ok(Some((txes, merkleproofs)));
             }
-            sleep(Duration::from_millis(1000)).await;
-        }
+            Ok(None)
+        })
+        .await
+    }
+
+    /// Wait until all the given transactions reach `confirmations` depth.
+    async fn wait_for_confirmations(
+        &self,
+        txids: &[Txid],
+        confirmations: u32,
+    ) -> Result<(), TakerError> {
+        log::info!(
+            "Waiting for received swap coins confirmations ({} conf required)",
+            confirmations
+        );
+        poll_until(self.poll_interval(), || {
+            let mut confirmed = 0;
+            for txid in txids {
+                let gettx = self.wallet.rpc.get_raw_transaction_info(txid, None)?;
+                if gettx.confirmations >= Some(confirmations) {
+                    confirmed += 1;
+                }
+            }
+            if confirmed == txids.len() {
+                log::info!("Received swap coins confirmed at {}", confirmations);
+                return Ok(Some(()));
+            }
+            Ok::<_, TakerError>(None)
+        })
+        .await
     }
 
     /// Create [FundingTxInfo] for the "next_maker". Next maker is the last stored [NextPeerInfo] in the swp state.
@@ -820,8 +1214,8 @@ impl Taker {
                                 e
                             );
                             // If its a protocol error and not just connection error, scream hard.
-                            if let TakerError::Protocol(msg) = e {
-                                return Err(TakerError::Protocol(msg))
+                            if !e.is_retryable() {
+                                return Err(e);
                             }
                             if ii <= reconnect_attempts {
                                 sleep(Duration::from_secs(
@@ -854,7 +1248,10 @@ impl Taker {
                         let maker = &self.ongoing_swap_state.peer_infos.last().expect("atleast one maker expected at this stage").peer;
                         log::warn!("Connection timeout exceeded with Maker:{}, Banning Maker.", maker.address);
                         self.offerbook.add_bad_maker(maker);
-                        return Err(NetError::ConnectionTimedOut.into());
+                        return Err(ProtocolError::Timeout {
+                            step: "send signatures and initiate next hop".to_string(),
+                        }
+                        .into());
                     }
                 },
             }
@@ -877,18 +1274,9 @@ impl Taker {
         let previous_maker = self.ongoing_swap_state.peer_infos.iter().rev().nth(1);
 
         log::info!("Connecting to {}", this_maker.address);
-        let address = this_maker.address.as_str();
-        let mut socket = match self.config.connection_type {
-            ConnectionType::CLEARNET => TcpStream::connect(address).await?,
-            ConnectionType::TOR => Socks5Stream::connect(
-                format!("127.0.0.1:{}", self.config.socks_port).as_str(),
-                address,
-            )
-            .await?
-            .into_inner(),
-        };
+        let mut socket = connect_to_maker(&this_maker.address, &self.config).await?;
         // let mut socket = TcpStream::connect(this_maker.address.get_tcpstream_address()).await?;
-        let (mut socket_reader, mut socket_writer) = handshake_maker(&mut socket).await?;
+        let (mut socket_reader, mut socket_writer, _) = handshake_maker(&mut socket).await?;
         let mut next_maker = this_maker.clone();
         let (
             next_peer_multisig_pubkeys,
@@ -906,19 +1294,10 @@ impl Taker {
                 next_peer_hashlock_pubkeys,
                 next_peer_hashlock_keys_or_nonces,
             ) = if self.ongoing_swap_state.taker_position == TakerPosition::LastPeer {
-                let (my_recv_ms_pubkeys, my_recv_ms_nonce): (Vec<_>, Vec<_>) =
-                    (0..self.ongoing_swap_state.swap_params.tx_count)
-                        .map(|_| generate_keypair())
-                        .unzip();
-                let (my_recv_hashlock_pubkeys, my_recv_hashlock_nonce): (Vec<_>, Vec<_>) = (0
-                    ..self.ongoing_swap_state.swap_params.tx_count)
-                    .map(|_| generate_keypair())
-                    .unzip();
-                (
-                    my_recv_ms_pubkeys,
-                    my_recv_ms_nonce,
-                    my_recv_hashlock_pubkeys,
-                    my_recv_hashlock_nonce,
+                // Our receiving keys are tweaks of our own tweakable key, held by the signer.
+                generate_maker_keys(
+                    &self.wallet.get_tweakable_pubkey(),
+                    self.ongoing_swap_state.swap_params.tx_count,
                 )
             } else {
                 next_maker = self.choose_next_maker()?.clone();
@@ -983,6 +1362,12 @@ impl Taker {
                 this_maker.address
             );
 
+            if self.behavior == TakerBehavior::StallAfterProofOfFunding {
+                log::error!("Special Behavior StallAfterProofOfFunding");
+                socket_reader.read_to_end(&mut Vec::new()).await?;
+                return Err(TakerError::IO(std::io::ErrorKind::UnexpectedEof.into()));
+            }
+
             // If This Maker is the Sender, and we (the Taker) are the Receiver (Last Hop). We provide the Sender's Contact Tx Sigs.
             let senders_sigs = if self.ongoing_swap_state.taker_position == TakerPosition::LastPeer
             {
@@ -995,18 +1380,21 @@ impl Taker {
                             .senders_contract_txs_info
                             .iter(),
                     )
-                    .map(
-                        |(my_receiving_multisig_privkey, senders_contract_tx_info)| {
-                            crate::protocol::contract::sign_contract_tx(
-                                &senders_contract_tx_info.contract_tx,
-                                &senders_contract_tx_info.multisig_redeemscript,
-                                senders_contract_tx_info.funding_amount,
-                                my_receiving_multisig_privkey,
-                            )
-                        },
-                    )
-                    .collect::<Result<Vec<_>, _>>()
-                    .map_err(ProtocolError::Contract)?
+                    .map(|(my_receiving_multisig_nonce, senders_contract_tx_info)| {
+                        let signer = self.wallet.signer();
+                        let key = SignerKey::Tweaked {
+                            nonce: *my_receiving_multisig_nonce,
+                        };
+                        sign_contract_tx_with_key(
+                            &senders_contract_tx_info.contract_tx,
+                            &senders_contract_tx_info.multisig_redeemscript,
+                            senders_contract_tx_info.funding_amount,
+                            &key,
+                            &signer.public_key(&key)?,
+                            signer,
+                        )
+                    })
+                    .collect::<Result<Vec<_>, _>>()?
             } else {
                 // If Next Maker is the Receiver, and This Maker is The Sender, Request Sender's Contract Tx Sig to Next Maker.
                 let watchonly_swapcoins = self.create_watch_only_swapcoins(
@@ -1028,6 +1416,15 @@ impl Taker {
                         self.offerbook.add_good_maker(&next_maker);
                         r
                     }
+                    Err(TakerError::SwapRejected(rejection)) => {
+                        self.offerbook.add_good_maker(&next_maker);
+                        log::warn!(
+                            "Next maker {} rejected the swap, trying another maker: {}",
+                            next_maker.address,
+                            rejection
+                        );
+                        continue;
+                    }
                     Err(e) => {
                         self.offerbook.add_bad_maker(&next_maker);
                         log::info!(
@@ -1062,7 +1459,8 @@ impl Taker {
                 .iter()
                 .zip(self.ongoing_swap_state.outgoing_swapcoins.iter())
                 .map(|(receivers_contract_tx, outgoing_swapcoin)| {
-                    outgoing_swapcoin.sign_contract_tx_with_my_privkey(receivers_contract_tx)
+                    outgoing_swapcoin
+                        .sign_contract_tx_with_my_key(receivers_contract_tx, self.wallet.signer())
                 })
                 .collect::<Result<Vec<_>, _>>()?
         } else {
@@ -1228,13 +1626,13 @@ impl Taker {
                             (
                                 (
                                     (multisig_redeemscript, &maker_funded_multisig_pubkey),
-                                    &maker_funded_multisig_privkey,
+                                    maker_funded_multisig_nonce,
                                 ),
                                 my_receivers_contract_tx,
                             ),
                             next_contract_redeemscript,
                         ),
-                        &hashlock_privkey,
+                        hashlock_nonce,
                     ),
                     &maker_funding_tx_value,
                 ),
@@ -1273,13 +1671,18 @@ impl Taker {
             self.wallet.sync()?;
 
             let mut incoming_swapcoin = IncomingSwapCoin::new(
-                maker_funded_multisig_privkey,
+                SignerKey::Tweaked {
+                    nonce: *maker_funded_multisig_nonce,
+                },
                 maker_funded_other_multisig_pubkey,
                 my_receivers_contract_tx.clone(),
                 next_contract_redeemscript.clone(),
-                hashlock_privkey,
+                SignerKey::Tweaked {
+                    nonce: *hashlock_nonce,
+                },
                 maker_funding_tx_value,
-            );
+                self.wallet.signer(),
+            )?;
             incoming_swapcoin.hash_preimage = Some(self.ongoing_swap_state.active_preimage);
             incoming_swapcoins.push(incoming_swapcoin);
         }
@@ -1335,7 +1738,7 @@ impl Taker {
             self.wallet.add_incoming_swapcoin(incoming_swapcoin);
         }
 
-        self.wallet.save_to_disk()?;
+        self.wallet.commit()?;
 
         Ok(())
     }
@@ -1369,7 +1772,7 @@ impl Taker {
             ii += 1;
             select! {
                 ret = req_sigs_for_sender_once(
-                    self.config.connection_type,
+                    &self.config,
                     maker_address,
                     outgoing_swapcoins,
                     maker_multisig_nonces,
@@ -1385,6 +1788,9 @@ impl Taker {
                                 maker_address,
                                 e
                             );
+                            if !e.is_retryable() {
+                                return Err(e);
+                            }
                             if ii <= first_connect_attempts {
                                 sleep(Duration::from_secs(sleep_delay)).await;
                                 continue;
@@ -1402,7 +1808,10 @@ impl Taker {
                     if ii <= self.config.first_connect_attempts {
                         continue;
                     } else {
-                        return Err(NetError::ConnectionTimedOut.into());
+                        return Err(ProtocolError::Timeout {
+                            step: "request senders contract tx sigs".to_string(),
+                        }
+                        .into());
                     }
                 },
             }
@@ -1438,7 +1847,7 @@ impl Taker {
             ii += 1;
             select! {
                 ret = req_sigs_for_recvr_once(
-                    self.config.connection_type,
+                    &self.config,
                     maker_address,
                     incoming_swapcoins,
                     receivers_contract_txes,
@@ -1452,6 +1861,9 @@ impl Taker {
                                 maker_address,
                                 e
                             );
+                            if !e.is_retryable() {
+                                return Err(e);
+                            }
                             if ii <= reconnect_attempts {
                                 sleep(Duration::from_secs(
                                     if ii <= self.config.short_long_sleep_delay_transition {
@@ -1476,7 +1888,10 @@ impl Taker {
                     if ii <= self.config.reconnect_attempts {
                         continue;
                     } else {
-                        return Err(NetError::ConnectionTimedOut.into());
+                        return Err(ProtocolError::Timeout {
+                            step: "request receivers contract tx sigs".to_string(),
+                        }
+                        .into());
                     }
                 },
             }
@@ -1489,10 +1904,12 @@ impl Taker {
     async fn settle_all_swaps(&mut self) -> Result<(), TakerError> {
         let mut outgoing_privkeys: Option<Vec<MultisigPrivkey>> = None;
 
-        // Because the last peer info is the Taker, we take upto (0..n-1), where n = peer_info.len()
-        let maker_addresses = self.ongoing_swap_state.peer_infos
-            [0..self.ongoing_swap_state.peer_infos.len() - 1]
+        // The peer infos past the Makers are the Taker's, as it's the last peer.
+        let maker_addresses = self
+            .ongoing_swap_state
+            .peer_infos
             .iter()
+            .take(self.ongoing_swap_state.swap_params.maker_count as usize)
             .map(|si| si.peer.clone())
             .collect::<Vec<_>>();
 
@@ -1572,6 +1989,10 @@ impl Taker {
                                 &maker_address.address,
                                 e
                             );
+                            if !e.is_retryable() {
+                                self.offerbook.add_bad_maker(maker_address);
+                                return Err(e);
+                            }
                             if ii <= reconnect_attempts {
                                 sleep(Duration::from_secs(
                                     if ii <= self.config.short_long_sleep_delay_transition {
@@ -1598,7 +2019,10 @@ impl Taker {
                             continue;
                         } else {
                             self.offerbook.add_bad_maker(maker_address);
-                            return Err(NetError::ConnectionTimedOut.into());
+                            return Err(ProtocolError::Timeout {
+                                step: "settle coinswap".to_string(),
+                            }
+                            .into());
                         }
                     },
                 }
@@ -1608,7 +2032,7 @@ impl Taker {
     }
 
     /// [Internal] Setlle one swap. This is recursively called for all the makers.
-    async fn settle_one_coinswap<'a>(
+    async fn settle_one_coinswap(
         &mut self,
         maker_address: &MakerAddress,
         index: usize,
@@ -1617,17 +2041,8 @@ impl Taker {
         receivers_multisig_redeemscripts: &[ScriptBuf],
     ) -> Result<(), TakerError> {
         log::info!("Connecting to {}", maker_address);
-        let address = maker_address.as_str();
-        let mut socket = match self.config.connection_type {
-            ConnectionType::CLEARNET => TcpStream::connect(address).await?,
-            ConnectionType::TOR => Socks5Stream::connect(
-                format!("127.0.0.1:{}", self.config.socks_port).as_str(),
-                address,
-            )
-            .await?
-            .into_inner(),
-        };
-        let (mut socket_reader, mut socket_writer) = handshake_maker(&mut socket).await?;
+        let mut socket = connect_to_maker(maker_address, &self.config).await?;
+        let (mut socket_reader, mut socket_writer, _) = handshake_maker(&mut socket).await?;
 
         log::info!("===> Sending HashPreimage to {}", maker_address);
         let maker_private_key_handover = send_hash_preimage_and_get_private_keys(
@@ -1644,11 +2059,16 @@ impl Taker {
             self.ongoing_swap_state
                 .outgoing_swapcoins
                 .iter()
-                .map(|outgoing_swapcoin| MultisigPrivkey {
-                    multisig_redeemscript: outgoing_swapcoin.get_multisig_redeemscript(),
-                    key: outgoing_swapcoin.my_privkey,
+                .map(|outgoing_swapcoin| {
+                    Ok(MultisigPrivkey {
+                        multisig_redeemscript: outgoing_swapcoin.get_multisig_redeemscript(),
+                        key: self
+                            .wallet
+                            .signer()
+                            .handover_key(&outgoing_swapcoin.my_key)?,
+                    })
                 })
-                .collect::<Vec<MultisigPrivkey>>()
+                .collect::<Result<Vec<MultisigPrivkey>, WalletError>>()?
         } else {
             assert!(outgoing_privkeys.is_some());
             let reply = outgoing_privkeys.as_ref().unwrap().to_vec();
@@ -1690,6 +2110,7 @@ impl Taker {
         if send_amount == Amount::ZERO {
             return Err(TakerError::SendAmountNotSet);
         }
+        let (_, refund_locktime_step) = self.refund_locktimes(&self.ongoing_swap_state.swap_params);
 
         // Ensure that we don't select a maker we are already swaping with.
         Ok(self
@@ -1699,6 +2120,7 @@ impl Taker {
             .find(|oa| {
                 send_amount > Amount::from_sat(oa.offer.min_size)
                     && send_amount < Amount::from_sat(oa.offer.max_size)
+                    && oa.offer.minimum_locktime <= refund_locktime_step
                     && !self
                         .ongoing_swap_state
                         .peer_infos
@@ -1709,6 +2131,76 @@ impl Taker {
             .ok_or(TakerError::NotEnoughMakersInOfferBook)?)
     }
 
+    /// Check the swap for patterns undermining its privacy benefit, like round-number amounts, reused
+    /// addresses among the funding coins or too few hops. The warnings are only advisory.
+    pub fn privacy_warnings(&self, swap_params: &SwapParams) -> Vec<PrivacyWarning> {
+        let mut warnings = swap_params_privacy_warnings(swap_params);
+        match self.wallet.spendable_utxos() {
+            Ok(utxos) => {
+                let utxos = utxos.into_iter().map(|(utxo, _)| utxo).collect::<Vec<_>>();
+                warnings.extend(reused_address_warnings(&utxos));
+            }
+            Err(e) => log::warn!("Could not check the funding coins for privacy: {:?}", e),
+        }
+        warnings
+    }
+
+    /// The makers [Taker::choose_next_maker] would pick from the [OfferBook] for the swap, in order.
+    fn route_makers(&self, swap_params: &SwapParams) -> Vec<&OfferAndAddress> {
+        let send_amount = swap_params.send_amount;
+        let (_, refund_locktime_step) = self.refund_locktimes(swap_params);
+        self.offerbook
+            .get_all_untried()
+            .into_iter()
+            .filter(|oa| {
+                send_amount > Amount::from_sat(oa.offer.min_size)
+                    && send_amount < Amount::from_sat(oa.offer.max_size)
+                    && oa.offer.minimum_locktime <= refund_locktime_step
+            })
+            .take(swap_params.maker_count as usize)
+            .collect()
+    }
+
+    /// Estimate what the swap would cost through the current [OfferBook], without contacting any maker
+    /// or touching the wallet coins. The offerbook is synced by [Taker::send_coinswap], or from disk.
+    ///
+    /// A [SwapParams::ESTIMATED_FEE_RATE] is resolved from the [TakerConfig::fee_source].
+    pub fn estimate_swap_cost(
+        &self,
+        swap_params: &SwapParams,
+    ) -> Result<SwapCostEstimate, TakerError> {
+        let swap_params = resolve_fee_rate(*swap_params, self.fee_estimator().as_ref())?;
+        swap_params.validate(&self.config)?;
+        let makers = self.route_makers(&swap_params);
+        if makers.len() < swap_params.maker_count as usize {
+            return Err(TakerError::NotEnoughMakersInOfferBook);
+        }
+        Ok(estimate_swap_cost(&makers, &swap_params))
+    }
+
+    /// Refuse the swap if the estimated total fees exceed [TakerConfig::max_swap_fee_ratio] of the send amount.
+    /// Fees are estimated over the makers [Taker::choose_next_maker] would pick from the [OfferBook].
+    fn check_swap_economics(&self, swap_params: &SwapParams) -> Result<(), TakerError> {
+        let makers = self
+            .route_makers(swap_params)
+            .into_iter()
+            .map(|oa| &oa.offer)
+            .collect::<Vec<_>>();
+
+        // Not enough makers is reported when the first hop is initiated.
+        if makers.len() < swap_params.maker_count as usize {
+            return Ok(());
+        }
+
+        check_swap_fee_ratio(&makers, swap_params, self.config.max_swap_fee_ratio)
+    }
+
+    /// The refund locktime of the last hop, and its increase per hop, for the given swap.
+    /// The [SwapParams] overrides take precedence over the [TakerConfig] defaults.
+    fn refund_locktimes(&self, swap_params: &SwapParams) -> (u16, u16) {
+        swap_params.refund_locktimes(&self.config)
+    }
+
     /// Get the [Preimage] of the ongoing swap. If no swap is in progress will return a `[0u8; 32]`.
     fn get_preimage(&self) -> &Preimage {
         &self.ongoing_swap_state.active_preimage
@@ -1724,6 +2216,38 @@ impl Taker {
         self.ongoing_swap_state = OngoingSwapState::default();
     }
 
+    /// List the swaps currently in flight. The Taker runs a single swap round at a time, so this
+    /// is either empty or holds the ongoing round, else the swap left by a previous run in the
+    /// [SwapCheckpoint], to be resumed.
+    pub fn list_active_swaps(&self) -> Vec<ActiveSwap> {
+        if let Some(swap) = self.ongoing_swap_state.active_swap() {
+            return vec![swap];
+        }
+        self.wallet
+            .load_swap_checkpoint()
+            .map(|checkpoint| ActiveSwap {
+                id: checkpoint.hashvalue,
+                step: "Waiting to be resumed".to_string(),
+                counterparties: checkpoint.makers.clone(),
+                committed_amount: checkpoint
+                    .outgoing_swapcoins
+                    .iter()
+                    .filter_map(|multisig_redeemscript| {
+                        self.wallet.find_outgoing_swapcoin(multisig_redeemscript)
+                    })
+                    .map(|swapcoin| swapcoin.funding_amount)
+                    .sum(),
+                earliest_locktime: checkpoint
+                    .incoming_locktime
+                    .map_or(checkpoint.outgoing_locktime, |incoming_locktime| {
+                        incoming_locktime.min(checkpoint.outgoing_locktime)
+                    })
+                    .into(),
+            })
+            .into_iter()
+            .collect()
+    }
+
     pub fn get_bad_makers(&self) -> Vec<&OfferAndAddress> {
         self.offerbook.get_bad_makers()
     }
@@ -1737,45 +2261,236 @@ impl Taker {
                 .other_privkey = incoming_swapcoin.other_privkey;
         }
         self.wallet.save_to_disk()?;
+        self.wallet.clear_swap_checkpoint()?;
 
         self.clear_ongoing_swaps();
 
         Ok(())
     }
 
-    /// Checks if any contreact transactions have been broadcasted.
-    /// Returns the txid list of all the broadcasted contract transaction.
-    /// Empty vector if nothing is nothing is broadcasted. (usual case).
-    pub fn check_for_broadcasted_contract_txes(&self) -> Vec<Txid> {
-        let contract_txids = self
+    /// Save a [SwapCheckpoint] of the ongoing swap round into the wallet file.
+    fn save_swap_checkpoint(&mut self) -> Result<(), TakerError> {
+        let state = &self.ongoing_swap_state;
+        let checkpoint = SwapCheckpoint {
+            hashvalue: Hash160::hash(&state.active_preimage),
+            outgoing_swapcoins: state
+                .outgoing_swapcoins
+                .iter()
+                .map(|swapcoin| swapcoin.get_multisig_redeemscript())
+                .collect(),
+            incoming_swapcoins: state
+                .incoming_swapcoins
+                .iter()
+                .map(|swapcoin| swapcoin.get_multisig_redeemscript())
+                .collect(),
+            contract_redeemscripts: state
+                .outgoing_swapcoins
+                .iter()
+                .map(|swapcoin| swapcoin.contract_redeemscript.clone())
+                .chain(
+                    state
+                        .incoming_swapcoins
+                        .iter()
+                        .map(|swapcoin| swapcoin.contract_redeemscript.clone()),
+                )
+                .collect(),
+            outgoing_locktime: state
+                .outgoing_swapcoins
+                .first()
+                .map_or(0, |swapcoin| swapcoin.get_timelock()),
+            incoming_locktime: state
+                .incoming_swapcoins
+                .first()
+                .map(|swapcoin| swapcoin.get_timelock()),
+            // The last peer info is the Taker itself, once the route is complete.
+            makers: state
+                .peer_infos
+                .iter()
+                .take(state.swap_params.maker_count as usize)
+                .map(|peer_info| peer_info.peer.address.to_string())
+                .collect(),
+            watchonly_swapcoins: state.watchonly_swapcoins.clone(),
+        };
+        self.wallet.save_swap_checkpoint(checkpoint)?;
+        Ok(())
+    }
+
+    /// Resume the swap left in progress by a previous run of the Taker, from its [SwapCheckpoint].
+    ///
+    /// A swap set up in full, up to the sigs of the incoming contracts, is settled with its Makers from
+    /// there. It's refunded with [Taker::refund_unfinished_swap] instead if it stopped before, if a
+    /// contract got broadcasted meanwhile, or if the settlement fails, e.g the Makers are gone.
+    /// Returns None if there was no swap in progress.
+    pub async fn resume_swap(&mut self) -> Result<Option<RecoveredSwap>, TakerError> {
+        let checkpoint = match self.wallet.load_swap_checkpoint() {
+            Some(checkpoint) => checkpoint.clone(),
+            None => return Ok(None),
+        };
+        if self.is_swap_completed(&checkpoint) || !self.restore_swap_state(&checkpoint) {
+            return self.refund_unfinished_swap();
+        }
+
+        if !self.check_for_broadcasted_contract_txes()?.is_empty() {
+            log::warn!(
+                "Contracts of swap {} were broadcasted",
+                checkpoint.hashvalue
+            );
+            self.clear_ongoing_swaps();
+            return self.refund_unfinished_swap();
+        }
+
+        log::info!("Settling swap {} with its Makers", checkpoint.hashvalue);
+        if let Err(e) = self.settle_all_swaps().await {
+            log::error!("Swap Settlement Failed : {:?}", e);
+            self.clear_ongoing_swaps();
+            return self.refund_unfinished_swap();
+        }
+        self.wallet.sync()?;
+        self.save_and_reset_swap_round()?;
+        log::info!("Swap {} settled", checkpoint.hashvalue);
+        Ok(Some(RecoveredSwap::Settled))
+    }
+
+    /// Whether the checkpointed swap got all its incoming privkeys, i.e it was settled.
+    fn is_swap_completed(&self, checkpoint: &SwapCheckpoint) -> bool {
+        !checkpoint.incoming_swapcoins.is_empty()
+            && checkpoint.incoming_swapcoins.iter().all(|redeemscript| {
+                self.wallet
+                    .find_incoming_swapcoin(redeemscript)
+                    .is_some_and(|swapcoin| swapcoin.other_privkey.is_some())
+            })
+    }
+
+    /// Rebuild the [OngoingSwapState] of a swap set up in full, from its [SwapCheckpoint], the wallet
+    /// and the offerbook. Returns false if they don't hold enough to settle the swap.
+    fn restore_swap_state(&mut self, checkpoint: &SwapCheckpoint) -> bool {
+        if checkpoint.incoming_swapcoins.is_empty()
+            || checkpoint.makers.len() != checkpoint.watchonly_swapcoins.len() + 1
+        {
+            return false;
+        }
+        let preimage = self
+            .wallet
+            .get_swap_preimage(&checkpoint.hashvalue)
+            .copied();
+        let outgoing_swapcoins = checkpoint
+            .outgoing_swapcoins
+            .iter()
+            .map(|redeemscript| self.wallet.find_outgoing_swapcoin(redeemscript).cloned())
+            .collect::<Option<Vec<_>>>();
+        let incoming_swapcoins = checkpoint
+            .incoming_swapcoins
+            .iter()
+            .map(|redeemscript| self.wallet.find_incoming_swapcoin(redeemscript).cloned())
+            .collect::<Option<Vec<_>>>();
+        let makers = checkpoint
+            .makers
+            .iter()
+            .map(|address| {
+                self.offerbook
+                    .all_makers
+                    .iter()
+                    .find(|maker| maker.address.as_str() == address)
+                    .cloned()
+            })
+            .collect::<Option<Vec<_>>>();
+        let (Some(preimage), Some(outgoing_swapcoins), Some(incoming_swapcoins), Some(makers)) =
+            (preimage, outgoing_swapcoins, incoming_swapcoins, makers)
+        else {
+            return false;
+        };
+
+        self.ongoing_swap_state = OngoingSwapState {
+            swap_params: SwapParams {
+                maker_count: makers.len() as u16,
+                ..SwapParams::default()
+            },
+            outgoing_swapcoins,
+            watchonly_swapcoins: checkpoint.watchonly_swapcoins.clone(),
+            incoming_swapcoins,
+            // The settlement only needs the Makers' addresses.
+            peer_infos: makers
+                .into_iter()
+                .map(|peer| NextPeerInfo {
+                    peer,
+                    multisig_pubkeys: Vec::new(),
+                    multisig_nonces: Vec::new(),
+                    hashlock_nonces: Vec::new(),
+                    contract_reedemscripts: Vec::new(),
+                })
+                .collect(),
+            funding_txs: Vec::new(),
+            active_preimage: preimage,
+            taker_position: TakerPosition::default(),
+        };
+        true
+    }
+
+    /// Refund the swap left in progress by a previous run of the Taker, from its [SwapCheckpoint].
+    ///
+    /// The swap isn't taken up again with the Makers from the checkpointed stage, see
+    /// [Taker::resume_swap] for that. A swap which got all its incoming privkeys is complete, and the
+    /// checkpoint is dropped. Otherwise the swap is recovered with [Taker::recover_from_swap], which
+    /// blocks until the refunds are broadcasted. Returns None if there was no swap in progress.
+    pub fn refund_unfinished_swap(&mut self) -> Result<Option<RecoveredSwap>, TakerError> {
+        let checkpoint = match self.wallet.load_swap_checkpoint() {
+            Some(checkpoint) => checkpoint.clone(),
+            None => return Ok(None),
+        };
+        log::info!(
+            "Recovering swap {} with {} outgoing and {} incoming swapcoins",
+            checkpoint.hashvalue,
+            checkpoint.outgoing_swapcoins.len(),
+            checkpoint.incoming_swapcoins.len()
+        );
+
+        if self.is_swap_completed(&checkpoint) {
+            log::info!("Swap {} was already completed", checkpoint.hashvalue);
+            self.wallet.clear_swap_checkpoint()?;
+            return Ok(Some(RecoveredSwap::Completed));
+        }
+
+        log::warn!("Swap {} is unfinished, refunding", checkpoint.hashvalue);
+        self.recover_from_swap()?;
+        Ok(Some(RecoveredSwap::Refunded))
+    }
+
+    /// Watch the backend for any broadcasted contract transaction of the ongoing swap.
+    /// Returns a [ContractBroadcastDetected] event for each of them, seen in the mempool or in a block.
+    pub fn check_for_broadcasted_contract_txes(
+        &self,
+    ) -> Result<Vec<ContractBroadcastDetected>, TakerError> {
+        let contract_txs = self
             .ongoing_swap_state
             .incoming_swapcoins
             .iter()
-            .map(|sc| sc.contract_tx.compute_txid())
+            .map(|sc| (&sc.contract_tx, &sc.contract_redeemscript))
             .chain(
                 self.ongoing_swap_state
                     .outgoing_swapcoins
                     .iter()
-                    .map(|sc| sc.contract_tx.compute_txid()),
+                    .map(|sc| (&sc.contract_tx, &sc.contract_redeemscript)),
             )
             .chain(
                 self.ongoing_swap_state
                     .watchonly_swapcoins
                     .iter()
                     .flatten()
-                    .map(|sc| sc.contract_tx.compute_txid()),
-            )
-            .collect::<Vec<_>>();
+                    .map(|sc| (&sc.contract_tx, &sc.contract_redeemscript)),
+            );
 
         // TODO: Find out which txid was boradcasted first
-        // This requires -txindex to be enabled in the node.
-        let seen_txids = contract_txids
-            .iter()
-            .filter(|txid| self.wallet.rpc.get_raw_transaction_info(txid, None).is_ok())
-            .cloned()
-            .collect::<Vec<Txid>>();
+        let mut detected = Vec::new();
+        for (contract_tx, contract_redeemscript) in contract_txs {
+            if let Some(event) = self
+                .wallet
+                .detect_contract_broadcast(contract_tx, contract_redeemscript)?
+            {
+                detected.push(event);
+            }
+        }
 
-        seen_txids
+        Ok(detected)
     }
 
     pub fn recover_from_swap(&mut self) -> Result<(), TakerError> {
@@ -1785,7 +2500,7 @@ impl Taker {
             .iter()
             .map(|incoming| {
                 Ok((
-                    incoming.get_fully_signed_contract_tx()?,
+                    incoming.get_fully_signed_contract_tx(self.wallet.signer())?,
                     incoming.get_multisig_redeemscript(),
                 ))
             })
@@ -1818,7 +2533,7 @@ impl Taker {
 
         // Broadcast the Outgoing Contracts
         for outgoing in outgoings {
-            let contract_tx = outgoing.get_fully_signed_contract_tx()?;
+            let contract_tx = outgoing.get_fully_signed_contract_tx(self.wallet.signer())?;
             if self
                 .wallet
                 .rpc
@@ -1836,7 +2551,11 @@ impl Taker {
             let reedemscript = outgoing.get_multisig_redeemscript();
             let timelock = outgoing.get_timelock();
             let next_internal = &self.wallet.get_next_internal_addresses(1)?[0];
-            let timelock_spend = outgoing.create_timelock_spend(next_internal);
+            let timelock_spend = outgoing.create_timelock_spend(
+                next_internal,
+                self.config.contract_fee_rate,
+                self.wallet.signer(),
+            )?;
             outgoing_infos.push(((reedemscript, contract_tx), (timelock, timelock_spend)));
         }
 
@@ -1848,6 +2567,7 @@ impl Taker {
             // Break early if nothing to broadcast.
             // This happens only when init_first_hop() fails at `NotEnoughMakersInOfferBook`
             if outgoing_infos.is_empty() {
+                self.wallet.clear_swap_checkpoint()?;
                 return Ok(());
             }
             for ((reedemscript, contract), (timelock, timelocked_tx)) in outgoing_infos.iter() {
@@ -1898,6 +2618,7 @@ impl Taker {
                 // Everything is broadcasted. Clear the connectionstate and break the loop
                 if timelock_boardcasted.len() == outgoing_infos.len() {
                     log::info!("All outgoing contracts reedemed. Cleared ongoing swap state");
+                    self.wallet.clear_swap_checkpoint()?;
                     self.clear_ongoing_swaps(); // This could be a bug if Taker is in middle of multiple swaps. For now we assume Taker will only do one swap at a time.
                     log::info!("Initializing Wallet sync and save");
                     self.wallet.sync()?;
@@ -1905,13 +2626,7 @@ impl Taker {
                     return Ok(());
                 }
             }
-            // Block wait time is varied between prod. and test builds.
-            let block_wait_time = if cfg!(feature = "integration-test") {
-                Duration::from_secs(10)
-            } else {
-                Duration::from_secs(10 * 60)
-            };
-            std::thread::sleep(block_wait_time);
+            std::thread::sleep(recovery_poll_interval(self.poll_interval()));
         }
     }
 
@@ -1957,17 +2672,26 @@ impl Taker {
             config.connection_type,
         )
         .await?;
-        let offers = fetch_offer_from_makers(addresses_from_dns, config).await;
-
-        let new_offers = offers
+        // Offers fetched recently enough are reused as they are.
+        let ttl = Duration::from_secs(config.offer_cache_ttl_secs);
+        let (cached, addresses_to_fetch): (Vec<_>, Vec<_>) = addresses_from_dns
             .into_iter()
-            .filter(|offer| !self.offerbook.bad_makers.contains(offer))
-            .collect::<Vec<_>>();
+            .filter(|address| !self.offerbook.is_bad_maker(address))
+            .partition(|address| self.offerbook.is_fresh(address, ttl));
+        log::info!(
+            "Reusing {} cached offers, fetching {}",
+            cached.len(),
+            addresses_to_fetch.len()
+        );
+        let fetched = fetch_offer_from_makers(addresses_to_fetch, config).await;
+        for address in &fetched.timed_out {
+            self.offerbook.add_unresponsive_maker(address);
+        }
 
-        for offer in new_offers {
+        for offer in fetched.offers {
             log::info!(
                 "Found New Offer from {}. Verifying Fidelity Proof",
-                offer.address.to_string()
+                offer.address
             );
             log::debug!("{:?}", offer);
             if let Err(e) = self
@@ -1977,13 +2701,114 @@ impl Taker {
                 log::warn!(
                     "Fidelity Proof Verification failed with error: {:?}. Rejecting Offer from Maker : {}",
                     e,
-                    offer.address.to_string()
+                    offer.address
                 );
             } else {
                 log::info!("Fideity Bond verification succes. Adding offer to our OfferBook");
                 self.offerbook.add_new_offer(&offer);
             }
         }
+        self.save_offerbook();
         Ok(())
     }
+
+    /// Persist the offerbook for the next runs. Failing to is not fatal, the offers get fetched again.
+    fn save_offerbook(&self) {
+        if let Err(e) = self.offerbook.save(&self.offerbook_path) {
+            log::warn!("Could not save the offerbook: {:?}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_swap_params_validation() {
+        let swap_params = SwapParams {
+            send_amount: Amount::from_sat(500_000),
+            maker_count: 2,
+            tx_count: 3,
+            required_confirms: 1,
+            fee_rate: Amount::from_sat(1000),
+            ..SwapParams::default()
+        };
+        let config = TakerConfig::default();
+        assert!(swap_params.validate(&config).is_ok());
+
+        assert!(matches!(
+            SwapParams {
+                maker_count: 0,
+                ..swap_params
+            }
+            .validate(&config),
+            Err(TakerError::NoMakersRequested)
+        ));
+        assert!(matches!(
+            SwapParams {
+                tx_count: 0,
+                ..swap_params
+            }
+            .validate(&config),
+            Err(TakerError::NoFundingTxsRequested)
+        ));
+        assert!(matches!(
+            SwapParams {
+                send_amount: Amount::ZERO,
+                ..swap_params
+            }
+            .validate(&config),
+            Err(TakerError::SendAmountNotSet)
+        ));
+        assert!(matches!(
+            SwapParams {
+                fee_rate: Amount::ZERO,
+                ..swap_params
+            }
+            .validate(&config),
+            Err(TakerError::FeeRateNotSet)
+        ));
+        // 10 sats/vbyte contract spends don't outbid 10.5 sats/vbyte funding txs.
+        assert!(matches!(
+            SwapParams {
+                fee_rate: Amount::from_sat(10_500),
+                ..swap_params
+            }
+            .validate(&config),
+            Err(TakerError::ContractFeeRateTooLow {
+                contract_fee_rate: 10,
+                funding_fee_rate: 11
+            })
+        ));
+
+        // The first hop's locktime overflows, with the overrides or the config values.
+        let long_locktimes = SwapParams {
+            refund_locktime: Some(40_000),
+            refund_locktime_step: Some(20_000),
+            ..swap_params
+        };
+        assert!(matches!(
+            long_locktimes.validate(&config),
+            Err(TakerError::LocktimeOverflow {
+                refund_locktime: 40_000,
+                refund_locktime_step: 20_000,
+                maker_count: 2
+            })
+        ));
+        assert!(matches!(
+            swap_params.validate(&TakerConfig {
+                refund_locktime_step: u16::MAX,
+                ..config.clone()
+            }),
+            Err(TakerError::LocktimeOverflow { .. })
+        ));
+        assert!(SwapParams {
+            refund_locktime: Some(40_000),
+            refund_locktime_step: Some(10_000),
+            ..swap_params
+        }
+        .validate(&config)
+        .is_ok());
+    }
 }
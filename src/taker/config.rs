@@ -3,9 +3,21 @@
 //!  Represents the configuration options for the Taker module, controlling behaviors
 //! such as refund locktime, connection attempts, sleep delays, and timeouts.
 
-use std::{io, path::PathBuf};
+use std::{
+    collections::HashMap,
+    io,
+    path::{Path, PathBuf},
+};
+
+use super::fees::FeeSource;
+use crate::{
+    error::ConfigError,
+    utill::{
+        get_taker_dir, parse_config_field, parse_toml, write_default_config, ConnectionType,
+        MIN_FEE_RATE,
+    },
+};
 
-use crate::utill::{get_taker_dir, parse_field, parse_toml, write_default_config, ConnectionType};
 /// Taker configuration with refund, connection, and sleep settings.
 #[derive(Debug, Clone, PartialEq)]
 pub struct TakerConfig {
@@ -27,6 +39,38 @@ pub struct TakerConfig {
     pub directory_server_onion_address: String,
     pub directory_server_clearnet_address: String,
     pub connection_type: ConnectionType,
+
+    /// Maximum ratio of total swap fees over the send amount. Swaps above this are refused.
+    pub max_swap_fee_ratio: f64,
+
+    /// Time interval between backend polls, while waiting for confirmations and watching for contract broadcasts.
+    /// If not set, follows the required confirmations of the swap. See [crate::utill::default_poll_interval_secs].
+    pub poll_interval_secs: Option<u64>,
+
+    /// Fee rate in sats/vbyte for contract spends, i.e the timelock recovery transactions.
+    /// Kept higher than the funding fee rate, so recoveries confirm in time during fee spikes.
+    pub contract_fee_rate: u64,
+
+    /// Minimum number of blocks the Taker keeps, on its incoming contracts, to broadcast its hashlock
+    /// claim before the counterparty can recover the coins through the timelock.
+    pub min_self_reaction_blocks: u16,
+
+    /// Use a distinct Tor circuit for every maker connection, so no single guard or exit sees the
+    /// whole route. Connections are slower, as a new circuit is built for each.
+    pub isolate_circuits: bool,
+
+    /// Where to get the funding fee rate of swaps requested with [SwapParams::ESTIMATED_FEE_RATE](super::SwapParams::ESTIMATED_FEE_RATE).
+    pub fee_source: FeeSource,
+
+    /// How long a fetched maker offer is reused, across runs, before it's fetched again.
+    pub offer_cache_ttl_secs: u64,
+
+    /// Maximum number of makers queried for their offers at the same time.
+    pub offer_fetch_concurrency: usize,
+
+    /// Passphrase the wallet file is encrypted with, see [Wallet::init](crate::wallet::Wallet::init).
+    /// Unencrypted if not set. Keep the config file apart from the backups of the wallet file.
+    pub wallet_encryption_passphrase: Option<String>,
 }
 
 impl Default for TakerConfig {
@@ -47,6 +91,15 @@ impl Default for TakerConfig {
             directory_server_onion_address: "directoryhiddenserviceaddress.onion:8080".to_string(),
             directory_server_clearnet_address: "127.0.0.1:8080".to_string(),
             connection_type: ConnectionType::TOR,
+            max_swap_fee_ratio: 0.25,
+            poll_interval_secs: None,
+            contract_fee_rate: 10,
+            min_self_reaction_blocks: 20,
+            isolate_circuits: false,
+            fee_source: FeeSource::default(),
+            offer_cache_ttl_secs: 1800,
+            offer_fetch_concurrency: 10,
+            wallet_encryption_passphrase: None,
         }
     }
 }
@@ -63,8 +116,16 @@ impl TakerConfig {
     /// Default data-dir for linux: `~/.coinswap/`
     /// Default config locations: `~/.coinswap/taker/config.toml`.
     pub fn new(config_path: Option<&PathBuf>) -> io::Result<Self> {
-        let default_config = Self::default();
+        Self::load(config_path, false)
+    }
+
+    /// Like [TakerConfig::new], but fields set to invalid values error with a
+    /// [ConfigError], instead of silently using their defaults.
+    pub fn new_strict(config_path: Option<&PathBuf>) -> io::Result<Self> {
+        Self::load(config_path, true)
+    }
 
+    fn load(config_path: Option<&PathBuf>, strict: bool) -> io::Result<Self> {
         let default_config_path = get_taker_dir().join("config.toml");
         let config_path = config_path.unwrap_or(&default_config_path);
 
@@ -84,64 +145,107 @@ impl TakerConfig {
 
         let taker_config_section = section.get("taker_config").cloned().unwrap_or_default();
 
-        Ok(Self {
-            refund_locktime: parse_field(
-                taker_config_section.get("refund_locktime"),
+        Ok(Self::from_section(&taker_config_section, strict)?)
+    }
+
+    /// Strictly loads a [TakerConfig] from the file at `config_path`.
+    ///
+    /// Unlike [TakerConfig::new_strict], a missing file or a missing `[taker_config]` section is
+    /// an error, and no default file is written. Fields absent from the section still take their
+    /// defaults.
+    pub fn from_file_strict(config_path: &Path) -> Result<Self, ConfigError> {
+        if !config_path.exists() {
+            return Err(ConfigError::MissingFile(config_path.to_path_buf()));
+        }
+
+        let section = parse_toml(&config_path.to_path_buf())?;
+        let taker_config_section = section
+            .get("taker_config")
+            .ok_or_else(|| ConfigError::MissingSection("taker_config".to_string()))?;
+
+        Self::from_section(taker_config_section, true)
+    }
+
+    /// Parses the fields of a config section. Invalid values error if `strict`, and otherwise
+    /// take their defaults.
+    ///
+    /// Whatever `strict`, a contract fee rate not above the funding fee rate errors: the `static`
+    /// one, or else the minimum relay fee rate. Estimated rates are checked once resolved, see
+    /// [SwapParams::validate](super::SwapParams::validate).
+    fn from_section(
+        taker_config_section: &HashMap<String, String>,
+        strict: bool,
+    ) -> Result<Self, ConfigError> {
+        let default_config = Self::default();
+
+        let config = Self {
+            refund_locktime: parse_config_field(
+                taker_config_section,
+                "refund_locktime",
                 default_config.refund_locktime,
-            )
-            .unwrap_or(default_config.refund_locktime),
-            refund_locktime_step: parse_field(
-                taker_config_section.get("refund_locktime_step"),
+                strict,
+            )?,
+            refund_locktime_step: parse_config_field(
+                taker_config_section,
+                "refund_locktime_step",
                 default_config.refund_locktime_step,
-            )
-            .unwrap_or(default_config.refund_locktime_step),
-            first_connect_attempts: parse_field(
-                taker_config_section.get("first_connect_attempts"),
+                strict,
+            )?,
+            first_connect_attempts: parse_config_field(
+                taker_config_section,
+                "first_connect_attempts",
                 default_config.first_connect_attempts,
-            )
-            .unwrap_or(default_config.first_connect_attempts),
-            first_connect_sleep_delay_sec: parse_field(
-                taker_config_section.get("first_connect_sleep_delay_sec"),
+                strict,
+            )?,
+            first_connect_sleep_delay_sec: parse_config_field(
+                taker_config_section,
+                "first_connect_sleep_delay_sec",
                 default_config.first_connect_sleep_delay_sec,
-            )
-            .unwrap_or(default_config.first_connect_sleep_delay_sec),
-            first_connect_attempt_timeout_sec: parse_field(
-                taker_config_section.get("first_connect_attempt_timeout_sec"),
+                strict,
+            )?,
+            first_connect_attempt_timeout_sec: parse_config_field(
+                taker_config_section,
+                "first_connect_attempt_timeout_sec",
                 default_config.first_connect_attempt_timeout_sec,
-            )
-            .unwrap_or(default_config.first_connect_attempt_timeout_sec),
-            reconnect_attempts: parse_field(
-                taker_config_section.get("reconnect_attempts"),
+                strict,
+            )?,
+            reconnect_attempts: parse_config_field(
+                taker_config_section,
+                "reconnect_attempts",
                 default_config.reconnect_attempts,
-            )
-            .unwrap_or(default_config.reconnect_attempts),
-            reconnect_short_sleep_delay: parse_field(
-                taker_config_section.get("reconnect_short_sleep_delay"),
+                strict,
+            )?,
+            reconnect_short_sleep_delay: parse_config_field(
+                taker_config_section,
+                "reconnect_short_sleep_delay",
                 default_config.reconnect_short_sleep_delay,
-            )
-            .unwrap_or(default_config.reconnect_short_sleep_delay),
-            reconnect_long_sleep_delay: parse_field(
-                taker_config_section.get("reconnect_long_sleep_delay"),
+                strict,
+            )?,
+            reconnect_long_sleep_delay: parse_config_field(
+                taker_config_section,
+                "reconnect_long_sleep_delay",
                 default_config.reconnect_long_sleep_delay,
-            )
-            .unwrap_or(default_config.reconnect_long_sleep_delay),
-            short_long_sleep_delay_transition: parse_field(
-                taker_config_section.get("short_long_sleep_delay_transition"),
+                strict,
+            )?,
+            short_long_sleep_delay_transition: parse_config_field(
+                taker_config_section,
+                "short_long_sleep_delay_transition",
                 default_config.short_long_sleep_delay_transition,
-            )
-            .unwrap_or(default_config.short_long_sleep_delay_transition),
-            reconnect_attempt_timeout_sec: parse_field(
-                taker_config_section.get("reconnect_attempt_timeout_sec"),
+                strict,
+            )?,
+            reconnect_attempt_timeout_sec: parse_config_field(
+                taker_config_section,
+                "reconnect_attempt_timeout_sec",
                 default_config.reconnect_attempt_timeout_sec,
-            )
-            .unwrap_or(default_config.reconnect_attempt_timeout_sec),
-            port: parse_field(taker_config_section.get("port"), default_config.port)
-                .unwrap_or(default_config.port),
-            socks_port: parse_field(
-                taker_config_section.get("socks_port"),
+                strict,
+            )?,
+            port: parse_config_field(taker_config_section, "port", default_config.port, strict)?,
+            socks_port: parse_config_field(
+                taker_config_section,
+                "socks_port",
                 default_config.socks_port,
-            )
-            .unwrap_or(default_config.socks_port),
+                strict,
+            )?,
             directory_server_onion_address: taker_config_section
                 .get("directory_server_onion_address")
                 .map(|s| s.to_string())
@@ -150,12 +254,82 @@ impl TakerConfig {
                 .get("directory_server_clearnet_address")
                 .map(|s| s.to_string())
                 .unwrap_or(default_config.directory_server_clearnet_address),
-            connection_type: parse_field(
-                taker_config_section.get("connection_type"),
+            connection_type: parse_config_field(
+                taker_config_section,
+                "connection_type",
                 default_config.connection_type,
-            )
-            .unwrap_or(default_config.connection_type),
-        })
+                strict,
+            )?,
+            max_swap_fee_ratio: parse_config_field(
+                taker_config_section,
+                "max_swap_fee_ratio",
+                default_config.max_swap_fee_ratio,
+                strict,
+            )?,
+            // 0 is the same as not set.
+            poll_interval_secs: match parse_config_field(
+                taker_config_section,
+                "poll_interval_secs",
+                default_config.poll_interval_secs.unwrap_or(0),
+                strict,
+            )? {
+                0 => None,
+                secs => Some(secs),
+            },
+            contract_fee_rate: parse_config_field(
+                taker_config_section,
+                "contract_fee_rate",
+                default_config.contract_fee_rate,
+                strict,
+            )?,
+            min_self_reaction_blocks: parse_config_field(
+                taker_config_section,
+                "min_self_reaction_blocks",
+                default_config.min_self_reaction_blocks,
+                strict,
+            )?,
+            isolate_circuits: parse_config_field(
+                taker_config_section,
+                "isolate_circuits",
+                default_config.isolate_circuits,
+                strict,
+            )?,
+            fee_source: parse_config_field(
+                taker_config_section,
+                "fee_source",
+                default_config.fee_source,
+                strict,
+            )?,
+            offer_cache_ttl_secs: parse_config_field(
+                taker_config_section,
+                "offer_cache_ttl_secs",
+                default_config.offer_cache_ttl_secs,
+                strict,
+            )?,
+            offer_fetch_concurrency: parse_config_field(
+                taker_config_section,
+                "offer_fetch_concurrency",
+                default_config.offer_fetch_concurrency,
+                strict,
+            )?,
+            wallet_encryption_passphrase: taker_config_section
+                .get("wallet_encryption_passphrase")
+                .map(|s| s.to_string())
+                .or(default_config.wallet_encryption_passphrase),
+        };
+
+        let funding_fee_rate = match config.fee_source {
+            FeeSource::Static(funding_fee_rate) => funding_fee_rate,
+            FeeSource::Core { .. } => MIN_FEE_RATE,
+        };
+        if config.contract_fee_rate <= funding_fee_rate {
+            return Err(ConfigError::ContractFeeRateTooLow {
+                contract_fee_rate: config.contract_fee_rate,
+                funding_fee_rate,
+            });
+        }
+
+        Ok(config)
     }
 }
 
@@ -177,7 +351,14 @@ fn write_default_taker_config(config_path: &PathBuf) {
                         socks_port = 19050\n\
                         directory_server_onion_address = directoryhiddenserviceaddress.onion:8080\n\
                         directory_server_clearnet_address = 127.0.0.1:8080\n\
-                        connection_type = tor\n
+                        connection_type = tor\n\
+                        max_swap_fee_ratio = 0.25\n\
+                        contract_fee_rate = 10\n\
+                        min_self_reaction_blocks = 20\n\
+                        isolate_circuits = false\n\
+                        fee_source = core\n\
+                        offer_cache_ttl_secs = 1800\n\
+                        offer_fetch_concurrency = 10\n
                         ",
     );
     write_default_config(config_path, config_string).unwrap();
@@ -242,6 +423,67 @@ mod tests {
         assert_eq!(config, TakerConfig::default());
     }
 
+    #[test]
+    fn test_poll_interval() {
+        // Not set, the taker follows the swap's required confirmations.
+        assert_eq!(TakerConfig::default().poll_interval_secs, None);
+
+        let contents = r#"
+            [taker_config]
+            poll_interval_secs = 5
+        "#;
+        let config_path = create_temp_config(contents, "poll_interval_taker_config.toml");
+        let config = TakerConfig::new(Some(&config_path)).unwrap();
+        remove_temp_config(&config_path);
+        assert_eq!(config.poll_interval_secs, Some(5));
+    }
+
+    #[test]
+    fn test_contract_fee_rate_above_funding() {
+        let contents = r#"
+            [taker_config]
+            contract_fee_rate = 5
+            fee_source = static:5
+        "#;
+        let config_path = create_temp_config(contents, "low_contract_fee_taker_config.toml");
+        let config = TakerConfig::from_file_strict(&config_path);
+        remove_temp_config(&config_path);
+        assert!(matches!(
+            config,
+            Err(ConfigError::ContractFeeRateTooLow {
+                contract_fee_rate: 5,
+                funding_fee_rate: 5
+            })
+        ));
+
+        let contents = r#"
+            [taker_config]
+            contract_fee_rate = 6
+            fee_source = static:5
+        "#;
+        let config_path = create_temp_config(contents, "high_contract_fee_taker_config.toml");
+        let config = TakerConfig::new(Some(&config_path)).unwrap();
+        remove_temp_config(&config_path);
+        assert_eq!(config.contract_fee_rate, 6);
+
+        // With estimated funding fee rates, it must at least be above the minimum relay fee rate.
+        let contents = r#"
+            [taker_config]
+            contract_fee_rate = 1
+            fee_source = core:3
+        "#;
+        let config_path = create_temp_config(contents, "relay_contract_fee_taker_config.toml");
+        let config = TakerConfig::from_file_strict(&config_path);
+        remove_temp_config(&config_path);
+        assert!(matches!(
+            config,
+            Err(ConfigError::ContractFeeRateTooLow {
+                contract_fee_rate: 1,
+                funding_fee_rate: 1
+            })
+        ));
+    }
+
     #[test]
     fn test_incorrect_data_type() {
         let contents = r#"
@@ -281,4 +523,58 @@ mod tests {
         remove_temp_config(&config_path);
         assert_eq!(config, TakerConfig::default());
     }
+
+    #[test]
+    fn test_from_file_strict() {
+        // A missing file errors, and isn't created.
+        let config_path = PathBuf::from("missing_strict_taker_config.toml");
+        assert!(matches!(
+            TakerConfig::from_file_strict(&config_path),
+            Err(ConfigError::MissingFile(path)) if path == config_path
+        ));
+        assert!(!config_path.exists());
+
+        // A missing section errors.
+        let contents = r#"
+            [maker_config]
+            refund_locktime = 48
+        "#;
+        let config_path = create_temp_config(contents, "missing_section_taker_config.toml");
+        let missing_section = TakerConfig::from_file_strict(&config_path);
+        remove_temp_config(&config_path);
+        assert!(matches!(
+            missing_section,
+            Err(ConfigError::MissingSection(section)) if section == "taker_config"
+        ));
+
+        // An invalid field errors, naming the key.
+        let contents = r#"
+            [taker_config]
+            refund_locktime = 48
+            reconnect_attempts = many
+        "#;
+        let config_path = create_temp_config(contents, "invalid_field_taker_config.toml");
+        let invalid_field = TakerConfig::from_file_strict(&config_path);
+        remove_temp_config(&config_path);
+        assert!(matches!(
+            invalid_field,
+            Err(ConfigError::InvalidField { field, .. }) if field == "reconnect_attempts"
+        ));
+
+        // A valid file loads, with the absent fields defaulted.
+        let contents = r#"
+            [taker_config]
+            refund_locktime = 48
+        "#;
+        let config_path = create_temp_config(contents, "valid_strict_taker_config.toml");
+        let config = TakerConfig::from_file_strict(&config_path).unwrap();
+        remove_temp_config(&config_path);
+        assert_eq!(
+            config,
+            TakerConfig {
+                refund_locktime: 48,
+                ..TakerConfig::default()
+            }
+        );
+    }
 }
@@ -7,6 +7,7 @@ use bitcoind::bitcoincore_rpc::Error as RpcError;
 use crate::{
     error::{NetError, ProtocolError},
     market::directory::DirectoryServerError,
+    protocol::messages::SwapRejection,
     wallet::WalletError,
 };
 
@@ -20,10 +21,93 @@ pub enum TakerError {
     Wallet(WalletError),
     Directory(DirectoryServerError),
     Net(NetError),
+    /// The SOCKS proxy was reached, but the handshake with it failed, or it couldn't reach the Maker.
+    /// Failing to connect to the proxy itself is an [TakerError::IO].
     Socks(tokio_socks::Error),
     Protocol(ProtocolError),
     SendAmountNotSet,
+    /// The swap was requested with no maker hops.
+    NoMakersRequested,
+    /// The swap was requested with no funding transactions.
+    NoFundingTxsRequested,
+    /// The swap was requested with a zero funding fee rate.
+    FeeRateNotSet,
+    /// The configured fee source has no fee rate estimate.
+    FeeEstimation(String),
+    /// The contract spend fee rate isn't above the funding fee rate of the swap, in sats/vbyte.
+    ContractFeeRateTooLow {
+        contract_fee_rate: u64,
+        funding_fee_rate: u64,
+    },
     FundingTxWaitTimeOut,
+    UneconomicSwap {
+        ratio: f64,
+    },
+    ReactionMarginTooShort {
+        locktime: u16,
+        required: u16,
+    },
+    /// The refund locktime of the first hop, `refund_locktime + refund_locktime_step * maker_count`,
+    /// doesn't fit a locktime.
+    LocktimeOverflow {
+        refund_locktime: u16,
+        refund_locktime_step: u16,
+        maker_count: u16,
+    },
+    /// The maker refused the swap. It isn't misbehaving, another maker can be tried.
+    SwapRejected(SwapRejection),
+    /// A privkey handed over by the maker doesn't match the swapcoin's pubkey.
+    WrongPrivkey(WalletError),
+    /// A maker's signature doesn't verify against the contract transaction.
+    InvalidMakerSignature(WalletError),
+}
+
+impl TakerError {
+    /// Whether the failed step is worth reattempting.
+    ///
+    /// Connection, node and directory failures can be transient, a slow maker might still respond.
+    /// A [ProtocolError::Timeout] is only returned once the attempts of a step are used up.
+    /// Malformed or unexpected messages, refused swaps and invalid swap parameters will not fix
+    /// themselves, so they are not retried.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Self::Net(NetError::Cbor(_))
+            | Self::Protocol(
+                ProtocolError::WrongMessage { .. }
+                | ProtocolError::WrongNumOfSigs { .. }
+                | ProtocolError::WrongNumOfContractTxs { .. }
+                | ProtocolError::WrongNumOfPrivkeys { .. }
+                | ProtocolError::IncorrectFundingAmount { .. }
+                | ProtocolError::Contract(_)
+                | ProtocolError::InvalidContractFields(_)
+                | ProtocolError::WrongContractPrevout { .. }
+                | ProtocolError::NoCompatibleVersion { .. }
+                | ProtocolError::InvalidPreimage { .. },
+            )
+            | Self::ContractsBroadcasted(_)
+            | Self::NotEnoughMakersInOfferBook
+            | Self::Wallet(_)
+            | Self::SendAmountNotSet
+            | Self::NoMakersRequested
+            | Self::NoFundingTxsRequested
+            | Self::FeeRateNotSet
+            | Self::ContractFeeRateTooLow { .. }
+            | Self::FundingTxWaitTimeOut
+            | Self::UneconomicSwap { .. }
+            | Self::ReactionMarginTooShort { .. }
+            | Self::LocktimeOverflow { .. }
+            | Self::SwapRejected(_)
+            | Self::WrongPrivkey(_)
+            | Self::InvalidMakerSignature(_) => false,
+            Self::IO(_)
+            | Self::Socks(_)
+            | Self::Net(_)
+            | Self::Protocol(ProtocolError::Timeout { .. })
+            | Self::RPCError(_)
+            | Self::Directory(_)
+            | Self::FeeEstimation(_) => true,
+        }
+    }
 }
 
 impl From<RpcError> for TakerError {
@@ -58,7 +142,14 @@ impl From<NetError> for TakerError {
 
 impl From<tokio_socks::Error> for TakerError {
     fn from(value: tokio_socks::Error) -> Self {
-        Self::Socks(value)
+        match value {
+            tokio_socks::Error::Io(e) => Self::IO(e),
+            tokio_socks::Error::ProxyServerUnreachable => Self::IO(std::io::Error::new(
+                std::io::ErrorKind::ConnectionRefused,
+                "SOCKS proxy unreachable",
+            )),
+            e => Self::Socks(e),
+        }
     }
 }
 
@@ -0,0 +1,158 @@
+//! Fee rate sources for the Taker's funding transactions.
+//!
+//! A swap requested with [SwapParams::ESTIMATED_FEE_RATE] takes its fee rate from the
+//! [TakerConfig::fee_source](super::TakerConfig::fee_source), instead of a caller picked value.
+
+use std::str::FromStr;
+
+use bitcoin::Amount;
+use bitcoind::bitcoincore_rpc::{Client, RpcApi};
+
+use super::{api::SwapParams, error::TakerError};
+
+/// Where the Taker gets its fee rate from. Written `core`, `core:<target blocks>` or
+/// `static:<sats/vbyte>` in the config file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FeeSource {
+    /// Bitcoin Core's `estimatesmartfee`, for confirmation within `conf_target` blocks.
+    Core { conf_target: u16 },
+    /// A fixed fee rate in sats/vbyte.
+    Static(u64),
+}
+
+impl Default for FeeSource {
+    fn default() -> Self {
+        Self::Core { conf_target: 6 }
+    }
+}
+
+impl FromStr for FeeSource {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.to_lowercase();
+        let (source, value) = match s.split_once(':') {
+            Some((source, value)) => (source, Some(value)),
+            None => (s.as_str(), None),
+        };
+        match (source, value) {
+            ("core", None) => Ok(Self::default()),
+            ("core", Some(target)) => target
+                .parse()
+                .map(|conf_target| Self::Core { conf_target })
+                .map_err(|_| format!("Invalid confirmation target: {}", target)),
+            ("static", Some(rate)) => rate
+                .parse()
+                .map(Self::Static)
+                .map_err(|_| format!("Invalid fee rate: {}", rate)),
+            _ => Err("Invalid fee source".to_string()),
+        }
+    }
+}
+
+/// A source of fee rates, in sats/vbyte.
+pub trait FeeEstimator {
+    fn estimate_fee_rate(&self) -> Result<Amount, TakerError>;
+}
+
+/// Fee rates from Bitcoin Core's `estimatesmartfee`.
+pub struct CoreFeeEstimator<'a> {
+    rpc: &'a Client,
+    conf_target: u16,
+}
+
+impl<'a> CoreFeeEstimator<'a> {
+    pub fn new(rpc: &'a Client, conf_target: u16) -> Self {
+        Self { rpc, conf_target }
+    }
+}
+
+impl FeeEstimator for CoreFeeEstimator<'_> {
+    fn estimate_fee_rate(&self) -> Result<Amount, TakerError> {
+        let estimate = self.rpc.estimate_smart_fee(self.conf_target, None)?;
+        // Core estimates in BTC/kvB. It has no estimate until it has seen enough blocks.
+        match estimate.fee_rate {
+            Some(fee_rate) => Ok(Amount::from_sat(fee_rate.to_sat().div_ceil(1000))),
+            None => Err(TakerError::FeeEstimation(
+                estimate.errors.unwrap_or_default().join(", "),
+            )),
+        }
+    }
+}
+
+/// A fixed fee rate.
+pub struct StaticFeeEstimator(pub Amount);
+
+impl FeeEstimator for StaticFeeEstimator {
+    fn estimate_fee_rate(&self) -> Result<Amount, TakerError> {
+        Ok(self.0)
+    }
+}
+
+/// Fill in the fee rate of swap params requested with [SwapParams::ESTIMATED_FEE_RATE], converting
+/// the estimate to the sats/kvbyte of [SwapParams::fee_rate]. Params with a fee rate already set are
+/// returned unchanged.
+pub(crate) fn resolve_fee_rate(
+    mut swap_params: SwapParams,
+    estimator: &dyn FeeEstimator,
+) -> Result<SwapParams, TakerError> {
+    if swap_params.fee_rate == SwapParams::ESTIMATED_FEE_RATE {
+        let fee_rate = estimator.estimate_fee_rate()?;
+        log::info!("Using estimated fee rate of {} per vbyte", fee_rate);
+        swap_params.fee_rate =
+            fee_rate
+                .checked_mul(1000)
+                .ok_or(TakerError::FeeEstimation(format!(
+                    "Fee rate {} per vbyte is too high",
+                    fee_rate
+                )))?;
+    }
+    Ok(swap_params)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct MockFeeEstimator;
+
+    impl FeeEstimator for MockFeeEstimator {
+        fn estimate_fee_rate(&self) -> Result<Amount, TakerError> {
+            Ok(Amount::from_sat(7))
+        }
+    }
+
+    #[test]
+    fn test_resolve_fee_rate() {
+        let swap_params = SwapParams {
+            send_amount: Amount::from_sat(500000),
+            maker_count: 2,
+            tx_count: 3,
+            fee_rate: SwapParams::ESTIMATED_FEE_RATE,
+            ..SwapParams::default()
+        };
+        let resolved = resolve_fee_rate(swap_params, &MockFeeEstimator).unwrap();
+        assert_eq!(resolved.fee_rate, Amount::from_sat(7000));
+        assert!(resolved
+            .validate(&crate::taker::TakerConfig::default())
+            .is_ok());
+
+        // A fee rate given by the caller wins.
+        let swap_params = SwapParams {
+            fee_rate: Amount::from_sat(1000),
+            ..swap_params
+        };
+        let resolved = resolve_fee_rate(swap_params, &MockFeeEstimator).unwrap();
+        assert_eq!(resolved.fee_rate, Amount::from_sat(1000));
+    }
+
+    #[test]
+    fn test_fee_source_from_str() {
+        assert_eq!("core".parse(), Ok(FeeSource::Core { conf_target: 6 }));
+        assert_eq!("Core:2".parse(), Ok(FeeSource::Core { conf_target: 2 }));
+        assert_eq!("static:15".parse(), Ok(FeeSource::Static(15)));
+        assert!("static".parse::<FeeSource>().is_err());
+        assert!("static:fast".parse::<FeeSource>().is_err());
+        assert!("mempool".parse::<FeeSource>().is_err());
+    }
+}
@@ -7,9 +7,13 @@
 mod api;
 mod config;
 pub mod error;
+mod fees;
 pub mod offers;
 mod routines;
 
 pub use self::api::TakerBehavior;
-pub use api::{SwapParams, Taker};
+pub use api::{
+    MakerFeeEstimate, PrivacyWarning, RecoveredSwap, SwapCostEstimate, SwapParams, Taker,
+};
 pub use config::TakerConfig;
+pub use fees::{CoreFeeEstimator, FeeEstimator, FeeSource, StaticFeeEstimator};
@@ -2,38 +2,55 @@
 //!
 //! It defines structures like [OfferAndAddress] and [MakerAddress] for representing maker offers and addresses.
 //! The [OfferBook] struct keeps track of good and bad makers, and it provides methods for managing offers.
+//! It's persisted in the Taker's data directory, so recent offers and bad makers survive restarts.
 //! The module handles the syncing of the offer book with addresses obtained from directory servers and local configurations.
 //! It uses asynchronous channels for concurrent processing of maker offers.
 
-use std::{fmt, thread, time::Duration};
+use std::{
+    collections::HashMap,
+    fmt, fs, io,
+    path::Path,
+    sync::Arc,
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
 
 use tokio::{
     io::{AsyncReadExt, AsyncWriteExt},
     net::TcpStream,
-    sync::mpsc,
+    sync::{mpsc, Semaphore},
 };
 
 use bitcoin::Network;
 
-use crate::{protocol::messages::Offer, utill::ConnectionType};
+use crate::{error::ProtocolError, protocol::messages::Offer, utill::ConnectionType};
 
 use crate::market::directory::DirectoryServerError;
 
-use super::{config::TakerConfig, routines::download_maker_offer};
+use super::{config::TakerConfig, error::TakerError, routines::download_maker_offer};
 use tokio_socks::tcp::Socks5Stream;
 
 /// Represents an offer along with the corresponding maker address.
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Hash, Serialize, Deserialize)]
 pub struct OfferAndAddress {
     pub offer: Offer,
     pub address: MakerAddress,
 }
 
+impl OfferAndAddress {
+    /// The maker's announcement, sanitized on receipt. Untrusted text, only to be displayed.
+    pub fn announcement(&self) -> Option<&str> {
+        self.offer.announcement.as_deref()
+    }
+}
+
 const _REGTEST_MAKER_ADDRESSES_PORT: &[&str] = &["6102", "16102", "26102", "36102", "46102"];
 
 type OnionAddress = String;
 /// Enum representing maker addresses.
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub struct MakerAddress(OnionAddress);
 
 impl MakerAddress {
@@ -57,29 +74,112 @@ impl fmt::Display for MakerAddress {
     }
 }
 
-/// An ephemeral Offerbook tracking good and bad makers. Currently, Offerbook is initiated
-/// at start of every swap. So good and bad maker list will ot be persisted.
-// TODO: Persist the offerbook in disk.
-#[derive(Debug, Default)]
+/// An Offerbook tracking good and bad makers.
+///
+/// The offers and the bad makers can be persisted with [OfferBook::save], and reused by the next run
+/// while they're fresh. Good makers are the makers tried in the current swap round, and unresponsive
+/// makers the ones whose offer fetch timed out, they aren't persisted.
+#[derive(Debug, Default, Serialize, Deserialize)]
 pub struct OfferBook {
     pub(super) all_makers: Vec<OfferAndAddress>,
+    #[serde(skip)]
     pub(super) good_makers: Vec<OfferAndAddress>,
     pub(super) bad_makers: Vec<OfferAndAddress>,
+    #[serde(skip)]
+    unresponsive_makers: Vec<MakerAddress>,
+    /// Unix time, in seconds, at which each maker's offer was fetched.
+    #[serde(default)]
+    fetched_at: HashMap<MakerAddress, u64>,
+}
+
+fn unix_time_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
 }
 
 impl OfferBook {
+    /// Loads an offerbook written by [OfferBook::save]. Offers from makers known as bad are dropped.
+    pub fn load(path: &Path) -> Result<Self, TakerError> {
+        let file = fs::File::open(path)?;
+        let mut offerbook: Self =
+            serde_json::from_reader(io::BufReader::new(file)).map_err(io::Error::from)?;
+        let bad_addresses = offerbook
+            .bad_makers
+            .iter()
+            .map(|maker| maker.address.clone())
+            .collect::<Vec<_>>();
+        offerbook
+            .all_makers
+            .retain(|offer| !bad_addresses.contains(&offer.address));
+        offerbook
+            .fetched_at
+            .retain(|address, _| !bad_addresses.contains(address));
+        Ok(offerbook)
+    }
+
+    /// Writes the offerbook to path as JSON, replacing any existing file.
+    pub fn save(&self, path: &Path) -> Result<(), TakerError> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let file = fs::File::create(path)?;
+        serde_json::to_writer_pretty(io::BufWriter::new(file), self).map_err(io::Error::from)?;
+        Ok(())
+    }
+
     /// Gets all untried offers.
     pub fn get_all_untried(&self) -> Vec<&OfferAndAddress> {
         self.all_makers
             .iter()
-            .filter(|offer| !self.good_makers.contains(offer) && !self.bad_makers.contains(offer))
+            .filter(|offer| !self.good_makers.contains(offer) && !self.is_bad_maker(&offer.address))
             .collect()
     }
 
-    /// Adds a new offer to the offer book.
+    /// Adds a new offer to the offer book, replacing any older offer from the same maker.
+    /// Returns false if the offer was already known, or its maker is bad.
     pub fn add_new_offer(&mut self, offer: &OfferAndAddress) -> bool {
-        if !self.all_makers.contains(offer) {
-            self.all_makers.push(offer.clone());
+        if self.is_bad_maker(&offer.address) {
+            return false;
+        }
+        self.fetched_at
+            .insert(offer.address.clone(), unix_time_now());
+        match self
+            .all_makers
+            .iter_mut()
+            .find(|known| known.address == offer.address)
+        {
+            Some(known) if known == offer => false,
+            Some(known) => {
+                *known = offer.clone();
+                true
+            }
+            None => {
+                self.all_makers.push(offer.clone());
+                true
+            }
+        }
+    }
+
+    /// Whether the maker's offer was fetched less than `ttl` ago.
+    pub fn is_fresh(&self, address: &MakerAddress, ttl: Duration) -> bool {
+        self.fetched_at
+            .get(address)
+            .is_some_and(|&fetched_at| unix_time_now().saturating_sub(fetched_at) < ttl.as_secs())
+    }
+
+    /// Whether the maker at this address has been marked bad, or unresponsive.
+    pub fn is_bad_maker(&self, address: &MakerAddress) -> bool {
+        self.bad_makers
+            .iter()
+            .any(|maker| &maker.address == address)
+            || self.unresponsive_makers.contains(address)
+    }
+
+    /// Marks a maker that didn't answer for its offer as bad, for the current run.
+    pub fn add_unresponsive_maker(&mut self, address: &MakerAddress) -> bool {
+        if !self.unresponsive_makers.contains(address) {
+            self.unresponsive_makers.push(address.clone());
             true
         } else {
             false
@@ -112,30 +212,62 @@ impl OfferBook {
     }
 }
 
-/// Synchronizes the offer book with specific maker addresses.
+/// The offers downloaded by [fetch_offer_from_makers].
+#[derive(Debug, Default)]
+pub struct FetchedOffers {
+    /// Offers of the makers that answered, in the order of the requested addresses.
+    pub offers: Vec<OfferAndAddress>,
+    /// Makers that didn't answer in time, in the order of the requested addresses.
+    pub timed_out: Vec<MakerAddress>,
+}
+
+/// Downloads the offers of specific maker addresses.
+///
+/// Up to [TakerConfig::offer_fetch_concurrency] makers are queried at the same time. Each of them gets
+/// [TakerConfig::first_connect_attempt_timeout_sec] per attempt to answer, from the start of its own
+/// query, so a slow maker only holds up its own slot. Makers whose last attempt timed out are listed
+/// in [FetchedOffers::timed_out].
 pub async fn fetch_offer_from_makers(
     maker_addresses: Vec<MakerAddress>,
     config: &TakerConfig,
-) -> Vec<OfferAndAddress> {
-    let (offers_writer_m, mut offers_reader) = mpsc::channel::<Option<OfferAndAddress>>(100);
+) -> FetchedOffers {
+    type FetchResult = (usize, Result<OfferAndAddress, TakerError>);
+    let (offers_writer_m, mut offers_reader) = mpsc::channel::<FetchResult>(100);
     //unbounded_channel makes more sense here, but results in a compile
     //error i cant figure out
-    let maker_addresses_len = maker_addresses.len();
-    for addr in maker_addresses {
+    let slots = Arc::new(Semaphore::new(config.offer_fetch_concurrency.max(1)));
+    for (index, addr) in maker_addresses.iter().cloned().enumerate() {
         let offers_writer = offers_writer_m.clone();
         let taker_config: TakerConfig = config.clone();
+        let slots = slots.clone();
         tokio::spawn(async move {
+            // Unwrap Safety: the semaphore is never closed.
+            let _slot = slots.acquire().await.unwrap();
             let offer = download_maker_offer(addr, taker_config).await;
-            offers_writer.send(offer).await.unwrap();
+            offers_writer.send((index, offer)).await.unwrap();
         });
     }
-    let mut result = Vec::<OfferAndAddress>::new();
-    for _ in 0..maker_addresses_len {
-        if let Some(offer_addr) = offers_reader.recv().await.unwrap() {
-            result.push(offer_addr);
+    let mut results = Vec::with_capacity(maker_addresses.len());
+    for _ in 0..maker_addresses.len() {
+        results.push(offers_reader.recv().await.unwrap());
+    }
+    results.sort_by_key(|(index, _)| *index);
+
+    let mut fetched = FetchedOffers::default();
+    for (index, result) in results {
+        match result {
+            Ok(offer_addr) => fetched.offers.push(offer_addr),
+            Err(TakerError::Protocol(ProtocolError::Timeout { .. })) => {
+                log::warn!(
+                    "Maker {} didn't send its offer in time",
+                    maker_addresses[index]
+                );
+                fetched.timed_out.push(maker_addresses[index].clone());
+            }
+            Err(_) => {}
         }
     }
-    result
+    fetched
 }
 
 /// Retrieves advertised maker addresses from directory servers based on the specified network.
@@ -204,3 +336,184 @@ pub async fn fetch_addresses_from_dns(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::taker::routines::tests::test_offer;
+
+    use bitcoind::tempfile::tempdir;
+
+    fn offer_at(address: &str) -> OfferAndAddress {
+        OfferAndAddress {
+            offer: test_offer(),
+            address: MakerAddress::new(address.to_string()),
+        }
+    }
+
+    #[test]
+    fn test_offerbook_save_load() {
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().join("offerbook.json");
+
+        let mut offerbook = OfferBook::default();
+        let good = offer_at("127.0.0.1:6102");
+        let bad = offer_at("127.0.0.1:16102");
+        assert!(offerbook.add_new_offer(&good));
+        assert!(offerbook.add_new_offer(&bad));
+        offerbook.add_good_maker(&good);
+        offerbook.add_bad_maker(&bad);
+        offerbook.save(&path).unwrap();
+
+        let loaded = OfferBook::load(&path).unwrap();
+        assert_eq!(loaded.all_makers, vec![good.clone()]);
+        assert_eq!(loaded.get_bad_makers(), vec![&bad]);
+        // Good makers are only for the current round.
+        assert!(loaded.good_makers.is_empty());
+        assert_eq!(loaded.get_all_untried(), vec![&good]);
+        assert!(loaded.is_fresh(&good.address, Duration::from_secs(60)));
+        assert!(!loaded.is_fresh(&bad.address, Duration::from_secs(60)));
+    }
+
+    #[test]
+    fn test_offerbook_skips_bad_makers() {
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().join("offerbook.json");
+
+        let mut offerbook = OfferBook::default();
+        let offer = offer_at("127.0.0.1:6102");
+        offerbook.add_new_offer(&offer);
+        // The maker turned bad with another offer than the persisted one.
+        let mut bad = offer.clone();
+        bad.offer.max_size = 1;
+        offerbook.add_bad_maker(&bad);
+        offerbook.save(&path).unwrap();
+
+        let mut loaded = OfferBook::load(&path).unwrap();
+        assert!(loaded.all_makers.is_empty());
+        assert!(!loaded.add_new_offer(&offer));
+    }
+
+    #[test]
+    fn test_offer_ttl_expiry() {
+        let mut offerbook = OfferBook::default();
+        let offer = offer_at("127.0.0.1:6102");
+        let ttl = Duration::from_secs(600);
+        assert!(!offerbook.is_fresh(&offer.address, ttl));
+
+        offerbook.add_new_offer(&offer);
+        assert!(offerbook.is_fresh(&offer.address, ttl));
+
+        // Fetched longer ago than the ttl.
+        offerbook
+            .fetched_at
+            .insert(offer.address.clone(), unix_time_now() - 601);
+        assert!(!offerbook.is_fresh(&offer.address, ttl));
+
+        // Refreshing replaces the old offer, and its timestamp.
+        let mut refreshed = offer.clone();
+        refreshed.offer.max_size = 50_000_000;
+        assert!(offerbook.add_new_offer(&refreshed));
+        assert_eq!(offerbook.all_makers, vec![refreshed]);
+        assert!(offerbook.is_fresh(&offer.address, ttl));
+    }
+
+    /// Spawn a fake maker, answering the handshake after `delay` and then sending its offer.
+    async fn spawn_offer_maker(delay: Duration) -> MakerAddress {
+        use crate::{
+            protocol::messages::{
+                MakerHello, MakerToTakerMessage, PROTOCOL_VERSION_MAX, PROTOCOL_VERSION_MIN,
+            },
+            utill::send_message,
+        };
+        use tokio::{io::BufReader, net::TcpListener};
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = MakerAddress::new(listener.local_addr().unwrap().to_string());
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                tokio::spawn(async move {
+                    let (read_half, mut write_half) = socket.split();
+                    let mut reader = BufReader::new(read_half);
+                    let replies = [
+                        MakerToTakerMessage::MakerHello(MakerHello {
+                            protocol_version_min: PROTOCOL_VERSION_MIN,
+                            protocol_version_max: PROTOCOL_VERSION_MAX,
+                        }),
+                        MakerToTakerMessage::RespOffer(Box::new(test_offer())),
+                    ];
+                    for (i, reply) in replies.iter().enumerate() {
+                        let length = reader.read_u32().await.unwrap();
+                        let mut buffer = vec![0; length as usize];
+                        reader.read_exact(&mut buffer).await.unwrap();
+                        if i == 0 {
+                            tokio::time::sleep(delay).await;
+                        }
+                        send_message(&mut write_half, reply).await.unwrap();
+                    }
+                });
+            }
+        });
+        address
+    }
+
+    #[tokio::test]
+    async fn test_fetch_offers_concurrently() {
+        let config = TakerConfig {
+            first_connect_attempts: 2,
+            first_connect_sleep_delay_sec: 0,
+            first_connect_attempt_timeout_sec: 1,
+            connection_type: ConnectionType::CLEARNET,
+            ..TakerConfig::default()
+        };
+        let mut addresses = Vec::new();
+        for delay in [0, 60, 0, 60, 60] {
+            addresses.push(spawn_offer_maker(Duration::from_secs(delay)).await);
+        }
+
+        // The slow makers time out together, not one after the other, after the first attempt and
+        // both reattempts.
+        let start = std::time::Instant::now();
+        let fetched = fetch_offer_from_makers(addresses.clone(), &config).await;
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_secs(3));
+        assert!(elapsed < Duration::from_secs(5));
+        assert_eq!(
+            fetched
+                .offers
+                .iter()
+                .map(|offer| offer.address.clone())
+                .collect::<Vec<_>>(),
+            vec![addresses[0].clone(), addresses[2].clone()]
+        );
+        assert_eq!(
+            fetched.timed_out,
+            vec![
+                addresses[1].clone(),
+                addresses[3].clone(),
+                addresses[4].clone()
+            ]
+        );
+
+        // With a single slot, they do.
+        let config = TakerConfig {
+            offer_fetch_concurrency: 1,
+            ..config
+        };
+        let start = std::time::Instant::now();
+        let fetched =
+            fetch_offer_from_makers(vec![addresses[1].clone(), addresses[3].clone()], &config)
+                .await;
+        assert!(start.elapsed() >= Duration::from_secs(6));
+        assert_eq!(fetched.timed_out.len(), 2);
+
+        // Timed out makers aren't fetched again.
+        let mut offerbook = OfferBook::default();
+        for address in &fetched.timed_out {
+            assert!(offerbook.add_unresponsive_maker(address));
+        }
+        assert!(offerbook.is_bad_maker(&addresses[1]));
+        assert!(!offerbook.is_bad_maker(&addresses[0]));
+    }
+}
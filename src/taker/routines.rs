@@ -1,33 +1,41 @@
 //! Various operational routines/functions.
 //!
 //! It includes functions for handshaking, requesting contract signatures, sending proofs of funding, and downloading maker offers.
-//! It also defines structs for contract transactions and contract information.
-//! Notable types include [ContractTransaction], [ContractsInfo], [ThisMakerInfo], and [NextPeerInfoArgs].
+//! Notable types include [ThisMakerInfo] and [NextPeerInfoArgs].
 //! It also handles downloading maker offers with retry mechanisms and implements the necessary message structures
 //! for communication between taker and maker.
 
-use serde::{Deserialize, Serialize};
-use std::time::Duration;
+use std::{collections::BTreeMap, time::Duration};
 
 use crate::{
     error::ProtocolError,
     protocol::{
         contract::{
             calculate_coinswap_fee, create_contract_redeemscript, find_funding_output_index,
-            validate_contract_tx, FUNDING_TX_VBYTE_SIZE,
+            funding_tx_fee, read_contract_locktime, validate_contract_prevout,
+            validate_contract_tx, validate_contract_tx_fields, FUNDING_TX_VBYTE_SIZE,
         },
         messages::{
-            ContractSigsAsRecvrAndSender, ContractSigsForRecvr, ContractSigsForSender,
-            ContractTxInfoForRecvr, ContractTxInfoForSender, FundingTxInfo, GiveOffer,
-            HashPreimage, MakerToTakerMessage, NextHopInfo, Offer, Preimage, PrivKeyHandover,
-            ProofOfFunding, ReqContractSigsForRecvr, ReqContractSigsForSender, TakerHello,
-            TakerToMakerMessage,
+            negotiate_version, sanitize_announcement, ContractSigsAsRecvrAndSender,
+            ContractSigsForRecvr, ContractSigsForSender, ContractTxInfoForRecvr,
+            ContractTxInfoForSender, FundingTxInfo, GiveOffer, HashPreimage, MakerToTakerMessage,
+            MultisigPrivkey, NextHopInfo, Offer, Preimage, PrivKeyHandover, ProofOfFunding,
+            ReqContractSigsForRecvr, ReqContractSigsForSender, TakerHello, TakerToMakerMessage,
+            PROTOCOL_VERSION_MAX, PROTOCOL_VERSION_MIN,
         },
         Hash160,
     },
     utill::{read_maker_message, send_message, ConnectionType},
 };
-use bitcoin::{secp256k1::SecretKey, Amount, PublicKey, ScriptBuf, Transaction};
+use bitcoin::{
+    hex::DisplayHex,
+    secp256k1::{
+        rand::{rngs::OsRng, RngCore},
+        SecretKey,
+    },
+    Amount, PublicKey, ScriptBuf, Transaction,
+};
+use bitcoind::bitcoincore_rpc::json::ListUnspentResultEntry;
 use tokio::{
     io::BufReader,
     net::{
@@ -40,6 +48,7 @@ use tokio::{
 use tokio_socks::tcp::Socks5Stream;
 
 use super::{
+    api::{MakerFeeEstimate, PrivacyWarning, SwapCostEstimate, SwapParams},
     config::TakerConfig,
     error::TakerError,
     offers::{MakerAddress, OfferAndAddress},
@@ -47,36 +56,59 @@ use super::{
 
 use crate::wallet::SwapCoin;
 
-#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
-pub struct ContractTransaction {
-    pub tx: Transaction,
-    pub redeemscript: ScriptBuf,
-    pub hashlock_spend_without_preimage: Option<Transaction>,
-    pub timelock_spend: Option<Transaction>,
-    pub timelock_spend_broadcasted: bool,
+/// Connects to a Maker, directly or through the Tor SOCKS proxy.
+///
+/// With [TakerConfig::isolate_circuits], every connection authenticates to the proxy with fresh
+/// credentials. Tor isolates streams by SOCKS credentials, so each connection gets its own circuit.
+pub(crate) async fn connect_to_maker(
+    maker_address: &MakerAddress,
+    config: &TakerConfig,
+) -> Result<TcpStream, TakerError> {
+    let address = maker_address.as_str();
+    let socks_address = format!("127.0.0.1:{}", config.socks_port);
+    let socket = match config.connection_type {
+        ConnectionType::CLEARNET => TcpStream::connect(address).await?,
+        ConnectionType::TOR if config.isolate_circuits => {
+            let (username, password) = circuit_isolation_credentials();
+            Socks5Stream::connect_with_password(
+                socks_address.as_str(),
+                address,
+                &username,
+                &password,
+            )
+            .await?
+            .into_inner()
+        }
+        ConnectionType::TOR => Socks5Stream::connect(socks_address.as_str(), address)
+            .await?
+            .into_inner(),
+    };
+    Ok(socket)
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
-pub struct ContractsInfo {
-    pub contract_txes: Vec<ContractTransaction>,
-    pub wallet_label: String,
+/// Random SOCKS credentials, used once, to put a connection on a fresh Tor circuit.
+fn circuit_isolation_credentials() -> (String, String) {
+    let mut bytes = [0u8; 16];
+    OsRng.fill_bytes(&mut bytes);
+    (bytes.to_lower_hex_string(), "coinswap".to_string())
 }
 
-/// Performs a handshake with a Maker and returns and Reader and Writer halves.
+/// Performs a handshake with a Maker and returns the Reader and Writer halves, along with the
+/// protocol version agreed with the Maker.
 pub async fn handshake_maker(
     socket: &mut TcpStream,
-) -> Result<(BufReader<ReadHalf>, WriteHalf), TakerError> {
+) -> Result<(BufReader<ReadHalf<'_>>, WriteHalf<'_>, u32), TakerError> {
     let (reader, mut socket_writer) = socket.split();
     let mut socket_reader = BufReader::new(reader);
     send_message(
         &mut socket_writer,
         &TakerToMakerMessage::TakerHello(TakerHello {
-            protocol_version_min: 0,
-            protocol_version_max: 0,
+            protocol_version_min: PROTOCOL_VERSION_MIN,
+            protocol_version_max: PROTOCOL_VERSION_MAX,
         }),
     )
     .await?;
-    let _makerhello = match read_maker_message(&mut socket_reader).await {
+    let makerhello = match read_maker_message(&mut socket_reader).await {
         Ok(MakerToTakerMessage::MakerHello(m)) => m,
         Ok(any) => {
             return Err((ProtocolError::WrongMessage {
@@ -89,28 +121,30 @@ pub async fn handshake_maker(
             return Err(e.into());
         }
     };
-    Ok((socket_reader, socket_writer))
+    let protocol_version = negotiate_version(
+        (PROTOCOL_VERSION_MIN, PROTOCOL_VERSION_MAX),
+        (
+            makerhello.protocol_version_min,
+            makerhello.protocol_version_max,
+        ),
+    )?;
+    log::debug!("Agreed on protocol version {}", protocol_version);
+    Ok((socket_reader, socket_writer, protocol_version))
 }
 
 /// Request signatures for sender side of the hop. Attempt once.
 pub(crate) async fn req_sigs_for_sender_once<S: SwapCoin>(
-    connection_type: ConnectionType,
+    config: &TakerConfig,
     maker_address: &MakerAddress,
     outgoing_swapcoins: &[S],
     maker_multisig_nonces: &[SecretKey],
     maker_hashlock_nonces: &[SecretKey],
     locktime: u16,
 ) -> Result<ContractSigsForSender, TakerError> {
+    check_contracts_reaction_margin(outgoing_swapcoins, config.min_self_reaction_blocks)?;
     log::info!("Connecting to {}", maker_address);
-    let address = maker_address.as_str();
-
-    let mut socket = match connection_type {
-        ConnectionType::CLEARNET => TcpStream::connect(address).await?,
-        ConnectionType::TOR => Socks5Stream::connect("127.0.0.1:19050", address)
-            .await?
-            .into_inner(),
-    };
-    let (mut socket_reader, mut socket_writer) = handshake_maker(&mut socket).await?;
+    let mut socket = connect_to_maker(maker_address, config).await?;
+    let (mut socket_reader, mut socket_writer, _) = handshake_maker(&mut socket).await?;
     log::info!("===> Sending ReqContractSigsForSender to {}", maker_address);
 
     // TODO: Take this construction out of function body.
@@ -153,6 +187,9 @@ pub(crate) async fn req_sigs_for_sender_once<S: SwapCoin>(
                 m
             }
         }
+        Ok(MakerToTakerMessage::RespSwapRejected(rejection)) => {
+            return Err(TakerError::SwapRejected(rejection));
+        }
         Ok(any) => {
             return Err((ProtocolError::WrongMessage {
                 expected: "RespContractSigsForSender".to_string(),
@@ -170,7 +207,9 @@ pub(crate) async fn req_sigs_for_sender_once<S: SwapCoin>(
         .iter()
         .zip(outgoing_swapcoins.iter())
     {
-        outgoing_swapcoin.verify_contract_tx_sender_sig(sig)?;
+        outgoing_swapcoin
+            .verify_contract_tx_sender_sig(sig)
+            .map_err(TakerError::InvalidMakerSignature)?;
     }
     log::info!("<=== Received ContractSigsForSender from {}", maker_address);
     Ok(contract_sigs_for_sender)
@@ -178,21 +217,16 @@ pub(crate) async fn req_sigs_for_sender_once<S: SwapCoin>(
 
 /// Request signatures for receiver side of the hop. Attempt once.
 pub(crate) async fn req_sigs_for_recvr_once<S: SwapCoin>(
-    connection_type: ConnectionType,
+    config: &TakerConfig,
     maker_address: &MakerAddress,
     incoming_swapcoins: &[S],
     receivers_contract_txes: &[Transaction],
 ) -> Result<ContractSigsForRecvr, TakerError> {
+    check_contracts_reaction_margin(incoming_swapcoins, config.min_self_reaction_blocks)?;
     log::info!("Connecting to {}", maker_address);
-    let address = maker_address.as_str();
-    let mut socket = match connection_type {
-        ConnectionType::CLEARNET => TcpStream::connect(address).await?,
-        ConnectionType::TOR => Socks5Stream::connect("127.0.0.1:19050", address)
-            .await?
-            .into_inner(),
-    };
+    let mut socket = connect_to_maker(maker_address, config).await?;
 
-    let (mut socket_reader, mut socket_writer) = handshake_maker(&mut socket).await?;
+    let (mut socket_reader, mut socket_writer, _) = handshake_maker(&mut socket).await?;
 
     // TODO: Take the message construction out of function body.
     send_message(
@@ -238,13 +272,30 @@ pub(crate) async fn req_sigs_for_recvr_once<S: SwapCoin>(
         .iter()
         .zip(incoming_swapcoins.iter())
     {
-        swapcoin.verify_contract_tx_receiver_sig(sig)?;
+        swapcoin
+            .verify_contract_tx_receiver_sig(sig)
+            .map_err(TakerError::InvalidMakerSignature)?;
     }
 
     log::info!("<=== Received ContractSigsForRecvr from {}", maker_address);
     Ok(contract_sigs_for_recvr)
 }
 
+/// Apply the maker's privatekey to swapcoins, and check it's the correct privkey for corresponding pubkey.
+///
+/// Errors with [TakerError::WrongPrivkey] if a privkey doesn't match its swapcoin.
+pub(crate) fn check_and_apply_maker_private_keys<S: SwapCoin>(
+    swapcoins: &mut [S],
+    swapcoin_private_keys: &[MultisigPrivkey],
+) -> Result<(), TakerError> {
+    for (swapcoin, swapcoin_private_key) in swapcoins.iter_mut().zip(swapcoin_private_keys.iter()) {
+        swapcoin
+            .apply_privkey(swapcoin_private_key.key)
+            .map_err(TakerError::WrongPrivkey)?;
+    }
+    Ok(())
+}
+
 // Type for information related to `this maker` consisting of:
 // `this_maker`, `funding_txs_infos`, `this_maker_contract_txs`
 #[derive(Clone)]
@@ -382,12 +433,16 @@ pub(crate) async fn send_proof_of_funding_and_init_next_hop(
                     .map(|fi| &fi.contract_redeemscript),
             )
     {
-        validate_contract_tx(
-            receivers_contract_tx,
-            Some(&contract_tx.input[0].previous_output),
-            contract_redeemscript,
-        )
-        .map_err(ProtocolError::Contract)?;
+        validate_contract_prevout(receivers_contract_tx, &contract_tx.input[0].previous_output)?;
+        validate_contract_tx(receivers_contract_tx, None, contract_redeemscript)
+            .map_err(ProtocolError::Contract)?;
+        validate_contract_tx_fields(receivers_contract_tx)?;
+    }
+    for senders_contract_tx_info in contract_sigs_as_recvr_and_sender
+        .senders_contract_txs_info
+        .iter()
+    {
+        validate_contract_tx_fields(&senders_contract_tx_info.contract_tx)?;
     }
     let next_swap_contract_redeemscripts = npi
         .next_peer_hashlock_pubkeys
@@ -456,19 +511,174 @@ pub(crate) async fn send_hash_preimage_and_get_private_keys(
     Ok(privkey_handover)
 }
 
+/// Estimate the total fees paid by the Taker for a swap through the given makers.
+///
+/// This includes the coinswap fees of each maker, the miner fees paid to each maker for their funding txs,
+/// and the miner fees of the Taker's own funding txs.
+pub(crate) fn estimate_swap_fees(makers: &[&Offer], swap_params: &SwapParams) -> Amount {
+    let maker_fees = makers
+        .iter()
+        .map(|offer| {
+            calculate_coinswap_fee(
+                offer.absolute_fee_sat,
+                offer.amount_relative_fee_ppb,
+                offer.time_relative_fee_ppb,
+                swap_params.send_amount,
+                1, //time_in_blocks just 1 for now
+            )
+        })
+        .sum::<u64>();
+    // Every funding tx, the Taker's and each maker's, is estimated at [FUNDING_TX_VBYTE_SIZE].
+    let funding_fees = funding_tx_fee(swap_params.fee_rate).to_sat() * swap_params.tx_count as u64;
+    let miner_fees_paid_to_makers = funding_fees * makers.len() as u64;
+    let own_funding_fees = funding_fees;
+
+    Amount::from_sat(maker_fees + miner_fees_paid_to_makers + own_funding_fees)
+}
+
+/// Walk the hops of a swap through the given makers, in route order.
+///
+/// Each maker takes its coinswap fee on the amount it receives, and the next hop is funded with the rest,
+/// minus the miner fees of the maker's funding txs. This is the amount the Taker checks during the swap,
+/// in [send_proof_of_funding_and_init_next_hop].
+pub(crate) fn estimate_swap_cost(
+    makers: &[&OfferAndAddress],
+    swap_params: &SwapParams,
+) -> SwapCostEstimate {
+    let mut amount = swap_params.send_amount;
+    let maker_fees = makers
+        .iter()
+        .map(|oa| {
+            let coinswap_fee = Amount::from_sat(calculate_coinswap_fee(
+                oa.offer.absolute_fee_sat,
+                oa.offer.amount_relative_fee_ppb,
+                oa.offer.time_relative_fee_ppb,
+                amount,
+                1, //time_in_blocks just 1 for now
+            ));
+            let miner_fee = Amount::from_sat(
+                (FUNDING_TX_VBYTE_SIZE
+                    * swap_params.fee_rate.to_sat()
+                    * swap_params.tx_count as u64)
+                    / 1000,
+            );
+            let fee = MakerFeeEstimate {
+                address: oa.address.clone(),
+                amount_in: amount,
+                coinswap_fee,
+                miner_fee,
+            };
+            amount = amount
+                .checked_sub(coinswap_fee + miner_fee)
+                .unwrap_or(Amount::ZERO);
+            fee
+        })
+        .collect();
+
+    SwapCostEstimate {
+        maker_fees,
+        own_miner_fee: funding_tx_fee(swap_params.fee_rate) * swap_params.tx_count as u64,
+        net_received: amount,
+    }
+}
+
+/// Refuse the swap if the estimated fees through the given makers exceed `max_fee_ratio` of the send amount.
+pub(crate) fn check_swap_fee_ratio(
+    makers: &[&Offer],
+    swap_params: &SwapParams,
+    max_fee_ratio: f64,
+) -> Result<(), TakerError> {
+    if swap_params.send_amount == Amount::ZERO {
+        return Err(TakerError::SendAmountNotSet);
+    }
+    let fees = estimate_swap_fees(makers, swap_params);
+    let ratio = fees.to_sat() as f64 / swap_params.send_amount.to_sat() as f64;
+    log::info!(
+        "Estimated swap fees: {} | Fee ratio: {:.4} | Max allowed ratio: {}",
+        fees,
+        ratio,
+        max_fee_ratio
+    );
+    if ratio > max_fee_ratio {
+        return Err(TakerError::UneconomicSwap { ratio });
+    }
+    Ok(())
+}
+
+/// Send amounts that are a multiple of this are considered round, and trivially linkable.
+const ROUND_AMOUNT_SATS: u64 = 10_000;
+
+/// Minimum number of makers for a swap, such that no single maker sees both ends of the swap.
+const MIN_PRIVATE_HOPS: u16 = 2;
+
+/// Privacy warnings derived from the swap parameters alone.
+pub(crate) fn swap_params_privacy_warnings(swap_params: &SwapParams) -> Vec<PrivacyWarning> {
+    let mut warnings = Vec::new();
+    let send_amount = swap_params.send_amount;
+    if send_amount != Amount::ZERO && send_amount.to_sat().is_multiple_of(ROUND_AMOUNT_SATS) {
+        warnings.push(PrivacyWarning::RoundAmount(send_amount));
+    }
+    if swap_params.maker_count < MIN_PRIVATE_HOPS {
+        warnings.push(PrivacyWarning::TooFewHops {
+            maker_count: swap_params.maker_count,
+        });
+    }
+    warnings
+}
+
+/// Warn for every address holding more than one of the given utxos. Spending them links them together.
+pub(crate) fn reused_address_warnings(utxos: &[ListUnspentResultEntry]) -> Vec<PrivacyWarning> {
+    let mut counts = BTreeMap::<&ScriptBuf, usize>::new();
+    for utxo in utxos {
+        *counts.entry(&utxo.script_pub_key).or_default() += 1;
+    }
+    counts
+        .into_iter()
+        .filter(|(_, count)| *count > 1)
+        .map(
+            |(script_pubkey, utxo_count)| PrivacyWarning::ReusedAddress {
+                script_pubkey: script_pubkey.clone(),
+                utxo_count,
+            },
+        )
+        .collect()
+}
+
+/// Refuse a route whose incoming contract locktime leaves the Taker less than `min_self_reaction_blocks`
+/// to broadcast its hashlock claim, before the last maker can recover the coins through the timelock.
+pub(crate) fn check_self_reaction_margin(
+    incoming_locktime: u16,
+    min_self_reaction_blocks: u16,
+) -> Result<(), TakerError> {
+    if incoming_locktime < min_self_reaction_blocks {
+        return Err(TakerError::ReactionMarginTooShort {
+            locktime: incoming_locktime,
+            required: min_self_reaction_blocks,
+        });
+    }
+    Ok(())
+}
+
+/// [check_self_reaction_margin] on the locktime read from every contract of the swapcoins, so no
+/// signature is exchanged over a contract squeezing the margin, whatever the locktime asked for.
+fn check_contracts_reaction_margin<S: SwapCoin>(
+    swapcoins: &[S],
+    min_self_reaction_blocks: u16,
+) -> Result<(), TakerError> {
+    for swapcoin in swapcoins {
+        let locktime = read_contract_locktime(&swapcoin.get_contract_redeemscript())
+            .map_err(ProtocolError::from)?;
+        check_self_reaction_margin(locktime, min_self_reaction_blocks)?;
+    }
+    Ok(())
+}
+
 async fn download_maker_offer_attempt_once(
     addr: &MakerAddress,
-    connection_type: ConnectionType,
+    config: &TakerConfig,
 ) -> Result<Offer, TakerError> {
-    let address = addr.as_str();
-
-    let mut socket = match connection_type {
-        ConnectionType::CLEARNET => TcpStream::connect(address).await?,
-        ConnectionType::TOR => Socks5Stream::connect("127.0.0.1:19050", address)
-            .await?
-            .into_inner(),
-    };
-    let (mut socket_reader, mut socket_writer) = handshake_maker(&mut socket).await?;
+    let mut socket = connect_to_maker(addr, config).await?;
+    let (mut socket_reader, mut socket_writer, _) = handshake_maker(&mut socket).await?;
 
     send_message(
         &mut socket_writer,
@@ -477,7 +687,7 @@ async fn download_maker_offer_attempt_once(
     .await?;
 
     let msg = read_maker_message(&mut socket_reader).await?;
-    let offer = match msg {
+    let mut offer = match msg {
         MakerToTakerMessage::RespOffer(offer) => offer,
         msg => {
             return Err(TakerError::Protocol(ProtocolError::WrongMessage {
@@ -486,21 +696,30 @@ async fn download_maker_offer_attempt_once(
             }));
         }
     };
+    // The announcement is untrusted, sanitize it again before it reaches the offerbook.
+    offer.announcement = offer
+        .announcement
+        .as_deref()
+        .and_then(sanitize_announcement);
 
     Ok(*offer)
 }
 
+/// Downloads the offer of a maker, reattempting up to [TakerConfig::first_connect_attempts] times.
+///
+/// Each attempt gets [TakerConfig::first_connect_attempt_timeout_sec]. Returns the error of the last
+/// attempt, a [ProtocolError::Timeout] if it didn't answer in time.
 pub async fn download_maker_offer(
     address: MakerAddress,
     config: TakerConfig,
-) -> Option<OfferAndAddress> {
+) -> Result<OfferAndAddress, TakerError> {
     let mut ii = 0;
     loop {
         ii += 1;
         select! {
-            ret = download_maker_offer_attempt_once(&address, config.connection_type) => {
+            ret = download_maker_offer_attempt_once(&address, &config) => {
                 match ret {
-                    Ok(offer) => return Some(OfferAndAddress { offer, address }),
+                    Ok(offer) => return Ok(OfferAndAddress { offer, address }),
                     Err(e) => {
                         log::warn!(
                             "Failed to request offer from maker {}, \
@@ -508,12 +727,10 @@ pub async fn download_maker_offer(
                             address,
                             e
                         );
-                        if ii <= config.first_connect_attempts {
-                            sleep(Duration::from_secs(config.first_connect_sleep_delay_sec)).await;
-                            continue;
-                        } else {
-                            return None;
+                        if !e.is_retryable() || ii > config.first_connect_attempts {
+                            return Err(e);
                         }
+                        sleep(Duration::from_secs(config.first_connect_sleep_delay_sec)).await;
                     }
                 }
             },
@@ -522,12 +739,637 @@ pub async fn download_maker_offer(
                     "Timeout for request offer from maker {}, reattempting...",
                     address
                 );
-                if ii <= config.first_connect_attempts {
-                    continue;
-                } else {
-                    return None;
+                if ii > config.first_connect_attempts {
+                    return Err(ProtocolError::Timeout {
+                        step: "request offer".to_string(),
+                    }
+                    .into());
                 }
             },
         }
     }
 }
+
+#[cfg(test)]
+pub(super) mod tests {
+    use super::*;
+    use crate::{
+        error::NetError,
+        protocol::{
+            contract::create_multisig_redeemscript,
+            messages::{FidelityProof, MakerHello, SwapRejection, MAX_ANNOUNCEMENT_LEN},
+        },
+        utill::generate_keypair,
+        wallet::{FidelityBond, WalletError, WatchOnlySwapCoin},
+    };
+    use bitcoin::{
+        absolute::LockTime,
+        hashes::Hash,
+        secp256k1::{ecdsa::Signature, Message, Secp256k1},
+        OutPoint,
+    };
+    use std::{
+        str::FromStr,
+        sync::{
+            atomic::{AtomicU32, Ordering},
+            Arc, Mutex,
+        },
+    };
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    pub(in crate::taker) fn test_offer() -> Offer {
+        let pubkey = PublicKey::from_str(
+            "032e58afe51f9ed8ad3cc7897f634d881fdbe49a81564629ded8156bebd2ffd1af",
+        )
+        .unwrap();
+        Offer {
+            absolute_fee_sat: Amount::from_sat(1000),
+            amount_relative_fee_ppb: Amount::from_sat(10_000_000),
+            time_relative_fee_ppb: Amount::from_sat(100_000),
+            required_confirms: 1,
+            minimum_locktime: 48,
+            max_size: 100_000_000,
+            min_size: 10_000,
+            tweakable_point: pubkey,
+            fidelity: FidelityProof {
+                bond: FidelityBond {
+                    outpoint: OutPoint::null(),
+                    amount: Amount::from_sat(5_000_000),
+                    lock_time: LockTime::from_height(100).unwrap(),
+                    pubkey,
+                    conf_height: 0,
+                    cert_expiry: 1,
+                },
+                cert_hash: Hash::all_zeros(),
+                cert_sig: Signature::from_compact(&[1; 64]).unwrap(),
+            },
+            announcement: None,
+        }
+    }
+
+    #[test]
+    fn test_offer_announcement_round_trip() {
+        let mut offer = test_offer();
+        offer.announcement = sanitize_announcement("contact: https://example.com\n\u{7}pgp: ABCD");
+        assert_eq!(
+            offer.announcement.as_deref(),
+            Some("contact: https://example.com pgp: ABCD")
+        );
+
+        let message = MakerToTakerMessage::RespOffer(Box::new(offer.clone()));
+        let decoded: MakerToTakerMessage =
+            serde_cbor::from_slice(&serde_cbor::ser::to_vec(&message).unwrap()).unwrap();
+        match decoded {
+            MakerToTakerMessage::RespOffer(decoded) => assert_eq!(*decoded, offer),
+            msg => panic!("expected RespOffer, got {}", msg),
+        }
+
+        // Long announcements are capped, and blank ones dropped.
+        let capped = sanitize_announcement(&"a".repeat(MAX_ANNOUNCEMENT_LEN + 10)).unwrap();
+        assert_eq!(capped.chars().count(), MAX_ANNOUNCEMENT_LEN);
+        assert_eq!(sanitize_announcement(" \n\t "), None);
+    }
+
+    #[test]
+    fn test_estimate_swap_fees() {
+        let offer = test_offer();
+        let swap_params = SwapParams {
+            send_amount: Amount::from_sat(500_000),
+            maker_count: 2,
+            tx_count: 3,
+            required_confirms: 1,
+            fee_rate: Amount::from_sat(1000),
+            wait_for_confirms: 0,
+            refund_locktime: None,
+            refund_locktime_step: None,
+        };
+        // maker fee = 1000 + 500_000 * 1% = 6000, for 2 makers.
+        // miner fees paid to makers = 372 * 1000 * 3 * 2 / 1000 = 2232.
+        // own funding fees = 372 * 1000 / 1000 * 3.
+        assert_eq!(
+            estimate_swap_fees(&[&offer, &offer], &swap_params),
+            Amount::from_sat(12_000 + 2232 + 1116)
+        );
+
+        // The miner fees scale with the funding tx size, not just the fee rate.
+        // At 2500 sats/kvbyte, a funding tx pays 372 * 2500 / 1000 = 930.
+        let swap_params = SwapParams {
+            fee_rate: Amount::from_sat(2500),
+            ..swap_params
+        };
+        assert_eq!(
+            estimate_swap_fees(&[&offer, &offer], &swap_params),
+            Amount::from_sat(12_000 + 930 * 3 * 2 + 930 * 3)
+        );
+    }
+
+    #[test]
+    fn test_estimate_swap_cost() {
+        let cheap = OfferAndAddress {
+            offer: test_offer(),
+            address: MakerAddress::new("127.0.0.1:6102".to_string()),
+        };
+        let pricey = OfferAndAddress {
+            offer: Offer {
+                absolute_fee_sat: Amount::from_sat(2000),
+                amount_relative_fee_ppb: Amount::from_sat(20_000_000),
+                ..test_offer()
+            },
+            address: MakerAddress::new("127.0.0.1:16102".to_string()),
+        };
+        let swap_params = SwapParams {
+            send_amount: Amount::from_sat(500_000),
+            maker_count: 2,
+            tx_count: 3,
+            fee_rate: Amount::from_sat(1000),
+            ..SwapParams::default()
+        };
+
+        let estimate = estimate_swap_cost(&[&cheap, &pricey], &swap_params);
+
+        // First hop: fee = 1000 + 500_000 * 1%, miner fees = 372 * 1000 * 3 / 1000.
+        // Second hop gets 500_000 - 6000 - 1116 = 492_884.
+        // fee = 2000 + 492_884 * 2% (rounded down), same miner fees.
+        assert_eq!(
+            estimate.maker_fees,
+            vec![
+                MakerFeeEstimate {
+                    address: cheap.address.clone(),
+                    amount_in: Amount::from_sat(500_000),
+                    coinswap_fee: Amount::from_sat(6000),
+                    miner_fee: Amount::from_sat(1116),
+                },
+                MakerFeeEstimate {
+                    address: pricey.address.clone(),
+                    amount_in: Amount::from_sat(492_884),
+                    coinswap_fee: Amount::from_sat(11_857),
+                    miner_fee: Amount::from_sat(1116),
+                },
+            ]
+        );
+        assert_eq!(estimate.net_received, Amount::from_sat(479_911));
+        assert_eq!(estimate.own_miner_fee, Amount::from_sat(1116));
+        assert_eq!(estimate.total_coinswap_fees(), Amount::from_sat(17_857));
+        assert_eq!(estimate.total_miner_fees(), Amount::from_sat(3348));
+        // Everything sent and not received is fees.
+        assert_eq!(
+            estimate.total_fees(),
+            swap_params.send_amount - estimate.net_received + estimate.own_miner_fee
+        );
+    }
+
+    #[test]
+    fn test_uneconomic_swap_refused() {
+        let offer = test_offer();
+
+        // Tiny send amount at a high fee rate.
+        let swap_params = SwapParams {
+            send_amount: Amount::from_sat(20_000),
+            maker_count: 2,
+            tx_count: 3,
+            required_confirms: 1,
+            fee_rate: Amount::from_sat(10_000),
+            wait_for_confirms: 0,
+            refund_locktime: None,
+            refund_locktime_step: None,
+        };
+        match check_swap_fee_ratio(&[&offer, &offer], &swap_params, 0.25) {
+            Err(TakerError::UneconomicSwap { ratio }) => assert!(ratio > 0.25),
+            other => panic!("expected UneconomicSwap, got {:?}", other),
+        }
+
+        // The same swap goes through if the threshold is overridden.
+        assert!(check_swap_fee_ratio(&[&offer, &offer], &swap_params, f64::MAX).is_ok());
+
+        // A reasonable swap goes through with the default threshold.
+        let swap_params = SwapParams {
+            send_amount: Amount::from_sat(5_000_000),
+            fee_rate: Amount::from_sat(1000),
+            ..swap_params
+        };
+        assert!(check_swap_fee_ratio(&[&offer, &offer], &swap_params, 0.25).is_ok());
+    }
+
+    #[test]
+    fn test_self_reaction_margin() {
+        assert!(check_self_reaction_margin(48, 20).is_ok());
+        assert!(check_self_reaction_margin(20, 20).is_ok());
+        match check_self_reaction_margin(10, 20) {
+            Err(TakerError::ReactionMarginTooShort { locktime, required }) => {
+                assert_eq!(locktime, 10);
+                assert_eq!(required, 20);
+            }
+            other => panic!("expected ReactionMarginTooShort, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_contract_reaction_margin_checked_before_signing() {
+        let (address, connections) = spawn_fake_maker(None).await;
+        let pubkey = test_offer().tweakable_point;
+        let swapcoin = |locktime| {
+            WatchOnlySwapCoin::new(
+                &create_multisig_redeemscript(&pubkey, &test_offer().tweakable_point),
+                pubkey,
+                Transaction {
+                    version: bitcoin::transaction::Version::TWO,
+                    lock_time: LockTime::ZERO,
+                    input: Vec::new(),
+                    output: Vec::new(),
+                },
+                create_contract_redeemscript(&pubkey, &pubkey, &Hash160::all_zeros(), &locktime),
+                Amount::from_sat(100_000),
+            )
+            .unwrap()
+        };
+        let config = TakerConfig {
+            min_self_reaction_blocks: 20,
+            ..test_config()
+        };
+
+        // A contract with a locktime under the margin, whatever the rest of the route.
+        let error = req_sigs_for_recvr_once(&config, &address, &[swapcoin(48), swapcoin(10)], &[])
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            TakerError::ReactionMarginTooShort {
+                locktime: 10,
+                required: 20
+            }
+        ));
+        let error = req_sigs_for_sender_once(&config, &address, &[swapcoin(10)], &[], &[], 48)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            TakerError::ReactionMarginTooShort { locktime: 10, .. }
+        ));
+        // Refused before ever reaching the maker.
+        assert_eq!(connections.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn test_privacy_warnings() {
+        let swap_params = SwapParams {
+            send_amount: Amount::from_sat(500_000),
+            maker_count: 1,
+            tx_count: 3,
+            required_confirms: 1,
+            fee_rate: Amount::from_sat(1000),
+            wait_for_confirms: 0,
+            refund_locktime: None,
+            refund_locktime_step: None,
+        };
+        assert_eq!(
+            swap_params_privacy_warnings(&swap_params),
+            vec![
+                PrivacyWarning::RoundAmount(Amount::from_sat(500_000)),
+                PrivacyWarning::TooFewHops { maker_count: 1 },
+            ]
+        );
+
+        // A non-round amount over two hops is fine.
+        let swap_params = SwapParams {
+            send_amount: Amount::from_sat(512_345),
+            maker_count: 2,
+            ..swap_params
+        };
+        assert!(swap_params_privacy_warnings(&swap_params).is_empty());
+    }
+
+    #[test]
+    fn test_check_and_apply_maker_private_keys() {
+        let (sender_pubkey, sender_privkey) = generate_keypair();
+        let (receiver_pubkey, _) = generate_keypair();
+        let (other_pubkey, _) = generate_keypair();
+        let contract_tx = Transaction {
+            version: bitcoin::transaction::Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: Vec::new(),
+            output: Vec::new(),
+        };
+        let multisig_redeemscript = create_multisig_redeemscript(&sender_pubkey, &receiver_pubkey);
+        let mut swapcoins = vec![WatchOnlySwapCoin::new(
+            &multisig_redeemscript,
+            receiver_pubkey,
+            contract_tx,
+            create_contract_redeemscript(&other_pubkey, &other_pubkey, &Hash160::all_zeros(), &20),
+            Amount::from_sat(100_000),
+        )
+        .unwrap()];
+
+        let privkey = |key| MultisigPrivkey {
+            multisig_redeemscript: multisig_redeemscript.clone(),
+            key,
+        };
+        assert!(
+            check_and_apply_maker_private_keys(&mut swapcoins, &[privkey(sender_privkey)]).is_ok()
+        );
+        let (_, wrong_privkey) = generate_keypair();
+        assert!(matches!(
+            check_and_apply_maker_private_keys(&mut swapcoins, &[privkey(wrong_privkey)]),
+            Err(TakerError::WrongPrivkey(_))
+        ));
+    }
+
+    /// Spawn a fake maker for one connection, answering each Taker message with the next of `replies`.
+    async fn spawn_scripted_maker(replies: Vec<MakerToTakerMessage>) -> MakerAddress {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = MakerAddress::new(listener.local_addr().unwrap().to_string());
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            for reply in replies {
+                let length = socket.read_u32().await.unwrap();
+                let mut buffer = vec![0; length as usize];
+                socket.read_exact(&mut buffer).await.unwrap();
+                let reply = serde_cbor::to_vec(&reply).unwrap();
+                socket.write_u32(reply.len() as u32).await.unwrap();
+                socket.write_all(&reply).await.unwrap();
+            }
+        });
+        address
+    }
+
+    #[tokio::test]
+    async fn test_invalid_maker_signature() {
+        let (sender_pubkey, sender_privkey) = generate_keypair();
+        let (receiver_pubkey, _) = generate_keypair();
+        let contract_tx = Transaction {
+            version: bitcoin::transaction::Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: vec![bitcoin::TxIn::default()],
+            output: Vec::new(),
+        };
+        let swapcoin = WatchOnlySwapCoin::new(
+            &create_multisig_redeemscript(&sender_pubkey, &receiver_pubkey),
+            receiver_pubkey,
+            contract_tx.clone(),
+            create_contract_redeemscript(
+                &sender_pubkey,
+                &sender_pubkey,
+                &Hash160::all_zeros(),
+                &48,
+            ),
+            Amount::from_sat(100_000),
+        )
+        .unwrap();
+
+        // Made with the right key, but not over the contract tx.
+        let sig = bitcoin::ecdsa::Signature::sighash_all(
+            Secp256k1::new().sign_ecdsa(&Message::from_digest([1; 32]), &sender_privkey),
+        );
+        let address = spawn_scripted_maker(vec![
+            MakerToTakerMessage::MakerHello(MakerHello {
+                protocol_version_min: PROTOCOL_VERSION_MIN,
+                protocol_version_max: PROTOCOL_VERSION_MAX,
+            }),
+            MakerToTakerMessage::RespContractSigsForRecvr(ContractSigsForRecvr { sigs: vec![sig] }),
+        ])
+        .await;
+
+        let error = req_sigs_for_recvr_once(&test_config(), &address, &[swapcoin], &[contract_tx])
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            TakerError::InvalidMakerSignature(WalletError::Contract(_))
+        ));
+    }
+
+    /// Spawn a fake maker, reading the [TakerHello] and replying with `reply`, if any.
+    /// Returns the maker address and a counter of accepted connections.
+    async fn spawn_fake_maker(reply: Option<Vec<u8>>) -> (MakerAddress, Arc<AtomicU32>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = MakerAddress::new(listener.local_addr().unwrap().to_string());
+        let connections = Arc::new(AtomicU32::new(0));
+        let connections_clone = connections.clone();
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                connections_clone.fetch_add(1, Ordering::SeqCst);
+                let reply = reply.clone();
+                tokio::spawn(async move {
+                    let length = socket.read_u32().await.unwrap();
+                    let mut buffer = vec![0; length as usize];
+                    socket.read_exact(&mut buffer).await.unwrap();
+                    match reply {
+                        Some(reply) => {
+                            socket.write_u32(reply.len() as u32).await.unwrap();
+                            socket.write_all(&reply).await.unwrap();
+                        }
+                        // Play slow maker, hold the connection without responding.
+                        None => sleep(Duration::from_secs(60)).await,
+                    }
+                });
+            }
+        });
+        (address, connections)
+    }
+
+    type SocksCredentials = Arc<Mutex<Vec<Option<(String, String)>>>>;
+
+    /// Spawn a fake SOCKS5 proxy, answering every connection request with `reply` (0 is success).
+    /// Returns the proxy port and the credentials used by each connection, if any.
+    async fn spawn_fake_socks_proxy(reply: u8) -> (u16, SocksCredentials) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let credentials = SocksCredentials::default();
+        let credentials_clone = credentials.clone();
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let credentials = credentials_clone.clone();
+                tokio::spawn(async move {
+                    // Method negotiation.
+                    let _version = socket.read_u8().await.unwrap();
+                    let mut methods = vec![0; socket.read_u8().await.unwrap() as usize];
+                    socket.read_exact(&mut methods).await.unwrap();
+                    if methods.contains(&2) {
+                        socket.write_all(&[5, 2]).await.unwrap();
+                        let _auth_version = socket.read_u8().await.unwrap();
+                        let mut username = vec![0; socket.read_u8().await.unwrap() as usize];
+                        socket.read_exact(&mut username).await.unwrap();
+                        let mut password = vec![0; socket.read_u8().await.unwrap() as usize];
+                        socket.read_exact(&mut password).await.unwrap();
+                        credentials.lock().unwrap().push(Some((
+                            String::from_utf8(username).unwrap(),
+                            String::from_utf8(password).unwrap(),
+                        )));
+                        socket.write_all(&[1, 0]).await.unwrap();
+                    } else {
+                        credentials.lock().unwrap().push(None);
+                        socket.write_all(&[5, 0]).await.unwrap();
+                    }
+
+                    // Connection request, to a domain target.
+                    let mut request = [0; 4];
+                    socket.read_exact(&mut request).await.unwrap();
+                    let mut target = vec![0; socket.read_u8().await.unwrap() as usize + 2];
+                    socket.read_exact(&mut target).await.unwrap();
+                    socket
+                        .write_all(&[5, reply, 0, 1, 127, 0, 0, 1, 0, 0])
+                        .await
+                        .unwrap();
+                });
+            }
+        });
+        (port, credentials)
+    }
+
+    #[tokio::test]
+    async fn test_isolated_circuits_use_distinct_credentials() {
+        let (socks_port, credentials) = spawn_fake_socks_proxy(0).await;
+        let maker_address = MakerAddress::new("makerhiddenserviceaddress.onion:6102".to_string());
+        let mut config = TakerConfig {
+            connection_type: ConnectionType::TOR,
+            socks_port,
+            isolate_circuits: true,
+            ..TakerConfig::default()
+        };
+
+        connect_to_maker(&maker_address, &config).await.unwrap();
+        connect_to_maker(&maker_address, &config).await.unwrap();
+        config.isolate_circuits = false;
+        connect_to_maker(&maker_address, &config).await.unwrap();
+
+        let credentials = credentials.lock().unwrap();
+        assert_eq!(credentials.len(), 3);
+        assert!(credentials[0].is_some() && credentials[1].is_some());
+        assert_ne!(credentials[0], credentials[1]);
+        // Without isolation, the proxy's default circuit is used.
+        assert_eq!(credentials[2], None);
+    }
+
+    #[tokio::test]
+    async fn test_socks_failure_is_distinct_from_tcp_failure() {
+        let maker_address = MakerAddress::new("makerhiddenserviceaddress.onion:6102".to_string());
+
+        // The proxy is up, but can't reach the Maker.
+        let (socks_port, _) = spawn_fake_socks_proxy(5).await;
+        let mut config = TakerConfig {
+            connection_type: ConnectionType::TOR,
+            socks_port,
+            ..TakerConfig::default()
+        };
+        assert!(matches!(
+            connect_to_maker(&maker_address, &config).await,
+            Err(TakerError::Socks(tokio_socks::Error::ConnectionRefused))
+        ));
+
+        // No proxy listening at all.
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        config.socks_port = listener.local_addr().unwrap().port();
+        drop(listener);
+        assert!(matches!(
+            connect_to_maker(&maker_address, &config).await,
+            Err(TakerError::IO(_))
+        ));
+    }
+
+    fn test_config() -> TakerConfig {
+        TakerConfig {
+            first_connect_attempts: 2,
+            first_connect_sleep_delay_sec: 0,
+            first_connect_attempt_timeout_sec: 1,
+            connection_type: ConnectionType::CLEARNET,
+            ..TakerConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_connection_errors_are_retryable() {
+        assert!(TakerError::from(NetError::ReachedEOF).is_retryable());
+        assert!(TakerError::from(NetError::ConnectionTimedOut).is_retryable());
+        assert!(
+            TakerError::from(std::io::Error::from(std::io::ErrorKind::ConnectionRefused))
+                .is_retryable()
+        );
+        assert!(TakerError::from(tokio_socks::Error::ProxyServerUnreachable).is_retryable());
+        assert!(TakerError::from(tokio_socks::Error::GeneralSocksServerFailure).is_retryable());
+    }
+
+    #[test]
+    fn test_timeout_is_retryable() {
+        assert!(TakerError::from(ProtocolError::Timeout {
+            step: "request offer".to_string()
+        })
+        .is_retryable());
+    }
+
+    #[test]
+    fn test_node_errors_are_retryable() {
+        assert!(TakerError::FeeEstimation("no estimate yet".to_string()).is_retryable());
+        assert!(
+            TakerError::RPCError(bitcoind::bitcoincore_rpc::Error::ReturnedError(
+                "warming up".to_string()
+            ))
+            .is_retryable()
+        );
+    }
+
+    #[test]
+    fn test_malformed_messages_are_not_retryable() {
+        assert!(!TakerError::from(ProtocolError::WrongMessage {
+            expected: "MakerHello".to_string(),
+            received: "RespOffer".to_string(),
+        })
+        .is_retryable());
+        assert!(!TakerError::from(ProtocolError::WrongNumOfSigs {
+            expected: 2,
+            received: 1,
+        })
+        .is_retryable());
+        assert!(!TakerError::from(NetError::Cbor(
+            serde_cbor::from_slice::<MakerToTakerMessage>(&[0xff]).unwrap_err()
+        ))
+        .is_retryable());
+    }
+
+    #[test]
+    fn test_refused_swaps_are_not_retryable() {
+        assert!(!TakerError::SwapRejected(SwapRejection::TooManySwaps {
+            max_concurrent_swaps: 10
+        })
+        .is_retryable());
+        assert!(!TakerError::ContractsBroadcasted(Vec::new()).is_retryable());
+    }
+
+    #[test]
+    fn test_invalid_swap_params_are_not_retryable() {
+        assert!(!TakerError::FeeRateNotSet.is_retryable());
+        assert!(!TakerError::ContractFeeRateTooLow {
+            contract_fee_rate: 1,
+            funding_fee_rate: 2,
+        }
+        .is_retryable());
+        assert!(!TakerError::UneconomicSwap { ratio: 0.5 }.is_retryable());
+        assert!(!TakerError::NotEnoughMakersInOfferBook.is_retryable());
+    }
+
+    #[tokio::test]
+    async fn test_timeout_is_retried() {
+        let (address, connections) = spawn_fake_maker(None).await;
+
+        assert!(matches!(
+            download_maker_offer(address, test_config()).await,
+            Err(TakerError::Protocol(ProtocolError::Timeout { .. }))
+        ));
+        // The first attempt, and then all the reattempts.
+        assert_eq!(connections.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_malformed_message_is_not_retried() {
+        let (address, connections) = spawn_fake_maker(Some(vec![0xff; 4])).await;
+
+        assert!(matches!(
+            download_maker_offer(address, test_config()).await,
+            Err(TakerError::Net(NetError::Cbor(_)))
+        ));
+        assert_eq!(connections.load(Ordering::SeqCst), 1);
+    }
+}
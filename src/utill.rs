@@ -12,7 +12,7 @@ use bitcoin::{
     hashes::{sha256, Hash},
     secp256k1::{
         rand::{rngs::OsRng, RngCore},
-        Secp256k1, SecretKey,
+        All, Secp256k1, SecretKey,
     },
     Network, PublicKey, ScriptBuf, WitnessProgram, WitnessVersion,
 };
@@ -34,15 +34,13 @@ use serde_json::Value;
 use tokio::{
     io::{AsyncReadExt, AsyncWriteExt, BufReader},
     net::tcp::{ReadHalf, WriteHalf},
+    time::sleep,
 };
 
 use crate::{
-    error::NetError,
-    protocol::{
-        contract::derive_maker_pubkey_and_nonce,
-        messages::{MakerToTakerMessage, MultisigPrivkey},
-    },
-    wallet::{SwapCoin, WalletError},
+    error::{ConfigError, DescriptorParseError, NetError, UnknownNetworkError},
+    protocol::{contract::derive_maker_pubkey_and_nonce, messages::MakerToTakerMessage},
+    wallet::WalletError,
 };
 
 const INPUT_CHARSET: &str =
@@ -53,6 +51,9 @@ const MASK_LOW_35_BITS: u64 = 0x7ffffffff;
 const SHIFT_FOR_C0: u64 = 35;
 const CHECKSUM_FINAL_XOR_VALUE: u64 = 1;
 
+/// Core's default minimum relay fee rate, in sats/vbyte. No funding transaction pays less.
+pub const MIN_FEE_RATE: u64 = 1;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum ConnectionType {
     TOR,
@@ -119,7 +120,8 @@ pub fn seed_phrase_to_unique_id(seed: &str) -> String {
 
 /// Setup function that will only run once, even if called multiple times.
 pub fn setup_logger() {
-    Once::new().call_once(|| {
+    static LOGGER: Once = Once::new();
+    LOGGER.call_once(|| {
         env::set_var("RUST_LOG", "coinswap=info");
         let taker_log_dir = get_taker_dir().join("debug.log");
         let maker_log_dir = get_maker_dir().join("debug.log");
@@ -181,17 +183,6 @@ pub async fn read_maker_message(
     Ok(message)
 }
 
-/// Apply the maker's privatekey to swapcoins, and check it's the correct privkey for corresponding pubkey.
-pub fn check_and_apply_maker_private_keys<S: SwapCoin>(
-    swapcoins: &mut [S],
-    swapcoin_private_keys: &[MultisigPrivkey],
-) -> Result<(), WalletError> {
-    for (swapcoin, swapcoin_private_key) in swapcoins.iter_mut().zip(swapcoin_private_keys.iter()) {
-        swapcoin.apply_privkey(swapcoin_private_key.key)?;
-    }
-    Ok(())
-}
-
 /// Generate The Maker's Multisig and HashLock keys and respective nonce values.
 /// Nonce values are random integers and resulting Pubkeys are derived by tweaking the
 /// Maker's advertised Pubkey with these two nonces.
@@ -204,11 +195,12 @@ pub fn generate_maker_keys(
     Vec<PublicKey>,
     Vec<SecretKey>,
 ) {
+    let secp = Secp256k1::new();
     let (multisig_pubkeys, multisig_nonces): (Vec<_>, Vec<_>) = (0..count)
-        .map(|_| derive_maker_pubkey_and_nonce(tweakable_point).unwrap())
+        .map(|_| derive_maker_pubkey_and_nonce(&secp, tweakable_point).unwrap())
         .unzip();
     let (hashlock_pubkeys, hashlock_nonces): (Vec<_>, Vec<_>) = (0..count)
-        .map(|_| derive_maker_pubkey_and_nonce(tweakable_point).unwrap())
+        .map(|_| derive_maker_pubkey_and_nonce(&secp, tweakable_point).unwrap())
         .unzip();
     (
         multisig_pubkeys,
@@ -219,59 +211,76 @@ pub fn generate_maker_keys(
 }
 
 /// Converts a Bitcoin amount from JSON-RPC representation to satoshis.
-pub fn convert_json_rpc_bitcoin_to_satoshis(amount: &Value) -> u64 {
+///
+/// Errors with [WalletError::InvalidRpcAmount] if the value isn't a non-negative number.
+pub fn convert_json_rpc_bitcoin_to_satoshis(amount: &Value) -> Result<u64, WalletError> {
+    let invalid_amount = || WalletError::InvalidRpcAmount(amount.clone());
+    let btc = amount.as_f64().ok_or_else(invalid_amount)?;
+    if btc.is_sign_negative() {
+        return Err(invalid_amount());
+    }
     //to avoid floating point arithmetic, convert the bitcoin amount to
     //string with 8 decimal places, then remove the decimal point to
     //obtain the value in satoshi
     //this is necessary because the json rpc represents bitcoin values
     //as floats :(
-    format!("{:.8}", amount.as_f64().unwrap())
+    format!("{:.8}", btc)
         .replace('.', "")
         .parse::<u64>()
-        .unwrap()
+        .map_err(|_| invalid_amount())
 }
 
 /// Extracts hierarchical deterministic (HD) path components from a descriptor.
 ///
-/// Parses an input descriptor string and returns `Some` with a tuple containing the HD path
-/// components if it's an HD descriptor. If it's not an HD descriptor, it returns `None`.
-pub fn get_hd_path_from_descriptor(descriptor: &str) -> Option<(&str, u32, i32)> {
+/// Returns the `(fingerprint, address type, index)` of the key origin, for the wallet's HD descriptors.
+/// Valid descriptors without such a key origin, like multisig descriptors, give `Ok(None)`.
+/// Malformed descriptors error.
+pub fn get_hd_path_from_descriptor(
+    descriptor: &str,
+) -> Result<Option<(&str, u32, i32)>, DescriptorParseError> {
     //e.g
     //"desc": "wpkh([a945b5ca/1/1]029b77637989868dcd502dbc07d6304dc2150301693ae84a60b379c3b696b289ad)#aq759em9",
-    let open = descriptor.find('[');
-    let close = descriptor.find(']');
-    if open.is_none() || close.is_none() {
-        //unexpected, so printing it to stdout
-        log::error!("unknown descriptor = {}", descriptor);
-        return None;
-    }
-    let path = &descriptor[open.unwrap() + 1..close.unwrap()];
+    let expression = descriptor.split('#').next().unwrap_or(descriptor);
+    if !matches!(expression.find('('), Some(i) if i > 0) || !expression.ends_with(')') {
+        return Err(DescriptorParseError::NotAnExpression(
+            descriptor.to_string(),
+        ));
+    }
+    let (open, close) = match (expression.find('['), expression.find(']')) {
+        (None, None) => return Ok(None),
+        (Some(open), Some(close)) if open < close => (open, close),
+        _ => {
+            return Err(DescriptorParseError::UnclosedKeyOrigin(
+                descriptor.to_string(),
+            ))
+        }
+    };
+    let path = &expression[open + 1..close];
     let path_chunks: Vec<&str> = path.split('/').collect();
     if path_chunks.len() != 3 {
-        return None;
-        //unexpected descriptor = wsh(multi(2,[f67b69a3]0245ddf535f08a04fd86d794b76f8e3949f27f7ae039b641bf277c6a4552b4c387,[dbcd3c6e]030f781e9d2a6d3a823cee56be2d062ed4269f5a6294b20cb8817eb540c641d9a2))#8f70vn2q
-    }
-    let addr_type = path_chunks[1].parse::<u32>();
-    if addr_type.is_err() {
-        log::error!(target: "wallet", "unexpected address_type = {}", path);
-        return None;
-    }
-    let index = path_chunks[2].parse::<i32>();
-    if index.is_err() {
-        return None;
+        //e.g multisig descriptor = wsh(multi(2,[f67b69a3]0245ddf535f08a04fd86d794b76f8e3949f27f7ae039b641bf277c6a4552b4c387,[dbcd3c6e]030f781e9d2a6d3a823cee56be2d062ed4269f5a6294b20cb8817eb540c641d9a2))#8f70vn2q
+        return Ok(None);
     }
-    Some((path_chunks[0], addr_type.unwrap(), index.unwrap()))
+    let invalid_path = || DescriptorParseError::InvalidPathComponent(descriptor.to_string());
+    let addr_type = path_chunks[1].parse::<u32>().map_err(|_| invalid_path())?;
+    let index = path_chunks[2].parse::<i32>().map_err(|_| invalid_path())?;
+    Ok(Some((path_chunks[0], addr_type, index)))
 }
 
 /// Generates a keypair using the secp256k1 elliptic curve.
 pub fn generate_keypair() -> (PublicKey, SecretKey) {
-    let mut privkey = [0u8; 32];
-    OsRng.fill_bytes(&mut privkey);
-    let secp = Secp256k1::new();
-    let privkey = SecretKey::from_slice(&privkey).unwrap();
+    generate_keypair_with(&Secp256k1::new(), &mut OsRng)
+}
+
+/// Generates a keypair from the given randomness, reusing the secp context.
+pub fn generate_keypair_with(
+    secp: &Secp256k1<All>,
+    rng: &mut impl RngCore,
+) -> (PublicKey, SecretKey) {
+    let privkey = SecretKey::new(rng);
     let pubkey = PublicKey {
         compressed: true,
-        inner: bitcoin::secp256k1::PublicKey::from_secret_key(&secp, &privkey),
+        inner: bitcoin::secp256k1::PublicKey::from_secret_key(secp, &privkey),
     };
     (pubkey, privkey)
 }
@@ -315,7 +324,7 @@ pub fn parse_toml(file_path: &PathBuf) -> io::Result<HashMap<String, HashMap<Str
         if line.trim().starts_with('[') {
             current_section = line
                 .trim()
-                .trim_matches(|p| (p == '[' || p == ']'))
+                .trim_matches(|p| p == '[' || p == ']')
                 .to_string();
             sections.insert(current_section.clone(), HashMap::new());
         } else if line.trim().starts_with('#') {
@@ -331,6 +340,39 @@ pub fn parse_toml(file_path: &PathBuf) -> io::Result<HashMap<String, HashMap<Str
     Ok(sections)
 }
 
+/// Default interval, in secs, between two backend polls while waiting on the chain.
+///
+/// Waiting for more confirmations takes longer, so the backend can be polled less often.
+pub fn default_poll_interval_secs(required_confirms: u64) -> u64 {
+    (required_confirms * 2).clamp(1, 60)
+}
+
+/// Interval between two backend polls while waiting out a contract timelock, on swap recovery.
+///
+/// Timelocks are many blocks long, so there's no point polling every few seconds in production, the
+/// chain is scanned every 10 mins. Integration tests mine a block every few seconds, and use the
+/// configured poll interval instead.
+pub fn recovery_poll_interval(poll_interval: Duration) -> Duration {
+    if cfg!(feature = "integration-test") {
+        poll_interval
+    } else {
+        Duration::from_secs(10 * 60)
+    }
+}
+
+/// Call `poll` every `poll_interval` until it returns a value, or fails.
+pub async fn poll_until<T, E>(
+    poll_interval: Duration,
+    mut poll: impl FnMut() -> Result<Option<T>, E>,
+) -> Result<T, E> {
+    loop {
+        if let Some(value) = poll()? {
+            return Ok(value);
+        }
+        sleep(poll_interval).await;
+    }
+}
+
 /// Parse and log errors for each field.
 pub fn parse_field<T: std::str::FromStr>(value: Option<&String>, default: T) -> io::Result<T> {
     match value {
@@ -341,6 +383,39 @@ pub fn parse_field<T: std::str::FromStr>(value: Option<&String>, default: T) ->
     }
 }
 
+/// Parse a field, erroring with a [ConfigError] if the value is invalid, instead of defaulting.
+pub fn parse_field_strict<T: FromStr>(
+    field: &str,
+    value: Option<&String>,
+    default: T,
+) -> Result<T, ConfigError> {
+    match value {
+        Some(value) => value.parse().map_err(|_e| ConfigError::InvalidField {
+            field: field.to_string(),
+            value: value.to_string(),
+            expected_type: std::any::type_name::<T>().to_string(),
+        }),
+        None => Ok(default),
+    }
+}
+
+/// Parse a field of a config section. Invalid values error if `strict`, and otherwise fall back to
+/// the default.
+pub fn parse_config_field<T: FromStr + Clone>(
+    section: &HashMap<String, String>,
+    field: &str,
+    default: T,
+    strict: bool,
+) -> Result<T, ConfigError> {
+    match parse_field_strict(field, section.get(field), default.clone()) {
+        Err(e) if !strict => {
+            log::warn!("{}, using the default", e);
+            Ok(default)
+        }
+        ret => ret,
+    }
+}
+
 /// Function to write data to default toml files
 pub fn write_default_config(path: &PathBuf, toml_data: String) -> std::io::Result<()> {
     if let Some(parent) = path.parent() {
@@ -372,7 +447,7 @@ pub fn monitor_log_for_completion(log_file: &PathBuf, pattern: &str) -> io::Resu
                         return Ok(());
                     }
                 } else {
-                    return Err(io::Error::new(io::ErrorKind::Other, "Error reading line"));
+                    return Err(io::Error::other("Error reading line"));
                 }
             }
 
@@ -461,13 +536,26 @@ pub fn parse_proxy_auth(s: &str) -> Result<(String, String), String> {
     Ok((user, passwd))
 }
 
+/// Parse the remote signer (REQUESTS:RESPONSES) argument from the cli input, the paths the signer
+/// requests are written to and its responses read from.
+pub fn parse_signer_paths(s: &str) -> Result<(PathBuf, PathBuf), String> {
+    let parts: Vec<_> = s.split(':').collect();
+    if parts.len() != 2 {
+        return Err("Invalid format".to_string());
+    }
+
+    Ok((PathBuf::from(parts[0]), PathBuf::from(parts[1])))
+}
+
 /// Parse the network string for Bitcoin Backend. Used in CLI apps.
-pub fn read_bitcoin_network_string(network: &str) -> Result<Network, String> {
+pub fn read_bitcoin_network_string(network: &str) -> Result<Network, UnknownNetworkError> {
     match network {
         "regtest" => Ok(Network::Regtest),
+        "testnet" => Ok(Network::Testnet),
+        "testnet4" => Ok(Network::Testnet4),
         "mainnet" => Ok(Network::Bitcoin),
         "signet" => Ok(Network::Signet),
-        _ => Err("Invalid Bitcoin Network".to_string()),
+        _ => Err(UnknownNetworkError(network.to_string())),
     }
 }
 
@@ -529,15 +617,81 @@ mod tests {
     fn test_convert_json_rpc_bitcoin_to_satoshis() {
         // Test with an integer value
         let amount = json!(1);
-        assert_eq!(convert_json_rpc_bitcoin_to_satoshis(&amount), 100_000_000);
+        assert_eq!(
+            convert_json_rpc_bitcoin_to_satoshis(&amount).unwrap(),
+            100_000_000
+        );
 
         // Test with a very large value
         let amount = json!(12345678.12345678);
         assert_eq!(
-            convert_json_rpc_bitcoin_to_satoshis(&amount),
+            convert_json_rpc_bitcoin_to_satoshis(&amount).unwrap(),
             1_234_567_812_345_678
         );
+
+        // Non-numeric and negative values error, instead of panicking
+        for amount in [json!("1.5"), json!(null), json!(-0.5)] {
+            assert!(matches!(
+                convert_json_rpc_bitcoin_to_satoshis(&amount),
+                Err(WalletError::InvalidRpcAmount(value)) if value == amount
+            ));
+        }
     }
+    #[test]
+    fn test_default_poll_interval() {
+        assert_eq!(default_poll_interval_secs(0), 1);
+        assert_eq!(default_poll_interval_secs(1), 2);
+        assert_eq!(default_poll_interval_secs(6), 12);
+        assert_eq!(default_poll_interval_secs(1000), 60);
+    }
+
+    #[test]
+    fn test_recovery_poll_interval() {
+        let poll_interval = Duration::from_secs(default_poll_interval_secs(1));
+        if cfg!(feature = "integration-test") {
+            assert_eq!(recovery_poll_interval(poll_interval), poll_interval);
+        } else {
+            assert_eq!(
+                recovery_poll_interval(poll_interval),
+                Duration::from_secs(600)
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_poll_until_cadence() {
+        let poll_interval = Duration::from_millis(100);
+        let mut polls = Vec::new();
+        let start = std::time::Instant::now();
+        let value = poll_until(poll_interval, || {
+            polls.push(start.elapsed());
+            Ok::<_, ()>((polls.len() == 5).then_some(polls.len()))
+        })
+        .await
+        .unwrap();
+        assert_eq!(value, 5);
+
+        // The first poll is immediate, the next ones follow the interval.
+        assert!(polls[0] < poll_interval);
+        for pair in polls.windows(2) {
+            let gap = pair[1] - pair[0];
+            assert!(gap >= poll_interval, "polled after {:?}", gap);
+            assert!(gap < poll_interval * 3, "polled after {:?}", gap);
+        }
+
+        // Errors stop the polling.
+        let mut count = 0;
+        assert_eq!(
+            poll_until(poll_interval, || {
+                count += 1;
+                Err::<Option<()>, _>("backend down")
+            })
+            .await,
+            Err("backend down")
+        );
+        assert_eq!(count, 1);
+    }
+
     #[test]
     fn test_to_hex() {
         let mut txid_test_vector = [
@@ -617,42 +771,72 @@ mod tests {
             get_hd_path_from_descriptor(
                 "wpkh([a945b5ca/1/1]020b77637989868dcd502dbc07d6304dc2150301693ae84a60b379c3b696b289ad)#aq759em9"
             ),
-            Some(("a945b5ca", 1, 1))
+            Ok(Some(("a945b5ca", 1, 1)))
         );
     }
     #[test]
     fn test_hd_path_from_descriptor_gets_none() {
+        // Multisig descriptor
         assert_eq!(
             get_hd_path_from_descriptor(
                 "wsh(multi(2,[f67b69a3]0245ddf535f08a04fd86d794b76f8e3949f27f7ae039b641bf277c6a4552b4c387,[dbcd3c6e]030f781e9d2a6d3a823cee56be2d062ed4269f5a6294b20cb8817eb540c641d9a2))#8f70vn2q"
             ),
-            None
+            Ok(None)
+        );
+        // Descriptor without key origin
+        assert_eq!(
+            get_hd_path_from_descriptor(
+                "wpkh(029b77637989868dcd502dbc07d6304dc2150301693ae84a60b379c3b696b289ad)"
+            ),
+            Ok(None)
         );
     }
 
     #[test]
     fn test_hd_path_from_descriptor_failure_cases() {
-        let test_cases = [
+        type ErrorVariant = fn(String) -> DescriptorParseError;
+        let test_cases: [(&str, ErrorVariant); 4] = [
             (
                 "wpkh a945b5ca/1/1 029b77637989868dcd502dbc07d6304dc2150301693ae84a60b379c3b696b289ad aq759em9",
-                None,
-            ), // without brackets
+                DescriptorParseError::NotAnExpression,
+            ), // not an expression
+            (
+                "wpkh([a945b5ca/1/1029b77637989868dcd502dbc07d6304dc2150301693ae84a60b379c3b696b289ad)#aq759em9",
+                DescriptorParseError::UnclosedKeyOrigin,
+            ), // without closing bracket
             (
                 "wpkh([a945b5ca/invalid/1]029b77637989868dcd502dbc07d6304dc2150301693ae84a60b379c3b696b289ad)#aq759em9",
-                None,
+                DescriptorParseError::InvalidPathComponent,
             ), // invalid address type
             (
                 "wpkh([a945b5ca/1/invalid]029b77637989868dcd502dbc07d6304dc2150301693ae84a60b379c3b696b289ad)#aq759em9",
-                None,
+                DescriptorParseError::InvalidPathComponent,
             ), // invalid index
         ];
 
-        for (descriptor, expected_output) in test_cases.iter() {
+        for (descriptor, expected_error) in test_cases.iter() {
             let result = get_hd_path_from_descriptor(descriptor);
-            assert_eq!(result, *expected_output);
+            assert_eq!(result, Err(expected_error(descriptor.to_string())));
         }
     }
 
+    #[test]
+    fn test_generate_keypair_with_seeded_rng() {
+        use bitcoin::secp256k1::rand::{rngs::StdRng, SeedableRng};
+
+        let secp = Secp256k1::new();
+        let keypair = generate_keypair_with(&secp, &mut StdRng::seed_from_u64(42));
+        assert_eq!(
+            keypair,
+            generate_keypair_with(&secp, &mut StdRng::seed_from_u64(42))
+        );
+        assert_ne!(
+            keypair,
+            generate_keypair_with(&secp, &mut StdRng::seed_from_u64(43))
+        );
+        assert_eq!(keypair.0, keypair.1.public_key(&secp).into());
+    }
+
     #[test]
     fn test_generate_maker_keys() {
         // generate_maker_keys: test that given a tweakable_point the return values satisfy the equation:
@@ -724,4 +908,21 @@ mod tests {
 
         remove_temp_config(&file_path);
     }
+
+    #[test]
+    fn test_read_bitcoin_network_string() {
+        for (name, network) in [
+            ("regtest", Network::Regtest),
+            ("testnet", Network::Testnet),
+            ("testnet4", Network::Testnet4),
+            ("signet", Network::Signet),
+            ("mainnet", Network::Bitcoin),
+        ] {
+            assert_eq!(read_bitcoin_network_string(name), Ok(network));
+        }
+        assert_eq!(
+            read_bitcoin_network_string("bitcoin"),
+            Err(UnknownNetworkError("bitcoin".to_string()))
+        );
+    }
 }
@@ -3,62 +3,94 @@
 //! Currently, wallet synchronization is exclusively performed through RPC for makers.
 //! In the future, takers might adopt alternative synchronization methods, such as lightweight wallet solutions.
 
-use std::{convert::TryFrom, fs, path::PathBuf, str::FromStr};
+use std::{
+    convert::TryFrom,
+    fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{Arc, Mutex, PoisonError},
+};
 
 use std::collections::{HashMap, HashSet};
 
 use bitcoin::{
-    bip32::{ChildNumber, DerivationPath, Xpub},
-    hashes::{hash160::Hash as Hash160, hex::FromHex},
-    secp256k1,
-    secp256k1::{Secp256k1, SecretKey},
-    sighash::{EcdsaSighashType, SighashCache},
-    Address, Amount, OutPoint, PublicKey, Script, ScriptBuf, Transaction, Txid,
+    bip32::{ChildNumber, Xpub},
+    hashes::{hash160::Hash as Hash160, hex::FromHex, Hash},
+    secp256k1::Secp256k1,
+    Address, Amount, OutPoint, PublicKey, Script, ScriptBuf, Transaction, TxOut, Txid,
 };
 
-use bitcoind::bitcoincore_rpc::{bitcoincore_rpc_json::ListUnspentResultEntry, Client, RpcApi};
+use bitcoind::bitcoincore_rpc::{
+    bitcoincore_rpc_json::{GetAddressInfoResult, ListUnspentResultEntry},
+    jsonrpc, Client, RpcApi,
+};
+use serde::{Deserialize, Serialize};
 
 use crate::{
-    protocol::contract,
-    utill::{
-        compute_checksum, generate_keypair, get_hd_path_from_descriptor,
-        redeemscript_to_scriptpubkey,
-    },
+    protocol::{contract, messages::Preimage},
+    utill::{compute_checksum, get_hd_path_from_descriptor, redeemscript_to_scriptpubkey},
 };
 
 use super::{
     error::WalletError,
     rpc::RPCConfig,
-    storage::WalletStore,
-    swapcoin::{IncomingSwapCoin, OutgoingSwapCoin, SwapCoin, WalletSwapCoin},
+    signer::{request_signature, LocalSigner, RemoteSigner, SignPayload, SignerKey, WalletPubkeys},
+    storage::{PersistBackend, StoreChange, WalletStore},
+    swapcoin::{
+        p2wsh_prevout, IncomingSwapCoin, OutgoingSwapCoin, SwapCoin, WalletSwapCoin,
+        WatchOnlySwapCoin,
+    },
 };
 
 // these subroutines are coded so that as much as possible they keep all their
 // data in the bitcoin core wallet
 // for example which privkey corresponds to a scriptpubkey is stored in hd paths
 
-const HARDENDED_DERIVATION: &str = "m/84'/1'/0'";
+pub(super) const HARDENDED_DERIVATION: &str = "m/84'/1'/0'";
 
 /// Represents a Bitcoin wallet with associated functionality and data.
 pub struct Wallet {
     pub(crate) rpc: Client,
-    wallet_file_path: PathBuf,
     pub(crate) store: WalletStore,
+    /// Performs all the private key operations, see [RemoteSigner].
+    signer: Arc<dyn RemoteSigner + Send + Sync>,
+    /// The public keys of the signer, checked against the wallet file on load.
+    pub(super) pubkeys: WalletPubkeys,
+    /// Coins locked in the core wallet as the inputs of funding transactions not broadcasted yet.
+    /// They are only unlocked by [Wallet::release_in_flight], the other locks come and go with
+    /// [Wallet::lock_unspendable_utxos]. Kept in memory, like the core wallet locks.
+    in_flight: Mutex<HashSet<OutPoint>>,
+}
+
+/// Derivation metadata of a wallet address, see [Wallet::get_address_info].
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct AddressInfo {
+    /// The keychain the address derives from.
+    pub keychain: KeychainKind,
+    /// The derivation index within the keychain.
+    pub index: u32,
+    /// Whether the core wallet watches the address.
+    pub is_mine: bool,
 }
 
 /// Speicfy the keychain derivation path from [`HARDENDED_DERIVATION`]
 /// Each kind represents an unhardened index value. Starting with External = 0.
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
 pub enum KeychainKind {
+    /// Receiving addresses.
     External = 0isize,
+    /// Change addresses.
     Internal,
+    /// Fidelity bond keys, at [`FIDELITY_DERIVATION_PATH`](super::fidelity::FIDELITY_DERIVATION_PATH).
+    Fidelity,
 }
 
 impl KeychainKind {
-    fn index_num(&self) -> u32 {
+    pub(super) fn index_num(&self) -> u32 {
         match self {
             Self::External => 0,
             Self::Internal => 1,
+            Self::Fidelity => 2,
         }
     }
 }
@@ -134,6 +166,79 @@ pub enum UTXOSpendInfo {
         index: u32,
         input_value: Amount,
     },
+    /// A swapcoin imported with [Wallet::import_watchonly_swapcoin]. It's tracked, never spendable.
+    WatchOnlySwapCoin {
+        multisig_redeemscript: ScriptBuf,
+    },
+}
+
+/// The wallet balance, split by what the coins can be used for. See [Wallet::balance_breakdown].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BalanceBreakdown {
+    /// Seed coins, freely spendable.
+    pub spendable: Amount,
+    /// Coins locked in fidelity bonds.
+    pub fidelity_locked: Amount,
+    /// Completed incoming swapcoins, spendable with the received privkeys.
+    pub swap_incoming: Amount,
+    /// Outgoing swapcoins, committed to a swap in progress.
+    pub swap_outgoing: Amount,
+    /// Broadcasted contract outputs, waiting to be claimed by hashlock or timelock.
+    pub contract_locked: Amount,
+}
+
+/// Restricts coin selection by the coin labels. See [Wallet::set_coin_label].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LabelFilter {
+    /// Only coins with one of these labels.
+    Include(Vec<String>),
+    /// Coins without any of these labels, unlabeled coins included.
+    Exclude(Vec<String>),
+}
+
+impl LabelFilter {
+    /// Whether a coin with this label passes the filter.
+    pub fn allows(&self, label: Option<&str>) -> bool {
+        match self {
+            Self::Include(labels) => label.is_some_and(|l| labels.iter().any(|x| x == l)),
+            Self::Exclude(labels) => !label.is_some_and(|l| labels.iter().any(|x| x == l)),
+        }
+    }
+}
+
+impl BalanceBreakdown {
+    /// The total balance, the sum of all categories.
+    pub fn total(&self) -> Amount {
+        self.spendable
+            + self.fidelity_locked
+            + self.swap_incoming
+            + self.swap_outgoing
+            + self.contract_locked
+    }
+}
+
+/// The decoded fields of a coinswap contract redeemscript. See [contract::create_contract_redeemscript].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContractDetails {
+    /// Pubkey of the party claiming the contract via the hash preimage.
+    pub hashlock_pubkey: PublicKey,
+    /// Pubkey of the party claiming the contract back after the timelock.
+    pub timelock_pubkey: PublicKey,
+    /// The hash value locking the hashlock branch.
+    pub hashvalue: Hash160,
+    /// The relative locktime (in blocks) of the timelock branch.
+    pub locktime: u16,
+}
+
+/// A swap contract transaction found on the network, either in the mempool or in a block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContractBroadcastDetected {
+    /// Txid of the broadcasted contract transaction.
+    pub txid: Txid,
+    /// The contract scriptpubkey this transaction pays to.
+    pub script_pubkey: ScriptBuf,
+    /// Number of confirmations. 0 if the transaction is still in the mempool.
+    pub confirmations: u32,
 }
 
 // Custom type to handle complex return values.
@@ -144,39 +249,94 @@ type SwapCoinsInfo<'a> = (
 
 impl Wallet {
     /// Initialize the wallet at a given path.
+    ///
+    /// encryption_passphrase: Some(value) = Encrypt the wallet file with "value", it's then needed to load it.
+    /// This isn't the BIP39 `passphrase` of the seed.
     pub fn init(
-        path: &PathBuf,
+        path: &Path,
         rpc_config: &RPCConfig,
         seedphrase: String,
         passphrase: String,
+        encryption_passphrase: Option<&str>,
     ) -> Result<Self, WalletError> {
-        let file_name = path
-            .file_name()
-            .expect("file name expected")
-            .to_str()
-            .expect("expected")
-            .to_string();
         let rpc = Client::try_from(rpc_config)?;
         let wallet_birthday = rpc.get_block_count()?;
         let store = WalletStore::init(
-            file_name,
+            Self::file_name(path),
             path,
             rpc_config.network,
             seedphrase,
             passphrase,
             Some(wallet_birthday),
+            encryption_passphrase,
         )?;
-        Ok(Self {
-            rpc,
-            wallet_file_path: path.clone(),
-            store,
-        })
+        let master_key = store.master_key.expect("initialized with a master key");
+        Self::from_store(rpc, store, Box::new(LocalSigner::new(master_key)))
+    }
+
+    /// Initialize the wallet at a given path, with its keys held by `signer`. The wallet file only
+    /// holds the signer's public keys. See [Wallet::init] for the `encryption_passphrase`.
+    pub fn init_with_signer(
+        path: &Path,
+        rpc_config: &RPCConfig,
+        signer: Box<dyn RemoteSigner + Send + Sync>,
+        encryption_passphrase: Option<&str>,
+    ) -> Result<Self, WalletError> {
+        let rpc = Client::try_from(rpc_config)?;
+        let wallet_birthday = rpc.get_block_count()?;
+        let store = WalletStore::init_with_signer_pubkeys(
+            Self::file_name(path),
+            path,
+            rpc_config.network,
+            signer.wallet_pubkeys()?,
+            Some(wallet_birthday),
+            encryption_passphrase,
+        )?;
+        Self::from_store(rpc, store, signer)
+    }
+
+    fn file_name(path: &Path) -> String {
+        path.file_name()
+            .expect("file name expected")
+            .to_str()
+            .expect("expected")
+            .to_string()
     }
 
     /// Load wallet data from file and connects to a core RPC.
     /// The core rpc wallet name, and wallet_id field in the file should match.
-    pub fn load(rpc_config: &RPCConfig, path: &PathBuf) -> Result<Wallet, WalletError> {
-        let store = WalletStore::read_from_disk(path)?;
+    ///
+    /// encryption_passphrase: The passphrase of an encrypted wallet file, None if it's unencrypted.
+    /// Errors with [WalletError::BadPassphrase] if it's wrong.
+    pub fn load(
+        rpc_config: &RPCConfig,
+        path: &Path,
+        encryption_passphrase: Option<&str>,
+    ) -> Result<Wallet, WalletError> {
+        let store = WalletStore::read(path, encryption_passphrase)?;
+        let master_key = store.master_key.ok_or(WalletError::Protocol(
+            "The wallet keys are held by a remote signer, see Wallet::load_with_signer".to_string(),
+        ))?;
+        Self::load_store(rpc_config, store, Box::new(LocalSigner::new(master_key)))
+    }
+
+    /// Load wallet data from file, with its keys held by `signer`. Errors if the signer doesn't hold
+    /// the keys of the wallet. See [Wallet::load] for the `encryption_passphrase`.
+    pub fn load_with_signer(
+        rpc_config: &RPCConfig,
+        path: &Path,
+        signer: Box<dyn RemoteSigner + Send + Sync>,
+        encryption_passphrase: Option<&str>,
+    ) -> Result<Wallet, WalletError> {
+        let store = WalletStore::read(path, encryption_passphrase)?;
+        Self::load_store(rpc_config, store, signer)
+    }
+
+    fn load_store(
+        rpc_config: &RPCConfig,
+        store: WalletStore,
+        signer: Box<dyn RemoteSigner + Send + Sync>,
+    ) -> Result<Wallet, WalletError> {
         if rpc_config.wallet_name != store.file_name {
             return Err(WalletError::Protocol(format!(
                 "Wallet name of database file and core missmatch, expected {}, found {}",
@@ -191,37 +351,69 @@ impl Wallet {
             store.incoming_swapcoins.len(),
             store.outgoing_swapcoins.len()
         );
-        let wallet = Self {
+        Self::from_store(rpc, store, signer)
+    }
+
+    fn from_store(
+        rpc: Client,
+        store: WalletStore,
+        signer: Box<dyn RemoteSigner + Send + Sync>,
+    ) -> Result<Self, WalletError> {
+        let pubkeys = match store.master_key {
+            Some(master_key) => LocalSigner::new(master_key).wallet_pubkeys()?,
+            None => store.signer_pubkeys.ok_or(WalletError::Protocol(
+                "The wallet file holds no keys".to_string(),
+            ))?,
+        };
+        if signer.wallet_pubkeys()? != pubkeys {
+            return Err(WalletError::Protocol(
+                "The signer doesn't hold the keys of this wallet".to_string(),
+            ));
+        }
+        Ok(Self {
             rpc,
-            wallet_file_path: path.clone(),
             store,
-        };
-        Ok(wallet)
+            signer: Arc::from(signer),
+            pubkeys,
+            in_flight: Mutex::default(),
+        })
     }
 
     /// Deletes the wallet file and returns the result as `Ok(())` on success.
     pub fn delete_wallet_file(&self) -> Result<(), WalletError> {
-        Ok(fs::remove_file(&self.wallet_file_path)?)
+        Ok(fs::remove_file(self.store.path())?)
     }
 
     /// Returns a reference to the file path of the wallet.
     pub fn get_file_path(&self) -> &PathBuf {
-        &self.wallet_file_path
+        self.store.path()
+    }
+
+    /// Encrypts the wallet file with `encryption_passphrase`, or decrypts it with None. Replaces the
+    /// previous passphrase, if any, and applies to all the later saves.
+    pub fn set_encryption_passphrase(
+        &mut self,
+        encryption_passphrase: Option<String>,
+    ) -> Result<(), WalletError> {
+        self.store.set_encryption_passphrase(encryption_passphrase)
     }
 
     /// Update external index and saves to disk.
     pub fn update_external_index(&mut self, new_external_index: u32) -> Result<(), WalletError> {
-        self.store.external_index = new_external_index;
-        self.save_to_disk()
+        self.store
+            .stage(StoreChange::ExternalIndex(new_external_index));
+        self.store.commit()
     }
 
     // pub fn get_external_index(&self) -> u32 {
     //     self.external_index
     // }
 
-    /// Update the existing file. Error if path does not exist.
+    /// Update the existing file, including the staged changes. Error if path does not exist.
+    /// Encrypted if the file is.
     pub fn save_to_disk(&self) -> Result<(), WalletError> {
-        self.store.write_to_disk(&self.wallet_file_path)
+        fs::metadata(self.store.path())?;
+        self.store.persist()
     }
 
     /// Finds an incoming swap coin with the specified multisig redeem script.
@@ -248,18 +440,16 @@ impl Wallet {
         self.store.incoming_swapcoins.get_mut(multisig_redeemscript)
     }
 
-    /// Adds an incoming swap coin to the wallet.
+    /// Adds an incoming swap coin to the wallet. It's staged, and saved by the next [Wallet::commit].
     pub fn add_incoming_swapcoin(&mut self, coin: &IncomingSwapCoin) {
         self.store
-            .incoming_swapcoins
-            .insert(coin.get_multisig_redeemscript(), coin.clone());
+            .stage(StoreChange::IncomingSwapCoin(coin.clone()));
     }
 
-    /// Adds an outgoing swap coin to the wallet.
+    /// Adds an outgoing swap coin to the wallet. It's staged, and saved by the next [Wallet::commit].
     pub fn add_outgoing_swapcoin(&mut self, coin: &OutgoingSwapCoin) {
         self.store
-            .outgoing_swapcoins
-            .insert(coin.get_multisig_redeemscript(), coin.clone());
+            .stage(StoreChange::OutgoingSwapCoin(coin.clone()));
     }
 
     /// Removes an incoming swap coin with the specified multisig redeem script from the wallet.
@@ -305,6 +495,36 @@ impl Wallet {
             .fold(Amount::ZERO, |a, (utxo, _)| a + utxo.amount))
     }
 
+    /// Calculates the balance of the wallet in each [BalanceBreakdown] category, in one pass over the utxos.
+    pub fn balance_breakdown(&self) -> Result<BalanceBreakdown, WalletError> {
+        let mut breakdown = BalanceBreakdown::default();
+        for (utxo, spend_info) in self.list_all_utxo_spend_info(None)? {
+            let category = match spend_info {
+                UTXOSpendInfo::SeedCoin { .. } => &mut breakdown.spendable,
+                UTXOSpendInfo::FidelityBondCoin { .. } => &mut breakdown.fidelity_locked,
+                UTXOSpendInfo::SwapCoin {
+                    multisig_redeemscript,
+                } => {
+                    if self
+                        .find_incoming_swapcoin(&multisig_redeemscript)
+                        .is_some()
+                    {
+                        &mut breakdown.swap_incoming
+                    } else {
+                        &mut breakdown.swap_outgoing
+                    }
+                }
+                UTXOSpendInfo::TimelockContract { .. } | UTXOSpendInfo::HashlockContract { .. } => {
+                    &mut breakdown.contract_locked
+                }
+                // Tracked for monitoring, they aren't the wallet's funds.
+                UTXOSpendInfo::WatchOnlySwapCoin { .. } => continue,
+            };
+            *category += utxo.amount;
+        }
+        Ok(breakdown)
+    }
+
     /// Calculates the fidelity balance of the wallet.
     /// Optionally takes in a list of UTXOs to reduce rpc call. If None is provided, the full list is fetched from core rpc.
     pub fn balance_fidelity_bonds(
@@ -411,36 +631,78 @@ impl Wallet {
 
     /// Wallet descriptors are derivable. Currently only supports two KeychainKind. Internal and External.
     fn get_wallet_descriptors(&self) -> Result<HashMap<KeychainKind, String>, WalletError> {
-        let secp = Secp256k1::new();
-        let wallet_xpub = Xpub::from_priv(
-            &secp,
-            &self
-                .store
-                .master_key
-                .derive_priv(
-                    &secp,
-                    &DerivationPath::from_str(HARDENDED_DERIVATION).unwrap(),
-                )
-                .unwrap(),
-        );
-
-        // Get descriptors for external and internal keychain. Other chains are not supported yet.
-        let x = [KeychainKind::External, KeychainKind::Internal]
+        [KeychainKind::External, KeychainKind::Internal]
             .iter()
-            .map(|keychain| {
-                let descriptor_without_checksum =
-                    format!("wpkh({}/{}/*)", wallet_xpub, keychain.index_num());
-                let decriptor = format!(
-                    "{}#{}",
-                    descriptor_without_checksum,
-                    compute_checksum(&descriptor_without_checksum).unwrap()
-                );
-                (*keychain, decriptor)
-            })
-            .collect::<HashMap<KeychainKind, String>>();
+            .map(|keychain| Ok((*keychain, self.get_descriptor(*keychain)?)))
+            .collect()
+    }
 
-        Ok(x)
-        //descriptors.map_err(|e| TeleportError::Rpc(e))
+    /// Returns the BIP380 output descriptor, with checksum, of the given keychain.
+    ///
+    /// The descriptor is of the form `wpkh(xpub/N/*)#checksum`, with the xpub at [`HARDENDED_DERIVATION`]
+    /// and `N` the [`KeychainKind`] index. It can be imported as is into other wallets.
+    ///
+    /// Errors with [`WalletError::NoDescriptor`] for the [`KeychainKind::Fidelity`] keychain. Its keys
+    /// lock the bonds in timelocked P2WSH scripts, one locktime per bond, which no ranged descriptor
+    /// covers.
+    pub fn get_descriptor(&self, kind: KeychainKind) -> Result<String, WalletError> {
+        if kind == KeychainKind::Fidelity {
+            return Err(WalletError::NoDescriptor(kind));
+        }
+        let wallet_xpub = self.get_wallet_xpub()?;
+
+        let descriptor_without_checksum = format!("wpkh({}/{}/*)", wallet_xpub, kind.index_num());
+        let checksum = compute_checksum(&descriptor_without_checksum)?;
+
+        Ok(format!("{}#{}", descriptor_without_checksum, checksum))
+    }
+
+    /// The account xpub at [`HARDENDED_DERIVATION`], all the wallet keychains derive from it.
+    pub(super) fn get_wallet_xpub(&self) -> Result<Xpub, WalletError> {
+        Ok(self.pubkeys.account_xpub)
+    }
+
+    /// Reverse lookup of an address to its keychain and derivation index.
+    ///
+    /// Scans the derived range of both keychains, i.e the addresses imported in the core wallet and the
+    /// external addresses handed out so far. Returns None for addresses not derived from this wallet.
+    pub fn get_address_info(&self, address: &Address) -> Result<Option<AddressInfo>, WalletError> {
+        let secp = Secp256k1::new();
+        let wallet_xpub = self.get_wallet_xpub()?;
+        let script_pubkey = address.script_pubkey();
+
+        // Fidelity bond addresses are P2WSH scripts, not derived addresses.
+        for (keychain, scan_count) in [
+            (
+                KeychainKind::External,
+                std::cmp::max(self.get_addrss_import_count(), self.store.external_index),
+            ),
+            (KeychainKind::Internal, self.get_addrss_import_count()),
+        ] {
+            let keychain_xpub = wallet_xpub.derive_pub(
+                &secp,
+                &[ChildNumber::Normal {
+                    index: keychain.index_num(),
+                }],
+            )?;
+            for index in 0..scan_count {
+                let pubkey = keychain_xpub
+                    .derive_pub(&secp, &[ChildNumber::Normal { index }])?
+                    .public_key;
+                let derived_spk =
+                    ScriptBuf::new_p2wpkh(&bitcoin::CompressedPublicKey(pubkey).wpubkey_hash());
+                if derived_spk == script_pubkey {
+                    let is_mine = self.rpc.get_address_info(address)?.is_mine.unwrap_or(false);
+                    return Ok(Some(AddressInfo {
+                        keychain,
+                        index,
+                        is_mine,
+                    }));
+                }
+            }
+        }
+
+        Ok(None)
     }
 
     /// Checks if the addresses derived from the wallet descriptor is imported upto full index range.
@@ -459,16 +721,14 @@ impl Wallet {
                 .derive_addresses(&descriptor, Some([last_index, last_index]))?[0]
                 .clone();
 
-            let first_addr_imported = self
-                .rpc
-                .get_address_info(&first_addr.assume_checked())?
-                .is_watchonly
-                .unwrap_or(false);
-            let last_addr_imported = self
-                .rpc
-                .get_address_info(&last_addr.assume_checked())?
-                .is_watchonly
-                .unwrap_or(false);
+            // Descriptor wallets report imported addresses as `ismine`, legacy ones as `iswatchonly`.
+            let is_imported = |info: GetAddressInfoResult| {
+                info.is_mine.unwrap_or(false) || info.is_watchonly.unwrap_or(false)
+            };
+            let first_addr_imported =
+                is_imported(self.rpc.get_address_info(&first_addr.assume_checked())?);
+            let last_addr_imported =
+                is_imported(self.rpc.get_address_info(&last_addr.assume_checked())?);
 
             if !first_addr_imported || !last_addr_imported {
                 unimported.push(descriptor);
@@ -478,6 +738,18 @@ impl Wallet {
         Ok(unimported)
     }
 
+    /// Records the hash preimage of a swap, keyed by its hashvalue. Returns the hashvalue.
+    pub fn add_swap_preimage(&mut self, preimage: Preimage) -> Hash160 {
+        let hashvalue = Hash160::hash(&preimage);
+        self.store.swap_preimages.insert(hashvalue, preimage);
+        hashvalue
+    }
+
+    /// Looks up the hash preimage of a swap by its hashvalue.
+    pub fn get_swap_preimage(&self, hashvalue: &Hash160) -> Option<&Preimage> {
+        self.store.swap_preimages.get(hashvalue)
+    }
+
     /// Gets the external index from the wallet.
     pub fn get_external_index(&self) -> &u32 {
         &self.store.external_index
@@ -485,9 +757,7 @@ impl Wallet {
 
     /// Core wallet label is the master XPub fingerint.
     pub fn get_core_wallet_label(&self) -> String {
-        let secp = Secp256k1::new();
-        let m_xpub = Xpub::from_priv(&secp, &self.store.master_key);
-        m_xpub.fingerprint().to_string()
+        self.pubkeys.master_fingerprint.to_string()
     }
 
     fn create_contract_scriptpubkey_outgoing_swapcoin_hashmap(
@@ -521,21 +791,79 @@ impl Wallet {
     }
 
     /// Locks the fidelity and live_contract utxos which are not considered for spending from the wallet.
+    ///
+    /// Stale locks are released, like the ones of swapcoins that became spendable once their swap
+    /// completed. The inputs of in-flight funding transactions stay locked, see [Wallet::lock_in_flight].
+    /// Frozen coins aren't locked, they are left out by [Wallet::spendable_utxos] instead, so they can
+    /// still be listed with `include_frozen`.
     pub fn lock_unspendable_utxos(&self) -> Result<(), WalletError> {
-        self.rpc.unlock_unspent_all()?;
+        let mut in_flight = self
+            .in_flight
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let locked_utxos = self.list_locked_unspent()?;
+        // The inputs of broadcasted funding transactions are spent, nothing to keep locked anymore.
+        in_flight.retain(|outpoint| {
+            locked_utxos
+                .iter()
+                .any(|utxo| OutPoint::new(utxo.txid, utxo.vout) == *outpoint)
+        });
 
-        let all_unspents = self
-            .rpc
-            .list_unspent(Some(0), Some(9999999), None, None, None)?;
-        let utxos_to_lock = &all_unspents
-            .into_iter()
+        let utxos_to_lock = self
+            .get_all_locked_utxo()?
+            .iter()
             .filter(|u| self.check_descriptor_utxo_or_swap_coin(u).is_none())
-            .map(|u| OutPoint {
-                txid: u.txid,
-                vout: u.vout,
+            .map(|u| OutPoint::new(u.txid, u.vout))
+            .collect::<Vec<_>>();
+        let utxos_to_unlock = locked_utxos
+            .iter()
+            .map(|u| (u, OutPoint::new(u.txid, u.vout)))
+            .filter(|(u, outpoint)| {
+                !in_flight.contains(outpoint)
+                    && self.check_descriptor_utxo_or_swap_coin(u).is_some()
             })
-            .collect::<Vec<OutPoint>>();
-        self.rpc.lock_unspent(utxos_to_lock)?;
+            .map(|(_, outpoint)| outpoint)
+            .collect::<Vec<_>>();
+        if !utxos_to_lock.is_empty() {
+            self.rpc.lock_unspent(&utxos_to_lock)?;
+        }
+        if !utxos_to_unlock.is_empty() {
+            self.rpc.unlock_unspent(&utxos_to_unlock)?;
+        }
+        Ok(())
+    }
+
+    /// Locks the inputs of a funding transaction not broadcasted yet, so no other transaction picks
+    /// them. They stay locked until [Wallet::release_in_flight], or until the transaction spends them.
+    pub(crate) fn lock_in_flight(&self, outpoints: &[OutPoint]) -> Result<(), WalletError> {
+        let mut in_flight = self
+            .in_flight
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        self.rpc.lock_unspent(outpoints)?;
+        in_flight.extend(outpoints);
+        Ok(())
+    }
+
+    /// Unlocks the inputs of funding transactions that won't be broadcasted, locked with
+    /// [Wallet::lock_in_flight].
+    pub(crate) fn release_in_flight(&self, outpoints: &[OutPoint]) -> Result<(), WalletError> {
+        let mut in_flight = self
+            .in_flight
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        // Core refuses to unlock the whole list if any of them isn't locked.
+        let still_locked = self
+            .list_locked_outpoints()?
+            .into_iter()
+            .filter(|outpoint| outpoints.contains(outpoint))
+            .collect::<Vec<_>>();
+        if !still_locked.is_empty() {
+            self.rpc.unlock_unspent(&still_locked)?;
+        }
+        for outpoint in outpoints {
+            in_flight.remove(outpoint);
+        }
         Ok(())
     }
 
@@ -583,6 +911,19 @@ impl Wallet {
         None
     }
 
+    /// Checks if a UTXO belongs to a watch-only swapcoin, and then returns corresponding UTXOSpendInfo
+    fn check_if_watchonly_swapcoin(&self, utxo: &ListUnspentResultEntry) -> Option<UTXOSpendInfo> {
+        self.store
+            .watchonly_swapcoins
+            .keys()
+            .find(|multisig_redeemscript| {
+                redeemscript_to_scriptpubkey(multisig_redeemscript) == utxo.script_pub_key
+            })
+            .map(|multisig_redeemscript| UTXOSpendInfo::WatchOnlySwapCoin {
+                multisig_redeemscript: multisig_redeemscript.clone(),
+            })
+    }
+
     /// Checks if a UTXO belongs to descriptor or swap coin, and then returns corresponding UTXOSpendInfo
     fn check_descriptor_utxo_or_swap_coin(
         &self,
@@ -590,20 +931,15 @@ impl Wallet {
     ) -> Option<UTXOSpendInfo> {
         if let Some(descriptor) = &utxo.descriptor {
             // Descriptor logic here
-            if let Some(ret) = get_hd_path_from_descriptor(descriptor) {
+            let hd_path = get_hd_path_from_descriptor(descriptor).unwrap_or_else(|e| {
+                log::warn!("Skipping descriptor: {}", e);
+                None
+            });
+            if let Some(ret) = hd_path {
                 //utxo is in a hd wallet
                 let (fingerprint, addr_type, index) = ret;
 
-                let secp = Secp256k1::new();
-                let master_private_key = self
-                    .store
-                    .master_key
-                    .derive_priv(
-                        &secp,
-                        &DerivationPath::from_str(HARDENDED_DERIVATION).unwrap(),
-                    )
-                    .unwrap();
-                if fingerprint == master_private_key.fingerprint(&secp).to_string() {
+                if fingerprint == self.pubkeys.account_xpub.fingerprint().to_string() {
                     return Some(UTXOSpendInfo::SeedCoin {
                         path: format!("m/{}/{}", addr_type, index),
                         input_value: utxo.amount,
@@ -617,14 +953,14 @@ impl Wallet {
                             .as_ref()
                             .unwrap_or(&ScriptBuf::from(Vec::from_hex("").unwrap())),
                     )
-                    .map_or(false, |sc| sc.other_privkey.is_some())
+                    .is_some_and(|sc| sc.other_privkey.is_some())
                     || self
                         .find_outgoing_swapcoin(
                             utxo.witness_script
                                 .as_ref()
                                 .unwrap_or(&ScriptBuf::from(Vec::from_hex("").unwrap())),
                         )
-                        .map_or(false, |sc| sc.hash_preimage.is_some());
+                        .is_some_and(|sc| sc.hash_preimage.is_some());
                 if found {
                     return Some(UTXOSpendInfo::SwapCoin {
                         multisig_redeemscript: utxo.witness_script.as_ref().unwrap().clone(),
@@ -636,14 +972,77 @@ impl Wallet {
     }
 
     /// Returns a list of all UTXOs tracked by the wallet. Including fidelity, live_contracts and swap coins.
+    ///
+    /// The locked ones too, without unlocking them.
     pub fn get_all_utxo(&self) -> Result<Vec<ListUnspentResultEntry>, WalletError> {
-        self.rpc.unlock_unspent_all()?;
-        let all_utxos = self
-            .rpc
-            .list_unspent(Some(0), Some(9999999), None, None, None)?;
+        let mut all_utxos = self.get_all_locked_utxo()?;
+        all_utxos.extend(self.list_locked_unspent()?);
         Ok(all_utxos)
     }
 
+    /// Lists the outpoints locked in the core wallet.
+    fn list_locked_outpoints(&self) -> Result<Vec<OutPoint>, WalletError> {
+        #[derive(Deserialize)]
+        struct LockedOutPoint {
+            txid: Txid,
+            vout: u32,
+        }
+        let locked: Vec<LockedOutPoint> = self.rpc.call("listlockunspent", &[])?;
+        Ok(locked
+            .into_iter()
+            .map(|outpoint| OutPoint::new(outpoint.txid, outpoint.vout))
+            .collect())
+    }
+
+    /// Lists the unspent coins locked in the core wallet, which `listunspent` leaves out.
+    ///
+    /// The entries are rebuilt from the utxo set and the core wallet's address info.
+    fn list_locked_unspent(&self) -> Result<Vec<ListUnspentResultEntry>, WalletError> {
+        let mut locked_utxos = Vec::new();
+        for outpoint in self.list_locked_outpoints()? {
+            // Spent already, in a block or in the mempool.
+            let Some(txout) = self
+                .rpc
+                .get_tx_out(&outpoint.txid, outpoint.vout, Some(true))?
+            else {
+                continue;
+            };
+            let script_pub_key = ScriptBuf::from(txout.script_pub_key.hex);
+            let address = Address::from_script(&script_pub_key, self.store.network).ok();
+            let (descriptor, witness_script) = match &address {
+                Some(address) => {
+                    let info: serde_json::Value = self
+                        .rpc
+                        .call("getaddressinfo", &[address.to_string().into()])?;
+                    (
+                        info["desc"].as_str().map(str::to_string),
+                        info["hex"]
+                            .as_str()
+                            .filter(|_| info["iswitness"] == true && info["isscript"] == true)
+                            .and_then(|hex| ScriptBuf::from_hex(hex).ok()),
+                    )
+                }
+                None => (None, None),
+            };
+            locked_utxos.push(ListUnspentResultEntry {
+                txid: outpoint.txid,
+                vout: outpoint.vout,
+                address: address.map(|address| address.into_unchecked()),
+                label: None,
+                redeem_script: None,
+                witness_script,
+                script_pub_key,
+                amount: txout.value,
+                confirmations: txout.confirmations,
+                spendable: descriptor.is_some(),
+                solvable: descriptor.is_some(),
+                descriptor,
+                safe: txout.confirmations > 0,
+            });
+        }
+        Ok(locked_utxos)
+    }
+
     pub fn get_all_locked_utxo(&self) -> Result<Vec<ListUnspentResultEntry>, WalletError> {
         let all_utxos = self
             .rpc
@@ -675,6 +1074,9 @@ impl Wallet {
                 if spend_info.is_none() {
                     spend_info = self.check_descriptor_utxo_or_swap_coin(utxo);
                 }
+                if spend_info.is_none() {
+                    spend_info = self.check_if_watchonly_swapcoin(utxo);
+                }
                 spend_info.map(|info| (utxo.clone(), info))
             })
             .collect::<Vec<(ListUnspentResultEntry, UTXOSpendInfo)>>();
@@ -741,12 +1143,79 @@ impl Wallet {
         Ok(filtered_utxos)
     }
 
+    /// Lists the UTXOs that can be spent right away, along with their [UTXOSpendInfo].
+    ///
+    /// This is the single source of truth for coin selection. Excluded are:
+    /// - fidelity bonds and live contracts.
+    /// - watch-only coins, like the swapcoins we don't have the keys for yet.
+    /// - unconfirmed coins not created by us, as they can still be double spent.
+    /// - coins locked in the core wallet, i.e the inputs of in-flight funding transactions.
+    /// - coins frozen by the user. See [Wallet::freeze_utxo].
+    /// - the smallest seed coins covering the fee reserve. See [Wallet::set_fee_reserve].
+    pub fn spendable_utxos(
+        &self,
+    ) -> Result<Vec<(ListUnspentResultEntry, UTXOSpendInfo)>, WalletError> {
+        self.spendable_utxos_with(None, false)
+    }
+
+    /// Same as [Wallet::spendable_utxos], keeping only the coins allowed by the label filter, and
+    /// listing the frozen coins too with `include_frozen`.
+    pub fn spendable_utxos_with(
+        &self,
+        labels: Option<&LabelFilter>,
+        include_frozen: bool,
+    ) -> Result<Vec<(ListUnspentResultEntry, UTXOSpendInfo)>, WalletError> {
+        // Locked coins aren't listed by core.
+        let unlocked_utxos = self.get_all_locked_utxo()?;
+
+        let candidates = self
+            .list_all_utxo_spend_info(Some(&unlocked_utxos))?
+            .into_iter()
+            .filter(|(utxo, spend_info)| {
+                matches!(
+                    spend_info,
+                    UTXOSpendInfo::SeedCoin { .. } | UTXOSpendInfo::SwapCoin { .. }
+                ) && utxo.safe
+            })
+            .collect::<Vec<_>>();
+
+        // Hold back the smallest unfrozen seed coins, until the fee reserve is covered.
+        let mut reserve_coins = candidates
+            .iter()
+            .filter(|(utxo, spend_info)| {
+                matches!(spend_info, UTXOSpendInfo::SeedCoin { .. })
+                    && !self.is_frozen(&OutPoint::new(utxo.txid, utxo.vout))
+            })
+            .map(|(utxo, _)| utxo)
+            .collect::<Vec<_>>();
+        reserve_coins.sort_by_key(|utxo| utxo.amount);
+        let mut reserved_amount = Amount::ZERO;
+        let mut reserved = HashSet::new();
+        for utxo in reserve_coins {
+            if reserved_amount >= self.fee_reserve() {
+                break;
+            }
+            reserved_amount += utxo.amount;
+            reserved.insert(OutPoint::new(utxo.txid, utxo.vout));
+        }
+
+        let spendable = candidates
+            .into_iter()
+            .filter(|(utxo, _)| {
+                let outpoint = OutPoint::new(utxo.txid, utxo.vout);
+                !reserved.contains(&outpoint)
+                    && (include_frozen || !self.is_frozen(&outpoint))
+                    && labels.is_none_or(|filter| filter.allows(self.get_coin_label(&outpoint)))
+            })
+            .collect();
+
+        Ok(spendable)
+    }
+
     /// Finds incomplete coin swaps in the wallet.
     pub fn find_incomplete_coinswaps(
         &self,
-    ) -> Result<HashMap<Hash160, SwapCoinsInfo>, WalletError> {
-        self.rpc.unlock_unspent_all()?;
-
+    ) -> Result<HashMap<Hash160, SwapCoinsInfo<'_>>, WalletError> {
         let completed_coinswap_hashvalues = self
             .store
             .incoming_swapcoins
@@ -763,10 +1232,7 @@ impl Wallet {
             }
             Some(swapcoin_hashvalue)
         };
-        for utxo in self
-            .rpc
-            .list_unspent(Some(0), Some(9999999), None, None, None)?
-        {
+        for utxo in self.get_all_utxo()? {
             if utxo.descriptor.is_none() {
                 continue;
             }
@@ -809,8 +1275,8 @@ impl Wallet {
         let unfinished_incomins = self
             .store
             .incoming_swapcoins
-            .iter()
-            .filter_map(|(_, ic)| {
+            .values()
+            .filter_map(|ic| {
                 if ic.other_privkey.is_none() {
                     Some(ic.clone())
                 } else {
@@ -821,8 +1287,8 @@ impl Wallet {
         let unfinished_outgoings = self
             .store
             .outgoing_swapcoins
-            .iter()
-            .filter_map(|(_, oc)| {
+            .values()
+            .filter_map(|oc| {
                 if oc.hash_preimage.is_none() {
                     Some(oc.clone())
                 } else {
@@ -837,17 +1303,14 @@ impl Wallet {
     /// Finds live contract unspent outputs in the wallet.
     // live contract refers to a contract tx which has been broadcast
     // i.e. where there are UTXOs protected by contract_redeemscript's that we know about
-    pub fn find_live_contract_unspents(&self) -> Result<SwapCoinsInfo, WalletError> {
+    pub fn find_live_contract_unspents(&self) -> Result<SwapCoinsInfo<'_>, WalletError> {
         // populate hashmaps where key is contract scriptpubkey and value is the swapcoin
         let contract_scriptpubkeys_incoming_swapcoins =
             self.create_contract_scriptpubkey_incoming_swapcoin_hashmap();
         let contract_scriptpubkeys_outgoing_swapcoins =
             self.create_contract_scriptpubkey_outgoing_swapcoin_hashmap();
 
-        self.rpc.unlock_unspent_all()?;
-        let listunspent = self
-            .rpc
-            .list_unspent(Some(0), Some(9999999), None, None, None)?;
+        let listunspent = self.get_all_utxo()?;
 
         let (incoming_swapcoins_utxos, outgoing_swapcoins_utxos): (Vec<_>, Vec<_>) = listunspent
             .iter()
@@ -858,7 +1321,7 @@ impl Wallet {
                     u,
                 )
             })
-            .filter(|isc_osc_u| (isc_osc_u.0.is_some() || isc_osc_u.1.is_some()))
+            .filter(|isc_osc_u| isc_osc_u.0.is_some() || isc_osc_u.1.is_some())
             .partition(|isc_osc_u| isc_osc_u.0.is_some());
 
         Ok((
@@ -886,11 +1349,14 @@ impl Wallet {
                 continue;
             }
             let descriptor = utxo.descriptor.expect("its not none");
-            let ret = get_hd_path_from_descriptor(&descriptor);
-            if ret.is_none() {
-                continue;
-            }
-            let (_, addr_type, index) = ret.expect("its not none");
+            let (_, addr_type, index) = match get_hd_path_from_descriptor(&descriptor) {
+                Ok(Some(ret)) => ret,
+                Ok(None) => continue,
+                Err(e) => {
+                    log::warn!("Skipping descriptor: {}", e);
+                    continue;
+                }
+            };
             if addr_type != keychain.index_num() {
                 continue;
             }
@@ -899,8 +1365,18 @@ impl Wallet {
         Ok((max_index + 1) as u32)
     }
 
-    /// Gets the next external address from the HD keychain.
+    /// Gets the next external address from the HD keychain, and commits the handout to disk, so the
+    /// address isn't handed out again.
     pub fn get_next_external_address(&mut self) -> Result<Address, WalletError> {
+        let receive_address = self.peek_next_external_address()?;
+        self.store
+            .stage(StoreChange::ExternalIndex(self.store.external_index + 1));
+        self.store.commit()?;
+        Ok(receive_address)
+    }
+
+    /// The address [Wallet::get_next_external_address] hands out next, without handing it out.
+    pub(super) fn peek_next_external_address(&self) -> Result<Address, WalletError> {
         let descriptors = self.get_wallet_descriptors()?;
         let receive_branch_descriptor = descriptors
             .get(&KeychainKind::External)
@@ -910,10 +1386,23 @@ impl Wallet {
             Some([self.store.external_index, self.store.external_index]),
         )?[0]
             .clone();
-        self.update_external_index(self.store.external_index + 1)?;
         Ok(receive_address.assume_checked())
     }
 
+    /// Derives a fresh external address, and its BIP21 payment URI, ready to be rendered as a QR code.
+    ///
+    /// The URI is of the form `bitcoin:<address>?amount=<btc>&label=<label>`, with the optional parts
+    /// only included when given. The label is percent-encoded.
+    pub fn new_payment_request(
+        &mut self,
+        amount: Option<Amount>,
+        label: Option<String>,
+    ) -> Result<(Address, String), WalletError> {
+        let address = self.get_next_external_address()?;
+        let uri = bip21_uri(&address, amount, label.as_deref());
+        Ok((address, uri))
+    }
+
     /// Gets the next internal addresses from the HD keychain.
     pub fn get_next_internal_addresses(&self, count: u32) -> Result<Vec<Address>, WalletError> {
         let next_change_addr_index = self.find_hd_next_index(KeychainKind::Internal)?;
@@ -948,21 +1437,121 @@ impl Wallet {
         self.store.offer_maxsize
     }
 
-    /// Gets a tweakable key pair from the master key of the wallet.
-    pub fn get_tweakable_keypair(&self) -> (SecretKey, PublicKey) {
-        let secp = Secp256k1::new();
-        let privkey = self
-            .store
-            .master_key
-            .derive_priv(&secp, &[ChildNumber::from_hardened_idx(0).unwrap()])
-            .unwrap()
-            .private_key;
+    /// The tweakable point of the wallet, the public key of [SignerKey::Tweakable].
+    pub fn get_tweakable_pubkey(&self) -> PublicKey {
+        self.pubkeys.tweakable_point
+    }
+
+    /// The signer performing the private key operations of the wallet.
+    pub fn signer(&self) -> &dyn RemoteSigner {
+        self.signer.as_ref()
+    }
+
+    /// A handle on the signer, for the calls needing the wallet mutably, like
+    /// [Wallet::spend_from_wallet_with].
+    pub fn signer_handle(&self) -> Arc<dyn RemoteSigner + Send + Sync> {
+        self.signer.clone()
+    }
 
-        let public_key = PublicKey {
-            compressed: true,
-            inner: privkey.public_key(&secp),
+    /// Derives the next swap key, for the multisig or timelock key of an outgoing swapcoin.
+    ///
+    /// The index is saved right away: the multisig keys are handed over at the end of the swap, a key
+    /// must never be used twice.
+    pub fn next_swap_key(&mut self) -> Result<(SignerKey, PublicKey), WalletError> {
+        let key = SignerKey::Swap {
+            index: self.store.swap_key_index,
         };
-        (privkey, public_key)
+        let pubkey = self.signer.public_key(&key)?;
+        self.store
+            .stage(StoreChange::SwapKeyIndex(self.store.swap_key_index + 1));
+        self.store.commit()?;
+        Ok((key, pubkey))
+    }
+
+    /// Decodes a contract redeemscript into its [ContractDetails].
+    ///
+    /// This is the inverse of [contract::create_contract_redeemscript]. Errors if the script
+    /// doesn't follow the exact coinswap contract template.
+    pub fn decode_contract(redeemscript: &Script) -> Result<ContractDetails, WalletError> {
+        // The locktime is the 13th instruction, don't let short scripts get that far.
+        if redeemscript.instructions().count() < 13 {
+            return Err(WalletError::Protocol(
+                "Contract redeemscript has too few instructions".to_string(),
+            ));
+        }
+        let details = ContractDetails {
+            hashlock_pubkey: contract::read_hashlock_pubkey_from_contract(redeemscript)?,
+            timelock_pubkey: contract::read_timelock_pubkey_from_contract(redeemscript)?,
+            hashvalue: contract::read_hashvalue_from_contract(redeemscript)?,
+            locktime: contract::read_contract_locktime(redeemscript)?,
+        };
+
+        // The readers only look at fixed positions, so rebuild the script to check the rest of it.
+        let expected = contract::create_contract_redeemscript(
+            &details.hashlock_pubkey,
+            &details.timelock_pubkey,
+            &details.hashvalue,
+            &details.locktime,
+        );
+        if expected.as_script() != redeemscript {
+            return Err(WalletError::Protocol(
+                "Script is not a coinswap contract redeemscript".to_string(),
+            ));
+        }
+
+        Ok(details)
+    }
+
+    /// Checks if a contract transaction has appeared in the mempool or in a block.
+    ///
+    /// The contract output must pay to the P2WSH of `contract_redeemscript`. It's first looked up in the
+    /// utxo set (including the mempool). If the output is already spent, falls back to looking up the
+    /// transaction itself. Returns `None` if the contract transaction isn't broadcasted.
+    pub fn detect_contract_broadcast(
+        &self,
+        contract_tx: &Transaction,
+        contract_redeemscript: &ScriptBuf,
+    ) -> Result<Option<ContractBroadcastDetected>, WalletError> {
+        let txid = contract_tx.compute_txid();
+        let script_pubkey = redeemscript_to_scriptpubkey(contract_redeemscript);
+        match contract_tx.output.first() {
+            Some(output) if output.script_pubkey == script_pubkey => {}
+            Some(_) => {
+                return Err(WalletError::Protocol(format!(
+                    "Contract transaction {} pays to an unexpected scriptpubkey",
+                    txid
+                )))
+            }
+            None => {
+                return Err(WalletError::Protocol(
+                    "Contract transaction has no output".to_string(),
+                ))
+            }
+        }
+
+        if let Some(txout) = self.rpc.get_tx_out(&txid, 0, Some(true))? {
+            return Ok(Some(ContractBroadcastDetected {
+                txid,
+                script_pubkey,
+                confirmations: txout.confirmations,
+            }));
+        }
+
+        // The contract output might be spent already, via the hashlock or timelock path.
+        match self.rpc.get_raw_transaction_info(&txid, None) {
+            Ok(tx_info) => Ok(Some(ContractBroadcastDetected {
+                txid,
+                script_pubkey,
+                confirmations: tx_info.confirmations.unwrap_or(0),
+            })),
+            // RPC_INVALID_ADDRESS_OR_KEY, the transaction isn't known to the node.
+            Err(bitcoind::bitcoincore_rpc::Error::JsonRpc(jsonrpc::Error::Rpc(e)))
+                if e.code == -5 =>
+            {
+                Ok(None)
+            }
+            Err(e) => Err(e.into()),
+        }
     }
 
     /// Signs a transaction corresponding to the provided UTXO spend information.
@@ -971,16 +1560,76 @@ impl Wallet {
         tx: &mut Transaction,
         inputs_info: impl Iterator<Item = UTXOSpendInfo>,
     ) -> Result<(), WalletError> {
-        let secp = Secp256k1::new();
-        let master_private_key = self
-            .store
-            .master_key
-            .derive_priv(
-                &secp,
-                &DerivationPath::from_str(HARDENDED_DERIVATION).unwrap(),
-            )
-            .unwrap();
+        self.sign_transaction_with(tx, inputs_info, self.signer())
+    }
+
+    /// The output spent by an input, rebuilt from its spend information.
+    fn spent_output(&self, input_info: &UTXOSpendInfo) -> Result<TxOut, WalletError> {
+        Ok(match input_info {
+            UTXOSpendInfo::SeedCoin { path, input_value } => {
+                let pubkey = self
+                    .signer
+                    .public_key(&SignerKey::SeedCoin { path: path.clone() })?;
+                TxOut {
+                    value: *input_value,
+                    script_pubkey: ScriptBuf::new_p2wpkh(
+                        &pubkey
+                            .wpubkey_hash()
+                            .map_err(|e| WalletError::Protocol(e.to_string()))?,
+                    ),
+                }
+            }
+            UTXOSpendInfo::SwapCoin {
+                multisig_redeemscript,
+            } => {
+                let swapcoin = self
+                    .find_incoming_swapcoin(multisig_redeemscript)
+                    .ok_or(WalletError::Protocol("Unknown swapcoin".to_string()))?;
+                p2wsh_prevout(multisig_redeemscript, swapcoin.funding_amount)
+            }
+            UTXOSpendInfo::TimelockContract {
+                swapcoin_multisig_redeemscript,
+                input_value,
+            } => {
+                let swapcoin = self
+                    .find_outgoing_swapcoin(swapcoin_multisig_redeemscript)
+                    .ok_or(WalletError::Protocol("Unknown swapcoin".to_string()))?;
+                p2wsh_prevout(&swapcoin.contract_redeemscript, *input_value)
+            }
+            UTXOSpendInfo::HashlockContract {
+                swapcoin_multisig_redeemscript,
+                input_value,
+            } => {
+                let swapcoin = self
+                    .find_incoming_swapcoin(swapcoin_multisig_redeemscript)
+                    .ok_or(WalletError::Protocol("Unknown swapcoin".to_string()))?;
+                p2wsh_prevout(&swapcoin.contract_redeemscript, *input_value)
+            }
+            UTXOSpendInfo::FidelityBondCoin { index, input_value } => {
+                p2wsh_prevout(&self.get_fidelity_reedemscript(*index)?, *input_value)
+            }
+            UTXOSpendInfo::WatchOnlySwapCoin { .. } => {
+                return Err(WalletError::Protocol(
+                    "Watch-only swapcoins can't be signed".to_string(),
+                ));
+            }
+        })
+    }
+
+    /// Signs a transaction corresponding to the provided UTXO spend information, asking `signer` for
+    /// all the signatures. The signer is given the transaction and the outputs it spends.
+    pub fn sign_transaction_with(
+        &self,
+        tx: &mut Transaction,
+        inputs_info: impl Iterator<Item = UTXOSpendInfo>,
+        signer: &dyn RemoteSigner,
+    ) -> Result<(), WalletError> {
         let tx_clone = tx.clone();
+        let inputs_info = inputs_info.collect::<Vec<_>>();
+        let prevouts = inputs_info
+            .iter()
+            .map(|input_info| self.spent_output(input_info))
+            .collect::<Result<Vec<_>, _>>()?;
 
         for (ix, (input, input_info)) in tx.input.iter_mut().zip(inputs_info).enumerate() {
             match input_info {
@@ -988,66 +1637,69 @@ impl Wallet {
                     multisig_redeemscript,
                 } => {
                     self.find_incoming_swapcoin(&multisig_redeemscript)
-                        .unwrap()
-                        .sign_transaction_input(ix, &tx_clone, input, &multisig_redeemscript)
-                        .unwrap();
+                        .expect("checked by spent_output")
+                        .sign_transaction_input(
+                            ix,
+                            &tx_clone,
+                            input,
+                            &multisig_redeemscript,
+                            &prevouts,
+                            signer,
+                        )?;
                 }
-                UTXOSpendInfo::SeedCoin { path, input_value } => {
-                    let privkey = master_private_key
-                        .derive_priv(&secp, &DerivationPath::from_str(&path).unwrap())
-                        .unwrap()
-                        .private_key;
-                    let pubkey = PublicKey {
-                        compressed: true,
-                        inner: privkey.public_key(&secp),
-                    };
-                    let scriptcode = ScriptBuf::new_p2wpkh(&pubkey.wpubkey_hash().unwrap());
-                    let sighash = SighashCache::new(&tx_clone)
-                        .p2wpkh_signature_hash(ix, &scriptcode, input_value, EcdsaSighashType::All)
-                        .unwrap();
-                    //use low-R value signatures for privacy
-                    //https://en.bitcoin.it/wiki/Privacy#Wallet_fingerprinting
-                    let signature = secp.sign_ecdsa_low_r(
-                        &secp256k1::Message::from_digest_slice(&sighash[..]).unwrap(),
-                        &privkey,
-                    );
-                    let mut sig_serialised = signature.serialize_der().to_vec();
-                    sig_serialised.push(EcdsaSighashType::All as u8);
-                    input.witness.push(sig_serialised);
+                UTXOSpendInfo::SeedCoin { path, .. } => {
+                    let key = SignerKey::SeedCoin { path };
+                    let pubkey = signer.public_key(&key)?;
+                    let signature = request_signature(
+                        signer,
+                        &key,
+                        &pubkey,
+                        SignPayload::Input {
+                            tx: tx_clone.clone(),
+                            input_index: ix,
+                            prevouts: prevouts.clone(),
+                            witness_script: None,
+                        },
+                    )?;
+                    input.witness.push(signature.to_vec());
                     input.witness.push(pubkey.to_bytes());
                 }
                 UTXOSpendInfo::TimelockContract {
                     swapcoin_multisig_redeemscript,
-                    input_value,
+                    ..
                 } => self
                     .find_outgoing_swapcoin(&swapcoin_multisig_redeemscript)
-                    .unwrap()
-                    .sign_timelocked_transaction_input(ix, &tx_clone, input, input_value)
-                    .unwrap(),
+                    .expect("checked by spent_output")
+                    .sign_timelocked_transaction_input(ix, &tx_clone, input, &prevouts, signer)?,
                 UTXOSpendInfo::HashlockContract {
                     swapcoin_multisig_redeemscript,
-                    input_value,
+                    ..
                 } => self
                     .find_incoming_swapcoin(&swapcoin_multisig_redeemscript)
-                    .unwrap()
-                    .sign_hashlocked_transaction_input(ix, &tx_clone, input, input_value)
-                    .unwrap(),
-                UTXOSpendInfo::FidelityBondCoin { index, input_value } => {
-                    let privkey = self.get_fidelity_keypair(index)?.secret_key();
+                    .expect("checked by spent_output")
+                    .sign_hashlocked_transaction_input(ix, &tx_clone, input, &prevouts, signer)?,
+                UTXOSpendInfo::FidelityBondCoin { index, .. } => {
                     let redeemscript = self.get_fidelity_reedemscript(index)?;
-                    let sighash = SighashCache::new(&tx_clone)
-                        .p2wsh_signature_hash(ix, &redeemscript, input_value, EcdsaSighashType::All)
-                        .unwrap();
-                    let sig = secp.sign_ecdsa(
-                        &secp256k1::Message::from_digest_slice(&sighash[..]).unwrap(),
-                        &privkey,
-                    );
-
-                    let mut sig_serialised = sig.serialize_der().to_vec();
-                    sig_serialised.push(EcdsaSighashType::All as u8);
-                    input.witness.push(sig_serialised);
+                    let (bond, _, _) = self
+                        .store
+                        .fidelity_bond
+                        .get(&index)
+                        .ok_or(super::FidelityError::BondDoesNotExist)?;
+                    let signature = request_signature(
+                        signer,
+                        &SignerKey::FidelityBond { index },
+                        &bond.pubkey,
+                        SignPayload::Input {
+                            tx: tx_clone.clone(),
+                            input_index: ix,
+                            prevouts: prevouts.clone(),
+                            witness_script: Some(redeemscript.clone()),
+                        },
+                    )?;
+                    input.witness.push(signature.to_vec());
                     input.witness.push(redeemscript.as_bytes());
                 }
+                UTXOSpendInfo::WatchOnlySwapCoin { .. } => unreachable!("checked by spent_output"),
             }
         }
         Ok(())
@@ -1057,19 +1709,9 @@ impl Wallet {
         &self,
         amount: Amount,
     ) -> Result<Vec<(ListUnspentResultEntry, UTXOSpendInfo)>, WalletError> {
-        let all_utxos = self.get_all_locked_utxo()?;
+        let mut unspents = self.spendable_utxos()?;
 
-        let mut seed_coin_utxo = self.list_descriptor_utxo_spend_info(Some(&all_utxos))?;
-        let mut swap_coin_utxo = self.list_swap_coin_utxo_spend_info(Some(&all_utxos))?;
-        seed_coin_utxo.append(&mut swap_coin_utxo);
-
-        // Fetch utxos, filter out existing fidelity coins
-        let mut unspents = seed_coin_utxo
-            .into_iter()
-            .filter(|(_, spend_info)| !matches!(spend_info, UTXOSpendInfo::FidelityBondCoin { .. }))
-            .collect::<Vec<_>>();
-
-        unspents.sort_by(|a, b| b.0.amount.cmp(&a.0.amount));
+        unspents.sort_by_key(|b| std::cmp::Reverse(b.0.amount));
 
         let mut selected_utxo = Vec::new();
         let mut remaining = amount;
@@ -1087,6 +1729,32 @@ impl Wallet {
         Ok(selected_utxo)
     }
 
+    /// Same as [Wallet::coin_select], refusing selections of more than `max_inputs_per_tx` coins with
+    /// [WalletError::TooManyInputs]. Such a transaction could be non-standard, or cost excessive fees.
+    pub fn coin_select_with_limit(
+        &self,
+        amount: Amount,
+        max_inputs_per_tx: usize,
+    ) -> Result<Vec<(ListUnspentResultEntry, UTXOSpendInfo)>, WalletError> {
+        let selected_utxo = self.coin_select(amount)?;
+        if selected_utxo.len() > max_inputs_per_tx {
+            return Err(WalletError::TooManyInputs {
+                required: selected_utxo.len(),
+                max: max_inputs_per_tx,
+            });
+        }
+        Ok(selected_utxo)
+    }
+
+    /// Split the coins into batches of at most `max_inputs_per_tx`, one per transaction. Used to
+    /// consolidate many coins without building oversized transactions.
+    pub fn split_by_input_limit(
+        coins: &[(ListUnspentResultEntry, UTXOSpendInfo)],
+        max_inputs_per_tx: usize,
+    ) -> Vec<&[(ListUnspentResultEntry, UTXOSpendInfo)]> {
+        coins.chunks(max_inputs_per_tx.max(1)).collect()
+    }
+
     pub fn get_utxo(
         &self,
         (txid, vout): (Txid, u32),
@@ -1109,8 +1777,8 @@ impl Wallet {
     fn create_and_import_coinswap_address(
         &mut self,
         other_pubkey: &PublicKey,
-    ) -> (Address, SecretKey) {
-        let (my_pubkey, my_privkey) = generate_keypair();
+    ) -> Result<(Address, SignerKey), WalletError> {
+        let (my_key, my_pubkey) = self.next_swap_key()?;
 
         let descriptor = self
             .rpc
@@ -1120,19 +1788,19 @@ impl Wallet {
             ))
             .unwrap()
             .descriptor;
-        self.import_descriptors(&[descriptor.clone()], None)
+        self.import_descriptors(std::slice::from_ref(&descriptor), None)
             .unwrap();
 
         //redeemscript and descriptor show up in `getaddressinfo` only after
         // the address gets outputs on it-
-        (
+        Ok((
             //TODO should completely avoid derive_addresses
             //because its slower and provides no benefit over using rust-bitcoin
             self.rpc.derive_addresses(&descriptor[..], None).unwrap()[0]
                 .clone()
                 .assume_checked(),
-            my_privkey,
-        )
+            my_key,
+        ))
     }
 
     /// Initialize a Coinswap with the Other party.
@@ -1146,9 +1814,11 @@ impl Wallet {
         locktime: u16,
         fee_rate: Amount,
     ) -> Result<(Vec<Transaction>, Vec<OutgoingSwapCoin>, Amount), WalletError> {
-        let (coinswap_addresses, my_multisig_privkeys): (Vec<_>, Vec<_>) = other_multisig_pubkeys
+        let (coinswap_addresses, my_multisig_keys): (Vec<_>, Vec<_>) = other_multisig_pubkeys
             .iter()
             .map(|other_key| self.create_and_import_coinswap_address(other_key))
+            .collect::<Result<Vec<_>, _>>()?
+            .into_iter()
             .unzip();
 
         let create_funding_txes_result =
@@ -1164,17 +1834,17 @@ impl Wallet {
 
         let mut outgoing_swapcoins = Vec::<OutgoingSwapCoin>::new();
         for (
-            (((my_funding_tx, &utxo_index), &my_multisig_privkey), &other_multisig_pubkey),
+            (((my_funding_tx, &utxo_index), my_multisig_key), &other_multisig_pubkey),
             hashlock_pubkey,
         ) in create_funding_txes_result
             .funding_txes
             .iter()
             .zip(create_funding_txes_result.payment_output_positions.iter())
-            .zip(my_multisig_privkeys.iter())
+            .zip(my_multisig_keys)
             .zip(other_multisig_pubkeys.iter())
             .zip(hashlock_pubkeys.iter())
         {
-            let (timelock_pubkey, timelock_privkey) = generate_keypair();
+            let (timelock_key, timelock_pubkey) = self.next_swap_key()?;
             let contract_redeemscript = contract::create_contract_redeemscript(
                 hashlock_pubkey,
                 &timelock_pubkey,
@@ -1193,13 +1863,14 @@ impl Wallet {
 
             // self.import_wallet_contract_redeemscript(&contract_redeemscript)?;
             outgoing_swapcoins.push(OutgoingSwapCoin::new(
-                my_multisig_privkey,
+                my_multisig_key,
                 other_multisig_pubkey,
                 my_senders_contract_tx,
                 contract_redeemscript,
-                timelock_privkey,
+                timelock_key,
                 funding_amount,
-            ));
+                self.signer(),
+            )?);
         }
 
         Ok((
@@ -1223,6 +1894,43 @@ impl Wallet {
         self.import_descriptors(&[descriptor], Some(WATCH_ONLY_SWAPCOIN_LABEL.to_string()))
     }
 
+    /// Tracks a swapcoin without any of its keys, so a monitoring wallet can follow the funding and
+    /// contract spends. Its multisig scriptpubkey is imported into the core wallet, and the swapcoin is
+    /// saved to disk. Its coins are listed with [UTXOSpendInfo::WatchOnlySwapCoin].
+    ///
+    /// Refuses swapcoins the wallet holds a key of, they're tracked as incoming or outgoing swapcoins.
+    pub fn import_watchonly_swapcoin(
+        &mut self,
+        swapcoin: WatchOnlySwapCoin,
+    ) -> Result<(), WalletError> {
+        let multisig_redeemscript = swapcoin.get_multisig_redeemscript();
+        if self
+            .find_incoming_swapcoin(&multisig_redeemscript)
+            .is_some()
+            || self
+                .find_outgoing_swapcoin(&multisig_redeemscript)
+                .is_some()
+        {
+            return Err(WalletError::Protocol(
+                "The wallet holds the keys of this swapcoin, not importing it as watch-only"
+                    .to_string(),
+            ));
+        }
+        self.import_watchonly_redeemscript(&multisig_redeemscript)?;
+        self.store
+            .watchonly_swapcoins
+            .insert(multisig_redeemscript, swapcoin);
+        self.save_to_disk()
+    }
+
+    /// Finds a watch-only swapcoin by its multisig redeemscript.
+    pub fn find_watchonly_swapcoin(
+        &self,
+        multisig_redeemscript: &ScriptBuf,
+    ) -> Option<&WatchOnlySwapCoin> {
+        self.store.watchonly_swapcoins.get(multisig_redeemscript)
+    }
+
     pub fn descriptors_to_import(&self) -> Result<Vec<String>, WalletError> {
         let mut descriptors_to_import = Vec::new();
 
@@ -1297,6 +2005,16 @@ impl Wallet {
                 .collect::<Vec<_>>(),
         );
 
+        descriptors_to_import.extend(self.store.watchonly_swapcoins.keys().map(|redeemscript| {
+            let descriptor_without_checksum =
+                format!("raw({:x})", redeemscript_to_scriptpubkey(redeemscript));
+            format!(
+                "{}#{}",
+                descriptor_without_checksum,
+                compute_checksum(&descriptor_without_checksum).unwrap()
+            )
+        }));
+
         descriptors_to_import.extend(self.store.fidelity_bond.iter().map(|(_, (_, spk, _))| {
             let descriptor_without_checksum = format!("raw({:x})", spk);
             format!(
@@ -1309,3 +2027,140 @@ impl Wallet {
         Ok(descriptors_to_import)
     }
 }
+
+/// Build a BIP21 URI for the given address.
+fn bip21_uri(address: &Address, amount: Option<Amount>, label: Option<&str>) -> String {
+    let mut params = Vec::new();
+    if let Some(amount) = amount {
+        params.push(format!(
+            "amount={}",
+            amount.to_string_in(bitcoin::Denomination::Bitcoin)
+        ));
+    }
+    if let Some(label) = label {
+        params.push(format!("label={}", percent_encode(label)));
+    }
+
+    if params.is_empty() {
+        format!("bitcoin:{}", address)
+    } else {
+        format!("bitcoin:{}?{}", address, params.join("&"))
+    }
+}
+
+/// Percent-encode everything but the RFC 3986 unreserved characters.
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::hashes::Hash;
+
+    fn test_pubkeys() -> (PublicKey, PublicKey) {
+        let hashlock_pubkey = PublicKey::from_str(
+            "032e58afe51f9ed8ad3cc7897f634d881fdbe49a81564629ded8156bebd2ffd1af",
+        )
+        .unwrap();
+        let timelock_pubkey = PublicKey::from_str(
+            "039b6347398505f5ec93826dc61c19f47c66c0283ee9be980e29ce325a0f4679ef",
+        )
+        .unwrap();
+        (hashlock_pubkey, timelock_pubkey)
+    }
+
+    #[test]
+    fn test_label_filter() {
+        let include = LabelFilter::Include(vec!["swap-output".to_string(), "donation".to_string()]);
+        assert!(include.allows(Some("donation")));
+        assert!(!include.allows(Some("kyc")));
+        assert!(!include.allows(None));
+
+        let exclude = LabelFilter::Exclude(vec!["kyc".to_string()]);
+        assert!(!exclude.allows(Some("kyc")));
+        assert!(exclude.allows(Some("donation")));
+        assert!(exclude.allows(None));
+    }
+
+    #[test]
+    fn test_decode_contract_roundtrip() {
+        let (hashlock_pubkey, timelock_pubkey) = test_pubkeys();
+        let hashvalue = Hash160::hash(&[7u8; 32]);
+
+        // Cover the different locktime encodings (small int opcodes, 1 byte and multi byte pushes).
+        for locktime in [1u16, 16, 17, 127, 128, 255, 256, 1000, u16::MAX] {
+            let redeemscript = contract::create_contract_redeemscript(
+                &hashlock_pubkey,
+                &timelock_pubkey,
+                &hashvalue,
+                &locktime,
+            );
+            let details = Wallet::decode_contract(&redeemscript).unwrap();
+            assert_eq!(
+                details,
+                ContractDetails {
+                    hashlock_pubkey,
+                    timelock_pubkey,
+                    hashvalue,
+                    locktime,
+                }
+            );
+        }
+    }
+
+    #[test]
+    fn test_decode_contract_rejects_non_contracts() {
+        let (hashlock_pubkey, timelock_pubkey) = test_pubkeys();
+
+        // A multisig redeemscript is not a contract.
+        let multisig = contract::create_multisig_redeemscript(&hashlock_pubkey, &timelock_pubkey);
+        assert!(Wallet::decode_contract(&multisig).is_err());
+
+        // Empty script.
+        assert!(Wallet::decode_contract(&ScriptBuf::new()).is_err());
+
+        // A valid contract with a trailing opcode appended.
+        let mut tampered = contract::create_contract_redeemscript(
+            &hashlock_pubkey,
+            &timelock_pubkey,
+            &Hash160::hash(&[1u8; 32]),
+            &100,
+        )
+        .into_bytes();
+        tampered.push(bitcoin::opcodes::all::OP_DROP.to_u8());
+        assert!(Wallet::decode_contract(&ScriptBuf::from(tampered)).is_err());
+    }
+
+    #[test]
+    fn test_bip21_uri() {
+        let address = Address::from_str("bcrt1q6rz28mcfaxtmd6v789l9rrlrusdprr9pz3cppk")
+            .unwrap()
+            .assume_checked();
+
+        assert_eq!(
+            bip21_uri(&address, None, None),
+            "bitcoin:bcrt1q6rz28mcfaxtmd6v789l9rrlrusdprr9pz3cppk"
+        );
+        assert_eq!(
+            bip21_uri(&address, Some(Amount::from_sat(2_030_000_000)), None),
+            "bitcoin:bcrt1q6rz28mcfaxtmd6v789l9rrlrusdprr9pz3cppk?amount=20.3"
+        );
+        assert_eq!(
+            bip21_uri(&address, Some(Amount::from_sat(1)), Some("coffee")),
+            "bitcoin:bcrt1q6rz28mcfaxtmd6v789l9rrlrusdprr9pz3cppk?amount=0.00000001&label=coffee"
+        );
+        assert_eq!(
+            bip21_uri(&address, None, Some("Luke-Jr's shop & café?=#1")),
+            "bitcoin:bcrt1q6rz28mcfaxtmd6v789l9rrlrusdprr9pz3cppk?label=Luke-Jr%27s%20shop%20%26%20caf%C3%A9%3F%3D%231"
+        );
+    }
+}
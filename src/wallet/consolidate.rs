@@ -0,0 +1,143 @@
+//! Consolidation of small coins.
+//!
+//! A wallet collecting many small change outputs pays for each of them as an input of its next spends.
+//! Sweeping them into a single output, while fees are low, makes those spends cheaper.
+
+use bitcoin::{Address, Amount, Transaction};
+use bitcoind::bitcoincore_rpc::json::ListUnspentResultEntry;
+
+use super::{
+    api::UTXOSpendInfo,
+    direct_send::{Destination, SendAmount},
+    error::WalletError,
+    Wallet,
+};
+
+/// Pick up to `max_inputs` coins, smallest first, skipping the ones worth less than the fee of their
+/// own input.
+fn select_consolidation_coins(
+    mut coins: Vec<(ListUnspentResultEntry, UTXOSpendInfo)>,
+    input_fee: Amount,
+    max_inputs: usize,
+) -> Vec<(ListUnspentResultEntry, UTXOSpendInfo)> {
+    coins.sort_by(|(a, _), (b, _)| {
+        a.amount
+            .cmp(&b.amount)
+            .then((a.txid, a.vout).cmp(&(b.txid, b.vout)))
+    });
+    coins
+        .into_iter()
+        .filter(|(utxo, _)| utxo.amount > input_fee)
+        .take(max_inputs)
+        .collect()
+}
+
+impl Wallet {
+    /// Sweep up to `max_inputs` seed coins, and no more than [Wallet::max_inputs_per_tx], into a single
+    /// output, paying `fee_rate` sats/vbyte.
+    ///
+    /// The smallest coins go first. Fidelity bonds, swapcoins and frozen coins are never picked, and
+    /// coins worth less than the fee of their input are left alone. The output goes to `destination`, or a
+    /// fresh internal address. The transaction is returned signed, and isn't broadcast.
+    ///
+    /// Errors with [WalletError::UneconomicConsolidation] if the output would be dust after the fee.
+    pub fn consolidate_utxos(
+        &mut self,
+        fee_rate: u64,
+        max_inputs: usize,
+        destination: Option<Address>,
+    ) -> Result<Transaction, WalletError> {
+        let address = match destination {
+            Some(address) => address,
+            None => self.get_next_internal_addresses(1)?.remove(0),
+        };
+        let dust = address.script_pubkey().minimal_non_dust();
+        let destination = Destination::Address(address);
+
+        let coins = self
+            .spendable_utxos()?
+            .into_iter()
+            .filter(|(_, spend_info)| matches!(spend_info, UTXOSpendInfo::SeedCoin { .. }))
+            .collect::<Vec<_>>();
+        let Some(coin) = coins.first() else {
+            return Err(WalletError::Protocol("No coins to consolidate".to_string()));
+        };
+        // Seed coins are all P2WPKH, their inputs all weigh the same.
+        let input_vsize =
+            self.estimate_direct_send_vsize(
+                &SendAmount::Max,
+                &destination,
+                std::slice::from_ref(coin),
+            )? - self.estimate_direct_send_vsize(&SendAmount::Max, &destination, &[])?;
+
+        let coins = select_consolidation_coins(
+            coins,
+            Amount::from_sat(input_vsize * fee_rate),
+            max_inputs.min(self.max_inputs_per_tx()),
+        );
+        if coins.len() < 2 {
+            return Err(WalletError::Protocol(format!(
+                "Need at least 2 coins to consolidate, found {}",
+                coins.len()
+            )));
+        }
+
+        let value = coins.iter().map(|(utxo, _)| utxo.amount).sum::<Amount>();
+        let vsize = self.estimate_direct_send_vsize(&SendAmount::Max, &destination, &coins)?;
+        let fee = Amount::from_sat(vsize * fee_rate);
+        if value.checked_sub(fee).is_none_or(|output| output <= dust) {
+            return Err(WalletError::UneconomicConsolidation { value, fee });
+        }
+
+        log::info!(
+            "Consolidating {} coins worth {}, paying {} in fees",
+            coins.len(),
+            value,
+            fee
+        );
+        self.spend_from_wallet(fee, SendAmount::Max, destination, &coins)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::{hashes::Hash, ScriptBuf, Txid};
+
+    fn coin(vout: u32, amount: u64) -> (ListUnspentResultEntry, UTXOSpendInfo) {
+        let utxo = ListUnspentResultEntry {
+            txid: Txid::all_zeros(),
+            vout,
+            address: None,
+            label: None,
+            redeem_script: None,
+            witness_script: None,
+            script_pub_key: ScriptBuf::new(),
+            amount: Amount::from_sat(amount),
+            confirmations: 1,
+            spendable: true,
+            solvable: true,
+            descriptor: None,
+            safe: true,
+        };
+        let spend_info = UTXOSpendInfo::SeedCoin {
+            path: "m/84'/1'/0'/0/0".to_string(),
+            input_value: Amount::from_sat(amount),
+        };
+        (utxo, spend_info)
+    }
+
+    #[test]
+    fn test_select_consolidation_coins() {
+        let coins = vec![coin(0, 5000), coin(1, 200), coin(2, 1000), coin(3, 3000)];
+        let selected = select_consolidation_coins(coins, Amount::from_sat(680), 2);
+        // The coin not paying for its input is skipped, then the smallest go first.
+        assert_eq!(
+            selected
+                .iter()
+                .map(|(utxo, _)| utxo.vout)
+                .collect::<Vec<_>>(),
+            vec![2, 3]
+        );
+    }
+}
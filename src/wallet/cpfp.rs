@@ -0,0 +1,102 @@
+//! Child-pays-for-parent fee bumping.
+//!
+//! A transaction stuck in the mempool at a low fee rate can be accelerated by spending one of its
+//! outputs in a child paying a higher fee. Miners consider the parent and the child together, so the
+//! child's fee is sized for the whole package to reach the target fee rate.
+
+use bitcoin::{Amount, OutPoint, Transaction, Txid};
+use bitcoind::bitcoincore_rpc::RpcApi;
+
+use super::{
+    api::UTXOSpendInfo,
+    direct_send::{Destination, SendAmount},
+    error::WalletError,
+    Wallet,
+};
+
+/// The fee of a child, for the parent and child package to pay `fee_rate` sats/vbyte.
+///
+/// The child pays at least `fee_rate` over its own size, even if the parent alone already pays more.
+fn cpfp_child_fee(
+    parent_fee: Amount,
+    parent_vsize: u64,
+    child_vsize: u64,
+    fee_rate: u64,
+) -> Amount {
+    let package_fee = Amount::from_sat((parent_vsize + child_vsize) * fee_rate);
+    let child_fee = Amount::from_sat(child_vsize * fee_rate);
+    std::cmp::max(
+        package_fee.checked_sub(parent_fee).unwrap_or(Amount::ZERO),
+        child_fee,
+    )
+}
+
+impl Wallet {
+    /// Create a child of the mempool transaction `parent_txid`, bumping the fee rate of the parent
+    /// and child package to `fee_rate` sats/vbyte.
+    ///
+    /// The child sweeps the largest seed coin output of the parent, like a change output, to a fresh
+    /// internal address. It's returned signed, and isn't broadcast.
+    ///
+    /// Errors with [WalletError::ParentNotInMempool] if the parent isn't unconfirmed in the mempool,
+    /// and with [WalletError::NoCpfpOutput] if none of its outputs is a spendable wallet coin.
+    pub fn create_cpfp_child(
+        &mut self,
+        parent_txid: Txid,
+        fee_rate: u64,
+    ) -> Result<Transaction, WalletError> {
+        let parent_entry = self
+            .rpc
+            .get_mempool_entry(&parent_txid)
+            .map_err(|_| WalletError::ParentNotInMempool(parent_txid))?;
+
+        // Swapcoins, contracts and fidelity bonds are reserved for their own spends. Frozen coins
+        // aren't spent at all.
+        let coin = self
+            .list_all_utxo_spend_info(None)?
+            .into_iter()
+            .filter(|(utxo, spend_info)| {
+                utxo.txid == parent_txid
+                    && matches!(spend_info, UTXOSpendInfo::SeedCoin { .. })
+                    && !self.is_frozen(&OutPoint::new(utxo.txid, utxo.vout))
+            })
+            .max_by_key(|(utxo, _)| utxo.amount)
+            .ok_or(WalletError::NoCpfpOutput(parent_txid))?;
+        let coins = vec![coin];
+
+        let child_vsize =
+            self.estimate_direct_send_vsize(&SendAmount::Max, &Destination::Wallet, &coins)?;
+        let fee = cpfp_child_fee(
+            parent_entry.fees.base,
+            parent_entry.vsize,
+            child_vsize,
+            fee_rate,
+        );
+        log::info!(
+            "Bumping {} with a child paying {}, for a package fee rate of {} sats/vbyte",
+            parent_txid,
+            fee,
+            fee_rate
+        );
+        self.spend_from_wallet(fee, SendAmount::Max, Destination::Wallet, &coins)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cpfp_child_fee() {
+        // The child covers the package's missing fee.
+        assert_eq!(
+            cpfp_child_fee(Amount::from_sat(150), 150, 110, 10),
+            Amount::from_sat(2450)
+        );
+        // A parent already paying more still gets a child at the fee rate.
+        assert_eq!(
+            cpfp_child_fee(Amount::from_sat(5000), 150, 110, 10),
+            Amount::from_sat(1100)
+        );
+    }
+}
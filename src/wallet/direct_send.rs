@@ -4,37 +4,89 @@
 //! direct sends. It leverages Bitcoin Core's RPC for wallet synchronization and implements various
 //! parsing mechanisms for transaction inputs and outputs.
 
-use std::{num::ParseIntError, str::FromStr};
+use std::{convert::TryFrom, str::FromStr};
 
 use bitcoin::{
-    absolute::LockTime, transaction::Version, Address, Amount, Network, OutPoint, ScriptBuf,
-    Sequence, Transaction, TxIn, TxOut, Witness,
+    absolute::LockTime,
+    hashes::Hash,
+    secp256k1::rand::{rngs::OsRng, RngCore},
+    transaction::Version,
+    Address, Amount, Denomination, Network, OutPoint, Script, ScriptBuf, Sequence, Transaction,
+    TxIn, TxOut, WPubkeyHash, Witness,
 };
 use bitcoind::bitcoincore_rpc::{json::ListUnspentResultEntry, RawTx, RpcApi};
 
-use crate::wallet::{api::UTXOSpendInfo, SwapCoin};
+use crate::{
+    protocol::{
+        contract::{relative_locktime_to_sequence, RelativeLocktime},
+        messages::PREIMAGE_LEN,
+    },
+    wallet::{api::UTXOSpendInfo, SwapCoin},
+};
 
-use super::{error::WalletError, Wallet};
+use super::{error::WalletError, fidelity::FidelityError, LabelFilter, RemoteSigner, Wallet};
 
 /// Enum representing different options for the amount to be sent in a transaction.
 #[derive(Debug, Clone, PartialEq)]
 pub enum SendAmount {
     Max,
     Amount(Amount),
+    /// A percentage, from 1 to 100, of the total input value.
+    Percent(u8),
 }
 
 impl FromStr for SendAmount {
-    type Err = ParseIntError;
+    type Err = WalletError;
 
+    /// Parses `max`, a percentage like `50%`, or an amount. Bare integers are satoshis, and so are
+    /// amounts with a `sat` suffix. Amounts with a `btc` suffix or a decimal point are bitcoins.
+    ///
+    /// Bitcoin amounts are parsed from the string digits, never through a float, so they convert to
+    /// satoshis exactly. More than 8 decimals is an error, not a rounding.
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        Ok(if s == "max" {
-            SendAmount::Max
+        let invalid = || WalletError::InvalidSendAmount(s.to_string());
+        let amount = s.trim().to_ascii_lowercase();
+        if amount == "max" {
+            return Ok(SendAmount::Max);
+        }
+        if let Some(percent) = amount.strip_suffix('%') {
+            return percent
+                .trim()
+                .parse::<u8>()
+                .ok()
+                .filter(|percent| (1..=100).contains(percent))
+                .map(SendAmount::Percent)
+                .ok_or_else(invalid);
+        }
+        let (value, denomination) = if let Some(btc) = amount.strip_suffix("btc") {
+            (btc, Denomination::Bitcoin)
+        } else if let Some(sat) = amount
+            .strip_suffix("sats")
+            .or_else(|| amount.strip_suffix("sat"))
+        {
+            (sat, Denomination::Satoshi)
+        } else if amount.contains('.') {
+            (amount.as_str(), Denomination::Bitcoin)
         } else {
-            SendAmount::Amount(Amount::from_sat(String::from(s).parse::<u64>()?))
-        })
+            (amount.as_str(), Denomination::Satoshi)
+        };
+        Amount::from_str_in(value.trim(), denomination)
+            .map(SendAmount::Amount)
+            .map_err(|_| invalid())
     }
 }
 
+/// Options of [Wallet::spend_from_wallet_with]. The defaults are the ones of [Wallet::spend_from_wallet].
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SpendOptions {
+    /// Spend the fidelity bonds of the coins too. They must be matured, else
+    /// [FidelityError::BondNotMatured] is returned. The spent bonds are marked as such by the next
+    /// [Wallet::sync] after the transaction is broadcast.
+    pub include_matured_fidelity: bool,
+    /// Signal BIP125 replaceability, so the fee can be bumped with [Wallet::bump_fee].
+    pub enable_rbf: bool,
+}
+
 /// Enum representing different destination options for a transaction.
 #[derive(Debug, Clone, PartialEq)]
 pub enum Destination {
@@ -66,24 +118,20 @@ pub enum CoinToSpend {
 }
 
 fn parse_short_form_coin(s: &str) -> Option<CoinToSpend> {
-    //example short form: 568a4e..83a2e8:0
-    if s.len() < 15 {
-        return None;
-    }
-    let dots = &s[6..8];
-    if dots != ".." {
+    //example short form: 568a4e..83a2e8:0, the prefix and suffix can be of any length.
+    let (txid, vout) = s.rsplit_once(':')?;
+    let (prefix, suffix) = txid.split_once("..")?;
+    let is_txid_fragment = |part: &str| {
+        !part.is_empty() && part.len() < 64 && part.chars().all(|c| c.is_ascii_hexdigit())
+    };
+    if !is_txid_fragment(prefix) || !is_txid_fragment(suffix) {
         return None;
     }
-    let colon = s.chars().nth(14).unwrap();
-    if colon != ':' {
-        return None;
-    }
-    let prefix = String::from(&s[0..6]);
-    let suffix = String::from(&s[8..14]);
-    let vout = s[15..].parse::<u32>().ok()?;
+    let vout = vout.parse::<u32>().ok()?;
+    // Txids are displayed in lowercase.
     Some(CoinToSpend::ShortForm {
-        prefix,
-        suffix,
+        prefix: prefix.to_lowercase(),
+        suffix: suffix.to_lowercase(),
         vout,
     })
 }
@@ -106,11 +154,266 @@ impl FromStr for CoinToSpend {
     }
 }
 
+impl CoinToSpend {
+    /// Whether this refers to the given wallet utxo.
+    fn matches(&self, utxo: &ListUnspentResultEntry) -> bool {
+        match self {
+            CoinToSpend::LongForm(outpoint) => *outpoint == OutPoint::new(utxo.txid, utxo.vout),
+            CoinToSpend::ShortForm {
+                prefix,
+                suffix,
+                vout,
+            } => {
+                let txid = utxo.txid.to_string();
+                txid.starts_with(prefix) && txid.ends_with(suffix) && *vout == utxo.vout
+            }
+        }
+    }
+}
+
+/// Summary of an unsigned direct send, to review it before signing.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TxSummary {
+    /// The spent outpoints, with their values.
+    pub inputs: Vec<(OutPoint, Amount)>,
+    /// All the outputs, including the change.
+    pub outputs: Vec<TxOut>,
+    pub fee: Amount,
+    /// Fee rate in sats/vbyte, over the unsigned transaction size. Signatures add to the size, so the
+    /// fee rate of the signed transaction is lower.
+    pub fee_rate: f64,
+    /// The change sent back to the wallet. Zero if none.
+    pub change: Amount,
+}
+
+/// The input value needed to pay the explicit send amounts and `fee`. Percentages don't count, they
+/// depend on the inputs.
+fn required_value(fee: Amount, send_amounts: &[SendAmount]) -> Result<Amount, WalletError> {
+    send_amounts
+        .iter()
+        .try_fold(fee, |acc, a| match a {
+            SendAmount::Max | SendAmount::Percent(_) => Some(acc),
+            SendAmount::Amount(a) => acc.checked_add(*a),
+        })
+        .ok_or(WalletError::Protocol("Send amount overflows".to_string()))
+}
+
+/// Split the total input value into the recipients values and the change, after paying `fee`.
+/// A [SendAmount::Max] recipient gets all the remaining value, leaving no change. A
+/// [SendAmount::Percent] recipient gets its share of the total input value, rounded down.
+///
+/// Errors with [WalletError::InsufficientFunds] if the inputs can't cover the send amounts and fee,
+/// and with [WalletError::Protocol] if more than one recipient is sent the max amount.
+fn split_input_value(
+    total_input_value: Amount,
+    fee: Amount,
+    send_amounts: &[SendAmount],
+) -> Result<(Vec<Amount>, Amount), WalletError> {
+    if send_amounts
+        .iter()
+        .filter(|a| **a == SendAmount::Max)
+        .count()
+        > 1
+    {
+        return Err(WalletError::Protocol(
+            "Only one recipient can be sent the max amount".to_string(),
+        ));
+    }
+    let send_amounts = send_amounts
+        .iter()
+        .map(|a| match a {
+            SendAmount::Percent(percent) => SendAmount::Amount(Amount::from_sat(
+                total_input_value.to_sat() * u64::from(*percent) / 100,
+            )),
+            a => a.clone(),
+        })
+        .collect::<Vec<_>>();
+    let required = required_value(fee, &send_amounts)?;
+    let remaining =
+        total_input_value
+            .checked_sub(required)
+            .ok_or(WalletError::InsufficientFunds {
+                available: total_input_value,
+                required,
+            })?;
+    let values = send_amounts
+        .iter()
+        .map(|a| match a {
+            SendAmount::Max => remaining,
+            SendAmount::Amount(a) => *a,
+            SendAmount::Percent(_) => unreachable!("percentages are resolved above"),
+        })
+        .collect();
+    let change = if send_amounts.contains(&SendAmount::Max) {
+        Amount::ZERO
+    } else {
+        remaining
+    };
+    Ok((values, change))
+}
+
+/// The change output value, or None if the remaining value is dust for the change script. A dust
+/// remainder isn't worth an output, it goes to the fee.
+fn change_value(remaining: Amount, change_script: &Script) -> Option<Amount> {
+    (remaining >= change_script.minimal_non_dust()).then_some(remaining)
+}
+
+/// Select the largest coins until they cover `target`. Coins of the same value are picked in outpoint
+/// order, so the selection is deterministic.
+fn select_largest_first(
+    mut coins: Vec<(ListUnspentResultEntry, UTXOSpendInfo)>,
+    target: Amount,
+) -> Result<Vec<(ListUnspentResultEntry, UTXOSpendInfo)>, WalletError> {
+    coins.sort_by(|(a, _), (b, _)| {
+        b.amount
+            .cmp(&a.amount)
+            .then((a.txid, a.vout).cmp(&(b.txid, b.vout)))
+    });
+
+    let mut selected = Vec::new();
+    let mut selected_value = Amount::ZERO;
+    for coin in coins {
+        if selected_value >= target {
+            break;
+        }
+        selected_value += coin.0.amount;
+        selected.push(coin);
+    }
+
+    if selected_value < target {
+        return Err(WalletError::InsufficientFunds {
+            available: selected_value,
+            required: target,
+        });
+    }
+    Ok(selected)
+}
+
+/// One in this many anti-fee-snipping locktimes is set below the tip, like Bitcoin Core does.
+const ANTI_FEE_SNIPPING_RANDOMIZE_ODDS: u32 = 10;
+
+/// How far below the tip a randomized anti-fee-snipping locktime can go, in blocks.
+const ANTI_FEE_SNIPPING_MAX_DEPTH: u32 = 100;
+
+/// The anti-fee-snipping locktime of a transaction created at `current_height`.
+///
+/// Always using the tip would fingerprint our transactions, so like Bitcoin Core it's occasionally
+/// set up to 100 blocks lower. See [anti_fee_snipping_locktime_with].
+pub(super) fn anti_fee_snipping_locktime(current_height: u64) -> Result<LockTime, WalletError> {
+    anti_fee_snipping_locktime_with(current_height, &mut OsRng)
+}
+
+/// Same as [anti_fee_snipping_locktime], from the given randomness. Never above `current_height`.
+pub(super) fn anti_fee_snipping_locktime_with(
+    current_height: u64,
+    rng: &mut impl RngCore,
+) -> Result<LockTime, WalletError> {
+    let mut height = u32::try_from(current_height)
+        .map_err(|_| WalletError::Protocol(format!("Invalid block height {}", current_height)))?;
+    if rng
+        .next_u32()
+        .is_multiple_of(ANTI_FEE_SNIPPING_RANDOMIZE_ODDS)
+    {
+        height = height.saturating_sub(rng.next_u32() % ANTI_FEE_SNIPPING_MAX_DEPTH);
+    }
+    Ok(LockTime::from_height(height)?)
+}
+
+/// The locktime of a transaction spending fidelity bonds locked until `bond_locktimes`.
+///
+/// The OP_CLTV of the bonds requires a transaction locktime of the same unit, at least as high. Without
+/// bonds, or for height locked bonds, this is the `anti_fee_snipping` height or above. Errors if the
+/// bonds mix heights and timestamps.
+fn spend_locktime(
+    anti_fee_snipping: LockTime,
+    bond_locktimes: &[LockTime],
+) -> Result<LockTime, WalletError> {
+    if bond_locktimes.iter().all(|l| l.is_block_height()) {
+        return Ok(bond_locktimes.iter().fold(anti_fee_snipping, |acc, l| {
+            if l.to_consensus_u32() > acc.to_consensus_u32() {
+                *l
+            } else {
+                acc
+            }
+        }));
+    }
+    if bond_locktimes.iter().all(|l| l.is_block_time()) {
+        return Ok(*bond_locktimes
+            .iter()
+            .max_by_key(|l| l.to_consensus_u32())
+            .expect("not empty, else all locktimes are heights"));
+    }
+    Err(WalletError::Protocol(
+        "Can't spend height and timestamp locked fidelity bonds together".to_string(),
+    ))
+}
+
+/// Largest size of a DER encoded signature, with its sighash byte.
+const MAX_SIG_LEN: usize = 73;
+
+/// A witness of the largest size a spend of `spend_info` can have, with placeholder signatures.
+/// `redeemscript` is the witness script of the spent output. It is unused for seed coins.
+pub(super) fn placeholder_witness(spend_info: &UTXOSpendInfo, redeemscript: &Script) -> Witness {
+    let sig = [0u8; MAX_SIG_LEN];
+    let mut witness = Witness::new();
+    match spend_info {
+        UTXOSpendInfo::SeedCoin { .. } => {
+            witness.push(sig);
+            witness.push([0u8; 33]);
+        }
+        UTXOSpendInfo::SwapCoin { .. } | UTXOSpendInfo::WatchOnlySwapCoin { .. } => {
+            witness.push(Vec::new());
+            witness.push(sig);
+            witness.push(sig);
+            witness.push(redeemscript.as_bytes());
+        }
+        UTXOSpendInfo::TimelockContract { .. } => {
+            witness.push(sig);
+            witness.push(Vec::new());
+            witness.push(redeemscript.as_bytes());
+        }
+        UTXOSpendInfo::HashlockContract { .. } => {
+            witness.push(sig);
+            witness.push([0u8; PREIMAGE_LEN]);
+            witness.push(redeemscript.as_bytes());
+        }
+        UTXOSpendInfo::FidelityBondCoin { .. } => {
+            witness.push(sig);
+            witness.push(redeemscript.as_bytes());
+        }
+    }
+    witness
+}
+
+/// Estimate the vsize of a transaction spending inputs with the given `witnesses` to `outputs`.
+/// This is an upper bound when the witnesses come from [placeholder_witness].
+pub(super) fn estimate_vsize(witnesses: Vec<Witness>, outputs: Vec<TxOut>) -> u64 {
+    let input = witnesses
+        .into_iter()
+        .map(|witness| TxIn {
+            previous_output: OutPoint::null(),
+            script_sig: ScriptBuf::new(),
+            sequence: Sequence::ZERO,
+            witness,
+        })
+        .collect();
+    Transaction {
+        version: Version::TWO,
+        lock_time: LockTime::ZERO,
+        input,
+        output: outputs,
+    }
+    .vsize() as u64
+}
+
 impl Wallet {
     /// API to perform spending from wallet utxos, Including descriptor coins, swap coins or contract outputs (timelock/hashlock).
-    /// This should not be used to spend the Fidelity Bond. Check [Wallet::redeem_fidelity] for fidelity spending.
+    /// Fidelity Bonds are refused. Check [Wallet::redeem_fidelity] for fidelity spending, or [Wallet::spend_from_wallet_with]
+    /// to include matured bonds in a send.
     ///
     /// The caller needs to specify the list of utxo data and their corresponding spend_info. These can be extracted by various `list_utxo_*` Wallet APIs.
+    /// For regular payments, use [Wallet::spendable_utxos] to not spend coins reserved elsewhere, or
+    /// leave `coins_to_spend` empty to let the wallet pick them from there. See [Wallet::select_direct_send_coins].
     ///
     /// Caller needs to specify a total Fee and Destination address. Using [Destination::Wallet] will create a transaction to an internal wallet change address.
     ///
@@ -122,10 +425,352 @@ impl Wallet {
         send_amount: SendAmount,
         destination: Destination,
         coins_to_spend: &[(ListUnspentResultEntry, UTXOSpendInfo)],
+    ) -> Result<Transaction, WalletError> {
+        let signer = self.signer_handle();
+        self.spend_from_wallet_with(
+            fee,
+            send_amount,
+            destination,
+            coins_to_spend,
+            signer.as_ref(),
+            SpendOptions::default(),
+        )
+    }
+
+    /// The coins a signed direct send of `send_amounts` spends: `coins_to_spend`, or the ones picked by
+    /// [Wallet::select_direct_send_coins] when it is empty.
+    fn coins_to_sign_for(
+        &self,
+        fee: Amount,
+        send_amounts: &[SendAmount],
+        coins_to_spend: &[(ListUnspentResultEntry, UTXOSpendInfo)],
+    ) -> Result<Vec<(ListUnspentResultEntry, UTXOSpendInfo)>, WalletError> {
+        // Signing fails cryptically if the core wallet isn't ready yet. Make sure it is.
+        self.ensure_descriptors_imported()?;
+
+        if coins_to_spend.is_empty() {
+            self.select_direct_send_coins(fee, send_amounts)
+        } else {
+            Ok(coins_to_spend.to_vec())
+        }
+    }
+
+    /// Same as [Wallet::spend_from_wallet], with the signatures made by `signer`, and the
+    /// [SpendOptions] of `options`.
+    pub fn spend_from_wallet_with(
+        &mut self,
+        fee: Amount,
+        send_amount: SendAmount,
+        destination: Destination,
+        coins_to_spend: &[(ListUnspentResultEntry, UTXOSpendInfo)],
+        signer: &dyn RemoteSigner,
+        options: SpendOptions,
     ) -> Result<Transaction, WalletError> {
         log::info!("Creating Direct-Spend from Wallet.");
+
+        let coins_to_spend =
+            self.coins_to_sign_for(fee, std::slice::from_ref(&send_amount), coins_to_spend)?;
+        let coins_to_spend = coins_to_spend.as_slice();
+
+        let hand_out_address = destination == Destination::Wallet;
+        let (mut tx, summary) = self.build_direct_send(
+            fee,
+            vec![(destination, send_amount)],
+            coins_to_spend,
+            options.include_matured_fidelity,
+            options.enable_rbf,
+        )?;
+        if hand_out_address {
+            self.get_next_external_address()?;
+        }
+
+        self.sign_transaction_with(
+            &mut tx,
+            &mut coins_to_spend.iter().map(|(_, usi)| usi.clone()),
+            signer,
+        )?;
+        log::debug!("Signed Transaction : {:?}", tx.raw_hex());
+
+        // The change is the last output, remember it for the fee bumps.
+        if options.enable_rbf && summary.change > Amount::ZERO {
+            self.store
+                .rbf_change_outputs
+                .insert(tx.compute_txid(), tx.output.len() as u32 - 1);
+            self.save_to_disk()?;
+        }
+        Ok(tx)
+    }
+
+    /// Same as [Wallet::spend_from_wallet], paying `fee_rate` sats/vbyte instead of a total fee.
+    ///
+    /// The fee is computed from [Wallet::estimate_direct_send_vsize]. When `coins_to_spend` is empty,
+    /// the selected coins cover the fee of their own spend.
+    pub fn spend_from_wallet_at_fee_rate(
+        &mut self,
+        fee_rate: u64,
+        send_amount: SendAmount,
+        destination: Destination,
+        coins_to_spend: &[(ListUnspentResultEntry, UTXOSpendInfo)],
+    ) -> Result<Transaction, WalletError> {
+        if !coins_to_spend.is_empty() {
+            let vsize =
+                self.estimate_direct_send_vsize(&send_amount, &destination, coins_to_spend)?;
+            let fee = Amount::from_sat(vsize * fee_rate);
+            return self.spend_from_wallet(fee, send_amount, destination, coins_to_spend);
+        }
+
+        // The fee depends on the selected coins. Grow the selection until it covers its own fee.
+        // Largest first selections only grow with the target, so this ends.
+        let mut fee = Amount::ZERO;
+        loop {
+            let coins = self.select_direct_send_coins(fee, std::slice::from_ref(&send_amount))?;
+            let vsize = self.estimate_direct_send_vsize(&send_amount, &destination, &coins)?;
+            let required_fee = Amount::from_sat(vsize * fee_rate);
+            if required_fee <= fee {
+                return self.spend_from_wallet(fee, send_amount, destination, &coins);
+            }
+            fee = required_fee;
+        }
+    }
+
+    /// Find the wallet utxos, and their [UTXOSpendInfo], of the given coins to spend.
+    ///
+    /// Errors with [WalletError::CoinNotFound] naming all the coins not in the wallet.
+    pub fn find_coins_to_spend(
+        &self,
+        coins: &[CoinToSpend],
+    ) -> Result<Vec<(ListUnspentResultEntry, UTXOSpendInfo)>, WalletError> {
+        let utxos = self.list_all_utxo_spend_info(None)?;
+        let mut found = Vec::new();
+        let mut not_found = Vec::new();
+        for coin in coins {
+            match utxos.iter().find(|(utxo, _)| coin.matches(utxo)) {
+                Some(utxo) => found.push(utxo.clone()),
+                None => not_found.push(coin.clone()),
+            }
+        }
+        if !not_found.is_empty() {
+            return Err(WalletError::CoinNotFound(not_found));
+        }
+        Ok(found)
+    }
+
+    /// Select the coins of a direct send from [Wallet::spendable_utxos], so fidelity bonds and
+    /// swapcoins of ongoing swaps are never picked.
+    ///
+    /// All coins are selected to send a [SendAmount::Max] or a [SendAmount::Percent]. Otherwise coins are picked largest first
+    /// until they cover the send amounts and `fee`, erroring with [WalletError::InsufficientFunds] if
+    /// the wallet can't.
+    pub fn select_direct_send_coins(
+        &self,
+        fee: Amount,
+        send_amounts: &[SendAmount],
+    ) -> Result<Vec<(ListUnspentResultEntry, UTXOSpendInfo)>, WalletError> {
+        self.select_direct_send_coins_with_labels(fee, send_amounts, None)
+    }
+
+    /// Same as [Wallet::select_direct_send_coins], picking only from the coins allowed by the label
+    /// filter. See [Wallet::spendable_utxos_with].
+    pub fn select_direct_send_coins_with_labels(
+        &self,
+        fee: Amount,
+        send_amounts: &[SendAmount],
+        labels: Option<&LabelFilter>,
+    ) -> Result<Vec<(ListUnspentResultEntry, UTXOSpendInfo)>, WalletError> {
+        let coins = self.spendable_utxos_with(labels, false)?;
+        if send_amounts
+            .iter()
+            .any(|a| matches!(a, SendAmount::Max | SendAmount::Percent(_)))
+        {
+            return Ok(coins);
+        }
+        select_largest_first(coins, required_value(fee, send_amounts)?)
+    }
+
+    /// Estimate the vsize of the signed direct send of [Wallet::spend_from_wallet], including the
+    /// change output unless sending a [SendAmount::Max].
+    ///
+    /// Signatures are counted at their largest size, so the estimate never falls short.
+    pub fn estimate_direct_send_vsize(
+        &self,
+        send_amount: &SendAmount,
+        destination: &Destination,
+        coins_to_spend: &[(ListUnspentResultEntry, UTXOSpendInfo)],
+    ) -> Result<u64, WalletError> {
+        let witnesses = coins_to_spend
+            .iter()
+            .map(|(_, spend_info)| {
+                let redeemscript = match spend_info {
+                    UTXOSpendInfo::SeedCoin { .. } => ScriptBuf::new(),
+                    UTXOSpendInfo::SwapCoin {
+                        multisig_redeemscript,
+                    }
+                    | UTXOSpendInfo::WatchOnlySwapCoin {
+                        multisig_redeemscript,
+                    } => multisig_redeemscript.clone(),
+                    UTXOSpendInfo::TimelockContract {
+                        swapcoin_multisig_redeemscript,
+                        ..
+                    } => self
+                        .find_outgoing_swapcoin(swapcoin_multisig_redeemscript)
+                        .ok_or(WalletError::Protocol(
+                            "Outgoing swapcoin not found".to_string(),
+                        ))?
+                        .contract_redeemscript
+                        .clone(),
+                    UTXOSpendInfo::HashlockContract {
+                        swapcoin_multisig_redeemscript,
+                        ..
+                    } => self
+                        .find_incoming_swapcoin(swapcoin_multisig_redeemscript)
+                        .ok_or(WalletError::Protocol(
+                            "Incoming swapcoin not found".to_string(),
+                        ))?
+                        .contract_redeemscript
+                        .clone(),
+                    UTXOSpendInfo::FidelityBondCoin { index, .. } => {
+                        self.get_fidelity_reedemscript(*index)?
+                    }
+                };
+                Ok(placeholder_witness(spend_info, &redeemscript))
+            })
+            .collect::<Result<Vec<_>, WalletError>>()?;
+
+        // The wallet's own addresses are all P2WPKH.
+        let wallet_spk = ScriptBuf::new_p2wpkh(&WPubkeyHash::all_zeros());
+        let mut outputs = vec![TxOut {
+            value: Amount::ZERO,
+            script_pubkey: match destination {
+                Destination::Wallet => wallet_spk.clone(),
+                Destination::Address(a) => a.script_pubkey(),
+            },
+        }];
+        if *send_amount != SendAmount::Max {
+            outputs.push(TxOut {
+                value: Amount::ZERO,
+                script_pubkey: wallet_spk,
+            });
+        }
+        Ok(estimate_vsize(witnesses, outputs))
+    }
+
+    /// Send to several recipients at once, with a single change output. The seed key signatures are
+    /// made by the wallet.
+    ///
+    /// At most one recipient can be sent [SendAmount::Max], which sweeps the inputs left after the
+    /// other amounts and the fee, leaving no change. See [Wallet::spend_from_wallet] for the other
+    /// arguments.
+    pub fn spend_from_wallet_multi(
+        &mut self,
+        fee: Amount,
+        recipients: Vec<(Address, SendAmount)>,
+        coins_to_spend: &[(ListUnspentResultEntry, UTXOSpendInfo)],
+    ) -> Result<Transaction, WalletError> {
+        log::info!("Creating Direct-Spend to {} recipients.", recipients.len());
+
+        let send_amounts = recipients
+            .iter()
+            .map(|(_, send_amount)| send_amount.clone())
+            .collect::<Vec<_>>();
+        let coins_to_spend = self.coins_to_sign_for(fee, &send_amounts, coins_to_spend)?;
+        let coins_to_spend = coins_to_spend.as_slice();
+
+        let (mut tx, _) = self.build_direct_send_multi_unsigned(fee, recipients, coins_to_spend)?;
+
+        self.sign_transaction(
+            &mut tx,
+            &mut coins_to_spend.iter().map(|(_, usi)| usi.clone()),
+        )?;
+        log::debug!("Signed Transaction : {:?}", tx.raw_hex());
+        Ok(tx)
+    }
+
+    /// Build the unsigned transaction of [Wallet::spend_from_wallet], along with its [TxSummary].
+    ///
+    /// This lets the user review the exact transaction before signing it with [Wallet::sign_transaction].
+    ///
+    /// A preview doesn't hand out any address, so building it again gives the same outputs. The change
+    /// goes to the current change address, and a [Destination::Wallet] output to the address
+    /// [Wallet::get_next_external_address] returns next. Call it before broadcasting such a preview.
+    pub fn build_direct_send_unsigned(
+        &self,
+        fee: Amount,
+        send_amount: SendAmount,
+        destination: Destination,
+        coins_to_spend: &[(ListUnspentResultEntry, UTXOSpendInfo)],
+    ) -> Result<(Transaction, TxSummary), WalletError> {
+        self.build_direct_send(
+            fee,
+            vec![(destination, send_amount)],
+            coins_to_spend,
+            false,
+            false,
+        )
+    }
+
+    /// Build the unsigned transaction of [Wallet::spend_from_wallet_multi], along with its [TxSummary].
+    pub fn build_direct_send_multi_unsigned(
+        &self,
+        fee: Amount,
+        recipients: Vec<(Address, SendAmount)>,
+        coins_to_spend: &[(ListUnspentResultEntry, UTXOSpendInfo)],
+    ) -> Result<(Transaction, TxSummary), WalletError> {
+        if recipients.is_empty() {
+            return Err(WalletError::Protocol(
+                "No recipients to send to".to_string(),
+            ));
+        }
+        let recipients = recipients
+            .into_iter()
+            .map(|(address, send_amount)| (Destination::Address(address), send_amount))
+            .collect();
+        self.build_direct_send(fee, recipients, coins_to_spend, false, false)
+    }
+
+    /// Builds the unsigned direct send, without handing out the [Destination::Wallet] address. See
+    /// [Wallet::build_direct_send_unsigned].
+    fn build_direct_send(
+        &self,
+        fee: Amount,
+        recipients: Vec<(Destination, SendAmount)>,
+        coins_to_spend: &[(ListUnspentResultEntry, UTXOSpendInfo)],
+        include_matured_fidelity: bool,
+        enable_rbf: bool,
+    ) -> Result<(Transaction, TxSummary), WalletError> {
+        if coins_to_spend.len() > self.max_inputs_per_tx() {
+            return Err(WalletError::TooManyInputs {
+                required: coins_to_spend.len(),
+                max: self.max_inputs_per_tx(),
+            });
+        }
+
+        // Frozen coins are never selected, so these were named by the caller.
+        if let Some((utxo, _)) = coins_to_spend
+            .iter()
+            .find(|(utxo, _)| self.is_frozen(&OutPoint::new(utxo.txid, utxo.vout)))
+        {
+            return Err(WalletError::CoinFrozen(OutPoint::new(utxo.txid, utxo.vout)));
+        }
+
+        let mut bond_locktimes = Vec::new();
+        for (_, spend_info) in coins_to_spend {
+            if let UTXOSpendInfo::FidelityBondCoin { index, .. } = spend_info {
+                if !include_matured_fidelity {
+                    return Err(WalletError::Protocol(
+                        "Fidelity bonds are only spent on request".to_string(),
+                    ));
+                }
+                bond_locktimes.push(self.matured_fidelity_locktime(*index)?);
+            }
+        }
+        // Set the Anti-Fee-Snipping locktime
+        let lock_time = spend_locktime(
+            anti_fee_snipping_locktime(self.rpc.get_block_count()?)?,
+            &bond_locktimes,
+        )?;
+
         let mut tx_inputs = Vec::<TxIn>::new();
-        let mut spend_infos = Vec::new();
+        let mut summary_inputs = Vec::new();
         let mut total_input_value = Amount::ZERO;
 
         for (utxo_data, spend_info) in coins_to_spend {
@@ -134,25 +779,34 @@ impl Wallet {
                 UTXOSpendInfo::TimelockContract {
                     ref swapcoin_multisig_redeemscript,
                     input_value: _,
-                } => self
-                    .find_outgoing_swapcoin(swapcoin_multisig_redeemscript)
-                    .unwrap()
-                    .get_timelock() as u32,
+                } => relative_locktime_to_sequence(RelativeLocktime::Blocks(
+                    self.find_outgoing_swapcoin(swapcoin_multisig_redeemscript)
+                        .ok_or(WalletError::Protocol(
+                            "Outgoing swapcoin not found".to_string(),
+                        ))?
+                        .get_timelock()
+                        .into(),
+                ))?,
                 UTXOSpendInfo::HashlockContract {
                     swapcoin_multisig_redeemscript: _,
                     input_value: _,
-                } => 1, //hashlock spends must have 1 because of the `OP_CSV 1`
-                _ => 0,
+                } => relative_locktime_to_sequence(RelativeLocktime::Blocks(1))?, //hashlock spends must have 1 because of the `OP_CSV 1`
+                // Both keep the locktime enforced, for anti fee sniping and fidelity bonds.
+                _ if enable_rbf => Sequence::ENABLE_RBF_NO_LOCKTIME,
+                _ => Sequence::ENABLE_LOCKTIME_NO_RBF,
             };
 
             tx_inputs.push(TxIn {
                 previous_output: OutPoint::new(utxo_data.txid, utxo_data.vout),
-                sequence: Sequence(sequence),
+                sequence,
                 witness: Witness::new(),
                 script_sig: ScriptBuf::new(),
             });
 
-            spend_infos.push(spend_info);
+            summary_inputs.push((
+                OutPoint::new(utxo_data.txid, utxo_data.vout),
+                utxo_data.amount,
+            ));
 
             total_input_value += utxo_data.amount;
         }
@@ -165,76 +819,119 @@ impl Wallet {
 
         log::info!("Total Input Amount: {} | Fees: {}", total_input_value, fee);
 
-        let dest_addr = match destination {
-            Destination::Wallet => self.get_next_external_address()?,
-            Destination::Address(a) => {
-                //testnet and signet addresses have the same vbyte
-                //so a.network is always testnet even if the address is signet
-                let testnet_signet_type = (a.as_unchecked().is_valid_for_network(Network::Testnet)
-                    || a.as_unchecked().is_valid_for_network(Network::Signet))
-                    && (self.store.network == Network::Testnet
-                        || self.store.network == Network::Signet);
-                if !a.as_unchecked().is_valid_for_network(self.store.network)
-                    && !testnet_signet_type
-                {
-                    return Err(WalletError::Protocol(
-                        "Wrong address type in destinations.".to_string(),
-                    ));
-                }
-                a
-            }
-        };
+        let send_amounts = recipients
+            .iter()
+            .map(|(_, send_amount)| send_amount.clone())
+            .collect::<Vec<_>>();
+        let (send_values, remaining) = split_input_value(total_input_value, fee, &send_amounts)?;
 
         let mut output = Vec::<TxOut>::new();
-
-        let txout = {
-            let value = match send_amount {
-                SendAmount::Max => (total_input_value - fee).to_sat(),
-                SendAmount::Amount(a) => a.to_sat(),
+        for ((destination, _), send_value) in recipients.into_iter().zip(send_values) {
+            let dest_addr = match destination {
+                Destination::Wallet => self.peek_next_external_address()?,
+                Destination::Address(a) => {
+                    self.check_address_network(&a)?;
+                    a
+                }
             };
-            log::info!("Sending {} to {}.", value, dest_addr);
-            TxOut {
-                script_pubkey: dest_addr.script_pubkey(),
-                value: Amount::from_sat(value),
+
+            // Dust outputs are non-standard, and never relayed.
+            let dust = dest_addr.script_pubkey().minimal_non_dust();
+            if send_value < dust {
+                return Err(WalletError::DustOutput {
+                    value: send_value,
+                    dust,
+                });
             }
-        };
 
-        output.push(txout);
+            log::info!("Sending {} to {}.", send_value, dest_addr);
+            output.push(TxOut {
+                script_pubkey: dest_addr.script_pubkey(),
+                value: send_value,
+            });
+        }
 
-        // Only include change if remaining > dust
-        if let SendAmount::Amount(amount) = send_amount {
+        let mut change = Amount::ZERO;
+        if !send_amounts.contains(&SendAmount::Max) {
             let internal_spk = self.get_next_internal_addresses(1)?[0].script_pubkey();
-            let remaining = total_input_value - amount - fee;
-            if remaining > internal_spk.minimal_non_dust() {
-                log::info!("Adding Change {}:{}", internal_spk, remaining);
+            if let Some(value) = change_value(remaining, &internal_spk) {
+                log::info!("Adding Change {}:{}", internal_spk, value);
+                change = value;
                 output.push(TxOut {
                     script_pubkey: internal_spk,
-                    value: remaining,
+                    value,
                 });
+            } else if remaining > Amount::ZERO {
+                log::info!("Dust change {} added to the fee", remaining);
             }
         }
 
-        // Set the Anti-Fee-Snipping locktime
-        let lock_time = LockTime::from_height(self.rpc.get_block_count().unwrap() as u32).unwrap();
-
-        let mut tx = Transaction {
+        let tx = Transaction {
             input: tx_inputs,
             output,
             lock_time,
             version: Version::TWO,
         };
-        self.sign_transaction(
-            &mut tx,
-            &mut coins_to_spend.iter().map(|(_, usi)| usi.clone()),
-        )?;
-        log::debug!("Signed Transaction : {:?}", tx.raw_hex());
-        Ok(tx)
+
+        // A dust remainder, not worth a change output, goes to the miners too.
+        let fee = total_input_value - tx.output.iter().map(|o| o.value).sum::<Amount>();
+        let summary = TxSummary {
+            inputs: summary_inputs,
+            outputs: tx.output.clone(),
+            fee,
+            fee_rate: fee.to_sat() as f64 / tx.vsize() as f64,
+            change,
+        };
+        Ok((tx, summary))
+    }
+
+    /// The locktime of the fidelity bond at `index`. Errors if the bond isn't matured.
+    fn matured_fidelity_locktime(&self, index: u32) -> Result<LockTime, WalletError> {
+        let (bond, _, is_spent) = self
+            .store
+            .fidelity_bond
+            .get(&index)
+            .ok_or(FidelityError::BondDoesNotExist)?;
+        if *is_spent {
+            return Err(FidelityError::BondAlreadySpent.into());
+        }
+        let remaining_blocks = self.fidelity_bond_maturity_blocks(bond)?;
+        if remaining_blocks > 0 {
+            return Err(FidelityError::BondNotMatured {
+                remaining_blocks: remaining_blocks as u64,
+            }
+            .into());
+        }
+        Ok(bond.lock_time)
+    }
+
+    /// Errors with [WalletError::NetworkMismatch] if `address` isn't for the wallet's network.
+    fn check_address_network(&self, address: &Address) -> Result<(), WalletError> {
+        //testnet and signet addresses have the same vbyte
+        //so a.network is always testnet even if the address is signet
+        let a = address.as_unchecked();
+        let testnet_signet_type = (a.is_valid_for_network(Network::Testnet)
+            || a.is_valid_for_network(Network::Signet))
+            && (self.store.network == Network::Testnet || self.store.network == Network::Signet);
+        if !a.is_valid_for_network(self.store.network) && !testnet_signet_type {
+            return Err(WalletError::NetworkMismatch {
+                address: address.clone(),
+                expected: self.store.network,
+            });
+        }
+        Ok(())
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{
+        protocol::contract::{create_contract_redeemscript, create_multisig_redeemscript},
+        utill::generate_keypair,
+        wallet::fidelity::fidelity_redeemscript,
+    };
+    use bitcoin::hashes::hash160::Hash as Hash160;
 
     #[test]
     fn test_send_amount_parsing() {
@@ -248,6 +945,48 @@ mod tests {
             SendAmount::from_str("100").unwrap()
         );
         assert!(SendAmount::from_str("not a number").is_err());
+
+        // Bitcoin amounts, converted exactly.
+        assert_eq!(
+            SendAmount::from_str("0.001").unwrap(),
+            SendAmount::Amount(Amount::from_sat(100_000))
+        );
+        assert_eq!(
+            SendAmount::from_str("0.00000001").unwrap(),
+            SendAmount::Amount(Amount::from_sat(1))
+        );
+        assert_eq!(
+            SendAmount::from_str("1.1btc").unwrap(),
+            SendAmount::Amount(Amount::from_sat(110_000_000))
+        );
+        assert_eq!(
+            SendAmount::from_str("2 BTC").unwrap(),
+            SendAmount::Amount(Amount::from_sat(200_000_000))
+        );
+        assert!(SendAmount::from_str("0.000000001").is_err());
+        assert!(SendAmount::from_str("-0.001").is_err());
+
+        assert_eq!(
+            SendAmount::from_str("1000sat").unwrap(),
+            SendAmount::Amount(Amount::from_sat(1000))
+        );
+        assert_eq!(
+            SendAmount::from_str("1000 sats").unwrap(),
+            SendAmount::Amount(Amount::from_sat(1000))
+        );
+        assert!(SendAmount::from_str("1.5sat").is_err());
+
+        assert_eq!(
+            SendAmount::from_str("50%").unwrap(),
+            SendAmount::Percent(50)
+        );
+        assert_eq!(
+            SendAmount::from_str("100%").unwrap(),
+            SendAmount::Percent(100)
+        );
+        assert!(SendAmount::from_str("0%").is_err());
+        assert!(SendAmount::from_str("101%").is_err());
+        assert!(SendAmount::from_str("12.5%").is_err());
     }
 
     #[test]
@@ -277,6 +1016,333 @@ mod tests {
         assert!(Destination::from_str("invalid address").is_err());
     }
 
+    #[test]
+    fn test_split_input_value() {
+        let total = Amount::from_sat(10_000);
+        let fee = Amount::from_sat(1000);
+        let sats = Amount::from_sat;
+        let amount = |a| SendAmount::Amount(Amount::from_sat(a));
+
+        assert_eq!(
+            split_input_value(total, fee, &[SendAmount::Max]).unwrap(),
+            (vec![sats(9000)], Amount::ZERO)
+        );
+        assert_eq!(
+            split_input_value(total, fee, &[amount(4000)]).unwrap(),
+            (vec![sats(4000)], sats(5000))
+        );
+        assert_eq!(
+            split_input_value(total, fee, &[amount(9000)]).unwrap(),
+            (vec![sats(9000)], Amount::ZERO)
+        );
+
+        // Several recipients share a single change.
+        assert_eq!(
+            split_input_value(total, fee, &[amount(1000), amount(2000), amount(3000)]).unwrap(),
+            (vec![sats(1000), sats(2000), sats(3000)], sats(3000))
+        );
+        // The max recipient takes the change.
+        assert_eq!(
+            split_input_value(total, fee, &[amount(1000), SendAmount::Max, amount(3000)]).unwrap(),
+            (vec![sats(1000), sats(5000), sats(3000)], Amount::ZERO)
+        );
+        assert!(matches!(
+            split_input_value(total, fee, &[SendAmount::Max, SendAmount::Max]),
+            Err(WalletError::Protocol(_))
+        ));
+
+        // A percentage of the inputs, the rest is change.
+        assert_eq!(
+            split_input_value(total, fee, &[SendAmount::Percent(50)]).unwrap(),
+            (vec![sats(5000)], sats(4000))
+        );
+        assert_eq!(
+            split_input_value(total, fee, &[SendAmount::Percent(25), SendAmount::Max]).unwrap(),
+            (vec![sats(2500), sats(6500)], Amount::ZERO)
+        );
+        // The whole input value leaves nothing for the fee.
+        assert!(matches!(
+            split_input_value(total, fee, &[SendAmount::Percent(100)]),
+            Err(WalletError::InsufficientFunds { .. })
+        ));
+
+        // Inputs don't cover the amount + fee.
+        match split_input_value(total, fee, &[amount(9500)]) {
+            Err(WalletError::InsufficientFunds {
+                available,
+                required,
+            }) => {
+                assert_eq!(available, total);
+                assert_eq!(required, Amount::from_sat(10_500));
+            }
+            other => panic!("expected InsufficientFunds, got {:?}", other),
+        }
+        assert!(matches!(
+            split_input_value(total, fee, &[amount(5000), amount(4500)]),
+            Err(WalletError::InsufficientFunds { .. })
+        ));
+        assert!(matches!(
+            split_input_value(Amount::from_sat(500), fee, &[SendAmount::Max]),
+            Err(WalletError::InsufficientFunds { .. })
+        ));
+    }
+
+    #[test]
+    fn test_change_value() {
+        let p2wpkh = ScriptBuf::new_p2wpkh(&WPubkeyHash::all_zeros());
+        let dust = p2wpkh.minimal_non_dust();
+        assert_eq!(
+            change_value(Amount::from_sat(5000), &p2wpkh),
+            Some(Amount::from_sat(5000))
+        );
+        assert_eq!(change_value(dust, &p2wpkh), Some(dust));
+        assert_eq!(change_value(dust - Amount::from_sat(1), &p2wpkh), None);
+        assert_eq!(change_value(Amount::ZERO, &p2wpkh), None);
+    }
+
+    #[test]
+    fn test_select_largest_first() {
+        let coin = |txid_byte: u8, vout: u32, sats: u64| {
+            (
+                ListUnspentResultEntry {
+                    txid: bitcoin::Txid::from_byte_array([txid_byte; 32]),
+                    vout,
+                    address: None,
+                    label: None,
+                    redeem_script: None,
+                    witness_script: None,
+                    script_pub_key: ScriptBuf::new(),
+                    amount: Amount::from_sat(sats),
+                    confirmations: 1,
+                    spendable: true,
+                    solvable: true,
+                    descriptor: None,
+                    safe: true,
+                },
+                UTXOSpendInfo::SeedCoin {
+                    path: "m/0/0".to_string(),
+                    input_value: Amount::from_sat(sats),
+                },
+            )
+        };
+        let coins = vec![
+            coin(1, 0, 1000),
+            coin(2, 0, 5000),
+            coin(3, 1, 3000),
+            coin(3, 0, 3000),
+        ];
+        let outpoints = |selected: Vec<(ListUnspentResultEntry, UTXOSpendInfo)>| {
+            selected
+                .iter()
+                .map(|(utxo, _)| (utxo.txid.to_byte_array()[0], utxo.vout))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            outpoints(select_largest_first(coins.clone(), Amount::from_sat(5000)).unwrap()),
+            vec![(2, 0)]
+        );
+        // Coins of the same value go in outpoint order.
+        assert_eq!(
+            outpoints(select_largest_first(coins.clone(), Amount::from_sat(7000)).unwrap()),
+            vec![(2, 0), (3, 0)]
+        );
+        assert_eq!(
+            outpoints(select_largest_first(coins.clone(), Amount::from_sat(12_000)).unwrap()),
+            vec![(2, 0), (3, 0), (3, 1), (1, 0)]
+        );
+
+        match select_largest_first(coins, Amount::from_sat(12_001)) {
+            Err(WalletError::InsufficientFunds {
+                available,
+                required,
+            }) => {
+                assert_eq!(available, Amount::from_sat(12_000));
+                assert_eq!(required, Amount::from_sat(12_001));
+            }
+            other => panic!("expected InsufficientFunds, got {:?}", other),
+        }
+        assert!(matches!(
+            select_largest_first(Vec::new(), Amount::from_sat(1)),
+            Err(WalletError::InsufficientFunds { .. })
+        ));
+    }
+
+    #[test]
+    fn test_anti_fee_snipping_locktime() {
+        use bitcoin::secp256k1::rand::{rngs::StdRng, SeedableRng};
+
+        let current_height = 1000;
+        let mut rng = StdRng::seed_from_u64(42);
+        let heights = (0..10_000)
+            .map(|_| {
+                anti_fee_snipping_locktime_with(current_height, &mut rng)
+                    .unwrap()
+                    .to_consensus_u32()
+            })
+            .collect::<Vec<_>>();
+
+        assert!(heights.iter().all(|h| (901..=1000).contains(h)));
+        // Mostly the tip, about 10% of the time lower.
+        let lower = heights.iter().filter(|h| **h < 1000).count();
+        assert!((800..1200).contains(&lower), "{} lower locktimes", lower);
+        assert!(heights.iter().any(|h| *h < 950));
+
+        // Saturates at genesis.
+        assert!((0..1000).all(|_| {
+            anti_fee_snipping_locktime_with(10, &mut rng)
+                .unwrap()
+                .to_consensus_u32()
+                <= 10
+        }));
+    }
+
+    #[test]
+    fn test_spend_locktime() {
+        let height = |h| LockTime::from_height(h).unwrap();
+        let time = |t| LockTime::from_time(t).unwrap();
+        let anti_fee_snipping = height(1000);
+
+        assert_eq!(
+            spend_locktime(anti_fee_snipping, &[]).unwrap(),
+            height(1000)
+        );
+        assert_eq!(
+            spend_locktime(anti_fee_snipping, &[height(900), height(950)]).unwrap(),
+            height(1000)
+        );
+        assert_eq!(
+            spend_locktime(anti_fee_snipping, &[height(900), height(1100)]).unwrap(),
+            height(1100)
+        );
+        assert_eq!(
+            spend_locktime(
+                anti_fee_snipping,
+                &[time(1_700_000_000), time(1_600_000_000)]
+            )
+            .unwrap(),
+            time(1_700_000_000)
+        );
+        assert!(spend_locktime(anti_fee_snipping, &[height(900), time(1_700_000_000)]).is_err());
+    }
+
+    #[test]
+    fn test_estimate_vsize() {
+        let p2wpkh_output = || TxOut {
+            value: Amount::ZERO,
+            script_pubkey: ScriptBuf::new_p2wpkh(&WPubkeyHash::all_zeros()),
+        };
+        let vsize = |spend_info: &UTXOSpendInfo, redeemscript: &Script| {
+            estimate_vsize(
+                vec![placeholder_witness(spend_info, redeemscript)],
+                vec![p2wpkh_output()],
+            )
+        };
+        // 1 input 1 output: 82 base bytes, 2 bytes of segwit marker and flag, then the witness.
+        let expected_vsize = |witness_size: u64| (82 * 4 + 2 + witness_size).div_ceil(4);
+
+        let (pubkey1, _) = generate_keypair();
+        let (pubkey2, _) = generate_keypair();
+        let multisig_redeemscript = create_multisig_redeemscript(&pubkey1, &pubkey2);
+        let contract_redeemscript =
+            create_contract_redeemscript(&pubkey1, &pubkey2, &Hash160::hash(&[0u8; 32]), &20);
+        let fidelity_redeemscript =
+            fidelity_redeemscript(&LockTime::from_height(1000).unwrap(), &pubkey1);
+        assert_eq!(multisig_redeemscript.len(), 71);
+        assert_eq!(contract_redeemscript.len(), 107);
+        assert_eq!(fidelity_redeemscript.len(), 40);
+
+        // <sig> <pubkey>
+        let seed_coin = UTXOSpendInfo::SeedCoin {
+            path: "m/0/0".to_string(),
+            input_value: Amount::ZERO,
+        };
+        assert_eq!(
+            vsize(&seed_coin, &ScriptBuf::new()),
+            expected_vsize(1 + 74 + 34)
+        );
+        assert_eq!(vsize(&seed_coin, &ScriptBuf::new()), 110);
+
+        // <> <sig> <sig> <multisig redeemscript>
+        let swapcoin = UTXOSpendInfo::SwapCoin {
+            multisig_redeemscript: multisig_redeemscript.clone(),
+        };
+        assert_eq!(
+            vsize(&swapcoin, &multisig_redeemscript),
+            expected_vsize(1 + 1 + 74 + 74 + 72)
+        );
+
+        // <sig> <> <contract redeemscript>
+        let timelock = UTXOSpendInfo::TimelockContract {
+            swapcoin_multisig_redeemscript: multisig_redeemscript.clone(),
+            input_value: Amount::ZERO,
+        };
+        assert_eq!(
+            vsize(&timelock, &contract_redeemscript),
+            expected_vsize(1 + 74 + 1 + 108)
+        );
+
+        // <sig> <preimage> <contract redeemscript>
+        let hashlock = UTXOSpendInfo::HashlockContract {
+            swapcoin_multisig_redeemscript: multisig_redeemscript,
+            input_value: Amount::ZERO,
+        };
+        assert_eq!(
+            vsize(&hashlock, &contract_redeemscript),
+            expected_vsize(1 + 74 + 33 + 108)
+        );
+
+        // <sig> <fidelity redeemscript>
+        let fidelity = UTXOSpendInfo::FidelityBondCoin {
+            index: 0,
+            input_value: Amount::ZERO,
+        };
+        assert_eq!(
+            vsize(&fidelity, &fidelity_redeemscript),
+            expected_vsize(1 + 74 + 41)
+        );
+
+        // Each extra input and output adds to the size.
+        assert_eq!(
+            estimate_vsize(
+                vec![
+                    placeholder_witness(&seed_coin, &ScriptBuf::new()),
+                    placeholder_witness(&seed_coin, &ScriptBuf::new()),
+                ],
+                vec![p2wpkh_output(), p2wpkh_output()],
+            ),
+            ((82 + 41 + 31) * 4 + 2 + 2 * (1 + 74 + 34u64)).div_ceil(4)
+        );
+    }
+
+    #[test]
+    fn test_coin_to_spend_matches() {
+        let txid = "5df6e0e2761359d30a8275058e299fcc0381534545f55cf43e41983f5d4c9456";
+        let utxo = ListUnspentResultEntry {
+            txid: txid.parse().unwrap(),
+            vout: 1,
+            address: None,
+            label: None,
+            redeem_script: None,
+            witness_script: None,
+            script_pub_key: ScriptBuf::new(),
+            amount: Amount::from_sat(1000),
+            confirmations: 1,
+            spendable: true,
+            solvable: true,
+            descriptor: None,
+            safe: true,
+        };
+
+        let matches = |s: &str| CoinToSpend::from_str(s).unwrap().matches(&utxo);
+        assert!(matches(&format!("{}:1", txid)));
+        assert!(!matches(&format!("{}:0", txid)));
+        assert!(matches("5df6e0..4c9456:1"));
+        assert!(!matches("5df6e0..4c9456:0"));
+        assert!(!matches("5df6e1..4c9456:1"));
+        assert!(!matches("5df6e0..4c9457:1"));
+    }
+
     #[test]
     fn test_coin_to_spend_long_form_and_short_form_parsing() {
         let valid_outpoint_str =
@@ -303,15 +1369,39 @@ mod tests {
             CoinToSpend::from_str(valid_short_form_str),
             Ok(CoinToSpend::ShortForm { .. })
         ));
-        let mut invalid_short_form_str = "123ab..def456:0";
-        assert!(CoinToSpend::from_str(invalid_short_form_str).is_err());
 
-        invalid_short_form_str = "123abc.def456:0";
+        // Prefixes and suffixes of any length.
+        let short_form = |prefix: &str, suffix: &str, vout| CoinToSpend::ShortForm {
+            prefix: prefix.to_string(),
+            suffix: suffix.to_string(),
+            vout,
+        };
+        assert_eq!(
+            CoinToSpend::from_str("123ab..def456:0").unwrap(),
+            short_form("123ab", "def456", 0)
+        );
+        assert_eq!(
+            CoinToSpend::from_str("5d..c9456:12").unwrap(),
+            short_form("5d", "c9456", 12)
+        );
+        assert_eq!(
+            CoinToSpend::from_str("5DF6E0E276..3F5D4C9456:1").unwrap(),
+            short_form("5df6e0e276", "3f5d4c9456", 1)
+        );
+
+        let mut invalid_short_form_str = "123abc.def456:0";
         assert!(CoinToSpend::from_str(invalid_short_form_str).is_err());
 
         invalid_short_form_str = "123abc..def4560";
         assert!(CoinToSpend::from_str(invalid_short_form_str).is_err());
 
+        // Non hex, empty fragments and bad vouts.
+        assert!(CoinToSpend::from_str("123xyz..def456:0").is_err());
+        assert!(CoinToSpend::from_str("..def456:0").is_err());
+        assert!(CoinToSpend::from_str("123abc..:0").is_err());
+        assert!(CoinToSpend::from_str("123abc..def456:").is_err());
+        assert!(CoinToSpend::from_str("123abc..def456:-1").is_err());
+
         assert!(CoinToSpend::from_str("invalid").is_err());
     }
 }
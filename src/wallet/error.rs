@@ -1,6 +1,8 @@
 //! All Wallet-related errors.
 
-use super::fidelity::FidelityError;
+use bitcoin::{Address, Amount, Network, OutPoint, Txid};
+
+use super::{api::KeychainKind, direct_send::CoinToSpend, fidelity::FidelityError};
 use crate::protocol::error::ContractError;
 
 /// Enum for handling wallet-related errors.
@@ -16,6 +18,56 @@ pub enum WalletError {
     Fidelity(FidelityError),
     Locktime(bitcoin::blockdata::locktime::absolute::ConversionError),
     Secp(bitcoin::secp256k1::Error),
+    DescriptorNotImported(Vec<String>),
+    Json(serde_json::Error),
+    SignerRejected(String),
+    TooManyInputs {
+        required: usize,
+        max: usize,
+    },
+    InsufficientFunds {
+        available: Amount,
+        required: Amount,
+    },
+    Psbt(bitcoin::psbt::Error),
+    /// The coins to spend that aren't in the wallet.
+    CoinNotFound(Vec<CoinToSpend>),
+    /// A coin to spend is frozen.
+    CoinFrozen(OutPoint),
+    NetworkMismatch {
+        address: Address,
+        expected: Network,
+    },
+    /// The transaction to bump isn't unconfirmed in the mempool.
+    ParentNotInMempool(Txid),
+    /// The transaction to bump has no spendable wallet output.
+    NoCpfpOutput(Txid),
+    /// A send output below the dust limit of its script.
+    DustOutput {
+        value: Amount,
+        dust: Amount,
+    },
+    /// The transaction to replace doesn't signal BIP125 replaceability.
+    NotReplaceable(Txid),
+    /// The transaction to replace has no change output to take the higher fee from.
+    NoFeeBumpOutput(Txid),
+    /// The fee of the consolidation leaves a dust output, or nothing.
+    UneconomicConsolidation {
+        value: Amount,
+        fee: Amount,
+    },
+    /// A JSON-RPC value that isn't a valid bitcoin amount.
+    InvalidRpcAmount(serde_json::Value),
+    /// A send amount string that isn't `max`, a percentage or a bitcoin amount.
+    InvalidSendAmount(String),
+    /// The wallet file couldn't be encrypted.
+    Encryption(String),
+    /// The encrypted wallet file doesn't decrypt with the given passphrase.
+    BadPassphrase,
+    /// The bitcoin core node has no wallet of this name, loaded or on disk.
+    CoreWalletNotFound(String),
+    /// The keychain can't be expressed as an output descriptor.
+    NoDescriptor(KeychainKind),
 }
 
 impl From<std::io::Error> for WalletError {
@@ -71,3 +123,15 @@ impl From<bitcoin::secp256k1::Error> for WalletError {
         Self::Secp(value)
     }
 }
+
+impl From<serde_json::Error> for WalletError {
+    fn from(value: serde_json::Error) -> Self {
+        Self::Json(value)
+    }
+}
+
+impl From<bitcoin::psbt::Error> for WalletError {
+    fn from(value: bitcoin::psbt::Error) -> Self {
+        Self::Psbt(value)
+    }
+}
@@ -1,17 +1,15 @@
 use std::{
     collections::HashMap,
-    str::FromStr,
     thread,
     time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
 use bitcoin::{
     absolute::LockTime,
-    bip32::{ChildNumber, DerivationPath},
     hashes::{sha256d, Hash},
     opcodes,
     script::{Builder, Instruction},
-    secp256k1::{Keypair, Message, Secp256k1},
+    secp256k1::{Message, Secp256k1},
     transaction::Version,
     Address, Amount, OutPoint, PublicKey, ScriptBuf, Sequence, Transaction, TxIn, TxOut, Txid,
     Witness,
@@ -21,11 +19,15 @@ use serde::{Deserialize, Serialize};
 
 use crate::{
     protocol::messages::FidelityProof,
-    utill::redeemscript_to_scriptpubkey,
-    wallet::{UTXOSpendInfo, Wallet},
+    utill::{compute_checksum, redeemscript_to_scriptpubkey},
+    wallet::{RemoteSigner, SignPayload, SignerKey, UTXOSpendInfo, Wallet},
 };
 
-use super::WalletError;
+use super::{
+    direct_send::anti_fee_snipping_locktime,
+    signer::{request_signature, signed_message_hash},
+    WalletError,
+};
 
 // To (strongly) disincentivize Sybil behavior, the value assessment of the bond
 // is based on the (time value of the bond)^x here x is the bond_value_exponent,
@@ -39,8 +41,12 @@ const BOND_VALUE_EXPONENT: f64 = 1.3;
 // Set as a real number, i.e. 1 = 100% and 0.01 = 1%
 const BOND_VALUE_INTEREST_RATE: f64 = 0.015;
 
+/// Miner fee of the fidelity bond creation and redemption transactions.
+// TODO: Update this with the feerate
+const FIDELITY_TX_FEE: Amount = Amount::from_sat(1000);
+
 /// Constant representing the derivation path for fidelity addresses.
-const FIDELITY_DERIVATION_PATH: &str = "m/84'/0'/0'/2";
+pub(super) const FIDELITY_DERIVATION_PATH: &str = "m/84'/0'/0'/2";
 
 /// Error structure defining possible fidelity related errors
 #[derive(Debug)]
@@ -51,6 +57,7 @@ pub enum FidelityError {
     BondAlreadySpent,
     CertExpired,
     InsufficientFund { available: u64, required: u64 },
+    BondNotMatured { remaining_blocks: u64 },
 }
 
 // impl From<bitcoin::secp256k1::Error> for FidelityError {
@@ -163,18 +170,17 @@ impl FidelityBond {
         redeemscript_to_scriptpubkey(&self.redeem_script())
     }
 
-    /// Generate the bond's certificate hash.
-    pub fn generate_cert_hash(&self, onion_addr: String) -> sha256d::Hash {
-        let cert_msg_str = format!(
+    /// The bond's certificate message, signed in the Bitcoin signed message format.
+    pub fn cert_message(&self, onion_addr: String) -> String {
+        format!(
             "fidelity-bond-cert|{}|{}|{}|{}|{}|{}",
             self.outpoint, self.pubkey, self.cert_expiry, self.lock_time, self.amount, onion_addr
-        );
-        let cert_msg = cert_msg_str.as_bytes();
-        let mut btc_signed_msg = Vec::<u8>::new();
-        btc_signed_msg.extend("\x18Bitcoin Signed Message:\n".as_bytes());
-        btc_signed_msg.push(cert_msg.len() as u8);
-        btc_signed_msg.extend(cert_msg);
-        sha256d::Hash::hash(&btc_signed_msg)
+        )
+    }
+
+    /// Generate the bond's certificate hash.
+    pub fn generate_cert_hash(&self, onion_addr: String) -> sha256d::Hash {
+        signed_message_hash(&self.cert_message(onion_addr))
     }
 }
 
@@ -202,19 +208,9 @@ impl Wallet {
             .max_by(|a, b| a.1.cmp(&b.1))
             .map(|(i, _)| *i))
     }
-    /// Get the [KeyPair] for the fidelity bond at given index.
-    pub fn get_fidelity_keypair(&self, index: u32) -> Result<Keypair, WalletError> {
-        let secp = Secp256k1::new();
-
-        let derivation_path = DerivationPath::from_str(FIDELITY_DERIVATION_PATH)?;
-
-        let child_derivation_path = derivation_path.child(ChildNumber::Normal { index });
-
-        Ok(self
-            .store
-            .master_key
-            .derive_priv(&secp, &child_derivation_path)?
-            .to_keypair(&secp))
+    /// Get the public key of the fidelity bond at given index.
+    pub fn get_fidelity_pubkey(&self, index: u32) -> Result<PublicKey, WalletError> {
+        self.signer().public_key(&SignerKey::FidelityBond { index })
     }
 
     /// Derives the fidelity redeemscript from bond values at given index.
@@ -243,10 +239,7 @@ impl Wallet {
             .last()
             .unwrap_or(0);
 
-        let fidelity_pubkey = PublicKey {
-            compressed: true,
-            inner: self.get_fidelity_keypair(next_index)?.public_key(),
-        };
+        let fidelity_pubkey = self.get_fidelity_pubkey(next_index)?;
 
         Ok((
             next_index,
@@ -305,35 +298,10 @@ impl Wallet {
     ) -> Result<u32, WalletError> {
         let (index, fidelity_addr, fidelity_pubkey) = self.get_next_fidelity_address(locktime)?;
 
-        let all_utxos = self.get_all_utxo()?;
-
-        let mut seed_coin_utxo = self.list_descriptor_utxo_spend_info(Some(&all_utxos))?;
-        let mut swap_coin_utxo = self.list_swap_coin_utxo_spend_info(Some(&all_utxos))?;
-        seed_coin_utxo.append(&mut swap_coin_utxo);
-
-        // Fetch utxos, filter out existing fidelity coins
-        let mut unspents = seed_coin_utxo
-            .into_iter()
-            .filter(|(_, spend_info)| !matches!(spend_info, UTXOSpendInfo::FidelityBondCoin { .. }))
-            .collect::<Vec<_>>();
-
-        unspents.sort_by(|a, b| b.0.amount.cmp(&a.0.amount));
-
-        let mut selected_utxo = Vec::new();
-        let mut remaining = amount;
+        self.lock_unspendable_utxos()?;
+        let selected_utxo = self.coin_select(amount)?;
 
-        // the simplest largest first coinselection.
-        for unspent in unspents {
-            if remaining.checked_sub(unspent.0.amount).is_none() {
-                selected_utxo.push(unspent);
-                break;
-            } else {
-                remaining -= unspent.0.amount;
-                selected_utxo.push(unspent);
-            }
-        }
-
-        let fee = Amount::from_sat(1000); // TODO: Update this with the feerate
+        let fee = FIDELITY_TX_FEE;
 
         let total_input_amount = selected_utxo.iter().fold(Amount::ZERO, |acc, (unspet, _)| {
             acc.checked_add(unspet.amount)
@@ -366,13 +334,15 @@ impl Wallet {
 
         if let Some(change) = change_amount {
             let change_addrs = self.get_next_internal_addresses(1)?[0].script_pubkey();
-            tx_outs.push(TxOut {
-                value: change,
-                script_pubkey: change_addrs,
-            });
+            // Dust change goes to the miners.
+            if change > change_addrs.minimal_non_dust() {
+                tx_outs.push(TxOut {
+                    value: change,
+                    script_pubkey: change_addrs,
+                });
+            }
         }
-        let current_height = self.rpc.get_block_count()?;
-        let anti_fee_snipping_locktime = LockTime::from_height(current_height as u32)?;
+        let anti_fee_snipping_locktime = anti_fee_snipping_locktime(self.rpc.get_block_count()?)?;
 
         let mut tx = Transaction {
             input: tx_inputs,
@@ -388,29 +358,7 @@ impl Wallet {
 
         let txid = self.rpc.send_raw_transaction(&tx)?;
 
-        let conf_height = loop {
-            if let Ok(get_tx_result) = self.rpc.get_transaction(&txid, None) {
-                if let Some(ht) = get_tx_result.info.blockheight {
-                    log::info!("Fidelity Bond confirmed at blockheight: {}", ht);
-                    break ht;
-                } else {
-                    log::info!(
-                        "Fildelity Transaction {} seen in mempool, waiting for confirmation.",
-                        txid
-                    );
-                    if cfg!(feature = "integration-test") {
-                        thread::sleep(Duration::from_secs(1)); // wait for 1 sec in tests
-                    } else {
-                        thread::sleep(Duration::from_secs(60 * 10)); // wait for 10 mins in prod
-                    }
-
-                    continue;
-                }
-            } else {
-                log::info!("Waiting for {} in mempool", txid);
-                continue;
-            }
-        };
+        let conf_height = self.wait_for_fidelity_confirmation(&txid);
 
         let cert_expiry = self.get_fidelity_expriy()?;
 
@@ -436,15 +384,7 @@ impl Wallet {
     /// This functions creates a spending transaction, signs and broadcasts it.
     /// Upon confirmation it marks the bond as `spent` in the wallet data.
     pub fn redeem_fidelity(&mut self, index: u32) -> Result<Txid, WalletError> {
-        let (bond, _, is_spent) = self
-            .store
-            .fidelity_bond
-            .get(&index)
-            .ok_or(FidelityError::BondDoesNotExist)?;
-
-        if *is_spent {
-            return Err(FidelityError::BondAlreadySpent.into());
-        }
+        let bond = self.redeemable_fidelity_bond(index)?;
 
         // create a spending transaction.
         let txin = TxIn {
@@ -455,7 +395,7 @@ impl Wallet {
         };
 
         // TODO take feerate as user input
-        let fee = Amount::from_sat(1000);
+        let fee = FIDELITY_TX_FEE;
 
         let change_addr = &self.get_next_internal_addresses(1)?[0];
 
@@ -480,8 +420,69 @@ impl Wallet {
 
         let txid = self.rpc.send_raw_transaction(&tx)?;
 
-        let conf_height = loop {
-            if let Ok(get_tx_result) = self.rpc.get_transaction(&txid, None) {
+        let conf_height = self.wait_for_fidelity_confirmation(&txid);
+
+        log::info!(
+            "Fidleity spend txid: {}, confirmed at height : {}",
+            txid,
+            conf_height
+        );
+
+        // mark is_spent
+        {
+            let (_, _, is_spent) = self
+                .store
+                .fidelity_bond
+                .get_mut(&index)
+                .ok_or(FidelityError::BondDoesNotExist)?;
+
+            *is_spent = true;
+        }
+
+        Ok(txid)
+    }
+
+    /// Mark the bonds spent on chain or in the mempool as spent, like the ones swept by a direct send.
+    pub(super) fn mark_spent_fidelity_bonds(&mut self) -> Result<(), WalletError> {
+        for (bond, _, is_spent) in self.store.fidelity_bond.values_mut() {
+            if !*is_spent
+                && self
+                    .rpc
+                    .get_tx_out(&bond.outpoint.txid, bond.outpoint.vout, Some(true))?
+                    .is_none()
+            {
+                log::info!("Fidelity bond {} is spent", bond.outpoint);
+                *is_spent = true;
+            }
+        }
+        Ok(())
+    }
+
+    /// The unspent bond at `index`, erroring if it isn't matured yet.
+    fn redeemable_fidelity_bond(&self, index: u32) -> Result<FidelityBond, WalletError> {
+        let (bond, _, is_spent) = self
+            .store
+            .fidelity_bond
+            .get(&index)
+            .ok_or(FidelityError::BondDoesNotExist)?;
+
+        if *is_spent {
+            return Err(FidelityError::BondAlreadySpent.into());
+        }
+        let remaining_blocks = self.fidelity_bond_maturity_blocks(bond)?;
+        if remaining_blocks > 0 {
+            return Err(FidelityError::BondNotMatured {
+                remaining_blocks: remaining_blocks as u64,
+            }
+            .into());
+        }
+        Ok(bond.clone())
+    }
+
+    /// Wait for the fidelity transaction `txid` to confirm, and return its confirmation height.
+    fn wait_for_fidelity_confirmation(&self, txid: &Txid) -> u32 {
+        loop {
+            if let Ok(get_tx_result) = self.rpc.get_transaction(txid, None) {
                 if let Some(ht) = get_tx_result.info.blockheight {
                     log::info!("Fidelity Bond confirmed at blockheight: {}", ht);
                     break ht;
@@ -490,7 +491,6 @@ impl Wallet {
                         "Fildelity Transaction {} seen in mempool, waiting for confirmation.",
                         txid
                     );
-
                     if cfg!(feature = "integration-test") {
                         thread::sleep(Duration::from_secs(1)); // wait for 1 sec in tests
                     } else {
@@ -503,26 +503,145 @@ impl Wallet {
                 log::info!("Waiting for {} in mempool", txid);
                 continue;
             }
+        }
+    }
+
+    /// Redeem the matured bond at `outpoint`, and lock the freed funds, minus fees, in a new bond until `locktime`.
+    ///
+    /// The bond is spent straight into the new bond, in a single transaction. So no other wallet coin
+    /// is used, and the old bond is marked spent only along with the new one being stored.
+    ///
+    /// Returns the outpoint of the new bond.
+    pub fn renew_fidelity_bond(
+        &mut self,
+        outpoint: OutPoint,
+        locktime: LockTime,
+    ) -> Result<OutPoint, WalletError> {
+        let index = self.fidelity_bond_index(&outpoint)?;
+        let bond = self.redeemable_fidelity_bond(index)?;
+        let (new_index, fidelity_addr, fidelity_pubkey) =
+            self.get_next_fidelity_address(locktime)?;
+
+        let fidelity_spk = fidelity_addr.script_pubkey();
+        let required = FIDELITY_TX_FEE + fidelity_spk.minimal_non_dust();
+        let amount = bond
+            .amount
+            .checked_sub(FIDELITY_TX_FEE)
+            .filter(|amount| *amount >= fidelity_spk.minimal_non_dust())
+            .ok_or(FidelityError::InsufficientFund {
+                available: bond.amount.to_sat(),
+                required: required.to_sat(),
+            })?;
+
+        let mut tx = Transaction {
+            input: vec![TxIn {
+                previous_output: bond.outpoint,
+                sequence: Sequence(0),
+                script_sig: ScriptBuf::new(),
+                witness: Witness::new(),
+            }],
+            output: vec![TxOut {
+                value: amount,
+                script_pubkey: fidelity_spk,
+            }],
+            lock_time: bond.lock_time,
+            version: Version::TWO,
         };
 
+        let utxo_spend_info = UTXOSpendInfo::FidelityBondCoin {
+            index,
+            input_value: bond.amount,
+        };
+        self.sign_transaction(&mut tx, vec![utxo_spend_info].into_iter())?;
+
+        // The transaction pays no wallet address, so watch the new bond to see it confirm.
+        let descriptor_without_checksum = format!("raw({:x})", tx.output[0].script_pubkey);
+        let descriptor = format!(
+            "{}#{}",
+            descriptor_without_checksum,
+            compute_checksum(&descriptor_without_checksum)?
+        );
+        self.import_descriptors(&[descriptor], None)?;
+
+        let txid = self.rpc.send_raw_transaction(&tx)?;
+        let conf_height = self.wait_for_fidelity_confirmation(&txid);
+
         log::info!(
-            "Fidleity spend txid: {}, confirmed at height : {}",
+            "Fidelity bond {} renewed into bond {}, txid: {}, confirmed at height : {}",
+            index,
+            new_index,
             txid,
             conf_height
         );
 
-        // mark is_spent
-        {
-            let (_, _, is_spent) = self
-                .store
-                .fidelity_bond
-                .get_mut(&index)
-                .ok_or(FidelityError::BondDoesNotExist)?;
+        let new_bond = FidelityBond {
+            outpoint: OutPoint::new(txid, 0),
+            amount,
+            lock_time: locktime,
+            pubkey: fidelity_pubkey,
+            conf_height,
+            cert_expiry: self.get_fidelity_expriy()?,
+        };
+        let new_bond_spk = new_bond.script_pub_key();
+        let new_outpoint = new_bond.outpoint;
 
+        if let Some((_, _, is_spent)) = self.store.fidelity_bond.get_mut(&index) {
             *is_spent = true;
         }
+        self.store
+            .fidelity_bond
+            .insert(new_index, (new_bond, new_bond_spk, false));
 
-        Ok(txid)
+        Ok(new_outpoint)
+    }
+
+    /// The index of the bond at `outpoint`.
+    fn fidelity_bond_index(&self, outpoint: &OutPoint) -> Result<u32, WalletError> {
+        self.store
+            .fidelity_bond
+            .iter()
+            .find(|(_, (bond, _, _))| bond.outpoint == *outpoint)
+            .map(|(index, _)| *index)
+            .ok_or(FidelityError::BondDoesNotExist.into())
+    }
+
+    /// Lists the unspent fidelity bonds by outpoint, oldest first, along with whether they are matured.
+    pub fn list_fidelity_bonds(&self) -> Result<Vec<(OutPoint, FidelityBond, bool)>, WalletError> {
+        let mut bonds = self
+            .store
+            .fidelity_bond
+            .iter()
+            .filter(|(_, (_, _, is_spent))| !is_spent)
+            .map(|(index, (bond, _, _))| {
+                let matured = self.fidelity_bond_maturity_blocks(bond)? <= 0;
+                Ok((*index, bond.clone(), matured))
+            })
+            .collect::<Result<Vec<_>, WalletError>>()?;
+        bonds.sort_by_key(|(index, _, _)| *index);
+        Ok(bonds
+            .into_iter()
+            .map(|(_, bond, matured)| (bond.outpoint, bond, matured))
+            .collect())
+    }
+
+    /// Number of blocks until the bond can be redeemed. Zero or negative once the bond is matured.
+    ///
+    /// For timestamp locktimes, this is estimated with 10 minute blocks from the median time past.
+    pub fn fidelity_bond_maturity_blocks(&self, bond: &FidelityBond) -> Result<i64, WalletError> {
+        match bond.lock_time {
+            LockTime::Blocks(height) => {
+                // The redemption can be mined in any block above the locktime height.
+                let current_height = self.rpc.get_block_count()? as i64;
+                Ok(height.to_consensus_u32() as i64 - current_height)
+            }
+            LockTime::Seconds(time) => {
+                let median_time = self.rpc.get_blockchain_info()?.median_time as i64;
+                Ok(maturity_blocks_from_time(
+                    time.to_consensus_u32() as i64,
+                    median_time,
+                ))
+            }
+        }
     }
 
     /// Generate a [FidelityProof] for bond at a given index and a specific onion address.
@@ -530,6 +649,16 @@ impl Wallet {
         &self,
         index: u32,
         maker_addr: String,
+    ) -> Result<FidelityProof, WalletError> {
+        self.generate_fidelity_proof_with(index, maker_addr, self.signer())
+    }
+
+    /// Generate a [FidelityProof], asking `signer` for the certificate signature.
+    pub fn generate_fidelity_proof_with(
+        &self,
+        index: u32,
+        maker_addr: String,
+        signer: &dyn RemoteSigner,
     ) -> Result<FidelityProof, WalletError> {
         // Generate a fidelity bond proof from the fidelity data.
         let (bond, _, is_spent) = self
@@ -542,15 +671,17 @@ impl Wallet {
             return Err(FidelityError::BondAlreadySpent.into());
         }
 
-        let fidelity_privkey = self.get_fidelity_keypair(index)?.secret_key();
+        let cert_hash = bond.generate_cert_hash(maker_addr.clone());
 
-        let cert_hash = bond.generate_cert_hash(maker_addr);
-
-        let secp = Secp256k1::new();
-        let cert_sig = secp.sign_ecdsa(
-            &Message::from_digest_slice(cert_hash.as_byte_array())?,
-            &fidelity_privkey,
-        );
+        let cert_sig = request_signature(
+            signer,
+            &SignerKey::FidelityBond { index },
+            &bond.pubkey,
+            SignPayload::Message {
+                message: bond.cert_message(maker_addr),
+            },
+        )?
+        .signature;
 
         Ok(FidelityProof {
             bond: bond.clone(),
@@ -610,9 +741,21 @@ impl Wallet {
     }
 }
 
+/// Blocks until a timestamp `locktime` passes the `median_time` past, with 10 minutes blocks.
+fn maturity_blocks_from_time(locktime: i64, median_time: i64) -> i64 {
+    // The locktime must be strictly below the median time past.
+    let remaining_secs = locktime - median_time + 1;
+    if remaining_secs > 0 {
+        (remaining_secs + 599) / 600
+    } else {
+        remaining_secs / 600
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
+    use std::str::FromStr;
 
     #[test]
     fn test_fidelity_bond_value_function_behavior() {
@@ -767,4 +910,18 @@ mod test {
             assert_eq!(lt, read_locktime_from_fidelity_script(&script).unwrap());
         }
     }
+
+    #[test]
+    fn test_maturity_blocks_from_time() {
+        let median_time = 1_700_000_000;
+        // Exactly at the median time past isn't enough, it must be passed.
+        assert_eq!(maturity_blocks_from_time(median_time, median_time), 1);
+        assert_eq!(maturity_blocks_from_time(median_time - 1, median_time), 0);
+        assert_eq!(maturity_blocks_from_time(median_time + 599, median_time), 1);
+        assert_eq!(maturity_blocks_from_time(median_time + 600, median_time), 2);
+        assert_eq!(
+            maturity_blocks_from_time(median_time - 1200, median_time),
+            -1
+        );
+    }
 }
@@ -10,10 +10,12 @@ use bitcoin::{
     Transaction, TxIn, TxOut, Txid, Witness,
 };
 
-use bitcoind::bitcoincore_rpc::{json::CreateRawTransactionInput, RpcApi};
+use bitcoind::bitcoincore_rpc::json::CreateRawTransactionInput;
 
 use bitcoin::secp256k1::rand::{rngs::OsRng, RngCore};
 
+use crate::protocol::contract::funding_tx_fee;
+
 use super::Wallet;
 
 use super::error::WalletError;
@@ -28,6 +30,8 @@ pub struct CreateFundingTxesResult {
 impl Wallet {
     // Attempts to create the funding transactions.
     /// Returns Ok(None) if there was no error but the wallet was unable to create funding txes
+    ///
+    /// `fee_rate` is in sats/kvbyte, each funding tx pays [funding_tx_fee].
     pub fn create_funding_txes(
         &self,
         coinswap_amount: Amount,
@@ -143,9 +147,22 @@ impl Wallet {
             let mut outputs = HashMap::<String, Amount>::new();
             outputs.insert(address.to_string(), Amount::from_sat(output_value));
 
-            let fee = fee_rate;
+            let fee = funding_tx_fee(fee_rate);
             let remaining = Amount::from_sat(output_value);
-            let selected_utxo = self.coin_select(remaining)?;
+            let selected_utxo =
+                match self.coin_select_with_limit(remaining + fee, self.max_inputs_per_tx()) {
+                    Ok(selected_utxo) => selected_utxo,
+                    Err(e) => {
+                        // Give the coins of the txes built so far back to the other methods.
+                        self.release_in_flight(
+                            &funding_txes
+                                .iter()
+                                .flat_map(|tx| tx.input.iter().map(|vin| vin.previous_output))
+                                .collect::<Vec<OutPoint>>(),
+                        )?;
+                        return Err(e);
+                    }
+                };
             let total_input_amount = selected_utxo.iter().fold(Amount::ZERO, |acc, (unspet, _)| {
                 acc.checked_add(unspet.amount)
                     .expect("Amount sum overflowed")
@@ -182,7 +199,7 @@ impl Wallet {
                 .map(|(_, spend_info)| spend_info.clone());
             self.sign_transaction(&mut funding_tx, &mut input_info)?;
 
-            self.rpc.lock_unspent(
+            self.lock_in_flight(
                 &funding_tx
                     .input
                     .iter()
@@ -194,7 +211,7 @@ impl Wallet {
 
             funding_txes.push(funding_tx);
             payment_output_positions.push(payment_pos);
-            total_miner_fee += fee_rate.to_sat();
+            total_miner_fee += fee.to_sat();
         }
 
         Ok(CreateFundingTxesResult {
@@ -213,6 +230,26 @@ impl Wallet {
         utxos: &mut dyn Iterator<Item = (Txid, u32, u64)>, //utxos item is (txid, vout, value)
                                                            //utxos should be sorted by size, largest first
     ) -> Result<CreateFundingTxesResult, WalletError> {
+        // All but one tx spend a single utxo, and the last one the rest.
+        let utxos = utxos.collect::<Vec<_>>();
+        let leftover_inputs_count = (utxos.len() + 1).saturating_sub(destinations.len());
+        if leftover_inputs_count > self.max_inputs_per_tx() {
+            return Err(WalletError::TooManyInputs {
+                required: leftover_inputs_count,
+                max: self.max_inputs_per_tx(),
+            });
+        }
+        let utxos = &mut utxos.into_iter();
+        let fee = funding_tx_fee(fee_rate);
+        let value_after_fee = |value: u64| {
+            value
+                .checked_sub(fee.to_sat())
+                .ok_or(WalletError::InsufficientFunds {
+                    available: Amount::from_sat(value),
+                    required: fee,
+                })
+        };
+
         let mut funding_txes = Vec::<Transaction>::new();
         let mut payment_output_positions = Vec::<u32>::new();
         let mut total_miner_fee = 0;
@@ -225,7 +262,7 @@ impl Wallet {
             let (txid, vout, value) = utxos.next().unwrap();
 
             let mut outputs = HashMap::<&Address, u64>::new();
-            outputs.insert(destinations_iter.next().unwrap(), value);
+            outputs.insert(destinations_iter.next().unwrap(), value_after_fee(value)?);
             let tx_inputs = vec![TxIn {
                 previous_output: OutPoint::new(txid, vout),
                 sequence: Sequence(0),
@@ -251,7 +288,7 @@ impl Wallet {
 
             leftover_coinswap_amount -= funding_tx.output[0].value;
 
-            total_miner_fee += fee_rate.to_sat();
+            total_miner_fee += fee.to_sat();
 
             funding_txes.push(funding_tx);
             payment_output_positions.push(0);
@@ -280,7 +317,7 @@ impl Wallet {
         let mut outputs = HashMap::<&Address, u64>::new();
         outputs.insert(
             destinations_iter.next().unwrap(),
-            leftover_inputs_values.iter().sum::<u64>(),
+            value_after_fee(leftover_inputs_values.iter().sum::<u64>())?,
         );
         let mut tx_outs = Vec::new();
         for (address, value) in outputs {
@@ -300,7 +337,7 @@ impl Wallet {
 
         leftover_coinswap_amount -= funding_tx.output[0].value;
 
-        total_miner_fee += fee_rate.to_sat();
+        total_miner_fee += fee.to_sat();
 
         funding_txes.push(funding_tx);
         payment_output_positions.push(0);
@@ -314,7 +351,7 @@ impl Wallet {
 
         tx_inputs = Vec::new();
         tx_outs = Vec::new();
-        let mut change_amount = first_value;
+        let mut change_amount = value_after_fee(first_value)?;
         tx_inputs.push(TxIn {
             previous_output: OutPoint::new(first_txid, first_vout),
             sequence: Sequence(0),
@@ -322,7 +359,13 @@ impl Wallet {
             script_sig: ScriptBuf::new(),
         });
         for (address, value) in outputs {
-            change_amount -= value;
+            change_amount =
+                change_amount
+                    .checked_sub(value)
+                    .ok_or(WalletError::InsufficientFunds {
+                        available: Amount::from_sat(first_value),
+                        required: Amount::from_sat(value) + fee,
+                    })?;
             tx_outs.push(TxOut {
                 value: Amount::from_sat(value),
                 script_pubkey: address.script_pubkey(),
@@ -341,7 +384,7 @@ impl Wallet {
         let mut info = iter::once(self.get_utxo((first_txid, first_vout))?.unwrap());
         self.sign_transaction(&mut funding_tx, &mut info)?;
 
-        total_miner_fee += fee_rate.to_sat();
+        total_miner_fee += fee.to_sat();
 
         funding_txes.push(funding_tx);
         payment_output_positions.push(1);
@@ -370,7 +413,7 @@ impl Wallet {
 
         self.lock_unspendable_utxos()?;
 
-        let fee = Amount::from_sat(1000);
+        let fee = funding_tx_fee(fee_rate) * destinations.len() as u64;
 
         let remaining = coinswap_amount;
 
@@ -444,13 +487,9 @@ impl Wallet {
         //this function will pick the top most valuable UTXOs and use them
         //to create funding transactions
 
-        let all_utxos = self.get_all_utxo()?;
-
-        let mut seed_coin_utxo = self.list_descriptor_utxo_spend_info(Some(&all_utxos))?;
-        let mut swap_coin_utxo = self.list_swap_coin_utxo_spend_info(Some(&all_utxos))?;
-        seed_coin_utxo.append(&mut swap_coin_utxo);
+        self.lock_unspendable_utxos()?;
 
-        let mut list_unspent_result = seed_coin_utxo;
+        let mut list_unspent_result = self.spendable_utxos()?;
         if list_unspent_result.len() < destinations.len() {
             return Err(WalletError::Protocol(
                 "Not enough UTXOs to create this many funding txes".to_string(),
@@ -0,0 +1,134 @@
+//! Transaction history of the wallet.
+//!
+//! Backed by Core's `listtransactions`. Core knows the sent and received amounts, the wallet knows
+//! which of its scripts are swapcoins, contracts and fidelity bonds, and tells the swap and fidelity
+//! transactions apart from plain sends and receives.
+
+use std::collections::HashSet;
+
+use bitcoin::{ScriptBuf, SignedAmount, Transaction, Txid};
+use bitcoind::bitcoincore_rpc::RpcApi;
+
+use super::{error::WalletError, SwapCoin, Wallet};
+
+/// Best-effort category of a wallet transaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TxCategory {
+    /// Pays out of the wallet.
+    Send,
+    /// Pays into the wallet.
+    Receive,
+    /// Funds, or spends from, a swap multisig or contract.
+    Swap,
+    /// Creates, or redeems, a fidelity bond.
+    Fidelity,
+}
+
+/// A transaction of the wallet. See [Wallet::list_transactions].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TxHistoryEntry {
+    pub txid: Txid,
+    /// The net change of the wallet balance, fees included. Negative for an outflow.
+    pub amount: SignedAmount,
+    /// Number of confirmations. 0 if the transaction is still in the mempool.
+    pub confirmations: u32,
+    /// Block time if confirmed, or the time it was first seen by the node, in unix seconds.
+    pub timestamp: u64,
+    pub category: TxCategory,
+}
+
+impl Wallet {
+    /// List the `count` most recent wallet transactions, after skipping the `skip` most recent ones.
+    /// Returned oldest first, like Core's `listtransactions`.
+    ///
+    /// Conflicted transactions, which will never confirm, are left out.
+    pub fn list_transactions(
+        &self,
+        count: usize,
+        skip: usize,
+    ) -> Result<Vec<TxHistoryEntry>, WalletError> {
+        // listtransactions has an entry per wallet output. Take them all, the paging is per transaction.
+        let mut txids = Vec::new();
+        for entry in self
+            .rpc
+            .list_transactions(None, Some(9999999), None, Some(true))?
+        {
+            if entry.info.confirmations >= 0 && !txids.contains(&entry.info.txid) {
+                txids.push(entry.info.txid);
+            }
+        }
+
+        let swap_scripts = self.swap_scripts();
+        let fidelity_scripts = self
+            .store
+            .fidelity_bond
+            .values()
+            .map(|(bond, _, _)| bond.redeem_script())
+            .collect::<HashSet<_>>();
+
+        let mut history = txids
+            .iter()
+            .rev()
+            .skip(skip)
+            .take(count)
+            .map(|txid| {
+                let result = self.rpc.get_transaction(txid, Some(true))?;
+                let tx = result
+                    .transaction()
+                    .map_err(|e| WalletError::Protocol(e.to_string()))?;
+                let amount = result.amount + result.fee.unwrap_or(SignedAmount::ZERO);
+                let category = if touches_scripts(&tx, &fidelity_scripts) {
+                    TxCategory::Fidelity
+                } else if touches_scripts(&tx, &swap_scripts) {
+                    TxCategory::Swap
+                } else if amount.is_negative() {
+                    TxCategory::Send
+                } else {
+                    TxCategory::Receive
+                };
+                Ok(TxHistoryEntry {
+                    txid: *txid,
+                    amount,
+                    confirmations: result.info.confirmations as u32,
+                    timestamp: result.info.blocktime.unwrap_or(result.info.time),
+                    category,
+                })
+            })
+            .collect::<Result<Vec<_>, WalletError>>()?;
+        history.reverse();
+        Ok(history)
+    }
+
+    /// The multisig and contract redeemscripts of all the swapcoins.
+    fn swap_scripts(&self) -> HashSet<ScriptBuf> {
+        let incoming = self.store.incoming_swapcoins.values().flat_map(|sc| {
+            [
+                sc.get_multisig_redeemscript(),
+                sc.contract_redeemscript.clone(),
+            ]
+        });
+        let outgoing = self.store.outgoing_swapcoins.values().flat_map(|sc| {
+            [
+                sc.get_multisig_redeemscript(),
+                sc.contract_redeemscript.clone(),
+            ]
+        });
+        incoming.chain(outgoing).collect()
+    }
+}
+
+/// Whether the transaction pays to one of the p2wsh redeemscripts, or spends from one.
+fn touches_scripts(tx: &Transaction, redeemscripts: &HashSet<ScriptBuf>) -> bool {
+    let pays_to = tx.output.iter().any(|txout| {
+        redeemscripts
+            .iter()
+            .any(|rs| ScriptBuf::new_p2wsh(&rs.wscript_hash()) == txout.script_pubkey)
+    });
+    // A p2wsh spend reveals its redeemscript as the last witness element.
+    let spends_from = tx.input.iter().any(|txin| {
+        txin.witness
+            .last()
+            .is_some_and(|script| redeemscripts.contains(&ScriptBuf::from_bytes(script.to_vec())))
+    });
+    pays_to || spends_from
+}
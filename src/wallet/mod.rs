@@ -1,20 +1,37 @@
 //! The Coinswap Wallet (unsecured). Used by both the Taker and Maker.
 
 mod api;
+mod consolidate;
+mod cpfp;
 mod direct_send;
 mod error;
 mod fidelity;
 mod funding;
+mod history;
+mod ownership;
+mod psbt;
+mod rbf;
 mod rpc;
+mod signer;
 mod storage;
 mod swapcoin;
 
-pub use api::{DisplayAddressType, UTXOSpendInfo, Wallet};
-pub use direct_send::{CoinToSpend, Destination, SendAmount};
+pub use api::{
+    AddressInfo, BalanceBreakdown, ContractBroadcastDetected, ContractDetails, DisplayAddressType,
+    KeychainKind, LabelFilter, UTXOSpendInfo, Wallet,
+};
+pub use direct_send::{CoinToSpend, Destination, SendAmount, SpendOptions, TxSummary};
 pub use error::WalletError;
 pub use fidelity::{FidelityBond, FidelityError};
+pub use history::{TxCategory, TxHistoryEntry};
+pub use ownership::{verify_address_ownership, OwnershipProof};
 pub use rpc::RPCConfig;
-pub use storage::WalletStore;
+pub use signer::{
+    serve_sign_requests, LocalSigner, RemoteSigner, SignPayload, SignRequest, SignResponse,
+    SignerKey, SignerRequest, StreamSigner, WalletPubkeys,
+};
+pub use storage::{PersistBackend, StoreChange, SwapCheckpoint, WalletStore};
+pub(crate) use swapcoin::sign_contract_tx_with_key;
 pub use swapcoin::{
     IncomingSwapCoin, OutgoingSwapCoin, SwapCoin, WalletSwapCoin, WatchOnlySwapCoin,
 };
@@ -0,0 +1,229 @@
+//! Address ownership proofs, for support flows.
+//!
+//! An [OwnershipProof] is a BIP322 simple signature over a challenge, made with the key of a wallet
+//! address. It proves the wallet controls the address, without revealing any key. Anyone can check
+//! it with [verify_address_ownership], no wallet is needed. The wallet's P2WPKH addresses are supported.
+
+use bitcoin::{
+    absolute::LockTime,
+    ecdsa,
+    hashes::{sha256, Hash, HashEngine},
+    opcodes::{all::OP_RETURN, OP_0},
+    script::Builder,
+    secp256k1::{Message, Secp256k1},
+    sighash::SighashCache,
+    transaction::Version,
+    Address, Amount, EcdsaSighashType, OutPoint, PublicKey, ScriptBuf, Sequence, Transaction, TxIn,
+    TxOut, Txid, Witness,
+};
+
+use super::{
+    error::WalletError,
+    signer::{request_signature, RemoteSigner, SignPayload, SignerKey},
+    Wallet,
+};
+
+/// Proof that the wallet controls `address`, over a `challenge` chosen by the verifier.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OwnershipProof {
+    pub address: Address,
+    pub challenge: String,
+    /// The BIP322 simple signature, i.e the witness spending the BIP322 `to_spend` output.
+    pub signature: Witness,
+}
+
+/// The BIP322 tagged hash of a message.
+fn message_hash(message: &str) -> [u8; 32] {
+    let tag = sha256::Hash::hash(b"BIP0322-signed-message");
+    let mut engine = sha256::Hash::engine();
+    engine.input(tag.as_ref());
+    engine.input(tag.as_ref());
+    engine.input(message.as_bytes());
+    sha256::Hash::from_engine(engine).to_byte_array()
+}
+
+/// The BIP322 virtual `to_spend` transaction, committing to the message and the address.
+fn to_spend(script_pubkey: &ScriptBuf, message: &str) -> Transaction {
+    Transaction {
+        version: Version(0),
+        lock_time: LockTime::ZERO,
+        input: vec![TxIn {
+            previous_output: OutPoint::new(Txid::all_zeros(), 0xFFFFFFFF),
+            script_sig: Builder::new()
+                .push_opcode(OP_0)
+                .push_slice(message_hash(message))
+                .into_script(),
+            sequence: Sequence::ZERO,
+            witness: Witness::new(),
+        }],
+        output: vec![TxOut {
+            value: Amount::ZERO,
+            script_pubkey: script_pubkey.clone(),
+        }],
+    }
+}
+
+/// The BIP322 virtual `to_sign` transaction, spending `to_spend`.
+fn to_sign(to_spend: &Transaction) -> Transaction {
+    Transaction {
+        version: Version(0),
+        lock_time: LockTime::ZERO,
+        input: vec![TxIn {
+            previous_output: OutPoint::new(to_spend.compute_txid(), 0),
+            script_sig: ScriptBuf::new(),
+            sequence: Sequence::ZERO,
+            witness: Witness::new(),
+        }],
+        output: vec![TxOut {
+            value: Amount::ZERO,
+            script_pubkey: Builder::new().push_opcode(OP_RETURN).into_script(),
+        }],
+    }
+}
+
+/// The digest signed by the address key.
+fn signature_digest(address: &Address, message: &str) -> Result<[u8; 32], WalletError> {
+    let script_pubkey = address.script_pubkey();
+    let to_sign = to_sign(&to_spend(&script_pubkey, message));
+    let sighash = SighashCache::new(&to_sign)
+        .p2wpkh_signature_hash(0, &script_pubkey, Amount::ZERO, EcdsaSighashType::All)
+        .map_err(|e| WalletError::Protocol(format!("Unsupported address: {}", e)))?;
+    Ok(sighash.to_byte_array())
+}
+
+impl Wallet {
+    /// Prove the wallet owns `address` by signing the given `challenge` with its key.
+    ///
+    /// Errors if the address doesn't belong to the wallet.
+    pub fn prove_address_ownership(
+        &self,
+        address: &Address,
+        challenge: &str,
+    ) -> Result<OwnershipProof, WalletError> {
+        self.prove_address_ownership_with(address, challenge, self.signer())
+    }
+
+    /// Prove the wallet owns `address`, asking `signer` for the signature.
+    pub fn prove_address_ownership_with(
+        &self,
+        address: &Address,
+        challenge: &str,
+        signer: &dyn RemoteSigner,
+    ) -> Result<OwnershipProof, WalletError> {
+        let info = self
+            .get_address_info(address)?
+            .ok_or(WalletError::Protocol(format!(
+                "Address {} doesn't belong to the wallet",
+                address
+            )))?;
+
+        let key = SignerKey::SeedCoin {
+            path: format!("m/{}/{}", info.keychain.index_num(), info.index),
+        };
+        let pubkey = signer.public_key(&key)?;
+        let to_spend = to_spend(&address.script_pubkey(), challenge);
+        let signature = request_signature(
+            signer,
+            &key,
+            &pubkey,
+            SignPayload::Input {
+                tx: to_sign(&to_spend),
+                input_index: 0,
+                prevouts: to_spend.output,
+                witness_script: None,
+            },
+        )?;
+
+        let mut witness = Witness::new();
+        witness.push(signature.serialize());
+        witness.push(pubkey.to_bytes());
+        Ok(OwnershipProof {
+            address: address.clone(),
+            challenge: challenge.to_string(),
+            signature: witness,
+        })
+    }
+}
+
+/// Verify an [OwnershipProof]. Returns false if the signature isn't valid for the address and challenge.
+///
+/// Errors for malformed proofs, or unsupported address types.
+pub fn verify_address_ownership(proof: &OwnershipProof) -> Result<bool, WalletError> {
+    let (signature, pubkey) = match (proof.signature.nth(0), proof.signature.nth(1)) {
+        (Some(signature), Some(pubkey)) if proof.signature.len() == 2 => (signature, pubkey),
+        _ => {
+            return Err(WalletError::Protocol(
+                "Malformed ownership proof".to_string(),
+            ))
+        }
+    };
+    let signature = ecdsa::Signature::from_slice(signature)
+        .map_err(|e| WalletError::Protocol(format!("Malformed ownership proof: {}", e)))?;
+    let pubkey = PublicKey::from_slice(pubkey)
+        .map_err(|e| WalletError::Protocol(format!("Malformed ownership proof: {}", e)))?;
+    if signature.sighash_type != EcdsaSighashType::All {
+        return Ok(false);
+    }
+
+    // The key must be the one of the address.
+    match pubkey.wpubkey_hash() {
+        Ok(wpubkey_hash)
+            if ScriptBuf::new_p2wpkh(&wpubkey_hash) == proof.address.script_pubkey() => {}
+        _ => return Ok(false),
+    }
+
+    let digest = signature_digest(&proof.address, &proof.challenge)?;
+    Ok(Secp256k1::verification_only()
+        .verify_ecdsa(
+            &Message::from_digest(digest),
+            &signature.signature,
+            &pubkey.inner,
+        )
+        .is_ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::{consensus::deserialize, hashes::hex::FromHex};
+    use std::str::FromStr;
+
+    // Test vectors from BIP322.
+    const ADDRESS: &str = "bc1q9vza2e8x573nczrlzms0wvx3gsqjx7vavgkx0l";
+    const HELLO_WORLD_SIGNATURE: &str = "0247304402206517c8637a7bfc3a154edcba6196d64bbd5b73955cb7da7d1626bcdde466c364022022bf10d19fc0bb69b4596e306b362acaa835293cf693bb176f7324b531f5afec012102c7f12003196442943d8588e01aee840423cc54fc1521526a3b85c2b0cbd58872";
+
+    #[test]
+    fn test_message_hash() {
+        assert_eq!(
+            message_hash(""),
+            <[u8; 32]>::from_hex(
+                "c90c269c4f8fcbe6880f72a721ddfbf1914268a794cbb21cfafee13770ae19f1"
+            )
+            .unwrap()
+        );
+        assert_eq!(
+            message_hash("Hello World"),
+            <[u8; 32]>::from_hex(
+                "f0eb03b1a75ac6d9847f55c624a99169b5dccba2a31f5b23bea77ba270de0a7a"
+            )
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_verify_address_ownership() {
+        let mut proof = OwnershipProof {
+            address: Address::from_str(ADDRESS).unwrap().assume_checked(),
+            challenge: "Hello World".to_string(),
+            signature: deserialize(&Vec::from_hex(HELLO_WORLD_SIGNATURE).unwrap()).unwrap(),
+        };
+        assert!(verify_address_ownership(&proof).unwrap());
+
+        // The signature doesn't hold for another challenge.
+        proof.challenge = "Hello World!".to_string();
+        assert!(!verify_address_ownership(&proof).unwrap());
+
+        proof.signature = Witness::new();
+        assert!(verify_address_ownership(&proof).is_err());
+    }
+}
@@ -0,0 +1,418 @@
+//! Combine and finalize Partially Signed Bitcoin Transactions.
+//!
+//! In multi-party flows, each signer adds its signatures to its own copy of a [Psbt]. The copies are
+//! then combined into one, and finalized into a broadcastable [Transaction]. Finalizing supports the
+//! input types used by the wallet: single key P2WPKH and the 2-of-2 P2WSH multisig of the swapcoins.
+//!
+//! Swapcoins funded by external tooling can also be registered from their funding [Psbt].
+//!
+//! Direct sends can be exported as a [Psbt], for the keys held by an external signer, like a hardware
+//! device. The wallet can sign such a [Psbt] itself too, see [Wallet::sign_psbt].
+
+use std::str::FromStr;
+
+use bitcoin::{
+    bip32::DerivationPath,
+    ecdsa,
+    psbt::Psbt,
+    secp256k1::{Secp256k1, SecretKey},
+    Amount, CompressedPublicKey, OutPoint, PublicKey, Script, ScriptBuf, Transaction, TxOut,
+    Witness,
+};
+use bitcoind::bitcoincore_rpc::json::ListUnspentResultEntry;
+
+use crate::{
+    protocol::contract::{
+        create_multisig_redeemscript, read_hashlock_pubkey_from_contract,
+        read_pubkeys_from_multisig_redeemscript,
+    },
+    utill::redeemscript_to_scriptpubkey,
+};
+
+use super::{
+    api::{UTXOSpendInfo, HARDENDED_DERIVATION},
+    direct_send::{Destination, SendAmount},
+    error::WalletError,
+    signer::SignerKey,
+    swapcoin::IncomingSwapCoin,
+    Wallet,
+};
+
+impl Wallet {
+    /// Combine the signatures and data of several [Psbt]s of the same unsigned transaction.
+    pub fn combine_psbts(psbts: Vec<Psbt>) -> Result<Psbt, WalletError> {
+        let mut psbts = psbts.into_iter();
+        let mut combined = psbts
+            .next()
+            .ok_or(WalletError::Protocol("No PSBT to combine".to_string()))?;
+        for psbt in psbts {
+            // Errors if the PSBTs are not for the same unsigned transaction.
+            combined.combine(psbt)?;
+        }
+        Ok(combined)
+    }
+
+    /// Finalize a fully signed [Psbt], and extract the signed [Transaction].
+    ///
+    /// Errors if any input is missing signatures, or is of an unsupported type.
+    pub fn finalize_psbt(mut psbt: Psbt) -> Result<Transaction, WalletError> {
+        for (index, input) in psbt.inputs.iter_mut().enumerate() {
+            if input.final_script_witness.is_some() {
+                continue;
+            }
+            let missing_sigs =
+                || WalletError::Protocol(format!("Missing signatures for input {}", index));
+
+            let witness = if let Some(witness_script) = &input.witness_script {
+                // 2-of-2 multisig. Signatures are pushed in the order of the keys in the script.
+                let (pubkey1, pubkey2) = read_pubkeys_from_multisig_redeemscript(witness_script)?;
+                let sig1 = input.partial_sigs.get(&pubkey1).ok_or_else(missing_sigs)?;
+                let sig2 = input.partial_sigs.get(&pubkey2).ok_or_else(missing_sigs)?;
+                let mut witness = Witness::new();
+                witness.push(Vec::new());
+                witness.push(sig1.serialize());
+                witness.push(sig2.serialize());
+                witness.push(witness_script.as_bytes());
+                witness
+            } else if input
+                .witness_utxo
+                .as_ref()
+                .is_some_and(|utxo| Script::is_p2wpkh(&utxo.script_pubkey))
+            {
+                let (pubkey, sig) = input.partial_sigs.iter().next().ok_or_else(missing_sigs)?;
+                Witness::p2wpkh(sig, &pubkey.inner)
+            } else {
+                return Err(WalletError::Protocol(format!(
+                    "Unsupported input type at input {}",
+                    index
+                )));
+            };
+
+            input.final_script_witness = Some(witness);
+            input.partial_sigs.clear();
+            input.witness_script = None;
+            input.bip32_derivation.clear();
+        }
+
+        // Fee sanity is left to the caller, as for the other wallet transactions.
+        Ok(psbt.extract_tx_unchecked_fee_rate())
+    }
+
+    /// Register a swapcoin funded by external tooling, so the wallet tracks and can spend it.
+    ///
+    /// The funding `psbt` must carry the 2-of-2 multisig redeemscript as the `witness_script` of the
+    /// swapcoin output, and `contract_tx` must pay its single output to `contract_redeemscript`. Both
+    /// multisig keys are required, as the coin is spent without a counterparty.
+    /// The coin is picked up by the next [Wallet::sync]. Returns the funding outpoint.
+    pub fn add_utxo_from_external_psbt(
+        &mut self,
+        psbt: &Psbt,
+        my_privkey: SecretKey,
+        other_privkey: SecretKey,
+        contract_tx: Transaction,
+        contract_redeemscript: ScriptBuf,
+        hashlock_privkey: SecretKey,
+    ) -> Result<OutPoint, WalletError> {
+        let secp = Secp256k1::new();
+        let my_pubkey = PublicKey::new(my_privkey.public_key(&secp));
+        let other_pubkey = PublicKey::new(other_privkey.public_key(&secp));
+        let multisig_redeemscript = create_multisig_redeemscript(&my_pubkey, &other_pubkey);
+
+        let vout = psbt
+            .outputs
+            .iter()
+            .position(|output| output.witness_script.as_ref() == Some(&multisig_redeemscript))
+            .ok_or(WalletError::Protocol(
+                "The keys don't derive the redeemscript of any PSBT output".to_string(),
+            ))?;
+        let funding_output = &psbt.unsigned_tx.output[vout];
+        if funding_output.script_pubkey != redeemscript_to_scriptpubkey(&multisig_redeemscript) {
+            return Err(WalletError::Protocol(
+                "PSBT output doesn't pay to its redeemscript".to_string(),
+            ));
+        }
+        let funding_outpoint = OutPoint::new(psbt.unsigned_tx.compute_txid(), vout as u32);
+
+        if contract_tx.input.len() != 1 || contract_tx.input[0].previous_output != funding_outpoint
+        {
+            return Err(WalletError::Protocol(
+                "Contract transaction doesn't spend the funding output".to_string(),
+            ));
+        }
+        if contract_tx.output.len() != 1
+            || contract_tx.output[0].script_pubkey
+                != redeemscript_to_scriptpubkey(&contract_redeemscript)
+        {
+            return Err(WalletError::Protocol(
+                "Contract transaction doesn't pay to the contract redeemscript".to_string(),
+            ));
+        }
+        if PublicKey::new(hashlock_privkey.public_key(&secp))
+            != read_hashlock_pubkey_from_contract(&contract_redeemscript)?
+        {
+            return Err(WalletError::Protocol(
+                "Hashlock key doesn't derive the contract redeemscript".to_string(),
+            ));
+        }
+
+        let mut swapcoin = IncomingSwapCoin::new(
+            SignerKey::Imported {
+                privkey: my_privkey,
+            },
+            other_pubkey,
+            contract_tx,
+            contract_redeemscript,
+            SignerKey::Imported {
+                privkey: hashlock_privkey,
+            },
+            funding_output.value,
+            self.signer(),
+        )?;
+        swapcoin.other_privkey = Some(other_privkey);
+
+        self.add_incoming_swapcoin(&swapcoin);
+        self.commit()?;
+        Ok(funding_outpoint)
+    }
+
+    /// Create the direct send of [Wallet::spend_from_wallet] as an unsigned [Psbt], for an external signer.
+    ///
+    /// Every input carries its `witness_utxo`. Seed coins carry the BIP32 derivation of their key from
+    /// the wallet master key. Swapcoins carry the 2-of-2 multisig `witness_script`, holding both
+    /// pubkeys. Only seed coins and swapcoins can be spent this way.
+    pub fn create_direct_send_psbt(
+        &mut self,
+        fee: Amount,
+        send_amount: SendAmount,
+        destination: Destination,
+        coins_to_spend: &[(ListUnspentResultEntry, UTXOSpendInfo)],
+    ) -> Result<Psbt, WalletError> {
+        let selected_coins;
+        let coins_to_spend = if coins_to_spend.is_empty() {
+            selected_coins =
+                self.select_direct_send_coins(fee, std::slice::from_ref(&send_amount))?;
+            &selected_coins
+        } else {
+            coins_to_spend
+        };
+
+        let hand_out_address = destination == Destination::Wallet;
+        let (tx, _) =
+            self.build_direct_send_unsigned(fee, send_amount, destination, coins_to_spend)?;
+        if hand_out_address {
+            self.get_next_external_address()?;
+        }
+        let mut psbt = Psbt::from_unsigned_tx(tx)?;
+
+        let secp = Secp256k1::new();
+        let wallet_xpub = self.get_wallet_xpub()?;
+        let fingerprint = self.pubkeys.master_fingerprint;
+        for (input, (utxo, spend_info)) in psbt.inputs.iter_mut().zip(coins_to_spend) {
+            match spend_info {
+                UTXOSpendInfo::SeedCoin { path, input_value } => {
+                    let path = DerivationPath::from_str(path)?;
+                    let pubkey = wallet_xpub.derive_pub(&secp, &path)?.public_key;
+                    input.witness_utxo = Some(TxOut {
+                        value: *input_value,
+                        script_pubkey: ScriptBuf::new_p2wpkh(
+                            &CompressedPublicKey(pubkey).wpubkey_hash(),
+                        ),
+                    });
+                    let full_path = DerivationPath::from_str(HARDENDED_DERIVATION)?.extend(path);
+                    input
+                        .bip32_derivation
+                        .insert(pubkey, (fingerprint, full_path));
+                }
+                UTXOSpendInfo::SwapCoin {
+                    multisig_redeemscript,
+                } => {
+                    input.witness_utxo = Some(TxOut {
+                        value: utxo.amount,
+                        script_pubkey: redeemscript_to_scriptpubkey(multisig_redeemscript),
+                    });
+                    input.witness_script = Some(multisig_redeemscript.clone());
+                }
+                _ => {
+                    return Err(WalletError::Protocol(
+                        "Only seed coins and swapcoins can be spent through a PSBT".to_string(),
+                    ))
+                }
+            }
+        }
+        Ok(psbt)
+    }
+
+    /// Sign all the inputs of a [Psbt] from [Wallet::create_direct_send_psbt] with the wallet keys.
+    ///
+    /// The signatures are the same as the ones of [Wallet::sign_transaction]. Errors if an input isn't
+    /// spendable by the wallet.
+    pub fn sign_psbt(&self, psbt: &mut Psbt) -> Result<(), WalletError> {
+        let fingerprint = self.pubkeys.master_fingerprint;
+        let account_path = DerivationPath::from_str(HARDENDED_DERIVATION)?;
+
+        let spend_infos = psbt
+            .inputs
+            .iter()
+            .enumerate()
+            .map(|(index, input)| {
+                let not_spendable = || {
+                    WalletError::Protocol(format!("Input {} isn't spendable by the wallet", index))
+                };
+                if let Some(multisig_redeemscript) = &input.witness_script {
+                    // Both keys of the swapcoin are needed, i.e the swap must be complete.
+                    match self.find_incoming_swapcoin(multisig_redeemscript) {
+                        Some(swapcoin) if swapcoin.other_privkey.is_some() => {}
+                        _ => return Err(not_spendable()),
+                    }
+                    return Ok(UTXOSpendInfo::SwapCoin {
+                        multisig_redeemscript: multisig_redeemscript.clone(),
+                    });
+                }
+                let (_, (key_fingerprint, path)) = input
+                    .bip32_derivation
+                    .iter()
+                    .next()
+                    .ok_or_else(not_spendable)?;
+                let input_value = input.witness_utxo.as_ref().ok_or_else(not_spendable)?.value;
+                if *key_fingerprint != fingerprint
+                    || !path.as_ref().starts_with(account_path.as_ref())
+                {
+                    return Err(not_spendable());
+                }
+                let path = DerivationPath::from(path[account_path.len()..].to_vec());
+                Ok(UTXOSpendInfo::SeedCoin {
+                    path: format!("m/{}", path),
+                    input_value,
+                })
+            })
+            .collect::<Result<Vec<_>, WalletError>>()?;
+
+        // Sign a copy of the transaction, and move the signatures from its witnesses to the PSBT.
+        let mut tx = psbt.unsigned_tx.clone();
+        self.sign_transaction(&mut tx, spend_infos.iter().cloned())?;
+        for ((input, signed_input), spend_info) in
+            psbt.inputs.iter_mut().zip(tx.input).zip(spend_infos)
+        {
+            let witness = signed_input.witness;
+            let malformed = || WalletError::Protocol("Unexpected signed witness".to_string());
+            if let UTXOSpendInfo::SwapCoin {
+                multisig_redeemscript,
+            } = spend_info
+            {
+                let (pubkey1, pubkey2) =
+                    read_pubkeys_from_multisig_redeemscript(&multisig_redeemscript)?;
+                for (pubkey, sig) in [(pubkey1, witness.nth(1)), (pubkey2, witness.nth(2))] {
+                    let sig = ecdsa::Signature::from_slice(sig.ok_or_else(malformed)?)
+                        .map_err(|_| malformed())?;
+                    input.partial_sigs.insert(pubkey, sig);
+                }
+            } else {
+                let sig = ecdsa::Signature::from_slice(witness.nth(0).ok_or_else(malformed)?)
+                    .map_err(|_| malformed())?;
+                let pubkey = PublicKey::from_slice(witness.nth(1).ok_or_else(malformed)?)
+                    .map_err(|_| malformed())?;
+                input.partial_sigs.insert(pubkey, sig);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::{
+        absolute::LockTime, ecdsa, secp256k1::Message, sighash::SighashCache, transaction::Version,
+        Amount, EcdsaSighashType, Sequence, TxIn, TxOut,
+    };
+
+    fn multisig_psbt(pubkey1: &PublicKey, pubkey2: &PublicKey) -> Psbt {
+        let witness_script = create_multisig_redeemscript(pubkey1, pubkey2);
+        let tx = Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: OutPoint::null(),
+                script_sig: ScriptBuf::new(),
+                sequence: Sequence::MAX,
+                witness: Witness::new(),
+            }],
+            output: vec![TxOut {
+                value: Amount::from_sat(90_000),
+                script_pubkey: redeemscript_to_scriptpubkey(&witness_script),
+            }],
+        };
+        let mut psbt = Psbt::from_unsigned_tx(tx).unwrap();
+        psbt.inputs[0].witness_utxo = Some(TxOut {
+            value: Amount::from_sat(100_000),
+            script_pubkey: redeemscript_to_scriptpubkey(&witness_script),
+        });
+        psbt.inputs[0].witness_script = Some(witness_script);
+        psbt
+    }
+
+    fn sign(psbt: &mut Psbt, privkey: &SecretKey) {
+        let secp = Secp256k1::new();
+        let input = &psbt.inputs[0];
+        let sighash = SighashCache::new(&psbt.unsigned_tx)
+            .p2wsh_signature_hash(
+                0,
+                input.witness_script.as_ref().unwrap(),
+                input.witness_utxo.as_ref().unwrap().value,
+                EcdsaSighashType::All,
+            )
+            .unwrap();
+        let signature = ecdsa::Signature::sighash_all(
+            secp.sign_ecdsa(&Message::from_digest_slice(&sighash[..]).unwrap(), privkey),
+        );
+        psbt.inputs[0]
+            .partial_sigs
+            .insert(PublicKey::new(privkey.public_key(&secp)), signature);
+    }
+
+    #[test]
+    fn test_combine_and_finalize_psbts() {
+        let secp = Secp256k1::new();
+        let privkey1 = SecretKey::from_slice(&[1u8; 32]).unwrap();
+        let privkey2 = SecretKey::from_slice(&[2u8; 32]).unwrap();
+        let pubkey1 = PublicKey::new(privkey1.public_key(&secp));
+        let pubkey2 = PublicKey::new(privkey2.public_key(&secp));
+
+        // Each party signs its own copy.
+        let mut psbt1 = multisig_psbt(&pubkey1, &pubkey2);
+        let mut psbt2 = psbt1.clone();
+        sign(&mut psbt1, &privkey1);
+        sign(&mut psbt2, &privkey2);
+
+        // A single signature isn't enough.
+        assert!(Wallet::finalize_psbt(psbt1.clone()).is_err());
+
+        let combined = Wallet::combine_psbts(vec![psbt1, psbt2]).unwrap();
+        assert_eq!(combined.inputs[0].partial_sigs.len(), 2);
+
+        let tx = Wallet::finalize_psbt(combined).unwrap();
+        let witness = &tx.input[0].witness;
+        assert_eq!(witness.len(), 4);
+        assert!(witness.nth(0).unwrap().is_empty());
+        assert_eq!(
+            witness.nth(3).unwrap(),
+            create_multisig_redeemscript(&pubkey1, &pubkey2).as_bytes()
+        );
+    }
+
+    #[test]
+    fn test_combine_mismatched_psbts() {
+        let secp = Secp256k1::new();
+        let pubkey1 = PublicKey::new(SecretKey::from_slice(&[1u8; 32]).unwrap().public_key(&secp));
+        let pubkey2 = PublicKey::new(SecretKey::from_slice(&[2u8; 32]).unwrap().public_key(&secp));
+        let pubkey3 = PublicKey::new(SecretKey::from_slice(&[3u8; 32]).unwrap().public_key(&secp));
+
+        let psbt1 = multisig_psbt(&pubkey1, &pubkey2);
+        let psbt2 = multisig_psbt(&pubkey1, &pubkey3);
+        assert!(matches!(
+            Wallet::combine_psbts(vec![psbt1, psbt2]),
+            Err(WalletError::Psbt(_))
+        ));
+        assert!(Wallet::combine_psbts(Vec::new()).is_err());
+    }
+}
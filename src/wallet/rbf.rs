@@ -0,0 +1,144 @@
+//! Replace-by-fee fee bumping.
+//!
+//! A replaceable transaction stuck in the mempool can be replaced by a conflicting one paying a higher
+//! fee. The replacement spends the same inputs to the same outputs, taking the extra fee from the
+//! change. It must pay more than the original, by at least the relay fee of its own size (BIP125).
+
+use bitcoin::{Address, Amount, ScriptBuf, Transaction, Txid, Witness};
+use bitcoind::bitcoincore_rpc::RpcApi;
+
+use super::{
+    api::UTXOSpendInfo,
+    direct_send::{estimate_vsize, placeholder_witness},
+    error::WalletError,
+    Wallet,
+};
+
+/// The minimum fee rate increase of a replacement, in sats/vbyte. Core's default incremental relay fee.
+const INCREMENTAL_RELAY_FEE_RATE: u64 = 1;
+
+/// The fee of a replacement of `vsize` vbytes, for a target of `fee_rate` sats/vbyte.
+///
+/// The replacement pays at least the fee of the original, plus the incremental relay fee over its size.
+fn replacement_fee(original_fee: Amount, vsize: u64, fee_rate: u64) -> Amount {
+    std::cmp::max(
+        Amount::from_sat(vsize * fee_rate),
+        original_fee + Amount::from_sat(vsize * INCREMENTAL_RELAY_FEE_RATE),
+    )
+}
+
+impl Wallet {
+    /// Create a replacement of the mempool transaction `txid`, paying `fee_rate` sats/vbyte.
+    ///
+    /// The replacement keeps the inputs and outputs of the original, and reduces its change output by
+    /// the extra fee. Only transactions of seed coins, created with the `enable_rbf` option of
+    /// [Wallet::spend_from_wallet_with], can be bumped. It's returned signed, and isn't broadcast. Its
+    /// change is remembered too, so it can be bumped again.
+    ///
+    /// Errors with [WalletError::ParentNotInMempool] if the transaction isn't unconfirmed in the mempool,
+    /// with [WalletError::NotReplaceable] if it doesn't signal replaceability, and with
+    /// [WalletError::NoFeeBumpOutput] if the wallet didn't create it with a change to take the fee from.
+    pub fn bump_fee(&mut self, txid: Txid, fee_rate: u64) -> Result<Transaction, WalletError> {
+        let entry = self
+            .rpc
+            .get_mempool_entry(&txid)
+            .map_err(|_| WalletError::ParentNotInMempool(txid))?;
+        let mut tx = self.rpc.get_raw_transaction(&txid, None)?;
+        if !tx.is_explicitly_rbf() {
+            return Err(WalletError::NotReplaceable(txid));
+        }
+
+        let network = self.store.network;
+        let spend_infos = tx
+            .input
+            .iter()
+            .map(|txin| {
+                let prevout = txin.previous_output;
+                let prev_tx = self
+                    .rpc
+                    .get_transaction(&prevout.txid, None)?
+                    .transaction()
+                    .map_err(|e| WalletError::Protocol(e.to_string()))?;
+                let txout = prev_tx
+                    .output
+                    .get(prevout.vout as usize)
+                    .ok_or(WalletError::Protocol("Input not found".to_string()))?;
+                let address = Address::from_script(&txout.script_pubkey, network)
+                    .map_err(|e| WalletError::Protocol(e.to_string()))?;
+                match self.get_address_info(&address)? {
+                    Some(info) => Ok(UTXOSpendInfo::SeedCoin {
+                        path: format!("m/{}/{}", info.keychain.index_num(), info.index),
+                        input_value: txout.value,
+                    }),
+                    None => Err(WalletError::Protocol(format!(
+                        "Only seed coin spends can be bumped, {} is not one",
+                        prevout
+                    ))),
+                }
+            })
+            .collect::<Result<Vec<_>, WalletError>>()?;
+
+        // The destination could be a wallet address too, only the recorded change pays the fee.
+        let change_index = *self
+            .store
+            .rbf_change_outputs
+            .get(&txid)
+            .filter(|index| (**index as usize) < tx.output.len())
+            .ok_or(WalletError::NoFeeBumpOutput(txid))?;
+
+        let witnesses = spend_infos
+            .iter()
+            .map(|spend_info| placeholder_witness(spend_info, &ScriptBuf::new()))
+            .collect();
+        let vsize = estimate_vsize(witnesses, tx.output.clone());
+        let fee = replacement_fee(entry.fees.base, vsize, fee_rate);
+        let extra_fee = fee - entry.fees.base;
+
+        let change = &mut tx.output[change_index as usize];
+        let dust = change.script_pubkey.minimal_non_dust();
+        change.value = change
+            .value
+            .checked_sub(extra_fee)
+            .filter(|value| *value >= dust)
+            .ok_or(WalletError::InsufficientFunds {
+                available: change.value,
+                required: extra_fee + dust,
+            })?;
+
+        log::info!(
+            "Replacing {} with a fee of {}, for a fee rate of {} sats/vbyte",
+            txid,
+            fee,
+            fee_rate
+        );
+        for txin in tx.input.iter_mut() {
+            txin.witness = Witness::new();
+        }
+        self.sign_transaction(&mut tx, spend_infos.into_iter())?;
+
+        self.store
+            .rbf_change_outputs
+            .insert(tx.compute_txid(), change_index);
+        self.save_to_disk()?;
+        Ok(tx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_replacement_fee() {
+        // The fee rate target wins.
+        assert_eq!(
+            replacement_fee(Amount::from_sat(200), 140, 10),
+            Amount::from_sat(1400)
+        );
+        // A target barely above the original still pays the incremental relay fee.
+        assert_eq!(
+            replacement_fee(Amount::from_sat(1400), 140, 10),
+            Amount::from_sat(1540)
+        );
+    }
+}
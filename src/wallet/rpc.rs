@@ -1,6 +1,6 @@
 //! Manages connection with a Bitcoin Core RPC.
 //!
-use std::{convert::TryFrom, thread, time::Duration};
+use std::{convert::TryFrom, path::Path, thread, time::Duration};
 
 use bitcoin::Network;
 use bitcoind::bitcoincore_rpc::{Auth, Client, RpcApi};
@@ -73,10 +73,52 @@ fn list_wallet_dir(client: &Client) -> Result<Vec<String>, WalletError> {
     Ok(result.wallets.into_iter().map(|n| n.name).collect())
 }
 
+/// Number of import attempts for missing descriptors, before giving up with [WalletError::DescriptorNotImported].
+const DESCRIPTOR_IMPORT_ATTEMPTS: u32 = 3;
+
 impl Wallet {
-    /// Sync the wallet with the configured Bitcoin Core RPC. Save data to disk.
-    pub fn sync(&mut self) -> Result<(), WalletError> {
-        // Create or load the watch-only bitcoin core wallet
+    /// Names of the wallets currently loaded in the bitcoin core node.
+    pub fn list_loaded_wallets(rpc_config: &RPCConfig) -> Result<Vec<String>, WalletError> {
+        Ok(Client::try_from(rpc_config)?.list_wallets()?)
+    }
+
+    /// Load the wallet file at `path`, with its RPC calls scoped to the core wallet `name`.
+    ///
+    /// Several wallets can run against the same node this way, each seeing only its own coins and
+    /// addresses. The core wallet is loaded if it's on the node's disk but not loaded yet. Errors with
+    /// [WalletError::CoreWalletNotFound] if the node doesn't have it at all. See [Wallet::load] for the
+    /// `encryption_passphrase`.
+    pub fn load_by_name(
+        path: &Path,
+        rpc_config: &RPCConfig,
+        name: &str,
+        encryption_passphrase: Option<&str>,
+    ) -> Result<Wallet, WalletError> {
+        let rpc_config = RPCConfig {
+            wallet_name: name.to_string(),
+            ..rpc_config.clone()
+        };
+        let wallet = Self::load(&rpc_config, path, encryption_passphrase)?;
+        if !wallet
+            .rpc
+            .list_wallets()?
+            .iter()
+            .any(|loaded| loaded == name)
+        {
+            if !list_wallet_dir(&wallet.rpc)?
+                .iter()
+                .any(|on_disk| on_disk == name)
+            {
+                return Err(WalletError::CoreWalletNotFound(name.to_string()));
+            }
+            wallet.rpc.load_wallet(name)?;
+            log::info!("wallet loaded: {}", name);
+        }
+        Ok(wallet)
+    }
+
+    /// Create or load the watch-only bitcoin core wallet.
+    fn create_or_load_core_wallet(&self) -> Result<(), WalletError> {
         let wallet_name = &self.store.file_name;
         if self.rpc.list_wallets()?.contains(wallet_name) {
             log::info!("wallet already loaded: {}", wallet_name);
@@ -103,6 +145,41 @@ impl Wallet {
 
             log::info!("wallet created: {}", wallet_name);
         }
+        Ok(())
+    }
+
+    /// Makes sure the core wallet is loaded and all the HD descriptors of this wallet are imported in it.
+    ///
+    /// Missing descriptors are imported and checked again, for upto [DESCRIPTOR_IMPORT_ATTEMPTS] times.
+    /// Errors with [WalletError::DescriptorNotImported] if they are still missing after that.
+    pub fn ensure_descriptors_imported(&self) -> Result<(), WalletError> {
+        self.create_or_load_core_wallet()?;
+
+        for attempt in 1..=DESCRIPTOR_IMPORT_ATTEMPTS {
+            let unimported = self.get_unimported_wallet_desc()?;
+            if unimported.is_empty() {
+                return Ok(());
+            }
+            log::warn!(
+                "{} wallet descriptors not imported in core wallet, importing. Attempt {}/{}",
+                unimported.len(),
+                attempt,
+                DESCRIPTOR_IMPORT_ATTEMPTS
+            );
+            self.import_descriptors(&unimported, None)?;
+        }
+
+        let unimported = self.get_unimported_wallet_desc()?;
+        if unimported.is_empty() {
+            Ok(())
+        } else {
+            Err(WalletError::DescriptorNotImported(unimported))
+        }
+    }
+
+    /// Sync the wallet with the configured Bitcoin Core RPC. Save data to disk.
+    pub fn sync(&mut self) -> Result<(), WalletError> {
+        self.create_or_load_core_wallet()?;
 
         let descriptors_to_import = self.descriptors_to_import()?;
 
@@ -148,6 +225,8 @@ impl Wallet {
             }
         }
 
+        self.mark_spent_fidelity_bonds()?;
+
         let max_external_index = self.find_hd_next_index(KeychainKind::External)?;
         self.update_external_index(max_external_index)?;
         Ok(())
@@ -178,10 +257,7 @@ impl Wallet {
                 })
             })
             .collect();
-        let _res: Vec<Value> = self
-            .rpc
-            .call("importdescriptors", &[import_requests])
-            .unwrap();
+        let _res: Vec<Value> = self.rpc.call("importdescriptors", &[import_requests])?;
         Ok(())
     }
 }
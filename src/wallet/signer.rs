@@ -0,0 +1,754 @@
+//! Signing interface of the wallet.
+//!
+//! All the private key operations of the wallet go through a [RemoteSigner], along with the lookup of
+//! the public keys. That is the keys derived from the wallet seed, listed by [SignerKey]: the descriptor
+//! coins, the fidelity bonds, the swap keys of the swapcoins (multisig, hashlock and timelock), and the
+//! maker's tweakable key the swap keys are derived from with the Taker's nonces. The swap multisig keys
+//! handed over to the counterparty at the end of a swap are asked to the signer too.
+//!
+//! The signer is given what it signs, not a digest: the transaction, the outputs its inputs spend, and
+//! the witness script of the input, or the text of the message. It computes the digest itself, checks
+//! the spent output is locked to the signing key, and can refuse anything it doesn't like, e.g a
+//! transaction paying too much fee or to an unknown address.
+//!
+//! The wallet file of a wallet using a remote signer holds no private key, only [WalletPubkeys]. The
+//! counterparty's multisig keys received in swaps are the only private keys the wallet holds itself.
+//!
+//! # Out-of-process signer protocol
+//!
+//! Messages are exchanged as newline delimited JSON, over any byte stream (a file, a pipe, a serial link).
+//!  - The online side writes one [SignerRequest] per line, and waits for the answer before sending the next one.
+//!  - The signer answers each request with exactly one [SignResponse] line, in the same order.
+//!  - The signer is free to refuse any request it doesn't like, by answering [SignResponse::Rejected].
+//!
+//! The only private keys the signer ever sends back are the swap keys of [SignerRequest::HandoverKey].
+//! [StreamSigner] implements the online side of the protocol and [serve_sign_requests] the signer side,
+//! on top of any [RemoteSigner].
+
+use std::{
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::Path,
+    str::FromStr,
+    sync::Mutex,
+};
+
+use bip39::Mnemonic;
+use bitcoin::{
+    bip32::{ChildNumber, DerivationPath, Fingerprint, Xpriv, Xpub},
+    ecdsa,
+    hashes::{sha256d, Hash},
+    script::Instruction,
+    secp256k1::{self, ecdsa::Signature, Message, Secp256k1, SecretKey},
+    sighash::{EcdsaSighashType, SighashCache},
+    Network, PublicKey, Script, ScriptBuf, Transaction, TxOut,
+};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    api::HARDENDED_DERIVATION, fidelity::FIDELITY_DERIVATION_PATH, WalletError, WalletStore,
+};
+use crate::protocol::error::ContractError;
+
+/// The swap keys derive from this path, at hardened indexes: a handed over key reveals nothing of
+/// the others.
+pub(super) const SWAP_DERIVATION_PATH: &str = "m/84'/0'/0'/3'";
+
+/// Identifies a key of the signer.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SignerKey {
+    /// A descriptor wallet key, at the given path from [`HARDENDED_DERIVATION`].
+    SeedCoin { path: String },
+    /// The fidelity bond key at the given index.
+    FidelityBond { index: u32 },
+    /// A swap key at the given index, see [Wallet::next_swap_key](super::Wallet::next_swap_key).
+    Swap { index: u32 },
+    /// The tweakable key, whose public key is the maker's tweakable point.
+    Tweakable,
+    /// The tweakable key, tweaked by the nonce the Taker chose for a swap key.
+    Tweaked { nonce: SecretKey },
+    /// A key given along with the request, for the swapcoins imported with their keys.
+    Imported { privkey: SecretKey },
+}
+
+/// What the signer is asked to sign. The signatures commit to the whole transaction (`SIGHASH_ALL`).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SignPayload {
+    /// A segwit v0 input of `tx`.
+    Input {
+        tx: Transaction,
+        input_index: usize,
+        /// The outputs spent by all the inputs of `tx`, in order.
+        prevouts: Vec<TxOut>,
+        /// The witness script of a P2WSH input, None for a P2WPKH input of the key.
+        witness_script: Option<ScriptBuf>,
+    },
+    /// A text message, signed with the Bitcoin signed message format.
+    Message { message: String },
+}
+
+/// The hash of a text message in the Bitcoin signed message format.
+pub(crate) fn signed_message_hash(message: &str) -> sha256d::Hash {
+    let mut btc_signed_msg = Vec::<u8>::new();
+    btc_signed_msg.extend("\x18Bitcoin Signed Message:\n".as_bytes());
+    btc_signed_msg.push(message.len() as u8);
+    btc_signed_msg.extend(message.as_bytes());
+    sha256d::Hash::hash(&btc_signed_msg)
+}
+
+impl SignPayload {
+    /// The digest signed by `pubkey`.
+    ///
+    /// Errors if the spent output isn't locked to `pubkey`, i.e it isn't its P2WPKH output, or the
+    /// witness script doesn't hold it.
+    pub fn digest(&self, pubkey: &PublicKey) -> Result<Message, WalletError> {
+        let (tx, input_index, prevouts, witness_script) = match self {
+            SignPayload::Input {
+                tx,
+                input_index,
+                prevouts,
+                witness_script,
+            } => (tx, *input_index, prevouts, witness_script),
+            SignPayload::Message { message } => {
+                return Ok(Message::from_digest(
+                    signed_message_hash(message).to_byte_array(),
+                ))
+            }
+        };
+        let prevout = match prevouts.get(input_index) {
+            Some(prevout) if prevouts.len() == tx.input.len() => prevout,
+            _ => {
+                return Err(WalletError::Protocol(
+                    "Missing the outputs spent by the transaction".to_string(),
+                ))
+            }
+        };
+        let not_locked_to_key =
+            || WalletError::Protocol("The spent output isn't locked to the key".to_string());
+        let mut cache = SighashCache::new(tx);
+        let sighash = match witness_script {
+            Some(witness_script) => {
+                if prevout.script_pubkey != ScriptBuf::new_p2wsh(&witness_script.wscript_hash())
+                    || !script_has_pubkey(witness_script, pubkey)
+                {
+                    return Err(not_locked_to_key());
+                }
+                cache
+                    .p2wsh_signature_hash(
+                        input_index,
+                        witness_script,
+                        prevout.value,
+                        EcdsaSighashType::All,
+                    )
+                    .map_err(ContractError::Sighash)?
+            }
+            None => {
+                let script_pubkey =
+                    ScriptBuf::new_p2wpkh(&pubkey.wpubkey_hash().map_err(|_| not_locked_to_key())?);
+                if prevout.script_pubkey != script_pubkey {
+                    return Err(not_locked_to_key());
+                }
+                cache
+                    .p2wpkh_signature_hash(
+                        input_index,
+                        &script_pubkey,
+                        prevout.value,
+                        EcdsaSighashType::All,
+                    )
+                    .map_err(|e| WalletError::Protocol(e.to_string()))?
+            }
+        };
+        Ok(Message::from_digest(sighash.to_byte_array()))
+    }
+}
+
+/// Whether `pubkey` is pushed by `script`.
+fn script_has_pubkey(script: &Script, pubkey: &PublicKey) -> bool {
+    let pubkey = pubkey.to_bytes();
+    script.instructions().any(|instruction| {
+        matches!(instruction, Ok(Instruction::PushBytes(bytes)) if bytes.as_bytes() == pubkey)
+    })
+}
+
+/// Ask the signer to sign a [SignPayload] with one of its keys.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignRequest {
+    pub key: SignerKey,
+    pub payload: SignPayload,
+}
+
+/// The public keys of the wallet, enough to watch it and derive the addresses of the descriptor coins.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WalletPubkeys {
+    /// The fingerprint of the master key.
+    pub master_fingerprint: Fingerprint,
+    /// The account xpub at [`HARDENDED_DERIVATION`].
+    pub account_xpub: Xpub,
+    /// The public key of [SignerKey::Tweakable].
+    pub tweakable_point: PublicKey,
+}
+
+/// A message of the out-of-process signer protocol, from the online side to the signer.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SignerRequest {
+    /// Ask for the public key of one of the signer's keys.
+    PublicKey(SignerKey),
+    /// Ask for the [WalletPubkeys].
+    WalletPubkeys,
+    /// Ask for a signature.
+    Sign(SignRequest),
+    /// Ask for the private key of a swap key, to hand it over to the counterparty.
+    HandoverKey(SignerKey),
+}
+
+/// The signer's answer to a [SignerRequest].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SignResponse {
+    /// The signature, along with the public key it verifies against.
+    Signed {
+        pubkey: PublicKey,
+        signature: Signature,
+    },
+    /// The public key asked for.
+    PublicKey { pubkey: PublicKey },
+    /// The wallet public keys.
+    WalletPubkeys { pubkeys: WalletPubkeys },
+    /// The handed over private key.
+    PrivateKey { privkey: SecretKey },
+    /// The signer refused the request.
+    Rejected { reason: String },
+}
+
+impl SignResponse {
+    /// The error for an answer that isn't the expected one.
+    fn unexpected(self, request: &str) -> WalletError {
+        match self {
+            SignResponse::Rejected { reason } => WalletError::SignerRejected(reason),
+            _ => WalletError::Protocol(format!(
+                "Signer sent an unexpected answer to a {} request",
+                request
+            )),
+        }
+    }
+
+    /// Extract the signature, checking it is valid for `payload`.
+    pub(crate) fn into_signature(
+        self,
+        payload: &SignPayload,
+    ) -> Result<(PublicKey, Signature), WalletError> {
+        match self {
+            SignResponse::Signed { pubkey, signature } => {
+                Secp256k1::verification_only().verify_ecdsa(
+                    &payload.digest(&pubkey)?,
+                    &signature,
+                    &pubkey.inner,
+                )?;
+                Ok((pubkey, signature))
+            }
+            other => Err(other.unexpected("signature")),
+        }
+    }
+
+    /// Extract the public key.
+    pub(crate) fn into_public_key(self) -> Result<PublicKey, WalletError> {
+        match self {
+            SignResponse::PublicKey { pubkey } => Ok(pubkey),
+            other => Err(other.unexpected("public key")),
+        }
+    }
+
+    /// Extract the wallet public keys.
+    pub(crate) fn into_wallet_pubkeys(self) -> Result<WalletPubkeys, WalletError> {
+        match self {
+            SignResponse::WalletPubkeys { pubkeys } => Ok(pubkeys),
+            other => Err(other.unexpected("wallet public keys")),
+        }
+    }
+
+    /// Extract the private key.
+    pub(crate) fn into_private_key(self) -> Result<SecretKey, WalletError> {
+        match self {
+            SignResponse::PrivateKey { privkey } => Ok(privkey),
+            other => Err(other.unexpected("key handover")),
+        }
+    }
+}
+
+/// Performs all the private key operations of the wallet.
+pub trait RemoteSigner {
+    /// The public key of one of the signer's keys.
+    fn public_key(&self, key: &SignerKey) -> Result<PublicKey, WalletError>;
+
+    /// The public keys of the wallet.
+    fn wallet_pubkeys(&self) -> Result<WalletPubkeys, WalletError>;
+
+    /// Sign the requested payload.
+    fn sign(&self, request: &SignRequest) -> Result<SignResponse, WalletError>;
+
+    /// The private key of a swap key, handed over to the counterparty at the end of a swap.
+    fn handover_key(&self, key: &SignerKey) -> Result<SecretKey, WalletError>;
+}
+
+/// Ask `signer` to sign `payload` with `key`, whose public key is `pubkey`.
+///
+/// The signature is checked against `pubkey`, and returned with `SIGHASH_ALL`.
+pub(crate) fn request_signature(
+    signer: &dyn RemoteSigner,
+    key: &SignerKey,
+    pubkey: &PublicKey,
+    payload: SignPayload,
+) -> Result<ecdsa::Signature, WalletError> {
+    let request = SignRequest {
+        key: key.clone(),
+        payload,
+    };
+    let (signed_pubkey, signature) = signer.sign(&request)?.into_signature(&request.payload)?;
+    if signed_pubkey != *pubkey {
+        return Err(WalletError::Protocol(
+            "Signer changed the signing key".to_string(),
+        ));
+    }
+    Ok(ecdsa::Signature::sighash_all(signature))
+}
+
+/// The default in-process signer, holding the wallet master key.
+pub struct LocalSigner {
+    master_key: Xpriv,
+}
+
+impl LocalSigner {
+    pub fn new(master_key: Xpriv) -> Self {
+        Self { master_key }
+    }
+
+    /// The signer of the wallet file at `path`.
+    ///
+    /// Creates the file with a new seed if it doesn't exist. Errors if the file holds no master key,
+    /// i.e it's the file of a wallet using a remote signer. The file is encrypted with
+    /// `encryption_passphrase` if any, see [WalletStore::init].
+    pub fn from_wallet_file(
+        path: &Path,
+        network: Network,
+        encryption_passphrase: Option<&str>,
+    ) -> Result<Self, WalletError> {
+        let store = if path.exists() {
+            WalletStore::read(path, encryption_passphrase)?
+        } else {
+            let file_name = path
+                .file_name()
+                .expect("file name expected")
+                .to_string_lossy()
+                .to_string();
+            let mnemonic = Mnemonic::generate(12)?;
+            WalletStore::init(
+                file_name,
+                path,
+                network,
+                mnemonic.to_string(),
+                "".to_string(),
+                None,
+                encryption_passphrase,
+            )?
+        };
+        let master_key = store.master_key.ok_or(WalletError::Protocol(
+            "The wallet file holds no master key".to_string(),
+        ))?;
+        Ok(Self::new(master_key))
+    }
+
+    fn tweakable_privkey(&self) -> Result<SecretKey, WalletError> {
+        Ok(self
+            .master_key
+            .derive_priv(&Secp256k1::new(), &[ChildNumber::from_hardened_idx(0)?])?
+            .private_key)
+    }
+
+    fn derive_privkey(&self, key: &SignerKey) -> Result<SecretKey, WalletError> {
+        let secp = Secp256k1::new();
+        let path = match key {
+            SignerKey::SeedCoin { path } => DerivationPath::from_str(HARDENDED_DERIVATION)?
+                .extend(DerivationPath::from_str(path)?),
+            SignerKey::FidelityBond { index } => {
+                DerivationPath::from_str(FIDELITY_DERIVATION_PATH)?
+                    .child(ChildNumber::Normal { index: *index })
+            }
+            SignerKey::Swap { index } => DerivationPath::from_str(SWAP_DERIVATION_PATH)?
+                .child(ChildNumber::from_hardened_idx(*index)?),
+            SignerKey::Tweakable => return self.tweakable_privkey(),
+            SignerKey::Tweaked { nonce } => {
+                return Ok(self.tweakable_privkey()?.add_tweak(&(*nonce).into())?)
+            }
+            SignerKey::Imported { privkey } => return Ok(*privkey),
+        };
+        Ok(self.master_key.derive_priv(&secp, &path)?.private_key)
+    }
+}
+
+impl RemoteSigner for LocalSigner {
+    fn public_key(&self, key: &SignerKey) -> Result<PublicKey, WalletError> {
+        let secp = Secp256k1::new();
+        Ok(PublicKey {
+            compressed: true,
+            inner: secp256k1::PublicKey::from_secret_key(&secp, &self.derive_privkey(key)?),
+        })
+    }
+
+    fn wallet_pubkeys(&self) -> Result<WalletPubkeys, WalletError> {
+        let secp = Secp256k1::new();
+        let account_key = self
+            .master_key
+            .derive_priv(&secp, &DerivationPath::from_str(HARDENDED_DERIVATION)?)?;
+        Ok(WalletPubkeys {
+            master_fingerprint: self.master_key.fingerprint(&secp),
+            account_xpub: Xpub::from_priv(&secp, &account_key),
+            tweakable_point: self.public_key(&SignerKey::Tweakable)?,
+        })
+    }
+
+    fn sign(&self, request: &SignRequest) -> Result<SignResponse, WalletError> {
+        let secp = Secp256k1::new();
+        let pubkey = self.public_key(&request.key)?;
+        let message = match request.payload.digest(&pubkey) {
+            Ok(message) => message,
+            Err(e) => {
+                return Ok(SignResponse::Rejected {
+                    reason: format!("{:?}", e),
+                })
+            }
+        };
+        let privkey = self.derive_privkey(&request.key)?;
+        let signature = match &request.key {
+            //use low-R value signatures for privacy
+            //https://en.bitcoin.it/wiki/Privacy#Wallet_fingerprinting
+            SignerKey::SeedCoin { .. } => secp.sign_ecdsa_low_r(&message, &privkey),
+            _ => secp.sign_ecdsa(&message, &privkey),
+        };
+        Ok(SignResponse::Signed { pubkey, signature })
+    }
+
+    fn handover_key(&self, key: &SignerKey) -> Result<SecretKey, WalletError> {
+        match key {
+            SignerKey::Swap { .. } | SignerKey::Imported { .. } => self.derive_privkey(key),
+            _ => Err(WalletError::SignerRejected(format!(
+                "Only swap keys are handed over, not {:?}",
+                key
+            ))),
+        }
+    }
+}
+
+/// The online side of an out-of-process signer. Writes [SignerRequest]s to `writer` and reads the
+/// [SignResponse]s from `reader`.
+pub struct StreamSigner<R: BufRead, W: Write> {
+    stream: Mutex<(R, W)>,
+}
+
+impl<R: BufRead, W: Write> StreamSigner<R, W> {
+    pub fn new(reader: R, writer: W) -> Self {
+        Self {
+            stream: Mutex::new((reader, writer)),
+        }
+    }
+}
+
+impl StreamSigner<BufReader<File>, File> {
+    /// A signer writing its requests to the file at `requests`, and reading the responses from the
+    /// file at `responses`, e.g two named pipes to the signer. `requests` is opened first.
+    pub fn open(requests: &Path, responses: &Path) -> Result<Self, WalletError> {
+        let writer = OpenOptions::new().write(true).open(requests)?;
+        let reader = BufReader::new(File::open(responses)?);
+        Ok(Self::new(reader, writer))
+    }
+}
+
+impl<R: BufRead, W: Write> StreamSigner<R, W> {
+    fn request(&self, request: &SignerRequest) -> Result<SignResponse, WalletError> {
+        let mut stream = self
+            .stream
+            .lock()
+            .map_err(|_| WalletError::Protocol("Signer stream poisoned".to_string()))?;
+        let (reader, writer) = &mut *stream;
+
+        writeln!(writer, "{}", serde_json::to_string(request)?)?;
+        writer.flush()?;
+
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(WalletError::Protocol(
+                "Signer closed the stream".to_string(),
+            ));
+        }
+        Ok(serde_json::from_str(&line)?)
+    }
+}
+
+impl<R: BufRead, W: Write> RemoteSigner for StreamSigner<R, W> {
+    fn public_key(&self, key: &SignerKey) -> Result<PublicKey, WalletError> {
+        self.request(&SignerRequest::PublicKey(key.clone()))?
+            .into_public_key()
+    }
+
+    fn wallet_pubkeys(&self) -> Result<WalletPubkeys, WalletError> {
+        self.request(&SignerRequest::WalletPubkeys)?
+            .into_wallet_pubkeys()
+    }
+
+    fn sign(&self, request: &SignRequest) -> Result<SignResponse, WalletError> {
+        self.request(&SignerRequest::Sign(request.clone()))
+    }
+
+    fn handover_key(&self, key: &SignerKey) -> Result<SecretKey, WalletError> {
+        self.request(&SignerRequest::HandoverKey(key.clone()))?
+            .into_private_key()
+    }
+}
+
+/// The signer side of the out-of-process protocol. Answers every [SignerRequest] read from `reader`
+/// with `signer`, until the stream is closed.
+pub fn serve_sign_requests<S: RemoteSigner, R: BufRead, W: Write>(
+    signer: &S,
+    reader: R,
+    mut writer: W,
+) -> Result<(), WalletError> {
+    for line in reader.lines() {
+        let response = match serde_json::from_str::<SignerRequest>(&line?)? {
+            SignerRequest::PublicKey(key) => signer
+                .public_key(&key)
+                .map(|pubkey| SignResponse::PublicKey { pubkey }),
+            SignerRequest::WalletPubkeys => signer
+                .wallet_pubkeys()
+                .map(|pubkeys| SignResponse::WalletPubkeys { pubkeys }),
+            SignerRequest::Sign(request) => signer.sign(&request),
+            SignerRequest::HandoverKey(key) => signer
+                .handover_key(&key)
+                .map(|privkey| SignResponse::PrivateKey { privkey }),
+        }
+        .unwrap_or_else(|e| SignResponse::Rejected {
+            reason: format!("{:?}", e),
+        });
+        writeln!(writer, "{}", serde_json::to_string(&response)?)?;
+        writer.flush()?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::{
+        absolute::LockTime, transaction::Version, Amount, OutPoint, Sequence, TxIn, Witness,
+    };
+    use std::io::Cursor;
+
+    fn test_signer() -> LocalSigner {
+        LocalSigner::new(Xpriv::new_master(Network::Regtest, &[7u8; 32]).unwrap())
+    }
+
+    /// A transaction spending a P2WPKH output of `pubkey`.
+    fn p2wpkh_spend(pubkey: &PublicKey) -> SignPayload {
+        let tx = Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: OutPoint::null(),
+                script_sig: ScriptBuf::new(),
+                sequence: Sequence::MAX,
+                witness: Witness::new(),
+            }],
+            output: vec![TxOut {
+                value: Amount::from_sat(9_000),
+                script_pubkey: ScriptBuf::new(),
+            }],
+        };
+        SignPayload::Input {
+            tx,
+            input_index: 0,
+            prevouts: vec![TxOut {
+                value: Amount::from_sat(10_000),
+                script_pubkey: ScriptBuf::new_p2wpkh(&pubkey.wpubkey_hash().unwrap()),
+            }],
+            witness_script: None,
+        }
+    }
+
+    #[test]
+    fn test_local_signer() {
+        let signer = test_signer();
+        let seed_key = SignerKey::SeedCoin {
+            path: "m/0/0".to_string(),
+        };
+        let seed_pubkey = signer.public_key(&seed_key).unwrap();
+        let seed_request = SignRequest {
+            key: seed_key.clone(),
+            payload: p2wpkh_spend(&seed_pubkey),
+        };
+        let fidelity_request = SignRequest {
+            key: SignerKey::FidelityBond { index: 0 },
+            payload: SignPayload::Message {
+                message: "fidelity-bond-cert".to_string(),
+            },
+        };
+
+        let (signed_pubkey, _) = signer
+            .sign(&seed_request)
+            .unwrap()
+            .into_signature(&seed_request.payload)
+            .unwrap();
+        assert_eq!(signed_pubkey, seed_pubkey);
+        let (fidelity_pubkey, _) = signer
+            .sign(&fidelity_request)
+            .unwrap()
+            .into_signature(&fidelity_request.payload)
+            .unwrap();
+        assert_ne!(seed_pubkey, fidelity_pubkey);
+        assert_eq!(
+            signer.public_key(&fidelity_request.key).unwrap(),
+            fidelity_pubkey
+        );
+
+        // A signature doesn't verify for another payload.
+        assert!(signer
+            .sign(&seed_request)
+            .unwrap()
+            .into_signature(&fidelity_request.payload)
+            .is_err());
+
+        // The signer refuses to sign an output that isn't locked to the key.
+        let other_key = SignerKey::SeedCoin {
+            path: "m/0/1".to_string(),
+        };
+        let response = signer
+            .sign(&SignRequest {
+                key: other_key,
+                payload: seed_request.payload.clone(),
+            })
+            .unwrap();
+        assert!(matches!(response, SignResponse::Rejected { .. }));
+    }
+
+    #[test]
+    fn test_swap_keys() {
+        let signer = test_signer();
+        let wallet_pubkeys = signer.wallet_pubkeys().unwrap();
+
+        // The tweaked keys are the tweakable point tweaked by the nonce.
+        let nonce = SecretKey::from_slice(&[3u8; 32]).unwrap();
+        let tweaked = signer.public_key(&SignerKey::Tweaked { nonce }).unwrap();
+        let secp = Secp256k1::new();
+        assert_eq!(
+            tweaked.inner,
+            wallet_pubkeys
+                .tweakable_point
+                .inner
+                .add_exp_tweak(&secp, &nonce.into())
+                .unwrap()
+        );
+
+        // Only the swap keys are handed over.
+        let swap_key = SignerKey::Swap { index: 4 };
+        let privkey = signer.handover_key(&swap_key).unwrap();
+        assert_eq!(
+            signer.public_key(&swap_key).unwrap().inner,
+            privkey.public_key(&secp)
+        );
+        assert_ne!(
+            signer.public_key(&SignerKey::Swap { index: 5 }).unwrap(),
+            signer.public_key(&swap_key).unwrap()
+        );
+        for key in [
+            SignerKey::SeedCoin {
+                path: "m/0/0".to_string(),
+            },
+            SignerKey::FidelityBond { index: 0 },
+            SignerKey::Tweakable,
+            SignerKey::Tweaked { nonce },
+        ] {
+            assert!(matches!(
+                signer.handover_key(&key),
+                Err(WalletError::SignerRejected(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_stream_protocol() {
+        let signer = test_signer();
+        let key = SignerKey::SeedCoin {
+            path: "m/1/3".to_string(),
+        };
+        let sign_request = SignRequest {
+            key: key.clone(),
+            payload: p2wpkh_spend(&signer.public_key(&key).unwrap()),
+        };
+        let bad_key = SignerKey::SeedCoin {
+            path: "not a path".to_string(),
+        };
+        let requests = [
+            SignerRequest::Sign(sign_request.clone()),
+            SignerRequest::PublicKey(sign_request.key.clone()),
+            SignerRequest::WalletPubkeys,
+            SignerRequest::HandoverKey(SignerKey::Swap { index: 0 }),
+            SignerRequest::Sign(SignRequest {
+                key: bad_key.clone(),
+                payload: sign_request.payload.clone(),
+            }),
+            SignerRequest::PublicKey(bad_key.clone()),
+            SignerRequest::HandoverKey(key.clone()),
+        ];
+
+        // The online side writes the requests.
+        let mut request_stream = Vec::new();
+        for request in requests.iter() {
+            writeln!(
+                request_stream,
+                "{}",
+                serde_json::to_string(request).unwrap()
+            )
+            .unwrap();
+        }
+
+        // The signer side answers them.
+        let mut response_stream = Vec::new();
+        serve_sign_requests(
+            &signer,
+            BufReader::new(Cursor::new(request_stream)),
+            &mut response_stream,
+        )
+        .unwrap();
+
+        // The online side reads them back in order.
+        let stream_signer =
+            StreamSigner::new(BufReader::new(Cursor::new(response_stream)), Vec::new());
+        assert_eq!(
+            stream_signer.sign(&sign_request).unwrap(),
+            signer.sign(&sign_request).unwrap()
+        );
+        assert_eq!(
+            stream_signer.public_key(&sign_request.key).unwrap(),
+            signer.public_key(&sign_request.key).unwrap()
+        );
+        assert_eq!(
+            stream_signer.wallet_pubkeys().unwrap(),
+            signer.wallet_pubkeys().unwrap()
+        );
+        assert_eq!(
+            stream_signer
+                .handover_key(&SignerKey::Swap { index: 0 })
+                .unwrap(),
+            signer.handover_key(&SignerKey::Swap { index: 0 }).unwrap()
+        );
+        assert!(matches!(
+            stream_signer.sign(&sign_request).unwrap(),
+            SignResponse::Rejected { .. }
+        ));
+        assert!(matches!(
+            stream_signer.public_key(&bad_key),
+            Err(WalletError::SignerRejected(_))
+        ));
+        assert!(matches!(
+            stream_signer.handover_key(&key),
+            Err(WalletError::SignerRejected(_))
+        ));
+        assert!(stream_signer.sign(&sign_request).is_err());
+    }
+}
@@ -1,30 +1,148 @@
 //! The Wallet Storage Interface.
 //!
-//! Wallet data is currently written in unencrypted CBOR files which are not directly human readable.
+//! Wallet data is written in CBOR files which are not directly human readable. With an encryption
+//! passphrase, see [WalletStore::load_encrypted], the file is encrypted with ChaCha20-Poly1305 under an
+//! Argon2id derived key, and every later write keeps it encrypted.
+//!
+//! Writes go to a temporary file first, renamed over the wallet file once complete, so a crash never
+//! leaves a half-written wallet. Changes can be staged in memory with [PersistBackend::stage], and
+//! written all at once with [PersistBackend::commit].
 
-use std::{collections::HashMap, path::PathBuf};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    path::PathBuf,
+};
 
+use argon2::Argon2;
 use bip39::Mnemonic;
-use bitcoin::{bip32::Xpriv, Network, OutPoint, ScriptBuf};
+use bitcoin::{
+    bip32::Xpriv,
+    secp256k1::rand::{rngs::OsRng, RngCore},
+    Amount, Network, OutPoint, ScriptBuf, Txid,
+};
+use chacha20poly1305::{
+    aead::{Aead, KeyInit},
+    ChaCha20Poly1305, Key, Nonce,
+};
 use serde::{Deserialize, Serialize};
+
+use crate::protocol::{messages::Preimage, Hash160};
 use std::{
-    fs::OpenOptions,
-    io::{BufReader, BufWriter},
+    ffi::OsString,
+    fs::{self, OpenOptions},
+    io::{BufReader, Write},
+    path::Path,
+    sync::Mutex,
 };
 
-use super::{error::WalletError, fidelity::FidelityBond};
+use super::{error::WalletError, fidelity::FidelityBond, signer::WalletPubkeys, Wallet};
+
+use super::swapcoin::{IncomingSwapCoin, OutgoingSwapCoin, SwapCoin, WatchOnlySwapCoin};
+
+/// The state of a Taker's swap in progress, saved as the swap goes. A Taker that crashed mid-swap
+/// settles or refunds it, see [Taker::resume_swap](crate::taker::Taker::resume_swap).
+///
+/// The swapcoins themselves are in the [WalletStore], keyed by their multisig redeemscript.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SwapCheckpoint {
+    /// The hashvalue of the swap's preimage.
+    pub hashvalue: Hash160,
+    /// Multisig redeemscripts of the outgoing swapcoins.
+    pub outgoing_swapcoins: Vec<ScriptBuf>,
+    /// Multisig redeemscripts of the incoming swapcoins, empty until the last hop is set up.
+    pub incoming_swapcoins: Vec<ScriptBuf>,
+    /// Contract redeemscripts of the outgoing and incoming swapcoins.
+    pub contract_redeemscripts: Vec<ScriptBuf>,
+    /// Refund locktime of the outgoing contracts, in blocks.
+    pub outgoing_locktime: u16,
+    /// Refund locktime of the incoming contracts, in blocks.
+    pub incoming_locktime: Option<u16>,
+    /// Addresses of the Makers of the route, in order.
+    #[serde(default)]
+    pub makers: Vec<String>,
+    /// Watch-only swapcoins between consecutive Makers of the route, one list per hop.
+    #[serde(default)]
+    pub watchonly_swapcoins: Vec<Vec<WatchOnlySwapCoin>>,
+}
+
+/// An encrypted [WalletStore] file. The salt and nonce are fresh for every save.
+#[derive(Serialize, Deserialize)]
+struct EncryptedStore {
+    salt: [u8; 16],
+    nonce: [u8; 12],
+    ciphertext: Vec<u8>,
+}
+
+/// Derive the encryption key for a passphrase with Argon2id.
+fn derive_key(passphrase: &str, salt: &[u8]) -> Result<Key, WalletError> {
+    let mut key = Key::default();
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| WalletError::Encryption(e.to_string()))?;
+    Ok(key)
+}
+
+/// A change to a [WalletStore], staged with [PersistBackend::stage].
+#[derive(Debug, Clone)]
+pub enum StoreChange {
+    /// Bump the external derivation index.
+    ExternalIndex(u32),
+    /// Insert an incoming swapcoin, replacing the one with the same multisig redeemscript.
+    IncomingSwapCoin(IncomingSwapCoin),
+    /// Insert an outgoing swapcoin, replacing the one with the same multisig redeemscript.
+    OutgoingSwapCoin(OutgoingSwapCoin),
+    /// Bump the swap key index.
+    SwapKeyIndex(u32),
+}
+
+/// A store whose changes are staged in memory, and persisted all at once.
+pub trait PersistBackend {
+    type Change;
+
+    /// Apply a change in memory. It isn't persisted until the next [PersistBackend::commit].
+    fn stage(&mut self, change: Self::Change);
+
+    /// Persist all the staged changes. Does nothing if none are staged.
+    fn commit(&mut self) -> Result<(), WalletError>;
+}
+
+/// Held while writing, so concurrent writes don't share the temporary file.
+static WRITE_LOCK: Mutex<()> = Mutex::new(());
 
-use super::swapcoin::{IncomingSwapCoin, OutgoingSwapCoin};
+/// Write `data` to a temporary file next to `path`, then rename it over `path`.
+///
+/// The rename is atomic, readers of `path` see either the old content or the new one. Concurrent
+/// writes are done one after the other, the last one wins.
+fn write_atomically<P: AsRef<Path>>(path: P, data: &[u8]) -> Result<(), WalletError> {
+    let path = path.as_ref();
+    let mut tmp_path = OsString::from(path);
+    tmp_path.push(".tmp");
+    let _write_guard = WRITE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&tmp_path)?;
+    file.write_all(data)?;
+    file.sync_all()?;
+    Ok(fs::rename(&tmp_path, path)?)
+}
 
 /// Represents the internal data store for a Bitcoin wallet.
-#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct WalletStore {
     /// The file name associated with the wallet store.
     pub(crate) file_name: String,
     /// Network the wallet operates on.
     pub(crate) network: Network,
-    /// The master key for the wallet.
-    pub(super) master_key: Xpriv,
+    /// The master key for the wallet. None for a wallet whose keys are held by a remote signer.
+    pub(super) master_key: Option<Xpriv>,
+    /// The public keys of the remote signer, for a wallet without its master key.
+    #[serde(default)]
+    pub(super) signer_pubkeys: Option<WalletPubkeys>,
+    /// The index of the next swap key, see [Wallet::next_swap_key].
+    #[serde(default)]
+    pub(super) swap_key_index: u32,
     /// The external index for the wallet.
     pub(super) external_index: u32,
     /// The maximum size for an offer in the wallet.
@@ -33,98 +151,715 @@ pub struct WalletStore {
     pub(super) incoming_swapcoins: HashMap<ScriptBuf, IncomingSwapCoin>,
     /// Map of multisig redeemscript to outgoing swapcoins.
     pub(super) outgoing_swapcoins: HashMap<ScriptBuf, OutgoingSwapCoin>,
+    /// Map of multisig redeemscript to watch-only swapcoins, tracked without any of their keys.
+    #[serde(default)]
+    pub(super) watchonly_swapcoins: HashMap<ScriptBuf, WatchOnlySwapCoin>,
     /// Map of prevout to contract redeemscript.
     pub(super) prevout_to_contract_map: HashMap<OutPoint, ScriptBuf>,
     /// Map for all the fidelity bond information. (index, (Bond, script_pubkey, is_spent)).
     pub(super) fidelity_bond: HashMap<u32, (FidelityBond, ScriptBuf, bool)>,
+    /// Map of hashvalue to the hash preimage of swaps.
+    #[serde(default, with = "hash160_hex_keys")]
+    pub(super) swap_preimages: BTreeMap<Hash160, Preimage>,
+    /// The Taker's swap in progress, if any.
+    #[serde(default)]
+    pub(super) swap_checkpoint: Option<SwapCheckpoint>,
+    /// User given labels of the wallet coins.
+    #[serde(default)]
+    pub(super) coin_labels: HashMap<OutPoint, String>,
+    /// Coins frozen by the user, never picked by coin selection.
+    #[serde(default)]
+    pub(super) frozen_utxos: HashSet<OutPoint>,
+    /// Amount of seed coins held back from coin selection, to pay the fees of contract spends.
+    #[serde(default)]
+    pub(super) fee_reserve: Amount,
+    /// Most inputs a single wallet transaction may spend.
+    #[serde(default = "default_max_inputs_per_tx")]
+    pub(super) max_inputs_per_tx: usize,
+    /// The change output index of the replaceable direct sends, which [Wallet::bump_fee] takes the
+    /// extra fee from.
+    #[serde(default)]
+    pub(super) rbf_change_outputs: HashMap<Txid, u32>,
     //TODO: Add last synced height and Wallet birthday.
     pub(super) last_synced_height: Option<u64>,
 
     pub(super) wallet_birthday: Option<u64>,
+    #[serde(skip)]
+    pub(super) persist_state: PersistState,
+}
+
+/// Where a [WalletStore] is committed to, and whether it has changes to commit.
+#[derive(Debug, Default)]
+pub(super) struct PersistState {
+    /// The file the store was initialized at or read from, written by [PersistBackend::commit].
+    path: PathBuf,
+    /// The passphrase the file is encrypted with. None for an unencrypted file.
+    encryption_passphrase: Option<String>,
+    /// Whether changes were staged since the last commit.
+    staged: bool,
+}
+
+/// Compares the stored data only: the [PersistState] is where the store lives, not part of it.
+impl PartialEq for WalletStore {
+    fn eq(&self, other: &Self) -> bool {
+        // Destructured, so a new field can't be left out of the comparison.
+        let Self {
+            file_name,
+            network,
+            master_key,
+            signer_pubkeys,
+            swap_key_index,
+            external_index,
+            offer_maxsize,
+            incoming_swapcoins,
+            outgoing_swapcoins,
+            watchonly_swapcoins,
+            prevout_to_contract_map,
+            fidelity_bond,
+            swap_preimages,
+            swap_checkpoint,
+            coin_labels,
+            frozen_utxos,
+            fee_reserve,
+            max_inputs_per_tx,
+            rbf_change_outputs,
+            last_synced_height,
+            wallet_birthday,
+            persist_state: _,
+        } = self;
+        *file_name == other.file_name
+            && *network == other.network
+            && *master_key == other.master_key
+            && *signer_pubkeys == other.signer_pubkeys
+            && *swap_key_index == other.swap_key_index
+            && *external_index == other.external_index
+            && *offer_maxsize == other.offer_maxsize
+            && *incoming_swapcoins == other.incoming_swapcoins
+            && *outgoing_swapcoins == other.outgoing_swapcoins
+            && *watchonly_swapcoins == other.watchonly_swapcoins
+            && *prevout_to_contract_map == other.prevout_to_contract_map
+            && *fidelity_bond == other.fidelity_bond
+            && *swap_preimages == other.swap_preimages
+            && *swap_checkpoint == other.swap_checkpoint
+            && *coin_labels == other.coin_labels
+            && *frozen_utxos == other.frozen_utxos
+            && *fee_reserve == other.fee_reserve
+            && *max_inputs_per_tx == other.max_inputs_per_tx
+            && *rbf_change_outputs == other.rbf_change_outputs
+            && *last_synced_height == other.last_synced_height
+            && *wallet_birthday == other.wallet_birthday
+    }
 }
 
 impl WalletStore {
     /// Initialize a store at a path (if path already exists, it will overwrite it).
+    ///
+    /// The file is encrypted with `encryption_passphrase` if any, not to be confused with the BIP39
+    /// `passphrase` of the seed.
     pub fn init(
         file_name: String,
-        path: &PathBuf,
+        path: &Path,
         network: Network,
         seedphrase: String,
         passphrase: String,
         wallet_birthday: Option<u64>,
+        encryption_passphrase: Option<&str>,
     ) -> Result<Self, WalletError> {
         let mnemonic = Mnemonic::parse(seedphrase)?;
         let seed = mnemonic.to_seed(passphrase);
         let master_key = Xpriv::new_master(network, &seed)?;
+        Self::init_with_keys(
+            file_name,
+            path,
+            network,
+            Some(master_key),
+            None,
+            wallet_birthday,
+            encryption_passphrase,
+        )
+    }
+
+    /// Initialize a store at a path for a wallet whose keys are held by a remote signer, with its
+    /// public keys (if path already exists, it will overwrite it). See [WalletStore::init] for the
+    /// `encryption_passphrase`.
+    pub fn init_with_signer_pubkeys(
+        file_name: String,
+        path: &Path,
+        network: Network,
+        signer_pubkeys: WalletPubkeys,
+        wallet_birthday: Option<u64>,
+        encryption_passphrase: Option<&str>,
+    ) -> Result<Self, WalletError> {
+        Self::init_with_keys(
+            file_name,
+            path,
+            network,
+            None,
+            Some(signer_pubkeys),
+            wallet_birthday,
+            encryption_passphrase,
+        )
+    }
 
+    fn init_with_keys(
+        file_name: String,
+        path: &Path,
+        network: Network,
+        master_key: Option<Xpriv>,
+        signer_pubkeys: Option<WalletPubkeys>,
+        wallet_birthday: Option<u64>,
+        encryption_passphrase: Option<&str>,
+    ) -> Result<Self, WalletError> {
         let store = Self {
             file_name,
             network,
             master_key,
+            signer_pubkeys,
+            swap_key_index: 0,
             external_index: 0,
             offer_maxsize: 0,
             incoming_swapcoins: HashMap::new(),
             outgoing_swapcoins: HashMap::new(),
+            watchonly_swapcoins: HashMap::new(),
             prevout_to_contract_map: HashMap::new(),
             fidelity_bond: HashMap::new(),
+            swap_preimages: BTreeMap::new(),
+            swap_checkpoint: None,
+            coin_labels: HashMap::new(),
+            frozen_utxos: HashSet::new(),
+            fee_reserve: Amount::ZERO,
+            max_inputs_per_tx: DEFAULT_MAX_INPUTS_PER_TX,
+            rbf_change_outputs: HashMap::new(),
             last_synced_height: None,
             wallet_birthday,
+            persist_state: PersistState {
+                path: path.to_path_buf(),
+                encryption_passphrase: encryption_passphrase.map(str::to_string),
+                staged: false,
+            },
         };
 
         std::fs::create_dir_all(path.parent().expect("Path should NOT be root!"))?;
-        // write: overwrites existing file.
-        // create: creates new file if doesn't exist.
-        let file = OpenOptions::new()
-            .write(true)
-            .create(true)
-            .truncate(true)
-            .open(path)?;
-        let writer = BufWriter::new(file);
-        serde_cbor::to_writer(writer, &store)?;
+        // Overwrites the existing file.
+        store.persist()?;
 
         Ok(store)
     }
 
+    /// Reads the file at `path`, decrypting it with `encryption_passphrase` if any.
+    pub fn read(path: &Path, encryption_passphrase: Option<&str>) -> Result<Self, WalletError> {
+        match encryption_passphrase {
+            Some(encryption_passphrase) => Self::load_encrypted(path, encryption_passphrase),
+            None => Self::read_from_disk(path),
+        }
+    }
+
+    /// The file the store is persisted to.
+    pub(super) fn path(&self) -> &PathBuf {
+        &self.persist_state.path
+    }
+
+    /// Sets the passphrase the file is encrypted with, None to decrypt it, and rewrites the file.
+    pub(super) fn set_encryption_passphrase(
+        &mut self,
+        encryption_passphrase: Option<String>,
+    ) -> Result<(), WalletError> {
+        self.persist_state.encryption_passphrase = encryption_passphrase;
+        self.persist()
+    }
+
+    /// Writes the store, including the staged changes, to its file. Encrypted if the file is.
+    pub(super) fn persist(&self) -> Result<(), WalletError> {
+        let path = &self.persist_state.path;
+        match &self.persist_state.encryption_passphrase {
+            Some(encryption_passphrase) => self.save_encrypted(path, encryption_passphrase),
+            None => write_atomically(path, &serde_cbor::to_vec(&self)?),
+        }
+    }
+
     /// Load existing file, updates it, writes it back (errors if path doesn't exist).
+    ///
+    /// This includes the staged changes.
     pub fn write_to_disk(&self, path: &PathBuf) -> Result<(), WalletError> {
-        let wallet_file = OpenOptions::new().write(true).open(path)?;
-        let writer = BufWriter::new(wallet_file);
-        Ok(serde_cbor::to_writer(writer, &self)?)
+        fs::metadata(path)?;
+        write_atomically(path, &serde_cbor::to_vec(&self)?)
     }
 
     /// Reads from a path (errors if path doesn't exist).
-    pub fn read_from_disk(path: &PathBuf) -> Result<Self, WalletError> {
+    pub fn read_from_disk(path: &Path) -> Result<Self, WalletError> {
         let wallet_file = OpenOptions::new().read(true).open(path)?;
         let reader = BufReader::new(wallet_file);
-        let store: Self = serde_cbor::from_reader(reader)?;
+        let mut store: Self = serde_cbor::from_reader(reader)?;
+        store.persist_state.path = path.to_path_buf();
+        Ok(store)
+    }
+
+    /// Writes the store to path encrypted with the passphrase, replacing any existing file.
+    pub fn save_encrypted(&self, path: &PathBuf, passphrase: &str) -> Result<(), WalletError> {
+        let mut salt = [0u8; 16];
+        let mut nonce = [0u8; 12];
+        OsRng.fill_bytes(&mut salt);
+        OsRng.fill_bytes(&mut nonce);
+
+        let cipher = ChaCha20Poly1305::new(&derive_key(passphrase, &salt)?);
+        let plaintext = serde_cbor::to_vec(&self)?;
+        let ciphertext = cipher
+            .encrypt(Nonce::from_slice(&nonce), plaintext.as_slice())
+            .map_err(|e| WalletError::Encryption(e.to_string()))?;
+
+        let encrypted = EncryptedStore {
+            salt,
+            nonce,
+            ciphertext,
+        };
+        write_atomically(path, &serde_cbor::to_vec(&encrypted)?)
+    }
+
+    /// Reads a store written by [WalletStore::save_encrypted]. Its commits keep the file encrypted
+    /// with the same passphrase.
+    ///
+    /// Errors with [WalletError::BadPassphrase] if the passphrase is wrong, or the file was tampered with.
+    pub fn load_encrypted(path: &Path, passphrase: &str) -> Result<Self, WalletError> {
+        let wallet_file = OpenOptions::new().read(true).open(path)?;
+        let reader = BufReader::new(wallet_file);
+        let encrypted: EncryptedStore = serde_cbor::from_reader(reader)?;
+
+        let cipher = ChaCha20Poly1305::new(&derive_key(passphrase, &encrypted.salt)?);
+        let plaintext = cipher
+            .decrypt(
+                Nonce::from_slice(&encrypted.nonce),
+                encrypted.ciphertext.as_slice(),
+            )
+            .map_err(|_| WalletError::BadPassphrase)?;
+        let mut store: Self = serde_cbor::from_slice(&plaintext)?;
+        store.persist_state.path = path.to_path_buf();
+        store.persist_state.encryption_passphrase = Some(passphrase.to_string());
         Ok(store)
     }
 }
+
+impl PersistBackend for WalletStore {
+    type Change = StoreChange;
+
+    fn stage(&mut self, change: StoreChange) {
+        match change {
+            StoreChange::ExternalIndex(index) => self.external_index = index,
+            StoreChange::SwapKeyIndex(index) => self.swap_key_index = index,
+            StoreChange::IncomingSwapCoin(coin) => {
+                self.incoming_swapcoins
+                    .insert(coin.get_multisig_redeemscript(), coin);
+            }
+            StoreChange::OutgoingSwapCoin(coin) => {
+                self.outgoing_swapcoins
+                    .insert(coin.get_multisig_redeemscript(), coin);
+            }
+        }
+        self.persist_state.staged = true;
+    }
+
+    fn commit(&mut self) -> Result<(), WalletError> {
+        if self.persist_state.staged {
+            self.persist()?;
+            self.persist_state.staged = false;
+        }
+        Ok(())
+    }
+}
+
+impl Wallet {
+    /// Writes the staged changes of the store to disk, see [PersistBackend].
+    pub fn commit(&mut self) -> Result<(), WalletError> {
+        self.store.commit()
+    }
+
+    /// Saves the checkpoint of the swap in progress to disk, replacing the previous one.
+    pub fn save_swap_checkpoint(&mut self, checkpoint: SwapCheckpoint) -> Result<(), WalletError> {
+        self.store.swap_checkpoint = Some(checkpoint);
+        self.save_to_disk()
+    }
+
+    /// The checkpoint of the swap in progress, as last saved. None if no swap is in progress.
+    pub fn load_swap_checkpoint(&self) -> Option<&SwapCheckpoint> {
+        self.store.swap_checkpoint.as_ref()
+    }
+
+    /// Removes the checkpoint, once the swap is completed or recovered.
+    pub fn clear_swap_checkpoint(&mut self) -> Result<(), WalletError> {
+        if self.store.swap_checkpoint.take().is_some() {
+            self.save_to_disk()?;
+        }
+        Ok(())
+    }
+
+    /// Labels a coin, replacing its previous label, and saves to disk.
+    pub fn set_coin_label(&mut self, outpoint: OutPoint, label: &str) -> Result<(), WalletError> {
+        self.store.coin_labels.insert(outpoint, label.to_string());
+        self.save_to_disk()
+    }
+
+    /// The label of a coin, if it has one.
+    pub fn get_coin_label(&self, outpoint: &OutPoint) -> Option<&str> {
+        self.store.coin_labels.get(outpoint).map(String::as_str)
+    }
+
+    /// Removes the label of a coin, and saves to disk.
+    pub fn remove_coin_label(&mut self, outpoint: &OutPoint) -> Result<(), WalletError> {
+        if self.store.coin_labels.remove(outpoint).is_some() {
+            self.save_to_disk()?;
+        }
+        Ok(())
+    }
+
+    /// Freezes a coin, so it's never selected for sends or swaps, and saves to disk.
+    /// Spending it explicitly errors with [WalletError::CoinFrozen] until it's unfrozen.
+    pub fn freeze_utxo(&mut self, outpoint: OutPoint) -> Result<(), WalletError> {
+        if self.store.frozen_utxos.insert(outpoint) {
+            self.save_to_disk()?;
+        }
+        Ok(())
+    }
+
+    /// Unfreezes a coin, and saves to disk.
+    pub fn unfreeze_utxo(&mut self, outpoint: &OutPoint) -> Result<(), WalletError> {
+        if self.store.frozen_utxos.remove(outpoint) {
+            self.save_to_disk()?;
+        }
+        Ok(())
+    }
+
+    /// Whether the coin is frozen.
+    pub fn is_frozen(&self, outpoint: &OutPoint) -> bool {
+        self.store.frozen_utxos.contains(outpoint)
+    }
+
+    /// Sets the fee reserve, and saves to disk. The smallest seed coins covering it are held back by
+    /// [Wallet::spendable_utxos], so there are always funds left to fee bump contract spends.
+    pub fn set_fee_reserve(&mut self, fee_reserve: Amount) -> Result<(), WalletError> {
+        self.store.fee_reserve = fee_reserve;
+        self.save_to_disk()
+    }
+
+    /// The fee reserve. See [Wallet::set_fee_reserve].
+    pub fn fee_reserve(&self) -> Amount {
+        self.store.fee_reserve
+    }
+
+    /// Sets the most inputs a single transaction may spend, and saves to disk. Direct sends and
+    /// funding transactions needing more fail with [WalletError::TooManyInputs], as such transactions
+    /// could be non-standard, or cost excessive fees.
+    pub fn set_max_inputs_per_tx(&mut self, max_inputs_per_tx: usize) -> Result<(), WalletError> {
+        self.store.max_inputs_per_tx = max_inputs_per_tx;
+        self.save_to_disk()
+    }
+
+    /// The input limit. See [Wallet::set_max_inputs_per_tx].
+    pub fn max_inputs_per_tx(&self) -> usize {
+        self.store.max_inputs_per_tx
+    }
+}
+
+/// The default of [Wallet::max_inputs_per_tx]. Far below the standardness limit on the transaction
+/// weight, for any of the wallet's input types.
+pub const DEFAULT_MAX_INPUTS_PER_TX: usize = 500;
+
+fn default_max_inputs_per_tx() -> usize {
+    DEFAULT_MAX_INPUTS_PER_TX
+}
+
+/// Serializes [Hash160] map keys as hex strings.
+///
+/// Binary formats like CBOR would otherwise store the raw hash bytes, while human readable ones use hex.
+/// Keeping it hex everywhere makes the keys the same across formats, and as displayed in logs.
+mod hash160_hex_keys {
+    use std::{collections::BTreeMap, str::FromStr};
+
+    use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+
+    use crate::protocol::Hash160;
+
+    pub fn serialize<S: Serializer, V: Serialize>(
+        map: &BTreeMap<Hash160, V>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(map.iter().map(|(k, v)| (k.to_string(), v)))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>, V: Deserialize<'de>>(
+        deserializer: D,
+    ) -> Result<BTreeMap<Hash160, V>, D::Error> {
+        BTreeMap::<String, V>::deserialize(deserializer)?
+            .into_iter()
+            .map(|(k, v)| Ok((Hash160::from_str(&k).map_err(D::Error::custom)?, v)))
+            .collect()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    use bitcoind::tempfile::tempdir;
+    use bitcoind::tempfile::{tempdir, TempDir};
 
-    #[test]
-    fn test_write_and_read_wallet_to_disk() {
+    /// A new [WalletStore] in a temporary directory, along with its file path.
+    fn test_store() -> (TempDir, PathBuf, WalletStore) {
         let temp_dir = tempdir().unwrap();
         let file_path = temp_dir.path().join("test_wallet.cbor");
         let mnemonic = Mnemonic::generate(12).unwrap().to_string();
-
-        let original_wallet_store = WalletStore::init(
+        let store = WalletStore::init(
             "test_wallet".to_string(),
             &file_path,
-            Network::Bitcoin,
+            Network::Regtest,
             mnemonic,
-            "passphrase".to_string(),
+            "".to_string(),
+            None,
             None,
         )
         .unwrap();
+        (temp_dir, file_path, store)
+    }
+
+    #[test]
+    fn test_write_and_read_wallet_to_disk() {
+        let (_temp_dir, file_path, original_wallet_store) = test_store();
 
         original_wallet_store.write_to_disk(&file_path).unwrap();
 
         let read_wallet = WalletStore::read_from_disk(&file_path).unwrap();
         assert_eq!(original_wallet_store, read_wallet);
     }
+
+    #[test]
+    fn test_concurrent_writes_to_disk() {
+        let (_temp_dir, file_path, wallet_store) = test_store();
+
+        // Concurrent writers don't trip on each other's temporary file.
+        std::thread::scope(|scope| {
+            let writers = (0..4)
+                .map(|_| scope.spawn(|| wallet_store.write_to_disk(&file_path)))
+                .collect::<Vec<_>>();
+            for writer in writers {
+                writer.join().unwrap().unwrap();
+            }
+        });
+
+        let read_wallet = WalletStore::read_from_disk(&file_path).unwrap();
+        assert_eq!(wallet_store, read_wallet);
+    }
+
+    #[test]
+    fn test_coin_labels_persist() {
+        use bitcoin::hashes::Hash;
+
+        let (_temp_dir, file_path, mut wallet_store) = test_store();
+        let outpoint = OutPoint::new(bitcoin::Txid::all_zeros(), 1);
+        wallet_store
+            .coin_labels
+            .insert(outpoint, "donation".to_string());
+        wallet_store.write_to_disk(&file_path).unwrap();
+
+        let read_wallet = WalletStore::read_from_disk(&file_path).unwrap();
+        assert_eq!(
+            read_wallet.coin_labels.get(&outpoint).map(String::as_str),
+            Some("donation")
+        );
+    }
+
+    #[test]
+    fn test_encrypted_store_roundtrip() {
+        let (_temp_dir, file_path, wallet_store) = test_store();
+        wallet_store
+            .save_encrypted(&file_path, "correct horse")
+            .unwrap();
+
+        // The seed isn't in the clear anymore.
+        assert!(WalletStore::read_from_disk(&file_path).is_err());
+
+        let read_wallet = WalletStore::load_encrypted(&file_path, "correct horse").unwrap();
+        assert_eq!(wallet_store, read_wallet);
+    }
+
+    #[test]
+    fn test_encrypted_store_wrong_passphrase() {
+        let (_temp_dir, file_path, wallet_store) = test_store();
+        wallet_store
+            .save_encrypted(&file_path, "correct horse")
+            .unwrap();
+
+        assert!(matches!(
+            WalletStore::load_encrypted(&file_path, "battery staple"),
+            Err(WalletError::BadPassphrase)
+        ));
+    }
+
+    #[test]
+    fn test_encrypted_store_commit() {
+        let (_temp_dir, file_path, mut wallet_store) = test_store();
+        wallet_store
+            .set_encryption_passphrase(Some("correct horse".to_string()))
+            .unwrap();
+
+        // The commits of a loaded store keep its file encrypted, at the path it was loaded from.
+        let mut read_wallet = WalletStore::read(&file_path, Some("correct horse")).unwrap();
+        assert_eq!(read_wallet.path(), &file_path);
+        read_wallet.stage(StoreChange::ExternalIndex(7));
+        read_wallet.commit().unwrap();
+        assert!(WalletStore::read_from_disk(&file_path).is_err());
+
+        let read_wallet = WalletStore::load_encrypted(&file_path, "correct horse").unwrap();
+        assert_eq!(read_wallet.external_index, 7);
+
+        wallet_store.set_encryption_passphrase(None).unwrap();
+        assert!(WalletStore::read(&file_path, None).is_ok());
+    }
+
+    #[test]
+    fn test_hash160_hex_roundtrip() {
+        use bitcoin::hashes::Hash;
+        use std::str::FromStr;
+
+        let hashvalue = Hash160::hash(&[1u8; 32]);
+        let hex = hashvalue.to_string();
+        assert_eq!(hex.len(), 40);
+        assert_eq!(Hash160::from_str(&hex).unwrap(), hashvalue);
+        // Hex is in the byte order of the hash, not reversed.
+        assert_eq!(hex[..2], format!("{:02x}", hashvalue.as_byte_array()[0]));
+
+        // Serialized as the same hex string, as a value and as a map key.
+        assert_eq!(
+            serde_json::to_string(&hashvalue).unwrap(),
+            format!("\"{}\"", hex)
+        );
+        let mut map = BTreeMap::new();
+        map.insert(hashvalue, [7u8; 32]);
+        let value = serde_json::to_value(SwapPreimages(map.clone())).unwrap();
+        assert!(value.get(&hex).is_some());
+        let decoded: SwapPreimages = serde_json::from_value(value).unwrap();
+        assert_eq!(decoded.0, map);
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct SwapPreimages(#[serde(with = "hash160_hex_keys")] BTreeMap<Hash160, Preimage>);
+
+    #[test]
+    fn test_swap_preimage_lookup() {
+        use bitcoin::hashes::Hash;
+
+        let (_temp_dir, file_path, mut wallet_store) = test_store();
+
+        let preimages = [[1u8; 32], [2u8; 32], [3u8; 32]];
+        for preimage in preimages {
+            wallet_store
+                .swap_preimages
+                .insert(Hash160::hash(&preimage), preimage);
+        }
+        wallet_store.write_to_disk(&file_path).unwrap();
+
+        // Lookup by hashvalue works across reloads.
+        let read_wallet = WalletStore::read_from_disk(&file_path).unwrap();
+        assert_eq!(read_wallet, wallet_store);
+        for preimage in preimages {
+            assert_eq!(
+                read_wallet.swap_preimages.get(&Hash160::hash(&preimage)),
+                Some(&preimage)
+            );
+        }
+        assert_eq!(
+            read_wallet.swap_preimages.get(&Hash160::hash(&[4u8; 32])),
+            None
+        );
+    }
+
+    #[test]
+    fn test_swap_checkpoint_persistence() {
+        use bitcoin::hashes::Hash;
+
+        let (_temp_dir, file_path, mut wallet_store) = test_store();
+        assert_eq!(wallet_store.swap_checkpoint, None);
+
+        let checkpoint = SwapCheckpoint {
+            hashvalue: Hash160::hash(&[1u8; 32]),
+            outgoing_swapcoins: vec![ScriptBuf::from_bytes(vec![1, 2, 3])],
+            incoming_swapcoins: Vec::new(),
+            contract_redeemscripts: vec![ScriptBuf::from_bytes(vec![4, 5, 6])],
+            outgoing_locktime: 60,
+            incoming_locktime: None,
+            makers: vec!["127.0.0.1:6102".to_string()],
+            watchonly_swapcoins: Vec::new(),
+        };
+        wallet_store.swap_checkpoint = Some(checkpoint.clone());
+        wallet_store.write_to_disk(&file_path).unwrap();
+
+        let read_wallet = WalletStore::read_from_disk(&file_path).unwrap();
+        assert_eq!(read_wallet.swap_checkpoint, Some(checkpoint));
+    }
+
+    #[test]
+    fn test_staged_changes_commit() {
+        use crate::{
+            protocol::contract::create_contract_redeemscript,
+            utill::generate_keypair,
+            wallet::{LocalSigner, SignerKey},
+        };
+        use bitcoin::{
+            absolute::LockTime, hashes::Hash, transaction::Version, Amount, Transaction,
+        };
+
+        let (_temp_dir, file_path, mut wallet_store) = test_store();
+
+        let (_, my_privkey) = generate_keypair();
+        let (other_pubkey, _) = generate_keypair();
+        let (hashlock_pubkey, hashlock_privkey) = generate_keypair();
+        let signer = LocalSigner::new(wallet_store.master_key.unwrap());
+        let coin = IncomingSwapCoin::new(
+            SignerKey::Imported {
+                privkey: my_privkey,
+            },
+            other_pubkey,
+            Transaction {
+                version: Version::TWO,
+                lock_time: LockTime::ZERO,
+                input: Vec::new(),
+                output: Vec::new(),
+            },
+            create_contract_redeemscript(&hashlock_pubkey, &other_pubkey, &Hash::all_zeros(), &20),
+            SignerKey::Imported {
+                privkey: hashlock_privkey,
+            },
+            Amount::from_sat(100_000),
+            &signer,
+        )
+        .unwrap();
+        let multisig_redeemscript = coin.get_multisig_redeemscript();
+        wallet_store.stage(StoreChange::ExternalIndex(5));
+        wallet_store.stage(StoreChange::IncomingSwapCoin(coin));
+        assert_eq!(wallet_store.external_index, 5);
+
+        // Crash before the commit, in the middle of writing the temporary file.
+        drop(wallet_store);
+        let mut tmp_path = file_path.clone().into_os_string();
+        tmp_path.push(".tmp");
+        fs::write(&tmp_path, b"half written").unwrap();
+
+        // The wallet file is unchanged.
+        let mut read_wallet = WalletStore::read_from_disk(&file_path).unwrap();
+        assert_eq!(read_wallet.external_index, 0);
+        assert!(read_wallet.incoming_swapcoins.is_empty());
+
+        // Committed changes are on disk, the ones lost in the crash are not.
+        read_wallet.stage(StoreChange::ExternalIndex(5));
+        let mut staged_wallet = WalletStore::read_from_disk(&file_path).unwrap();
+        staged_wallet.stage(StoreChange::ExternalIndex(5));
+        read_wallet.commit().unwrap();
+        assert!(!Path::new(&tmp_path).exists());
+        let read_wallet = WalletStore::read_from_disk(&file_path).unwrap();
+        assert_eq!(read_wallet.external_index, 5);
+        assert!(!read_wallet
+            .incoming_swapcoins
+            .contains_key(&multisig_redeemscript));
+
+        // Stores with the same data are equal, whether their changes are committed or not.
+        assert_eq!(read_wallet, staged_wallet);
+        staged_wallet.stage(StoreChange::ExternalIndex(6));
+        assert_ne!(read_wallet, staged_wallet);
+    }
 }
@@ -12,7 +12,6 @@ use bitcoin::{
     absolute::LockTime,
     ecdsa::Signature,
     secp256k1::{self, Secp256k1, SecretKey},
-    sighash::{EcdsaSighashType, SighashCache},
     transaction::Version,
     Address, Amount, OutPoint, PublicKey, Script, ScriptBuf, Sequence, Transaction, TxIn, TxOut,
     Witness,
@@ -23,37 +22,95 @@ use crate::protocol::{
         apply_two_signatures_to_2of2_multisig_spend, create_multisig_redeemscript,
         read_contract_locktime, read_hashlock_pubkey_from_contract, read_hashvalue_from_contract,
         read_pubkeys_from_multisig_redeemscript, read_timelock_pubkey_from_contract,
-        sign_contract_tx, verify_contract_tx_sig,
+        relative_locktime_to_sequence, verify_contract_tx_sig, RelativeLocktime,
     },
-    error::ContractError,
     messages::Preimage,
     Hash160,
 };
 
-use super::WalletError;
+use super::{
+    signer::{request_signature, RemoteSigner, SignPayload, SignerKey},
+    WalletError,
+};
+
+/// Virtual size of a contract timelock spend, calculated using testmempoolaccept.
+const TIMELOCK_SPEND_VBYTE_SIZE: u64 = 128;
+
+/// Virtual size of a contract hashlock spend, calculated using testmempoolaccept.
+const HASHLOCK_SPEND_VBYTE_SIZE: u64 = 136;
+
+/// The contract value left after paying `miner_fee`.
+fn contract_value_after_fee(
+    contract_tx: &Transaction,
+    miner_fee: u64,
+) -> Result<Amount, WalletError> {
+    let value = contract_tx.output[0].value;
+    value
+        .checked_sub(Amount::from_sat(miner_fee))
+        .ok_or(WalletError::InsufficientFunds {
+            available: value,
+            required: Amount::from_sat(miner_fee),
+        })
+}
+
+/// The P2WSH output of `witness_script`, worth `value`.
+pub(super) fn p2wsh_prevout(witness_script: &Script, value: Amount) -> TxOut {
+    TxOut {
+        value,
+        script_pubkey: ScriptBuf::new_p2wsh(&witness_script.wscript_hash()),
+    }
+}
+
+/// Sign the contract transaction spending the 2-of-2 `multisig_redeemscript` funding output, asking
+/// `signer` for the signature of `key`.
+pub(crate) fn sign_contract_tx_with_key(
+    contract_tx: &Transaction,
+    multisig_redeemscript: &Script,
+    funding_amount: Amount,
+    key: &SignerKey,
+    pubkey: &PublicKey,
+    signer: &dyn RemoteSigner,
+) -> Result<Signature, WalletError> {
+    request_signature(
+        signer,
+        key,
+        pubkey,
+        SignPayload::Input {
+            tx: contract_tx.clone(),
+            input_index: 0,
+            prevouts: vec![p2wsh_prevout(multisig_redeemscript, funding_amount)],
+            witness_script: Some(multisig_redeemscript.to_owned()),
+        },
+    )
+}
 
 /// Represents an incoming swapcoin.
+///
+/// The swap keys are held by the wallet's [RemoteSigner], the swapcoin only identifies them. The
+/// counterparty's multisig key is held here, once handed over.
 #[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
 pub struct IncomingSwapCoin {
-    pub my_privkey: SecretKey,
+    pub my_key: SignerKey,
+    pub my_pubkey: PublicKey,
     pub other_pubkey: PublicKey,
     pub other_privkey: Option<SecretKey>,
     pub contract_tx: Transaction,
     pub contract_redeemscript: ScriptBuf,
-    pub hashlock_privkey: SecretKey,
+    pub hashlock_key: SignerKey,
     pub funding_amount: Amount,
     pub others_contract_sig: Option<Signature>,
     pub hash_preimage: Option<Preimage>,
 }
 
-/// Represents an outgoing swapcoin.
+/// Represents an outgoing swapcoin. The swap keys are held by the wallet's [RemoteSigner].
 #[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
 pub struct OutgoingSwapCoin {
-    pub my_privkey: SecretKey,
+    pub my_key: SignerKey,
+    pub my_pubkey: PublicKey,
     pub other_pubkey: PublicKey,
     pub contract_tx: Transaction,
     pub contract_redeemscript: ScriptBuf,
-    pub timelock_privkey: SecretKey,
+    pub timelock_key: SignerKey,
     pub funding_amount: Amount,
     pub others_contract_sig: Option<Signature>,
     pub hash_preimage: Option<Preimage>,
@@ -62,7 +119,7 @@ pub struct OutgoingSwapCoin {
 /// Represents a watch-only view of a coinswap between two makers.
 //like the Incoming/OutgoingSwapCoin structs but no privkey or signature information
 //used by the taker to monitor coinswaps between two makers
-#[derive(Debug, Clone)]
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
 pub struct WatchOnlySwapCoin {
     /// Public key of the sender (maker).
     pub sender_pubkey: PublicKey,
@@ -106,7 +163,11 @@ pub trait SwapCoin {
 pub trait WalletSwapCoin: SwapCoin {
     fn get_my_pubkey(&self) -> PublicKey;
     fn get_other_pubkey(&self) -> &PublicKey;
-    fn get_fully_signed_contract_tx(&self) -> Result<Transaction, WalletError>;
+    /// The contract transaction with both signatures, asking `signer` for ours.
+    fn get_fully_signed_contract_tx(
+        &self,
+        signer: &dyn RemoteSigner,
+    ) -> Result<Transaction, WalletError>;
     fn is_hash_preimage_known(&self) -> bool;
 }
 
@@ -114,18 +175,17 @@ macro_rules! impl_walletswapcoin {
     ($coin:ident) => {
         impl WalletSwapCoin for $coin {
             fn get_my_pubkey(&self) -> bitcoin::PublicKey {
-                let secp = Secp256k1::new();
-                PublicKey {
-                    compressed: true,
-                    inner: secp256k1::PublicKey::from_secret_key(&secp, &self.my_privkey),
-                }
+                self.my_pubkey
             }
 
             fn get_other_pubkey(&self) -> &PublicKey {
                 &self.other_pubkey
             }
 
-            fn get_fully_signed_contract_tx(&self) -> Result<Transaction, WalletError> {
+            fn get_fully_signed_contract_tx(
+                &self,
+                signer: &dyn RemoteSigner,
+            ) -> Result<Transaction, WalletError> {
                 if self.others_contract_sig.is_none() {
                     return Err(WalletError::Protocol(
                         "Other's contract signature not known".to_string(),
@@ -135,22 +195,14 @@ macro_rules! impl_walletswapcoin {
                 let multisig_redeemscript =
                     create_multisig_redeemscript(&my_pubkey, &self.other_pubkey);
                 let index = 0;
-                let secp = Secp256k1::new();
-                let sighash = secp256k1::Message::from_digest_slice(
-                    &SighashCache::new(&self.contract_tx)
-                        .p2wsh_signature_hash(
-                            index,
-                            &multisig_redeemscript,
-                            self.funding_amount,
-                            EcdsaSighashType::All,
-                        )
-                        .map_err(ContractError::Sighash)?[..],
-                )
-                .map_err(ContractError::Secp)?;
-                let sig_mine = Signature {
-                    signature: secp.sign_ecdsa(&sighash, &self.my_privkey),
-                    sighash_type: EcdsaSighashType::All,
-                };
+                let sig_mine = sign_contract_tx_with_key(
+                    &self.contract_tx,
+                    &multisig_redeemscript,
+                    self.funding_amount,
+                    &self.my_key,
+                    &my_pubkey,
+                    signer,
+                )?;
 
                 let mut signed_contract_tx = self.contract_tx.clone();
                 apply_two_signatures_to_2of2_multisig_spend(
@@ -205,70 +257,66 @@ macro_rules! impl_swapcoin_getters {
 }
 
 impl IncomingSwapCoin {
+    /// The swapcoin of the swap keys `my_key` and `hashlock_key`, held by `signer`.
+    ///
+    /// Errors if the hashlock key isn't the one of the contract.
     pub fn new(
-        my_privkey: SecretKey,
+        my_key: SignerKey,
         other_pubkey: PublicKey,
         contract_tx: Transaction,
         contract_redeemscript: ScriptBuf,
-        hashlock_privkey: SecretKey,
+        hashlock_key: SignerKey,
         funding_amount: Amount,
-    ) -> Self {
-        let secp = Secp256k1::new();
-        let hashlock_pubkey = PublicKey {
-            compressed: true,
-            inner: secp256k1::PublicKey::from_secret_key(&secp, &hashlock_privkey),
-        };
-        assert!(
-            hashlock_pubkey == read_hashlock_pubkey_from_contract(&contract_redeemscript).unwrap()
-        );
-        Self {
-            my_privkey,
+        signer: &dyn RemoteSigner,
+    ) -> Result<Self, WalletError> {
+        if signer.public_key(&hashlock_key)?
+            != read_hashlock_pubkey_from_contract(&contract_redeemscript)?
+        {
+            return Err(WalletError::Protocol(
+                "Hashlock key doesn't match the contract".to_string(),
+            ));
+        }
+        Ok(Self {
+            my_pubkey: signer.public_key(&my_key)?,
+            my_key,
             other_pubkey,
             other_privkey: None,
             contract_tx,
             contract_redeemscript,
-            hashlock_privkey,
+            hashlock_key,
             funding_amount,
             others_contract_sig: None,
             hash_preimage: None,
-        }
+        })
     }
 
+    /// Sign the input of `tx` spending the swapcoin, with both multisig keys. `prevouts` are the
+    /// outputs spent by all the inputs of `tx`.
     pub fn sign_transaction_input(
         &self,
         index: usize,
         tx: &Transaction,
         input: &mut TxIn,
         redeemscript: &Script,
+        prevouts: &[TxOut],
+        signer: &dyn RemoteSigner,
     ) -> Result<(), WalletError> {
-        if self.other_privkey.is_none() {
-            return Err(WalletError::Protocol(
-                "Unable to sign: incomplete coinswap for this input".to_string(),
-            ));
-        }
-        let secp = Secp256k1::new();
+        let other_privkey = self.other_privkey.ok_or(WalletError::Protocol(
+            "Unable to sign: incomplete coinswap for this input".to_string(),
+        ))?;
         let my_pubkey = self.get_my_pubkey();
 
-        let sighash = secp256k1::Message::from_digest_slice(
-            &SighashCache::new(tx)
-                .p2wsh_signature_hash(
-                    index,
-                    redeemscript,
-                    self.funding_amount,
-                    EcdsaSighashType::All,
-                )
-                .map_err(ContractError::Sighash)?[..],
-        )
-        .map_err(ContractError::Secp)?;
-
-        let sig_mine = Signature {
-            signature: secp.sign_ecdsa(&sighash, &self.my_privkey),
-            sighash_type: EcdsaSighashType::All,
-        };
-        let sig_other = Signature {
-            signature: secp.sign_ecdsa(&sighash, &self.other_privkey.unwrap()),
-            sighash_type: EcdsaSighashType::All,
+        let payload = SignPayload::Input {
+            tx: tx.clone(),
+            input_index: index,
+            prevouts: prevouts.to_vec(),
+            witness_script: Some(redeemscript.to_owned()),
         };
+        // The other key was handed over to us, it's not held by the signer.
+        let sig_other = Signature::sighash_all(
+            Secp256k1::new().sign_ecdsa(&payload.digest(&self.other_pubkey)?, &other_privkey),
+        );
+        let sig_mine = request_signature(signer, &self.my_key, &my_pubkey, payload)?;
 
         apply_two_signatures_to_2of2_multisig_spend(
             &my_pubkey,
@@ -281,31 +329,29 @@ impl IncomingSwapCoin {
         Ok(())
     }
 
+    /// Sign the input of `tx` spending the contract through its hashlock. `prevouts` are the
+    /// outputs spent by all the inputs of `tx`.
     pub fn sign_hashlocked_transaction_input_given_preimage(
         &self,
         index: usize,
         tx: &Transaction,
         input: &mut TxIn,
-        input_value: Amount,
+        prevouts: &[TxOut],
         hash_preimage: &[u8],
+        signer: &dyn RemoteSigner,
     ) -> Result<(), WalletError> {
-        let secp = Secp256k1::new();
-        let sighash = secp256k1::Message::from_digest_slice(
-            &SighashCache::new(tx)
-                .p2wsh_signature_hash(
-                    index,
-                    &self.contract_redeemscript,
-                    input_value,
-                    EcdsaSighashType::All,
-                )
-                .map_err(ContractError::Sighash)?[..],
-        )
-        .map_err(ContractError::Secp)?;
-
-        let sig_hashlock = secp.sign_ecdsa(&sighash, &self.hashlock_privkey);
-        let mut sig_hashlock_bytes = sig_hashlock.serialize_der().to_vec();
-        sig_hashlock_bytes.push(EcdsaSighashType::All as u8);
-        input.witness.push(sig_hashlock_bytes);
+        let sig_hashlock = request_signature(
+            signer,
+            &self.hashlock_key,
+            &read_hashlock_pubkey_from_contract(&self.contract_redeemscript)?,
+            SignPayload::Input {
+                tx: tx.clone(),
+                input_index: index,
+                prevouts: prevouts.to_vec(),
+                witness_script: Some(self.contract_redeemscript.clone()),
+            },
+        )?;
+        input.witness.push(sig_hashlock.to_vec());
         input.witness.push(hash_preimage);
         input.witness.push(self.contract_redeemscript.to_bytes());
         Ok(())
@@ -316,7 +362,8 @@ impl IncomingSwapCoin {
         index: usize,
         tx: &Transaction,
         input: &mut TxIn,
-        input_value: Amount,
+        prevouts: &[TxOut],
+        signer: &dyn RemoteSigner,
     ) -> Result<(), WalletError> {
         if self.hash_preimage.is_none() {
             panic!("invalid state, unable to sign: preimage unknown");
@@ -325,16 +372,23 @@ impl IncomingSwapCoin {
             index,
             tx,
             input,
-            input_value,
+            prevouts,
             &self.hash_preimage.unwrap(),
+            signer,
         )
     }
 
+    /// Create the hashlock spend of the contract, paying `fee_rate` sats/vbyte.
+    ///
+    /// Errors if the contract value doesn't cover the fee.
     pub fn create_hashlock_spend_without_preimage(
         &self,
         destination_address: &Address,
-    ) -> Transaction {
-        let miner_fee = 136 * 10; //126 vbytes x 10 sat/vb, size calculated using testmempoolaccept
+        fee_rate: u64,
+        signer: &dyn RemoteSigner,
+    ) -> Result<Transaction, WalletError> {
+        let value =
+            contract_value_after_fee(&self.contract_tx, HASHLOCK_SPEND_VBYTE_SIZE * fee_rate)?;
         let mut tx = Transaction {
             input: vec![TxIn {
                 previous_output: OutPoint {
@@ -347,7 +401,7 @@ impl IncomingSwapCoin {
             }],
             output: vec![TxOut {
                 script_pubkey: destination_address.script_pubkey(),
-                value: Amount::from_sat(self.contract_tx.output[0].value.to_sat() - miner_fee),
+                value,
             }],
             lock_time: LockTime::ZERO,
             version: Version::TWO,
@@ -358,11 +412,11 @@ impl IncomingSwapCoin {
             index,
             &tx.clone(),
             &mut tx.input[0],
-            self.contract_tx.output[0].value,
+            &self.contract_tx.output[..1],
             &preimage,
-        )
-        .unwrap();
-        tx
+            signer,
+        )?;
+        Ok(tx)
     }
 
     pub fn verify_contract_tx_sig(&self, sig: &Signature) -> Result<(), WalletError> {
@@ -377,79 +431,92 @@ impl IncomingSwapCoin {
 }
 
 impl OutgoingSwapCoin {
+    /// The swapcoin of the swap keys `my_key` and `timelock_key`, held by `signer`.
+    ///
+    /// Errors if the timelock key isn't the one of the contract.
     pub fn new(
-        my_privkey: SecretKey,
+        my_key: SignerKey,
         other_pubkey: PublicKey,
         contract_tx: Transaction,
         contract_redeemscript: ScriptBuf,
-        timelock_privkey: SecretKey,
+        timelock_key: SignerKey,
         funding_amount: Amount,
-    ) -> Self {
-        let secp = Secp256k1::new();
-        let timelock_pubkey = PublicKey {
-            compressed: true,
-            inner: secp256k1::PublicKey::from_secret_key(&secp, &timelock_privkey),
-        };
-        assert!(
-            timelock_pubkey == read_timelock_pubkey_from_contract(&contract_redeemscript).unwrap()
-        );
-        Self {
-            my_privkey,
+        signer: &dyn RemoteSigner,
+    ) -> Result<Self, WalletError> {
+        if signer.public_key(&timelock_key)?
+            != read_timelock_pubkey_from_contract(&contract_redeemscript)?
+        {
+            return Err(WalletError::Protocol(
+                "Timelock key doesn't match the contract".to_string(),
+            ));
+        }
+        Ok(Self {
+            my_pubkey: signer.public_key(&my_key)?,
+            my_key,
             other_pubkey,
             contract_tx,
             contract_redeemscript,
-            timelock_privkey,
+            timelock_key,
             funding_amount,
             others_contract_sig: None,
             hash_preimage: None,
-        }
+        })
     }
 
+    /// Sign the input of `tx` spending the contract through its timelock. `prevouts` are the
+    /// outputs spent by all the inputs of `tx`.
     pub fn sign_timelocked_transaction_input(
         &self,
         index: usize,
         tx: &Transaction,
         input: &mut TxIn,
-        input_value: Amount,
+        prevouts: &[TxOut],
+        signer: &dyn RemoteSigner,
     ) -> Result<(), WalletError> {
-        let secp = Secp256k1::new();
-        let sighash = secp256k1::Message::from_digest_slice(
-            &SighashCache::new(tx)
-                .p2wsh_signature_hash(
-                    index,
-                    &self.contract_redeemscript,
-                    input_value,
-                    EcdsaSighashType::All,
-                )
-                .map_err(ContractError::Sighash)?[..],
-        )
-        .map_err(ContractError::Secp)?;
-
-        let sig_timelock = secp.sign_ecdsa(&sighash, &self.timelock_privkey);
-
-        let mut sig_timelock_bytes = sig_timelock.serialize_der().to_vec();
-        sig_timelock_bytes.push(EcdsaSighashType::All as u8);
-        input.witness.push(sig_timelock_bytes);
+        let sig_timelock = request_signature(
+            signer,
+            &self.timelock_key,
+            &read_timelock_pubkey_from_contract(&self.contract_redeemscript)?,
+            SignPayload::Input {
+                tx: tx.clone(),
+                input_index: index,
+                prevouts: prevouts.to_vec(),
+                witness_script: Some(self.contract_redeemscript.clone()),
+            },
+        )?;
+        input.witness.push(sig_timelock.to_vec());
         input.witness.push(Vec::new());
         input.witness.push(self.contract_redeemscript.to_bytes());
         Ok(())
     }
 
-    pub fn create_timelock_spend(&self, destination_address: &Address) -> Transaction {
-        let miner_fee = 128 * 2; //128 vbytes x 2 sat/vb, size calculated using testmempoolaccept
+    /// Create the timelock spend of the contract, paying `fee_rate` sats/vbyte.
+    ///
+    /// Errors if the contract value doesn't cover the fee.
+    pub fn create_timelock_spend(
+        &self,
+        destination_address: &Address,
+        fee_rate: u64,
+        signer: &dyn RemoteSigner,
+    ) -> Result<Transaction, WalletError> {
+        let value =
+            contract_value_after_fee(&self.contract_tx, TIMELOCK_SPEND_VBYTE_SIZE * fee_rate)?;
         let mut tx = Transaction {
             input: vec![TxIn {
                 previous_output: OutPoint {
                     txid: self.contract_tx.compute_txid(),
                     vout: 0, //contract_tx is one-input-one-output
                 },
-                sequence: Sequence(self.get_timelock() as u32),
+                sequence: relative_locktime_to_sequence(RelativeLocktime::Blocks(
+                    self.get_timelock().into(),
+                ))
+                .expect("contract timelocks fit in 16 bits"),
                 witness: Witness::new(),
                 script_sig: ScriptBuf::new(),
             }],
             output: vec![TxOut {
                 script_pubkey: destination_address.script_pubkey(),
-                value: Amount::from_sat(self.contract_tx.output[0].value.to_sat() - miner_fee),
+                value,
             }],
             lock_time: LockTime::ZERO,
             version: Version::TWO,
@@ -459,24 +526,26 @@ impl OutgoingSwapCoin {
             index,
             &tx.clone(),
             &mut tx.input[0],
-            self.contract_tx.output[0].value,
-        )
-        .unwrap();
-        tx
+            &self.contract_tx.output[..1],
+            signer,
+        )?;
+        Ok(tx)
     }
 
-    //"_with_my_privkey" as opposed to with other_privkey
-    pub fn sign_contract_tx_with_my_privkey(
+    //"_with_my_key" as opposed to with other_privkey
+    pub fn sign_contract_tx_with_my_key(
         &self,
         contract_tx: &Transaction,
+        signer: &dyn RemoteSigner,
     ) -> Result<Signature, WalletError> {
-        let multisig_redeemscript = self.get_multisig_redeemscript();
-        Ok(sign_contract_tx(
+        sign_contract_tx_with_key(
             contract_tx,
-            &multisig_redeemscript,
+            &self.get_multisig_redeemscript(),
             self.funding_amount,
-            &self.my_privkey,
-        )?)
+            &self.my_key,
+            &self.my_pubkey,
+            signer,
+        )
     }
 
     pub fn verify_contract_tx_sig(&self, sig: &Signature) -> Result<(), WalletError> {
@@ -526,14 +595,7 @@ impl SwapCoin for IncomingSwapCoin {
     impl_swapcoin_getters!();
 
     fn get_multisig_redeemscript(&self) -> ScriptBuf {
-        let secp = Secp256k1::new();
-        create_multisig_redeemscript(
-            &self.other_pubkey,
-            &PublicKey {
-                compressed: true,
-                inner: secp256k1::PublicKey::from_secret_key(&secp, &self.my_privkey),
-            },
-        )
+        create_multisig_redeemscript(&self.other_pubkey, &self.my_pubkey)
     }
 
     fn verify_contract_tx_receiver_sig(&self, sig: &Signature) -> Result<(), WalletError> {
@@ -562,14 +624,7 @@ impl SwapCoin for OutgoingSwapCoin {
     impl_swapcoin_getters!();
 
     fn get_multisig_redeemscript(&self) -> ScriptBuf {
-        let secp = Secp256k1::new();
-        create_multisig_redeemscript(
-            &self.other_pubkey,
-            &PublicKey {
-                compressed: true,
-                inner: secp256k1::PublicKey::from_secret_key(&secp, &self.my_privkey),
-            },
-        )
+        create_multisig_redeemscript(&self.other_pubkey, &self.my_pubkey)
     }
 
     fn verify_contract_tx_receiver_sig(&self, sig: &Signature) -> Result<(), WalletError> {
@@ -645,7 +700,22 @@ mod tests {
     use std::str::FromStr;
 
     use super::*;
-    use bitcoin::{NetworkKind, PrivateKey};
+    use crate::{
+        protocol::contract::create_contract_redeemscript, taker::TakerConfig, wallet::LocalSigner,
+    };
+    use bitcoin::{bip32::Xpriv, hashes::Hash, NetworkKind, PrivateKey};
+
+    /// A signer for the imported keys of the test swapcoins.
+    fn test_signer() -> LocalSigner {
+        LocalSigner::new(Xpriv::new_master(bitcoin::Network::Regtest, &[7u8; 32]).unwrap())
+    }
+
+    /// The imported key of a private key in hex, along with its public key.
+    fn imported_key(privkey: &str) -> (SignerKey, PublicKey) {
+        let privkey = SecretKey::from_str(privkey).unwrap();
+        let pubkey = PublicKey::new(privkey.public_key(&Secp256k1::new()));
+        (SignerKey::Imported { privkey }, pubkey)
+    }
 
     #[test]
     fn test_apply_privkey_watchonly_swapcoin() {
@@ -706,11 +776,11 @@ mod tests {
             .unwrap(),
         };
 
+        let (my_key, my_pubkey) =
+            imported_key("0000000000000000000000000000000000000000000000000000000000000003");
         let mut incoming_swapcoin = IncomingSwapCoin {
-            my_privkey: secp256k1::SecretKey::from_str(
-                "0000000000000000000000000000000000000000000000000000000000000003",
-            )
-            .unwrap(),
+            my_key,
+            my_pubkey,
             other_privkey: Some(
                 secp256k1::SecretKey::from_str(
                     "0000000000000000000000000000000000000000000000000000000000000005",
@@ -725,10 +795,10 @@ mod tests {
                 version: Version::TWO,
             },
             contract_redeemscript: ScriptBuf::default(),
-            hashlock_privkey: secp256k1::SecretKey::from_str(
+            hashlock_key: imported_key(
                 "0000000000000000000000000000000000000000000000000000000000000004",
             )
-            .unwrap(),
+            .0,
             funding_amount: Amount::ZERO,
             others_contract_sig: None,
             hash_preimage: None,
@@ -763,11 +833,11 @@ mod tests {
             )
             .unwrap(),
         };
+        let (my_key, my_pubkey) =
+            imported_key("0000000000000000000000000000000000000000000000000000000000000002");
         let mut outgoing_swapcoin = OutgoingSwapCoin {
-            my_privkey: secp256k1::SecretKey::from_str(
-                "0000000000000000000000000000000000000000000000000000000000000002",
-            )
-            .unwrap(),
+            my_key,
+            my_pubkey,
             other_pubkey: PublicKey::from_private_key(&secp, &other_privkey),
             contract_tx: Transaction {
                 input: vec![],
@@ -776,10 +846,10 @@ mod tests {
                 version: Version::TWO,
             },
             contract_redeemscript: ScriptBuf::default(),
-            timelock_privkey: secp256k1::SecretKey::from_str(
+            timelock_key: imported_key(
                 "0000000000000000000000000000000000000000000000000000000000000003",
             )
-            .unwrap(),
+            .0,
             funding_amount: Amount::ZERO,
             others_contract_sig: None,
             hash_preimage: None,
@@ -826,11 +896,11 @@ mod tests {
 
         let contract_redeemscript = ScriptBuf::default(); // Example redeem script
 
+        let (my_key, my_pubkey) =
+            imported_key("0000000000000000000000000000000000000000000000000000000000000003");
         let incoming_swapcoin = IncomingSwapCoin {
-            my_privkey: secp256k1::SecretKey::from_str(
-                "0000000000000000000000000000000000000000000000000000000000000003",
-            )
-            .unwrap(),
+            my_key,
+            my_pubkey,
             other_privkey: Some(
                 secp256k1::SecretKey::from_str(
                     "0000000000000000000000000000000000000000000000000000000000000005",
@@ -845,17 +915,24 @@ mod tests {
                 version: Version::TWO,
             },
             contract_redeemscript: ScriptBuf::default(),
-            hashlock_privkey: secp256k1::SecretKey::from_str(
+            hashlock_key: imported_key(
                 "0000000000000000000000000000000000000000000000000000000000000004",
             )
-            .unwrap(),
+            .0,
             funding_amount: Amount::from_sat(100_000),
             others_contract_sig: None,
             hash_preimage: None,
         };
         // Intentionally failing to sign with incomplete swapcoin
         assert!(incoming_swapcoin
-            .sign_transaction_input(index, &tx, &mut input, &contract_redeemscript,)
+            .sign_transaction_input(
+                index,
+                &tx,
+                &mut input,
+                &contract_redeemscript,
+                &[],
+                &test_signer(),
+            )
             .is_err());
         let sign = bitcoin::ecdsa::Signature {
             signature: secp256k1::ecdsa::Signature::from_compact(&[0; 64]).unwrap(),
@@ -867,6 +944,69 @@ mod tests {
             .is_err());
     }
 
+    #[test]
+    fn test_timelock_spend_fee_rate() {
+        let secp = Secp256k1::new();
+        let timelock_privkey = secp256k1::SecretKey::from_str(
+            "0000000000000000000000000000000000000000000000000000000000000003",
+        )
+        .unwrap();
+        let timelock_pubkey = PublicKey {
+            compressed: true,
+            inner: secp256k1::PublicKey::from_secret_key(&secp, &timelock_privkey),
+        };
+        let contract_redeemscript = crate::protocol::contract::create_contract_redeemscript(
+            &timelock_pubkey,
+            &timelock_pubkey,
+            &Hash160::all_zeros(),
+            &20,
+        );
+        let (my_key, my_pubkey) =
+            imported_key("0000000000000000000000000000000000000000000000000000000000000002");
+        let outgoing_swapcoin = OutgoingSwapCoin {
+            my_key,
+            my_pubkey,
+            other_pubkey: timelock_pubkey,
+            contract_tx: Transaction {
+                input: vec![TxIn::default()],
+                output: vec![TxOut {
+                    script_pubkey: ScriptBuf::new_p2wsh(&contract_redeemscript.wscript_hash()),
+                    value: Amount::from_sat(100_000),
+                }],
+                lock_time: LockTime::ZERO,
+                version: Version::TWO,
+            },
+            contract_redeemscript,
+            timelock_key: SignerKey::Imported {
+                privkey: timelock_privkey,
+            },
+            funding_amount: Amount::from_sat(100_000),
+            others_contract_sig: None,
+            hash_preimage: None,
+        };
+        let destination_address = Address::p2wpkh(
+            &bitcoin::CompressedPublicKey(timelock_pubkey.inner),
+            bitcoin::Network::Regtest,
+        );
+
+        for fee_rate in [2, 10, 50] {
+            let tx = outgoing_swapcoin
+                .create_timelock_spend(&destination_address, fee_rate, &test_signer())
+                .unwrap();
+            let fee = 100_000 - tx.output[0].value.to_sat();
+            assert_eq!(fee, TIMELOCK_SPEND_VBYTE_SIZE * fee_rate);
+            // The effective fee rate of the signed transaction matches, up to signature size variance.
+            let effective_fee_rate = fee as f64 / tx.vsize() as f64;
+            assert!((effective_fee_rate - fee_rate as f64).abs() <= 0.01 * fee_rate as f64);
+        }
+
+        // A fee above the contract value errors.
+        assert!(matches!(
+            outgoing_swapcoin.create_timelock_spend(&destination_address, 1000, &test_signer()),
+            Err(WalletError::InsufficientFunds { .. })
+        ));
+    }
+
     #[test]
 
     fn test_create_hashlock_spend_without_preimage() {
@@ -880,30 +1020,39 @@ mod tests {
             .unwrap(),
         };
         let input = TxIn::default();
-        let output = TxOut::NULL;
+        let (hashlock_key, hashlock_pubkey) =
+            imported_key("0000000000000000000000000000000000000000000000000000000000000004");
+        let other_pubkey = PublicKey::from_private_key(&secp, &other_privkey);
+        let contract_redeemscript = create_contract_redeemscript(
+            &hashlock_pubkey,
+            &other_pubkey,
+            &Hash160::all_zeros(),
+            &20,
+        );
+        let output = TxOut {
+            script_pubkey: ScriptBuf::new_p2wsh(&contract_redeemscript.wscript_hash()),
+            value: Amount::from_sat(100_000),
+        };
+        let (my_key, my_pubkey) =
+            imported_key("0000000000000000000000000000000000000000000000000000000000000003");
         let incoming_swapcoin = IncomingSwapCoin {
-            my_privkey: secp256k1::SecretKey::from_str(
-                "0000000000000000000000000000000000000000000000000000000000000003",
-            )
-            .unwrap(),
+            my_key,
+            my_pubkey,
             other_privkey: Some(
                 secp256k1::SecretKey::from_str(
                     "0000000000000000000000000000000000000000000000000000000000000005",
                 )
                 .unwrap(),
             ),
-            other_pubkey: PublicKey::from_private_key(&secp, &other_privkey),
+            other_pubkey,
             contract_tx: Transaction {
                 input: vec![input.clone()],
                 output: vec![output.clone()],
                 lock_time: LockTime::ZERO,
                 version: Version::TWO,
             },
-            contract_redeemscript: ScriptBuf::default(),
-            hashlock_privkey: secp256k1::SecretKey::from_str(
-                "0000000000000000000000000000000000000000000000000000000000000004",
-            )
-            .unwrap(),
+            contract_redeemscript,
+            hashlock_key,
             funding_amount: Amount::from_sat(100_000),
             others_contract_sig: None,
             hash_preimage: Some(Preimage::from([0; 32])),
@@ -913,7 +1062,7 @@ mod tests {
             .require_network(bitcoin::Network::Bitcoin)
             .unwrap();
 
-        let miner_fee = 136 * 10; //126 vbytes x 10 sat/vb, size calculated using testmempoolaccept
+        let miner_fee = HASHLOCK_SPEND_VBYTE_SIZE * TakerConfig::default().contract_fee_rate;
         let mut tx = Transaction {
             input: vec![TxIn {
                 previous_output: OutPoint {
@@ -940,8 +1089,9 @@ mod tests {
                 index,
                 &tx.clone(),
                 &mut tx.input[0],
-                incoming_swapcoin.contract_tx.output[0].value,
+                &incoming_swapcoin.contract_tx.output[..1],
                 &preimage,
+                &test_signer(),
             )
             .unwrap();
         // If the tx is succesful, check some field like:
@@ -960,30 +1110,39 @@ mod tests {
             .unwrap(),
         };
         let mut input = TxIn::default();
-        let output = TxOut::NULL;
+        let (hashlock_key, hashlock_pubkey) =
+            imported_key("0000000000000000000000000000000000000000000000000000000000000004");
+        let other_pubkey = PublicKey::from_private_key(&secp, &other_privkey);
+        let contract_redeemscript = create_contract_redeemscript(
+            &hashlock_pubkey,
+            &other_pubkey,
+            &Hash160::all_zeros(),
+            &20,
+        );
+        let output = TxOut {
+            script_pubkey: ScriptBuf::new_p2wsh(&contract_redeemscript.wscript_hash()),
+            value: Amount::from_sat(100_000),
+        };
+        let (my_key, my_pubkey) =
+            imported_key("0000000000000000000000000000000000000000000000000000000000000003");
         let incoming_swapcoin = IncomingSwapCoin {
-            my_privkey: secp256k1::SecretKey::from_str(
-                "0000000000000000000000000000000000000000000000000000000000000003",
-            )
-            .unwrap(),
+            my_key,
+            my_pubkey,
             other_privkey: Some(
                 secp256k1::SecretKey::from_str(
                     "0000000000000000000000000000000000000000000000000000000000000005",
                 )
                 .unwrap(),
             ),
-            other_pubkey: PublicKey::from_private_key(&secp, &other_privkey),
+            other_pubkey,
             contract_tx: Transaction {
                 input: vec![input.clone()],
                 output: vec![output.clone()],
                 lock_time: LockTime::ZERO,
                 version: Version::TWO,
             },
-            contract_redeemscript: ScriptBuf::default(),
-            hashlock_privkey: secp256k1::SecretKey::from_str(
-                "0000000000000000000000000000000000000000000000000000000000000004",
-            )
-            .unwrap(),
+            contract_redeemscript,
+            hashlock_key,
             funding_amount: Amount::from_sat(100_000),
             others_contract_sig: None,
             hash_preimage: Some(Preimage::from([0; 32])),
@@ -993,7 +1152,7 @@ mod tests {
             .require_network(bitcoin::Network::Bitcoin)
             .unwrap();
 
-        let miner_fee = 136 * 10;
+        let miner_fee = HASHLOCK_SPEND_VBYTE_SIZE * TakerConfig::default().contract_fee_rate;
         let mut tx = Transaction {
             input: vec![TxIn {
                 previous_output: OutPoint {
@@ -1021,8 +1180,9 @@ mod tests {
                 index,
                 &tx.clone(),
                 &mut tx.input[0],
-                incoming_swapcoin.contract_tx.output[0].value,
+                &incoming_swapcoin.contract_tx.output[..1],
                 &preimage,
+                &test_signer(),
             )
             .unwrap();
         // Check if the hashlocked transaction input is successful
@@ -1030,7 +1190,11 @@ mod tests {
             index,
             &tx,
             &mut input,
-            input_value,
+            &[TxOut {
+                value: input_value,
+                ..output
+            }],
+            &test_signer(),
         );
         assert!(final_return.is_ok());
     }
@@ -36,6 +36,7 @@ async fn test_stop_taker_after_setup() {
         makers_config_map.into(),
         Some(TakerBehavior::DropConnectionAfterFullSetup),
         ConnectionType::CLEARNET,
+        None,
     )
     .await;
 
@@ -137,6 +138,9 @@ async fn test_stop_taker_after_setup() {
         tx_count: 3,
         required_confirms: 1,
         fee_rate: Amount::from_sat(1000),
+        wait_for_confirms: 0,
+        refund_locktime: None,
+        refund_locktime_step: None,
     };
 
     info!("Initiating coinswap protocol");
@@ -246,7 +250,7 @@ async fn test_stop_taker_after_setup() {
         .unwrap();
     let taker_balance = taker_balance_descriptor_utxo + taker_balance_swap_coins;
 
-    assert_eq!(org_taker_balance - taker_balance, Amount::from_sat(6768));
+    assert_eq!(org_taker_balance - taker_balance, Amount::from_sat(7956));
     assert_eq!(org_taker_balance_fidelity, Amount::from_btc(0.0).unwrap());
     assert_eq!(
         org_taker_balance_descriptor_utxo,
@@ -260,7 +264,7 @@ async fn test_stop_taker_after_setup() {
     assert_eq!(taker_balance_fidelity, Amount::from_btc(0.0).unwrap());
     assert_eq!(
         taker_balance_descriptor_utxo,
-        Amount::from_btc(0.14993232).unwrap()
+        Amount::from_btc(0.14992044).unwrap()
     );
     assert_eq!(taker_balance_swap_coins, Amount::from_btc(0.0).unwrap());
     assert_eq!(taker_balance_live_contract, Amount::from_btc(0.0).unwrap());
@@ -307,12 +311,12 @@ async fn test_stop_taker_after_setup() {
                     .balance_swap_coins(Some(&all_utxos))
                     .unwrap();
 
-            assert_eq!(*org_balance - new_balance, Amount::from_sat(6768));
+            assert_eq!(*org_balance - new_balance, Amount::from_sat(7956));
 
             assert_eq!(maker_balance_fidelity, Amount::from_btc(0.05).unwrap());
             assert_eq!(
                 maker_balance_descriptor_utxo,
-                Amount::from_btc(0.14992232).unwrap()
+                Amount::from_btc(0.14991044).unwrap()
             );
             assert_eq!(maker_balance_swap_coins, Amount::from_btc(0.0).unwrap());
             assert_eq!(maker_balance_live_contract, Amount::from_btc(0.0).unwrap());
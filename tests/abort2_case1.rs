@@ -37,6 +37,7 @@ async fn test_abort_case_2_move_on_with_other_makers() {
         makers_config_map.into(),
         None,
         ConnectionType::CLEARNET,
+        None,
     )
     .await;
 
@@ -111,6 +112,9 @@ async fn test_abort_case_2_move_on_with_other_makers() {
         tx_count: 3,
         required_confirms: 1,
         fee_rate: Amount::from_sat(1000),
+        wait_for_confirms: 0,
+        refund_locktime: None,
+        refund_locktime_step: None,
     };
 
     info!("Initiating coinswap protocol");
@@ -43,6 +43,7 @@ async fn test_abort_case_2_recover_if_no_makers_found() {
         makers_config_map.into(),
         None,
         ConnectionType::CLEARNET,
+        None,
     )
     .await;
 
@@ -129,6 +130,9 @@ async fn test_abort_case_2_recover_if_no_makers_found() {
         tx_count: 3,
         required_confirms: 1,
         fee_rate: Amount::from_sat(1000),
+        wait_for_confirms: 0,
+        refund_locktime: None,
+        refund_locktime_step: None,
     };
 
     // Calculate Original balance excluding fidelity bonds.
@@ -256,7 +260,7 @@ async fn test_abort_case_2_recover_if_no_makers_found() {
     if new_taker_balance != org_taker_balance {
         assert_eq!(
             org_taker_balance - new_taker_balance,
-            Amount::from_sat(6768)
+            Amount::from_sat(4884)
         );
     }
     makers
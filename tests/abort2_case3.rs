@@ -36,6 +36,7 @@ async fn maker_drops_after_sending_senders_sigs() {
         makers_config_map.into(),
         None,
         ConnectionType::CLEARNET,
+        None,
     )
     .await;
 
@@ -110,6 +111,9 @@ async fn maker_drops_after_sending_senders_sigs() {
         tx_count: 3,
         required_confirms: 1,
         fee_rate: Amount::from_sat(1000),
+        wait_for_confirms: 0,
+        refund_locktime: None,
+        refund_locktime_step: None,
     };
 
     info!("Initiating coinswap protocol");
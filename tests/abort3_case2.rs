@@ -35,6 +35,7 @@ async fn abort3_case2_close_at_contract_sigs_for_recvr() {
         makers_config_map.into(),
         None,
         ConnectionType::CLEARNET,
+        None,
     )
     .await;
 
@@ -109,6 +110,9 @@ async fn abort3_case2_close_at_contract_sigs_for_recvr() {
         tx_count: 3,
         required_confirms: 1,
         fee_rate: Amount::from_sat(1000),
+        wait_for_confirms: 0,
+        refund_locktime: None,
+        refund_locktime_step: None,
     };
 
     // Spawn a Taker coinswap thread.
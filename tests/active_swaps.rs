@@ -0,0 +1,123 @@
+#![cfg(feature = "integration-test")]
+use bitcoin::Amount;
+use coinswap::{
+    maker::{start_maker_server, MakerBehavior},
+    taker::{SwapParams, TakerBehavior},
+    utill::ConnectionType,
+};
+
+mod test_framework;
+use log::info;
+use std::{thread, time::Duration};
+use test_framework::*;
+
+/// Test that a paused swap shows up in the active swap lists of the Taker and the Makers.
+///
+/// The Taker drops after the full setup, leaving the swap in flight. Once everybody has recovered,
+/// the swap isn't listed anymore.
+#[tokio::test]
+async fn test_list_active_swaps() {
+    // ---- Setup ----
+    let makers_config_map = [
+        ((6102, None), MakerBehavior::Normal),
+        ((16102, None), MakerBehavior::Normal),
+    ];
+
+    let (test_framework, taker, makers, directory_server_instance) = TestFramework::init(
+        None,
+        makers_config_map.into(),
+        Some(TakerBehavior::DropConnectionAfterFullSetup),
+        ConnectionType::CLEARNET,
+        None,
+    )
+    .await;
+
+    // Fund the Taker and Makers with 3 utxos of 0.05 btc each, and the Makers with one more for fidelity.
+    test_framework.fund_wallet(
+        taker.write().unwrap().get_wallet_mut(),
+        &[Amount::from_btc(0.05).unwrap(); 3],
+    );
+    makers.iter().for_each(|maker| {
+        test_framework.fund_wallet(
+            &mut maker.get_wallet().write().unwrap(),
+            &[Amount::from_btc(0.05).unwrap(); 4],
+        );
+    });
+
+    // Nothing in flight yet.
+    assert!(taker.read().unwrap().list_active_swaps().is_empty());
+
+    // ---- Start Servers and pause a Swap after the full setup ----
+    let maker_threads = makers
+        .iter()
+        .map(|maker| {
+            let maker_clone = maker.clone();
+            thread::spawn(move || {
+                start_maker_server(maker_clone).unwrap();
+            })
+        })
+        .collect::<Vec<_>>();
+
+    makers.iter().for_each(|maker| {
+        while !*maker.is_setup_complete.read().unwrap() {
+            info!("Waiting for maker setup completion");
+            thread::sleep(Duration::from_secs(10));
+        }
+    });
+
+    let swap_params = SwapParams {
+        send_amount: Amount::from_sat(500000),
+        maker_count: 2,
+        tx_count: 3,
+        required_confirms: 1,
+        fee_rate: Amount::from_sat(1000),
+        wait_for_confirms: 0,
+        refund_locktime: None,
+        refund_locktime_step: None,
+    };
+
+    let taker_clone = taker.clone();
+    thread::spawn(move || {
+        taker_clone
+            .write()
+            .unwrap()
+            .do_coinswap(swap_params)
+            .unwrap();
+    })
+    .join()
+    .unwrap();
+
+    // ---- The paused swap is listed by everybody ----
+    let taker_swaps = taker.read().unwrap().list_active_swaps();
+    assert_eq!(taker_swaps.len(), 1);
+    let swap = &taker_swaps[0];
+    assert_eq!(swap.step, "Settling swaps");
+    assert!(swap.counterparties.len() >= 2);
+    assert_eq!(swap.committed_amount, swap_params.send_amount);
+    assert!(swap.earliest_locktime.is_some());
+
+    for maker in makers.iter() {
+        let maker_swaps = maker.list_active_swaps().unwrap();
+        assert_eq!(maker_swaps.len(), 1);
+        assert_eq!(maker_swaps[0].id, swap.id);
+        assert!(maker_swaps[0].committed_amount > Amount::ZERO);
+        assert!(maker_swaps[0].earliest_locktime.is_some());
+    }
+
+    // ---- Recovered swaps are not listed anymore ----
+    // Makers recover from the dropped Taker via timelock and shut down.
+    maker_threads
+        .into_iter()
+        .for_each(|thread| thread.join().unwrap());
+    for maker in makers.iter() {
+        assert!(maker.list_active_swaps().unwrap().is_empty());
+    }
+
+    let _ = directory_server_instance.shutdown();
+    thread::sleep(Duration::from_secs(10));
+
+    taker.write().unwrap().recover_from_swap().unwrap();
+    assert!(taker.read().unwrap().list_active_swaps().is_empty());
+
+    test_framework.stop();
+}
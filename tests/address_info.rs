@@ -0,0 +1,68 @@
+#![cfg(feature = "integration-test")]
+use std::collections::HashMap;
+
+use bitcoind::bitcoincore_rpc::RpcApi;
+use coinswap::{
+    utill::ConnectionType,
+    wallet::{KeychainKind, RPCConfig, Wallet},
+};
+
+mod test_framework;
+use test_framework::*;
+
+/// Test the reverse lookup of addresses to their keychain and derivation index.
+#[tokio::test]
+async fn test_address_info() {
+    // ---- Setup ----
+    let (test_framework, taker, _, directory_server_instance) =
+        TestFramework::init(None, HashMap::new(), None, ConnectionType::CLEARNET, None).await;
+
+    let mut taker_write = taker.write().unwrap();
+    let wallet = taker_write.get_wallet_mut();
+
+    // ---- Owned external address ----
+    let external_index = *wallet.get_external_index();
+    let external_address = wallet.get_next_external_address().unwrap();
+    let info = wallet
+        .get_address_info(&external_address)
+        .unwrap()
+        .expect("owned address expected");
+    assert_eq!(info.keychain, KeychainKind::External);
+    assert_eq!(info.index, external_index);
+    assert!(info.is_mine);
+
+    // The handout is on disk, so a reloaded wallet doesn't hand the address out again.
+    let file_path = wallet.get_file_path().clone();
+    let reloaded = Wallet::load(
+        &RPCConfig {
+            wallet_name: file_path.file_name().unwrap().to_str().unwrap().to_string(),
+            ..RPCConfig::from(test_framework.as_ref())
+        },
+        &file_path,
+        None,
+    )
+    .unwrap();
+    assert_eq!(*reloaded.get_external_index(), external_index + 1);
+
+    // ---- Owned change address ----
+    let change_address = wallet.get_next_internal_addresses(1).unwrap()[0].clone();
+    let info = wallet
+        .get_address_info(&change_address)
+        .unwrap()
+        .expect("owned address expected");
+    assert_eq!(info.keychain, KeychainKind::Internal);
+    assert!(info.is_mine);
+
+    // ---- Foreign address ----
+    let foreign_address = test_framework
+        .get_client()
+        .get_new_address(None, None)
+        .unwrap()
+        .assume_checked();
+    assert_eq!(wallet.get_address_info(&foreign_address).unwrap(), None);
+
+    // ---- Cleanup ----
+    drop(taker_write);
+    directory_server_instance.shutdown().unwrap();
+    test_framework.stop();
+}
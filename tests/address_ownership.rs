@@ -0,0 +1,47 @@
+#![cfg(feature = "integration-test")]
+use std::collections::HashMap;
+
+use bitcoind::bitcoincore_rpc::RpcApi;
+use coinswap::{utill::ConnectionType, wallet::verify_address_ownership};
+
+mod test_framework;
+use test_framework::*;
+
+/// Test ownership proofs, for owned and foreign addresses.
+#[tokio::test]
+async fn test_address_ownership_proof() {
+    // ---- Setup ----
+    let (test_framework, taker, _, directory_server_instance) =
+        TestFramework::init(None, HashMap::new(), None, ConnectionType::CLEARNET, None).await;
+
+    let mut taker_write = taker.write().unwrap();
+    let wallet = taker_write.get_wallet_mut();
+    let challenge = "support ticket #42";
+
+    // ---- Owned addresses ----
+    let external_address = wallet.get_next_external_address().unwrap();
+    let change_address = wallet.get_next_internal_addresses(1).unwrap()[0].clone();
+    for address in [external_address, change_address] {
+        let mut proof = wallet.prove_address_ownership(&address, challenge).unwrap();
+        assert!(verify_address_ownership(&proof).unwrap());
+
+        // A proof doesn't carry over to another challenge.
+        proof.challenge = "another challenge".to_string();
+        assert!(!verify_address_ownership(&proof).unwrap());
+    }
+
+    // ---- Foreign address ----
+    let foreign_address = test_framework
+        .get_client()
+        .get_new_address(None, None)
+        .unwrap()
+        .assume_checked();
+    assert!(wallet
+        .prove_address_ownership(&foreign_address, challenge)
+        .is_err());
+
+    // ---- Cleanup ----
+    drop(taker_write);
+    directory_server_instance.shutdown().unwrap();
+    test_framework.stop();
+}
@@ -0,0 +1,49 @@
+#![cfg(feature = "integration-test")]
+use std::collections::HashMap;
+
+use bitcoin::{absolute::LockTime, Amount};
+use coinswap::utill::ConnectionType;
+
+mod test_framework;
+use test_framework::*;
+
+/// Test the balance breakdown of a wallet holding seed coins and a fidelity bond.
+#[tokio::test]
+async fn test_balance_breakdown() {
+    // ---- Setup ----
+    let (test_framework, taker, _, directory_server_instance) =
+        TestFramework::init(None, HashMap::new(), None, ConnectionType::CLEARNET, None).await;
+
+    let mut taker_write = taker.write().unwrap();
+    let wallet = taker_write.get_wallet_mut();
+
+    test_framework.fund_wallet(wallet, &[Amount::from_btc(0.05).unwrap(); 2]);
+
+    let breakdown = wallet.balance_breakdown().unwrap();
+    assert_eq!(breakdown.spendable, Amount::from_btc(0.1).unwrap());
+    assert_eq!(breakdown.fidelity_locked, Amount::ZERO);
+    assert_eq!(breakdown.total(), wallet.balance().unwrap());
+
+    // ---- Lock some coins in a fidelity bond ----
+    let locktime = LockTime::from_height(test_framework.get_block_count() as u32 + 1000).unwrap();
+    wallet
+        .create_fidelity(Amount::from_btc(0.03).unwrap(), locktime)
+        .unwrap();
+    test_framework.generate_blocks(1);
+    wallet.sync().unwrap();
+
+    let breakdown = wallet.balance_breakdown().unwrap();
+    assert_eq!(breakdown.fidelity_locked, Amount::from_btc(0.03).unwrap());
+    // The fidelity funding fee is gone from the spendable coins.
+    assert!(breakdown.spendable < Amount::from_btc(0.07).unwrap());
+    assert!(breakdown.spendable > Amount::from_btc(0.069).unwrap());
+    assert_eq!(breakdown.swap_incoming, Amount::ZERO);
+    assert_eq!(breakdown.swap_outgoing, Amount::ZERO);
+    assert_eq!(breakdown.contract_locked, Amount::ZERO);
+    assert_eq!(breakdown.total(), wallet.balance().unwrap());
+
+    // ---- Cleanup ----
+    drop(taker_write);
+    directory_server_instance.shutdown().unwrap();
+    test_framework.stop();
+}
@@ -0,0 +1,100 @@
+#![cfg(feature = "integration-test")]
+use std::collections::HashMap;
+
+use bitcoin::{Amount, OutPoint};
+use coinswap::{
+    utill::ConnectionType,
+    wallet::{Destination, SendAmount, Wallet, WalletError},
+};
+
+mod test_framework;
+use test_framework::*;
+
+fn outpoints(wallet: &Wallet, include_frozen: bool) -> Vec<OutPoint> {
+    wallet
+        .spendable_utxos_with(None, include_frozen)
+        .unwrap()
+        .iter()
+        .map(|(utxo, _)| OutPoint::new(utxo.txid, utxo.vout))
+        .collect()
+}
+
+/// Test that a frozen coin is left out of the coin selection, is still listed on request, and can't be
+/// spent explicitly until it's unfrozen.
+#[tokio::test]
+async fn test_coin_freeze() {
+    // ---- Setup ----
+    let (test_framework, taker, _, directory_server_instance) =
+        TestFramework::init(None, HashMap::new(), None, ConnectionType::CLEARNET, None).await;
+
+    let mut taker_write = taker.write().unwrap();
+    let wallet = taker_write.get_wallet_mut();
+
+    test_framework.fund_wallet(
+        wallet,
+        &[Amount::from_sat(100_000), Amount::from_sat(300_000)],
+    );
+
+    let (frozen_utxo, frozen_spend_info) = wallet
+        .spendable_utxos()
+        .unwrap()
+        .into_iter()
+        .find(|(utxo, _)| utxo.amount == Amount::from_sat(300_000))
+        .unwrap();
+    let frozen = OutPoint::new(frozen_utxo.txid, frozen_utxo.vout);
+
+    // ---- Freeze the largest coin ----
+    assert!(!wallet.is_frozen(&frozen));
+    wallet.freeze_utxo(frozen).unwrap();
+    assert!(wallet.is_frozen(&frozen));
+
+    assert_eq!(outpoints(wallet, false).len(), 1);
+    assert!(!outpoints(wallet, false).contains(&frozen));
+    // Still listed with the override.
+    assert_eq!(outpoints(wallet, true).len(), 2);
+    assert!(outpoints(wallet, true).contains(&frozen));
+
+    // Locking the unspendable coins, as before building funding txes, doesn't hide it.
+    wallet.lock_unspendable_utxos().unwrap();
+    assert!(outpoints(wallet, true).contains(&frozen));
+    assert!(!outpoints(wallet, false).contains(&frozen));
+
+    // The selection can't use it, even when it's the only way to cover the send.
+    assert!(matches!(
+        wallet.select_direct_send_coins(
+            Amount::from_sat(1000),
+            &[SendAmount::Amount(Amount::from_sat(200_000))]
+        ),
+        Err(WalletError::InsufficientFunds { .. })
+    ));
+
+    // ---- Naming the frozen coin errors ----
+    let destination = Destination::Address(wallet.get_next_external_address().unwrap());
+    assert!(matches!(
+        wallet.spend_from_wallet(
+            Amount::from_sat(1000),
+            SendAmount::Max,
+            destination.clone(),
+            &[(frozen_utxo.clone(), frozen_spend_info.clone())],
+        ),
+        Err(WalletError::CoinFrozen(outpoint)) if outpoint == frozen
+    ));
+
+    // ---- Unfreeze ----
+    wallet.unfreeze_utxo(&frozen).unwrap();
+    assert!(!wallet.is_frozen(&frozen));
+    assert!(outpoints(wallet, false).contains(&frozen));
+    assert!(wallet
+        .spend_from_wallet(
+            Amount::from_sat(1000),
+            SendAmount::Max,
+            destination,
+            &[(frozen_utxo, frozen_spend_info)],
+        )
+        .is_ok());
+
+    // ---- Cleanup ----
+    drop(taker_write);
+    directory_server_instance.shutdown().unwrap();
+    test_framework.stop();
+}
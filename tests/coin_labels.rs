@@ -0,0 +1,125 @@
+#![cfg(feature = "integration-test")]
+use std::collections::HashMap;
+
+use bitcoin::{Amount, OutPoint};
+use coinswap::{
+    utill::ConnectionType,
+    wallet::{LabelFilter, RPCConfig, SendAmount, Wallet, WalletError},
+};
+
+mod test_framework;
+use test_framework::*;
+
+/// Test labeling coins, restricting the coin selection by label, and reloading the labels from disk.
+#[tokio::test]
+async fn test_coin_labels() {
+    // ---- Setup ----
+    let (test_framework, taker, _, directory_server_instance) =
+        TestFramework::init(None, HashMap::new(), None, ConnectionType::CLEARNET, None).await;
+
+    let mut taker_write = taker.write().unwrap();
+    let wallet = taker_write.get_wallet_mut();
+
+    test_framework.fund_wallet(
+        wallet,
+        &[
+            Amount::from_sat(100_000),
+            Amount::from_sat(200_000),
+            Amount::from_sat(300_000),
+        ],
+    );
+
+    let outpoint_of = |amount: u64| {
+        let (utxo, _) = wallet
+            .spendable_utxos()
+            .unwrap()
+            .into_iter()
+            .find(|(utxo, _)| utxo.amount == Amount::from_sat(amount))
+            .unwrap();
+        OutPoint::new(utxo.txid, utxo.vout)
+    };
+    let kyc = outpoint_of(300_000);
+    let donation = outpoint_of(100_000);
+
+    // ---- Set and read labels ----
+    assert_eq!(wallet.get_coin_label(&kyc), None);
+    wallet.set_coin_label(kyc, "kyc").unwrap();
+    wallet.set_coin_label(donation, "swap-output").unwrap();
+    // Relabeling replaces the label.
+    wallet.set_coin_label(donation, "donation").unwrap();
+    assert_eq!(wallet.get_coin_label(&kyc), Some("kyc"));
+    assert_eq!(wallet.get_coin_label(&donation), Some("donation"));
+
+    // ---- Filter by label ----
+    let outpoints = |labels: &LabelFilter| {
+        wallet
+            .spendable_utxos_with(Some(labels), false)
+            .unwrap()
+            .iter()
+            .map(|(utxo, _)| OutPoint::new(utxo.txid, utxo.vout))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        outpoints(&LabelFilter::Include(vec!["kyc".to_string()])),
+        vec![kyc]
+    );
+    let not_kyc = outpoints(&LabelFilter::Exclude(vec!["kyc".to_string()]));
+    assert_eq!(not_kyc.len(), 2);
+    assert!(!not_kyc.contains(&kyc));
+    assert!(not_kyc.contains(&donation));
+
+    // The largest coin is skipped when excluded.
+    let selected = wallet
+        .select_direct_send_coins_with_labels(
+            Amount::from_sat(1000),
+            &[SendAmount::Amount(Amount::from_sat(150_000))],
+            Some(&LabelFilter::Exclude(vec!["kyc".to_string()])),
+        )
+        .unwrap();
+    assert_eq!(selected.len(), 1);
+    assert_eq!(selected[0].0.amount, Amount::from_sat(200_000));
+
+    // Not enough funds among the included coins.
+    assert!(wallet
+        .select_direct_send_coins_with_labels(
+            Amount::from_sat(1000),
+            &[SendAmount::Amount(Amount::from_sat(150_000))],
+            Some(&LabelFilter::Include(vec!["donation".to_string()])),
+        )
+        .is_err());
+
+    // ---- Labels persist across reloads ----
+    wallet.remove_coin_label(&donation).unwrap();
+    let wallet_path = wallet.get_file_path().clone();
+    let rpc_config = RPCConfig {
+        wallet_name: wallet_path
+            .file_name()
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_string(),
+        ..RPCConfig::from(test_framework.as_ref())
+    };
+    let mut reloaded = Wallet::load(&rpc_config, &wallet_path, None).unwrap();
+    assert_eq!(reloaded.get_coin_label(&kyc), Some("kyc"));
+    assert_eq!(reloaded.get_coin_label(&donation), None);
+
+    // ---- And across reloads of the encrypted wallet file ----
+    reloaded
+        .set_encryption_passphrase(Some("correct horse".to_string()))
+        .unwrap();
+    reloaded.save_to_disk().unwrap();
+    drop(reloaded);
+    assert!(Wallet::load(&rpc_config, &wallet_path, None).is_err());
+    assert!(matches!(
+        Wallet::load(&rpc_config, &wallet_path, Some("battery staple")),
+        Err(WalletError::BadPassphrase)
+    ));
+    let reloaded = Wallet::load(&rpc_config, &wallet_path, Some("correct horse")).unwrap();
+    assert_eq!(reloaded.get_coin_label(&kyc), Some("kyc"));
+
+    // ---- Cleanup ----
+    drop(taker_write);
+    directory_server_instance.shutdown().unwrap();
+    test_framework.stop();
+}
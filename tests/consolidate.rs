@@ -0,0 +1,61 @@
+#![cfg(feature = "integration-test")]
+use std::collections::HashMap;
+
+use bitcoin::Amount;
+use bitcoind::bitcoincore_rpc::RpcApi;
+use coinswap::{utill::ConnectionType, wallet::WalletError};
+
+mod test_framework;
+use test_framework::*;
+
+/// Test consolidating five small coins into a single one.
+#[tokio::test]
+async fn test_consolidate_utxos() {
+    // ---- Setup ----
+    let (test_framework, taker, _, directory_server_instance) =
+        TestFramework::init(None, HashMap::new(), None, ConnectionType::CLEARNET, None).await;
+
+    let mut taker_write = taker.write().unwrap();
+    let wallet = taker_write.get_wallet_mut();
+
+    test_framework.fund_wallet(wallet, &[Amount::from_sat(10_000); 5]);
+    assert_eq!(wallet.spendable_utxos().unwrap().len(), 5);
+
+    // ---- Uneconomic consolidations ----
+    // Every coin pays for its own input, but not for the whole transaction.
+    assert!(matches!(
+        wallet.consolidate_utxos(140, 10, None),
+        Err(WalletError::UneconomicConsolidation { value, fee })
+            if value == Amount::from_sat(50_000) && fee > value
+    ));
+    // No coin pays for its own input.
+    assert!(matches!(
+        wallet.consolidate_utxos(1000, 10, None),
+        Err(WalletError::Protocol(_))
+    ));
+
+    // ---- Consolidate ----
+    let tx = wallet.consolidate_utxos(2, 10, None).unwrap();
+    assert_eq!(tx.input.len(), 5);
+    assert_eq!(tx.output.len(), 1);
+    let fee = Amount::from_sat(50_000) - tx.output[0].value;
+    // The fee rate is met, without overpaying.
+    assert!(fee.to_sat() >= 2 * tx.vsize() as u64);
+    assert!(fee.to_sat() <= 3 * tx.vsize() as u64);
+
+    test_framework
+        .get_client()
+        .send_raw_transaction(&tx)
+        .unwrap();
+    test_framework.generate_blocks(1);
+    wallet.sync().unwrap();
+
+    let utxos = wallet.spendable_utxos().unwrap();
+    assert_eq!(utxos.len(), 1);
+    assert_eq!(utxos[0].0.amount, tx.output[0].value);
+
+    // ---- Cleanup ----
+    drop(taker_write);
+    directory_server_instance.shutdown().unwrap();
+    test_framework.stop();
+}
@@ -0,0 +1,89 @@
+#![cfg(feature = "integration-test")]
+use std::{collections::HashMap, str::FromStr};
+
+use bitcoin::{hashes::Hash, Address, Amount, Network, PublicKey};
+use bitcoind::bitcoincore_rpc::RpcApi;
+use coinswap::{
+    protocol::contract::create_contract_redeemscript,
+    utill::{redeemscript_to_scriptpubkey, ConnectionType},
+    wallet::{Destination, SendAmount},
+};
+
+mod test_framework;
+use test_framework::*;
+
+/// Test that a broadcasted contract transaction is detected, both in the mempool and once confirmed.
+#[tokio::test]
+async fn test_contract_broadcast_detection() {
+    // ---- Setup ----
+    let (test_framework, taker, _, directory_server_instance) =
+        TestFramework::init(None, HashMap::new(), None, ConnectionType::CLEARNET, None).await;
+
+    test_framework.fund_wallet(
+        taker.write().unwrap().get_wallet_mut(),
+        &[Amount::from_btc(0.05).unwrap()],
+    );
+
+    // ---- Create a transaction paying to a contract ----
+    let pubkey =
+        PublicKey::from_str("032e58afe51f9ed8ad3cc7897f634d881fdbe49a81564629ded8156bebd2ffd1af")
+            .unwrap();
+    let contract_redeemscript =
+        create_contract_redeemscript(&pubkey, &pubkey, &Hash::all_zeros(), &20);
+    let contract_spk = redeemscript_to_scriptpubkey(&contract_redeemscript);
+    let contract_address = Address::from_script(&contract_spk, Network::Regtest).unwrap();
+
+    let mut taker_write = taker.write().unwrap();
+    let taker_wallet = taker_write.get_wallet_mut();
+    taker_wallet.sync().unwrap();
+    let coins = taker_wallet.list_descriptor_utxo_spend_info(None).unwrap();
+    let contract_tx = taker_wallet
+        .spend_from_wallet(
+            Amount::from_sat(1000),
+            SendAmount::Max,
+            Destination::Address(contract_address),
+            &coins,
+        )
+        .unwrap();
+
+    // Not broadcasted yet, nothing to detect.
+    assert_eq!(
+        taker_wallet
+            .detect_contract_broadcast(&contract_tx, &contract_redeemscript)
+            .unwrap(),
+        None
+    );
+
+    // ---- Broadcast, and detect in the mempool ----
+    test_framework
+        .get_client()
+        .send_raw_transaction(&contract_tx)
+        .unwrap();
+    let detected = taker_wallet
+        .detect_contract_broadcast(&contract_tx, &contract_redeemscript)
+        .unwrap()
+        .expect("contract broadcast expected");
+    assert_eq!(detected.txid, contract_tx.compute_txid());
+    assert_eq!(detected.script_pubkey, contract_spk);
+    assert_eq!(detected.confirmations, 0);
+
+    // ---- Detect once confirmed ----
+    test_framework.generate_blocks(1);
+    let detected = taker_wallet
+        .detect_contract_broadcast(&contract_tx, &contract_redeemscript)
+        .unwrap()
+        .expect("contract broadcast expected");
+    assert!(detected.confirmations >= 1);
+
+    // A contract tx not paying to the expected contract is refused.
+    let other_redeemscript =
+        create_contract_redeemscript(&pubkey, &pubkey, &Hash::all_zeros(), &21);
+    assert!(taker_wallet
+        .detect_contract_broadcast(&contract_tx, &other_redeemscript)
+        .is_err());
+
+    // ---- Cleanup ----
+    drop(taker_write);
+    directory_server_instance.shutdown().unwrap();
+    test_framework.stop();
+}
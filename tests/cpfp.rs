@@ -0,0 +1,94 @@
+#![cfg(feature = "integration-test")]
+use std::collections::HashMap;
+
+use bitcoin::Amount;
+use bitcoind::bitcoincore_rpc::RpcApi;
+use coinswap::{
+    utill::ConnectionType,
+    wallet::{Destination, SendAmount, WalletError},
+};
+
+mod test_framework;
+use test_framework::*;
+
+/// Test bumping a low fee transaction with a CPFP child, spending its change.
+#[tokio::test]
+async fn test_cpfp_fee_bump() {
+    // ---- Setup ----
+    let (test_framework, taker, _, directory_server_instance) =
+        TestFramework::init(None, HashMap::new(), None, ConnectionType::CLEARNET, None).await;
+
+    let mut taker_write = taker.write().unwrap();
+    let wallet = taker_write.get_wallet_mut();
+
+    test_framework.fund_wallet(wallet, &[Amount::from_sat(100_000); 2]);
+
+    let destination = test_framework
+        .get_client()
+        .get_new_address(None, None)
+        .unwrap()
+        .assume_checked();
+    let coins = wallet.spendable_utxos().unwrap();
+
+    // ---- A parent without wallet outputs can't be bumped ----
+    let sweep = wallet
+        .spend_from_wallet(
+            Amount::from_sat(1000),
+            SendAmount::Max,
+            Destination::Address(destination.clone()),
+            &coins[..1],
+        )
+        .unwrap();
+    let sweep_txid = test_framework
+        .get_client()
+        .send_raw_transaction(&sweep)
+        .unwrap();
+    assert!(matches!(
+        wallet.create_cpfp_child(sweep_txid, 20),
+        Err(WalletError::NoCpfpOutput(txid)) if txid == sweep_txid
+    ));
+
+    // ---- Bump a low fee parent, through its change ----
+    let parent = wallet
+        .spend_from_wallet(
+            Amount::from_sat(200),
+            SendAmount::Amount(Amount::from_sat(50_000)),
+            Destination::Address(destination),
+            &coins[1..],
+        )
+        .unwrap();
+    let parent_txid = test_framework
+        .get_client()
+        .send_raw_transaction(&parent)
+        .unwrap();
+    let child = wallet.create_cpfp_child(parent_txid, 20).unwrap();
+    assert_eq!(child.input.len(), 1);
+    assert_eq!(child.input[0].previous_output.txid, parent_txid);
+    let child_txid = test_framework
+        .get_client()
+        .send_raw_transaction(&child)
+        .unwrap();
+
+    let child_entry = test_framework
+        .get_client()
+        .get_mempool_entry(&child_txid)
+        .unwrap();
+    assert_eq!(child_entry.ancestor_count, 2);
+    let package_fee_rate =
+        child_entry.fees.ancestor.to_sat() as f64 / child_entry.ancestor_size as f64;
+    assert!(package_fee_rate >= 20.0);
+    // The child isn't overpaying.
+    assert!(package_fee_rate < 21.0);
+
+    // ---- A confirmed parent can't be bumped ----
+    test_framework.generate_blocks(1);
+    assert!(matches!(
+        wallet.create_cpfp_child(parent_txid, 20),
+        Err(WalletError::ParentNotInMempool(txid)) if txid == parent_txid
+    ));
+
+    // ---- Cleanup ----
+    drop(taker_write);
+    directory_server_instance.shutdown().unwrap();
+    test_framework.stop();
+}
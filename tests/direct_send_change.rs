@@ -0,0 +1,86 @@
+#![cfg(feature = "integration-test")]
+use std::collections::HashMap;
+
+use bitcoin::Amount;
+use bitcoind::bitcoincore_rpc::RpcApi;
+use coinswap::{
+    utill::ConnectionType,
+    wallet::{Destination, SendAmount, WalletError},
+};
+
+mod test_framework;
+use test_framework::*;
+
+/// Test the change of direct sends around the dust limit. Dust change is left to the fee, and no send
+/// output is ever dust.
+#[tokio::test]
+async fn test_direct_send_dust_change() {
+    // ---- Setup ----
+    let (test_framework, taker, _, directory_server_instance) =
+        TestFramework::init(None, HashMap::new(), None, ConnectionType::CLEARNET, None).await;
+
+    let mut taker_write = taker.write().unwrap();
+    let wallet = taker_write.get_wallet_mut();
+
+    test_framework.fund_wallet(wallet, &[Amount::from_sat(100_000); 2]);
+
+    let coins = wallet.spendable_utxos().unwrap();
+    let destination = Destination::Address(
+        test_framework
+            .get_client()
+            .get_new_address(None, None)
+            .unwrap()
+            .assume_checked(),
+    );
+    let fee = Amount::from_sat(1000);
+    let amount = |sats| SendAmount::Amount(Amount::from_sat(sats));
+
+    // ---- Change above dust: two outputs ----
+    let (tx, summary) = wallet
+        .build_direct_send_unsigned(fee, amount(150_000), destination.clone(), &coins)
+        .unwrap();
+    assert_eq!(tx.output.len(), 2);
+    assert_eq!(summary.change, Amount::from_sat(49_000));
+    assert_eq!(summary.fee, fee);
+
+    // ---- Dust change: one output, the remainder goes to the fee ----
+    let (tx, summary) = wallet
+        .build_direct_send_unsigned(fee, amount(198_900), destination.clone(), &coins)
+        .unwrap();
+    assert_eq!(tx.output.len(), 1);
+    assert_eq!(tx.output[0].value, Amount::from_sat(198_900));
+    assert_eq!(summary.change, Amount::ZERO);
+    assert_eq!(summary.fee, Amount::from_sat(1100));
+
+    // ---- Inputs barely cover the amount and fee ----
+    let (tx, summary) = wallet
+        .build_direct_send_unsigned(fee, amount(199_000), destination.clone(), &coins)
+        .unwrap();
+    assert_eq!(tx.output.len(), 1);
+    assert_eq!(summary.change, Amount::ZERO);
+    assert_eq!(summary.fee, fee);
+    assert!(matches!(
+        wallet.build_direct_send_unsigned(fee, amount(199_001), destination.clone(), &coins),
+        Err(WalletError::InsufficientFunds { .. })
+    ));
+
+    // ---- Dust sends are refused ----
+    assert!(matches!(
+        wallet.build_direct_send_unsigned(
+            Amount::from_sat(199_800),
+            SendAmount::Max,
+            destination.clone(),
+            &coins
+        ),
+        Err(WalletError::DustOutput { value, .. }) if value == Amount::from_sat(200)
+    ));
+    assert!(matches!(
+        wallet.build_direct_send_unsigned(fee, amount(100), destination, &coins),
+        Err(WalletError::DustOutput { .. })
+    ));
+
+    // ---- Cleanup ----
+    drop(taker_write);
+    directory_server_instance.shutdown().unwrap();
+    test_framework.stop();
+}
@@ -0,0 +1,135 @@
+#![cfg(feature = "integration-test")]
+use std::{collections::HashMap, str::FromStr};
+
+use bitcoin::{Amount, OutPoint};
+use bitcoind::bitcoincore_rpc::RpcApi;
+use coinswap::{
+    utill::ConnectionType,
+    wallet::{CoinToSpend, Destination, SendAmount, WalletError},
+};
+
+mod test_framework;
+use test_framework::*;
+
+/// Test direct sends letting the wallet select the coins to spend.
+#[tokio::test]
+async fn test_direct_send_coin_selection() {
+    // ---- Setup ----
+    let (test_framework, taker, _, directory_server_instance) =
+        TestFramework::init(None, HashMap::new(), None, ConnectionType::CLEARNET, None).await;
+
+    let mut taker_write = taker.write().unwrap();
+    let wallet = taker_write.get_wallet_mut();
+
+    test_framework.fund_wallet(
+        wallet,
+        &[
+            Amount::from_sat(100_000),
+            Amount::from_sat(300_000),
+            Amount::from_sat(200_000),
+        ],
+    );
+
+    let destination = Destination::Address(
+        test_framework
+            .get_client()
+            .get_new_address(None, None)
+            .unwrap()
+            .assume_checked(),
+    );
+    let fee = Amount::from_sat(1000);
+
+    // ---- Coins are found by outpoint ----
+    let utxos = wallet.spendable_utxos().unwrap();
+    let long_form = CoinToSpend::LongForm(OutPoint::new(utxos[0].0.txid, utxos[0].0.vout));
+    let txid = utxos[1].0.txid.to_string();
+    let short_form = CoinToSpend::from_str(&format!(
+        "{}..{}:{}",
+        &txid[0..6],
+        &txid[58..64],
+        utxos[1].0.vout
+    ))
+    .unwrap();
+    let found = wallet
+        .find_coins_to_spend(&[long_form.clone(), short_form.clone()])
+        .unwrap();
+    assert_eq!(found[0].0, utxos[0].0);
+    assert_eq!(found[1].0, utxos[1].0);
+
+    let unknown = CoinToSpend::LongForm(OutPoint::new(utxos[0].0.txid, 99));
+    match wallet.find_coins_to_spend(&[long_form, unknown.clone(), short_form]) {
+        Err(WalletError::CoinNotFound(not_found)) => assert_eq!(not_found, vec![unknown]),
+        other => panic!("expected CoinNotFound, got {:?}", other),
+    }
+
+    // ---- The wallet can't cover the amount ----
+    match wallet.spend_from_wallet(
+        fee,
+        SendAmount::Amount(Amount::from_sat(600_000)),
+        destination.clone(),
+        &[],
+    ) {
+        Err(WalletError::InsufficientFunds {
+            available,
+            required,
+        }) => {
+            assert_eq!(available, Amount::from_sat(600_000));
+            assert_eq!(required, Amount::from_sat(601_000));
+        }
+        other => panic!("expected InsufficientFunds, got {:?}", other),
+    }
+
+    // ---- The largest coins are picked first ----
+    let tx = wallet
+        .spend_from_wallet(
+            fee,
+            SendAmount::Amount(Amount::from_sat(400_000)),
+            destination.clone(),
+            &[],
+        )
+        .unwrap();
+    let selected_values = tx
+        .input
+        .iter()
+        .map(|input| {
+            let OutPoint { txid, vout } = input.previous_output;
+            test_framework
+                .get_client()
+                .get_raw_transaction(&txid, None)
+                .unwrap()
+                .output[vout as usize]
+                .value
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        selected_values,
+        vec![Amount::from_sat(300_000), Amount::from_sat(200_000)]
+    );
+    test_framework
+        .get_client()
+        .send_raw_transaction(&tx)
+        .unwrap();
+    test_framework.generate_blocks(1);
+    wallet.sync().unwrap();
+
+    // ---- Sweep everything at a fee rate ----
+    let tx = wallet
+        .spend_from_wallet_at_fee_rate(2, SendAmount::Max, destination, &[])
+        .unwrap();
+    // The 100_000 sats coin and the change.
+    assert_eq!(tx.input.len(), 2);
+    let txid = test_framework
+        .get_client()
+        .send_raw_transaction(&tx)
+        .unwrap();
+    let mempool_entry = test_framework
+        .get_client()
+        .get_mempool_entry(&txid)
+        .unwrap();
+    assert!(mempool_entry.fees.base >= Amount::from_sat(2 * mempool_entry.vsize));
+
+    // ---- Cleanup ----
+    drop(taker_write);
+    directory_server_instance.shutdown().unwrap();
+    test_framework.stop();
+}
@@ -0,0 +1,107 @@
+#![cfg(feature = "integration-test")]
+use std::collections::HashMap;
+
+use bitcoin::{Address, Amount, Network};
+use bitcoind::bitcoincore_rpc::RpcApi;
+use coinswap::{
+    utill::ConnectionType,
+    wallet::{SendAmount, WalletError},
+};
+
+mod test_framework;
+use test_framework::*;
+
+/// Test batching payments to several recipients in one transaction, with a single change output.
+#[tokio::test]
+async fn test_direct_send_multi() {
+    // ---- Setup ----
+    let (test_framework, taker, _, directory_server_instance) =
+        TestFramework::init(None, HashMap::new(), None, ConnectionType::CLEARNET, None).await;
+
+    let mut taker_write = taker.write().unwrap();
+    let wallet = taker_write.get_wallet_mut();
+
+    test_framework.fund_wallet(wallet, &[Amount::from_sat(1_000_000)]);
+
+    let recipients = (0..3)
+        .map(|_| {
+            test_framework
+                .get_client()
+                .get_new_address(None, None)
+                .unwrap()
+                .assume_checked()
+        })
+        .collect::<Vec<_>>();
+    let coins = wallet.spendable_utxos().unwrap();
+    let fee = Amount::from_sat(1000);
+
+    // ---- Invalid recipient lists ----
+    let mainnet_address = Address::p2wpkh(
+        &bitcoin::CompressedPublicKey::from_slice(&[0x02; 33]).unwrap(),
+        Network::Bitcoin,
+    );
+    assert!(matches!(
+        wallet.spend_from_wallet_multi(
+            fee,
+            vec![
+                (
+                    recipients[0].clone(),
+                    SendAmount::Amount(Amount::from_sat(1000))
+                ),
+                (
+                    mainnet_address.clone(),
+                    SendAmount::Amount(Amount::from_sat(1000))
+                ),
+            ],
+            &coins,
+        ),
+        Err(WalletError::NetworkMismatch { address, expected: Network::Regtest })
+            if address == mainnet_address
+    ));
+    assert!(wallet
+        .spend_from_wallet_multi(
+            fee,
+            vec![
+                (recipients[0].clone(), SendAmount::Max),
+                (recipients[1].clone(), SendAmount::Max),
+            ],
+            &coins,
+        )
+        .is_err());
+
+    // ---- Pay 3 recipients ----
+    let amounts = [100_000, 200_000, 300_000].map(Amount::from_sat);
+    let tx = wallet
+        .spend_from_wallet_multi(
+            fee,
+            recipients
+                .iter()
+                .cloned()
+                .zip(amounts.iter().map(|a| SendAmount::Amount(*a)))
+                .collect(),
+            &coins,
+        )
+        .unwrap();
+
+    assert_eq!(tx.output.len(), 4);
+    for (output, (recipient, amount)) in tx.output.iter().zip(recipients.iter().zip(amounts)) {
+        assert_eq!(output.script_pubkey, recipient.script_pubkey());
+        assert_eq!(output.value, amount);
+    }
+    assert_eq!(tx.output[3].value, Amount::from_sat(399_000));
+
+    let txid = test_framework
+        .get_client()
+        .send_raw_transaction(&tx)
+        .unwrap();
+    let mempool_entry = test_framework
+        .get_client()
+        .get_mempool_entry(&txid)
+        .unwrap();
+    assert_eq!(mempool_entry.fees.base, fee);
+
+    // ---- Cleanup ----
+    drop(taker_write);
+    directory_server_instance.shutdown().unwrap();
+    test_framework.stop();
+}
@@ -0,0 +1,134 @@
+#![cfg(feature = "integration-test")]
+use std::collections::HashMap;
+
+use bitcoin::Amount;
+use bitcoind::bitcoincore_rpc::RpcApi;
+use coinswap::{
+    utill::ConnectionType,
+    wallet::{Destination, SendAmount},
+};
+
+mod test_framework;
+use test_framework::*;
+
+/// Test reviewing a direct send before signing it. The summary must match the signed transaction,
+/// and previewing again must give the same outputs, as a preview doesn't hand out addresses.
+/// Then test a sweep at a fee rate, with the fee computed from the estimated vsize.
+#[tokio::test]
+async fn test_direct_send_preview() {
+    // ---- Setup ----
+    let (test_framework, taker, _, directory_server_instance) =
+        TestFramework::init(None, HashMap::new(), None, ConnectionType::CLEARNET, None).await;
+
+    let mut taker_write = taker.write().unwrap();
+    let wallet = taker_write.get_wallet_mut();
+
+    test_framework.fund_wallet(wallet, &[Amount::from_sat(100_000); 2]);
+
+    // ---- Preview the send ----
+    let coins = wallet.spendable_utxos().unwrap();
+    let destination = test_framework
+        .get_client()
+        .get_new_address(None, None)
+        .unwrap()
+        .assume_checked();
+    let (mut tx, summary) = wallet
+        .build_direct_send_unsigned(
+            Amount::from_sat(1000),
+            SendAmount::Amount(Amount::from_sat(150_000)),
+            Destination::Address(destination.clone()),
+            &coins,
+        )
+        .unwrap();
+
+    assert_eq!(summary.inputs.len(), 2);
+    assert_eq!(summary.fee, Amount::from_sat(1000));
+    assert_eq!(summary.change, Amount::from_sat(49_000));
+    assert_eq!(summary.outputs, tx.output);
+    assert_eq!(
+        summary.outputs[0].script_pubkey,
+        destination.script_pubkey()
+    );
+    assert!(tx.input.iter().all(|input| input.witness.is_empty()));
+
+    // ---- Preview again: same change address ----
+    let (_, again) = wallet
+        .build_direct_send_unsigned(
+            Amount::from_sat(1000),
+            SendAmount::Amount(Amount::from_sat(150_000)),
+            Destination::Address(destination.clone()),
+            &coins,
+        )
+        .unwrap();
+    assert_eq!(again.outputs, summary.outputs);
+
+    // ---- A wallet destination previews the next receive address ----
+    let (_, to_wallet) = wallet
+        .build_direct_send_unsigned(
+            Amount::from_sat(1000),
+            SendAmount::Amount(Amount::from_sat(150_000)),
+            Destination::Wallet,
+            &coins,
+        )
+        .unwrap();
+    let next_address = wallet.get_next_external_address().unwrap();
+    assert_eq!(
+        to_wallet.outputs[0].script_pubkey,
+        next_address.script_pubkey()
+    );
+
+    // ---- Sign and broadcast the reviewed transaction ----
+    wallet
+        .sign_transaction(&mut tx, coins.iter().map(|(_, info)| info.clone()))
+        .unwrap();
+    let input_value = summary
+        .inputs
+        .iter()
+        .map(|(_, value)| *value)
+        .sum::<Amount>();
+    let output_value = tx.output.iter().map(|output| output.value).sum::<Amount>();
+    assert_eq!(input_value - output_value, summary.fee);
+    assert!(summary.fee_rate >= summary.fee.to_sat() as f64 / tx.vsize() as f64);
+
+    let txid = test_framework
+        .get_client()
+        .send_raw_transaction(&tx)
+        .unwrap();
+    let mempool_entry = test_framework
+        .get_client()
+        .get_mempool_entry(&txid)
+        .unwrap();
+    assert_eq!(mempool_entry.fees.base, summary.fee);
+    test_framework.generate_blocks(1);
+    wallet.sync().unwrap();
+
+    // ---- Sweep the change at a fee rate ----
+    let coins = wallet.spendable_utxos().unwrap();
+    let send_amount = SendAmount::Max;
+    let destination = Destination::Address(destination);
+    let estimated_vsize = wallet
+        .estimate_direct_send_vsize(&send_amount, &destination, &coins)
+        .unwrap();
+    let tx = wallet
+        .spend_from_wallet_at_fee_rate(2, send_amount, destination, &coins)
+        .unwrap();
+    let txid = test_framework
+        .get_client()
+        .send_raw_transaction(&tx)
+        .unwrap();
+    let mempool_entry = test_framework
+        .get_client()
+        .get_mempool_entry(&txid)
+        .unwrap();
+    assert_eq!(
+        mempool_entry.fees.base,
+        Amount::from_sat(2 * estimated_vsize)
+    );
+    assert!(mempool_entry.vsize <= estimated_vsize);
+    assert!(mempool_entry.vsize + 1 >= estimated_vsize);
+
+    // ---- Cleanup ----
+    drop(taker_write);
+    directory_server_instance.shutdown().unwrap();
+    test_framework.stop();
+}
@@ -0,0 +1,150 @@
+#![cfg(feature = "integration-test")]
+use std::collections::HashMap;
+
+use bitcoin::{
+    hashes::{hash160::Hash as Hash160, Hash},
+    psbt::Psbt,
+    Address, Amount, Network, OutPoint, Witness,
+};
+use bitcoind::bitcoincore_rpc::RpcApi;
+use coinswap::{
+    protocol::contract::{
+        create_contract_redeemscript, create_multisig_redeemscript, create_receivers_contract_tx,
+    },
+    utill::{generate_keypair, redeemscript_to_scriptpubkey, ConnectionType},
+    wallet::{Destination, SendAmount, UTXOSpendInfo, Wallet},
+};
+
+mod test_framework;
+use test_framework::*;
+
+/// Test the PSBT flow of direct sends: export, sign, finalize. The result must be the transaction the
+/// wallet signs directly.
+#[tokio::test]
+async fn test_direct_send_psbt() {
+    // ---- Setup ----
+    let (test_framework, taker, _, directory_server_instance) =
+        TestFramework::init(None, HashMap::new(), None, ConnectionType::CLEARNET, None).await;
+
+    let mut taker_write = taker.write().unwrap();
+    let wallet = taker_write.get_wallet_mut();
+
+    // A seed coin.
+    let address = wallet.get_next_external_address().unwrap();
+    test_framework.send_to_address(&address, Amount::from_sat(100_000));
+
+    // And a swapcoin, with both multisig keys.
+    let (my_pubkey, my_privkey) = generate_keypair();
+    let (other_pubkey, other_privkey) = generate_keypair();
+    let multisig_redeemscript = create_multisig_redeemscript(&my_pubkey, &other_pubkey);
+    let multisig_address = Address::from_script(
+        &redeemscript_to_scriptpubkey(&multisig_redeemscript),
+        Network::Regtest,
+    )
+    .unwrap();
+    let funding_txid = test_framework
+        .get_client()
+        .send_to_address(
+            &multisig_address,
+            Amount::from_sat(200_000),
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+    test_framework.generate_blocks(1);
+
+    let mut funding_tx = test_framework
+        .get_client()
+        .get_raw_transaction(&funding_txid, None)
+        .unwrap();
+    funding_tx
+        .input
+        .iter_mut()
+        .for_each(|input| input.witness = Witness::new());
+    let vout = funding_tx
+        .output
+        .iter()
+        .position(|output| output.script_pubkey == multisig_address.script_pubkey())
+        .unwrap();
+    let mut funding_psbt = Psbt::from_unsigned_tx(funding_tx).unwrap();
+    funding_psbt.outputs[vout].witness_script = Some(multisig_redeemscript.clone());
+    let (hashlock_pubkey, hashlock_privkey) = generate_keypair();
+    let (timelock_pubkey, _) = generate_keypair();
+    let contract_redeemscript = create_contract_redeemscript(
+        &hashlock_pubkey,
+        &timelock_pubkey,
+        &Hash160::hash(&[0u8; 32]),
+        &20,
+    );
+    let contract_tx = create_receivers_contract_tx(
+        OutPoint::new(funding_txid, vout as u32),
+        Amount::from_sat(200_000),
+        &contract_redeemscript,
+    );
+    wallet
+        .add_utxo_from_external_psbt(
+            &funding_psbt,
+            my_privkey,
+            other_privkey,
+            contract_tx,
+            contract_redeemscript,
+            hashlock_privkey,
+        )
+        .unwrap();
+    wallet.sync().unwrap();
+
+    let coins = wallet.spendable_utxos().unwrap();
+    assert_eq!(coins.len(), 2);
+    assert!(coins
+        .iter()
+        .any(|(_, info)| matches!(info, UTXOSpendInfo::SwapCoin { .. })));
+
+    // ---- Export the PSBT ----
+    let destination = test_framework
+        .get_client()
+        .get_new_address(None, None)
+        .unwrap()
+        .assume_checked();
+    let mut psbt = wallet
+        .create_direct_send_psbt(
+            Amount::from_sat(1000),
+            SendAmount::Amount(Amount::from_sat(250_000)),
+            Destination::Address(destination),
+            &coins,
+        )
+        .unwrap();
+    for (input, (utxo, spend_info)) in psbt.inputs.iter().zip(&coins) {
+        assert_eq!(input.witness_utxo.as_ref().unwrap().value, utxo.amount);
+        match spend_info {
+            UTXOSpendInfo::SeedCoin { .. } => {
+                assert_eq!(input.bip32_derivation.len(), 1);
+                assert!(input.witness_script.is_none());
+            }
+            _ => assert_eq!(input.witness_script, Some(multisig_redeemscript.clone())),
+        }
+    }
+
+    // ---- Sign, finalize, and compare with the direct signing ----
+    let mut expected_tx = psbt.unsigned_tx.clone();
+    wallet
+        .sign_transaction(&mut expected_tx, coins.iter().map(|(_, info)| info.clone()))
+        .unwrap();
+
+    wallet.sign_psbt(&mut psbt).unwrap();
+    let tx = Wallet::finalize_psbt(psbt).unwrap();
+    assert_eq!(tx, expected_tx);
+
+    test_framework
+        .get_client()
+        .send_raw_transaction(&tx)
+        .unwrap();
+
+    // ---- Cleanup ----
+    drop(taker_write);
+    directory_server_instance.shutdown().unwrap();
+    test_framework.stop();
+}
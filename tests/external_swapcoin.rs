@@ -0,0 +1,153 @@
+#![cfg(feature = "integration-test")]
+use std::collections::HashMap;
+
+use bitcoin::{
+    hashes::{hash160::Hash as Hash160, Hash},
+    psbt::Psbt,
+    Address, Amount, Network, OutPoint, Witness,
+};
+use bitcoind::bitcoincore_rpc::RpcApi;
+use coinswap::{
+    protocol::contract::{
+        create_contract_redeemscript, create_multisig_redeemscript, create_receivers_contract_tx,
+    },
+    utill::{generate_keypair, redeemscript_to_scriptpubkey, ConnectionType},
+    wallet::UTXOSpendInfo,
+};
+
+mod test_framework;
+use test_framework::*;
+
+/// Test registering a swapcoin funded outside the wallet, from its funding PSBT.
+#[tokio::test]
+async fn test_add_utxo_from_external_psbt() {
+    // ---- Setup ----
+    let (test_framework, taker, _, directory_server_instance) =
+        TestFramework::init(None, HashMap::new(), None, ConnectionType::CLEARNET, None).await;
+
+    let mut taker_write = taker.write().unwrap();
+    let wallet = taker_write.get_wallet_mut();
+
+    // ---- Fund a 2-of-2 multisig externally ----
+    let (my_pubkey, my_privkey) = generate_keypair();
+    let (other_pubkey, other_privkey) = generate_keypair();
+    let multisig_redeemscript = create_multisig_redeemscript(&my_pubkey, &other_pubkey);
+    let multisig_address = Address::from_script(
+        &redeemscript_to_scriptpubkey(&multisig_redeemscript),
+        Network::Regtest,
+    )
+    .unwrap();
+
+    let funding_amount = Amount::from_sat(500_000);
+    let funding_txid = test_framework
+        .get_client()
+        .send_to_address(
+            &multisig_address,
+            funding_amount,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+    test_framework.generate_blocks(1);
+
+    let mut funding_tx = test_framework
+        .get_client()
+        .get_raw_transaction(&funding_txid, None)
+        .unwrap();
+    funding_tx
+        .input
+        .iter_mut()
+        .for_each(|input| input.witness = Witness::new());
+    let vout = funding_tx
+        .output
+        .iter()
+        .position(|output| output.script_pubkey == multisig_address.script_pubkey())
+        .unwrap();
+    let mut psbt = Psbt::from_unsigned_tx(funding_tx).unwrap();
+    psbt.outputs[vout].witness_script = Some(multisig_redeemscript.clone());
+
+    let (hashlock_pubkey, hashlock_privkey) = generate_keypair();
+    let (timelock_pubkey, _) = generate_keypair();
+    let contract_redeemscript = create_contract_redeemscript(
+        &hashlock_pubkey,
+        &timelock_pubkey,
+        &Hash160::hash(&[0u8; 32]),
+        &20,
+    );
+    let contract_tx = create_receivers_contract_tx(
+        OutPoint::new(funding_txid, vout as u32),
+        funding_amount,
+        &contract_redeemscript,
+    );
+
+    // ---- Keys not matching the redeemscript are refused ----
+    assert!(wallet
+        .add_utxo_from_external_psbt(
+            &psbt,
+            my_privkey,
+            hashlock_privkey,
+            contract_tx.clone(),
+            contract_redeemscript.clone(),
+            hashlock_privkey,
+        )
+        .is_err());
+    assert_eq!(wallet.get_swapcoins_count(), 0);
+
+    // ---- A contract tx paying another contract is refused ----
+    let other_contract_tx = create_receivers_contract_tx(
+        OutPoint::new(funding_txid, vout as u32),
+        funding_amount,
+        &create_contract_redeemscript(
+            &hashlock_pubkey,
+            &timelock_pubkey,
+            &Hash160::hash(&[0u8; 32]),
+            &30,
+        ),
+    );
+    assert!(wallet
+        .add_utxo_from_external_psbt(
+            &psbt,
+            my_privkey,
+            other_privkey,
+            other_contract_tx,
+            contract_redeemscript.clone(),
+            hashlock_privkey,
+        )
+        .is_err());
+    assert_eq!(wallet.get_swapcoins_count(), 0);
+
+    // ---- Register the swapcoin ----
+    let outpoint = wallet
+        .add_utxo_from_external_psbt(
+            &psbt,
+            my_privkey,
+            other_privkey,
+            contract_tx,
+            contract_redeemscript,
+            hashlock_privkey,
+        )
+        .unwrap();
+    assert_eq!(outpoint, OutPoint::new(funding_txid, vout as u32));
+    wallet.sync().unwrap();
+
+    let swapcoin_utxos = wallet.list_swap_coin_utxo_spend_info(None).unwrap();
+    assert_eq!(swapcoin_utxos.len(), 1);
+    let (utxo, spend_info) = &swapcoin_utxos[0];
+    assert_eq!(utxo.txid, funding_txid);
+    assert_eq!(utxo.vout, vout as u32);
+    assert_eq!(utxo.amount, funding_amount);
+    assert!(matches!(
+        spend_info,
+        UTXOSpendInfo::SwapCoin { multisig_redeemscript: redeemscript }
+            if *redeemscript == multisig_redeemscript
+    ));
+
+    // ---- Cleanup ----
+    drop(taker_write);
+    directory_server_instance.shutdown().unwrap();
+    test_framework.stop();
+}
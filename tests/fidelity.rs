@@ -1,9 +1,8 @@
 #![cfg(feature = "integration-test")]
 use bitcoin::{absolute::LockTime, Amount};
 use coinswap::{
-    maker::{error::MakerError, start_maker_server, MakerBehavior},
+    maker::{start_maker_server, MakerBehavior},
     utill::ConnectionType,
-    wallet::{FidelityError, WalletError},
 };
 
 mod test_framework;
@@ -22,7 +21,7 @@ use std::{thread, time::Duration};
 /// on the wallet. Both of them are performed here. At the start of the maker server it will try to create a fidelity
 /// bond with value and timelock provided in the configuration (default: value = 5_000_000 sats, locktime = 100 block).
 ///
-/// Maker server will error if not enough balance is present to create fidelity bond.
+/// Maker server will wait for funds if not enough balance is present to create fidelity bond.
 /// A custom fidelity bond can be create using the `create_fidelity()` API.
 #[tokio::test]
 async fn test_fidelity() {
@@ -35,6 +34,7 @@ async fn test_fidelity() {
         makers_config_map.into(),
         None,
         ConnectionType::CLEARNET,
+        None,
     )
     .await;
 
@@ -43,7 +43,7 @@ async fn test_fidelity() {
     // ----- Test -----
 
     // Give insufficient fund to maker and start the server.
-    // This should return Error of Insufficient fund.
+    // The server waits for more funds, without creating a bond, until it is shut down.
     let maker_addrs = maker
         .get_wallet()
         .write()
@@ -58,15 +58,13 @@ async fn test_fidelity() {
 
     thread::sleep(Duration::from_secs(5));
     maker.shutdown().unwrap();
-    let expected_error = maker_thread.join().unwrap();
-
-    matches!(
-        expected_error.err().unwrap(),
-        MakerError::Wallet(WalletError::Fidelity(FidelityError::InsufficientFund {
-            available: 4000000,
-            required: 5000000,
-        }))
-    );
+    assert!(maker_thread.join().unwrap().is_ok());
+    assert!(maker
+        .get_wallet()
+        .read()
+        .unwrap()
+        .get_fidelity_bonds()
+        .is_empty());
 
     // Give Maker more funds and check fidelity bond is created at the restart of server.
     test_framework.send_to_address(&maker_addrs, Amount::from_btc(0.04).unwrap());
@@ -0,0 +1,112 @@
+#![cfg(feature = "integration-test")]
+use std::collections::HashMap;
+
+use bitcoin::{absolute::LockTime, Amount};
+use bitcoind::bitcoincore_rpc::RpcApi;
+use coinswap::{
+    utill::ConnectionType,
+    wallet::{Destination, FidelityError, SendAmount, SpendOptions, Wallet, WalletError},
+};
+
+mod test_framework;
+use test_framework::*;
+
+/// Test sweeping a matured fidelity bond through a direct send, along with a regular coin.
+#[tokio::test]
+async fn test_fidelity_direct_send() {
+    // ---- Setup ----
+    let (test_framework, taker, _, directory_server_instance) =
+        TestFramework::init(None, HashMap::new(), None, ConnectionType::CLEARNET, None).await;
+
+    let mut taker_write = taker.write().unwrap();
+    let wallet = taker_write.get_wallet_mut();
+
+    test_framework.fund_wallet(wallet, &[Amount::from_btc(0.05).unwrap()]);
+
+    let locktime = LockTime::from_height(test_framework.get_block_count() as u32 + 1000).unwrap();
+    let index = wallet
+        .create_fidelity(Amount::from_sat(1_000_000), locktime)
+        .unwrap();
+    // Import the bond into the core wallet.
+    wallet.sync().unwrap();
+
+    let fee = Amount::from_sat(1000);
+    let signer = wallet.signer_handle();
+    let coins = |wallet: &Wallet| {
+        let mut coins = wallet.list_fidelity_spend_info(None).unwrap();
+        coins.append(&mut wallet.spendable_utxos().unwrap());
+        coins
+    };
+
+    // ---- Bonds are only spent on request, once matured ----
+    let spendable = coins(wallet);
+    assert_eq!(spendable.len(), 2);
+    assert!(matches!(
+        wallet.spend_from_wallet(fee, SendAmount::Max, Destination::Wallet, &spendable),
+        Err(WalletError::Protocol(_))
+    ));
+    assert!(matches!(
+        wallet.spend_from_wallet_with(
+            fee,
+            SendAmount::Max,
+            Destination::Wallet,
+            &spendable,
+            signer.as_ref(),
+            SpendOptions {
+                include_matured_fidelity: true,
+                ..SpendOptions::default()
+            }
+        ),
+        Err(WalletError::Fidelity(FidelityError::BondNotMatured { .. }))
+    ));
+
+    // ---- Sweep the matured bond ----
+    let (_, bond, _) = wallet.list_fidelity_bonds().unwrap().remove(0);
+    let remaining_blocks = wallet.fidelity_bond_maturity_blocks(&bond).unwrap();
+    test_framework.generate_blocks(remaining_blocks as u64);
+
+    let spendable = coins(wallet);
+    let total = spendable
+        .iter()
+        .map(|(utxo, _)| utxo.amount)
+        .sum::<Amount>();
+    let tx = wallet
+        .spend_from_wallet_with(
+            fee,
+            SendAmount::Max,
+            Destination::Wallet,
+            &spendable,
+            signer.as_ref(),
+            SpendOptions {
+                include_matured_fidelity: true,
+                ..SpendOptions::default()
+            },
+        )
+        .unwrap();
+    assert_eq!(tx.input.len(), 2);
+    assert!(tx.lock_time.to_consensus_u32() >= locktime.to_consensus_u32());
+    assert_eq!(tx.output[0].value, total - fee);
+
+    let txid = test_framework
+        .get_client()
+        .send_raw_transaction(&tx)
+        .unwrap();
+    test_framework.generate_blocks(1);
+    assert!(test_framework
+        .get_client()
+        .get_raw_transaction_info(&txid, None)
+        .unwrap()
+        .confirmations
+        .is_some());
+
+    // The bond is marked spent once the sweep is seen in a sync.
+    assert!(!wallet.get_fidelity_bonds().get(&index).unwrap().2);
+    wallet.sync().unwrap();
+    assert!(wallet.get_fidelity_bonds().get(&index).unwrap().2);
+    assert!(wallet.list_fidelity_bonds().unwrap().is_empty());
+
+    // ---- Cleanup ----
+    drop(taker_write);
+    directory_server_instance.shutdown().unwrap();
+    test_framework.stop();
+}
@@ -0,0 +1,107 @@
+#![cfg(feature = "integration-test")]
+use std::collections::HashMap;
+
+use bitcoin::{absolute::LockTime, Amount, OutPoint};
+use bitcoind::bitcoincore_rpc::RpcApi;
+use coinswap::{
+    utill::ConnectionType,
+    wallet::{FidelityError, WalletError},
+};
+
+mod test_framework;
+use test_framework::*;
+
+/// Test listing fidelity bonds, tracking their maturity, and renewing them once matured.
+#[tokio::test]
+async fn test_fidelity_lifecycle() {
+    // ---- Setup ----
+    let (test_framework, taker, _, directory_server_instance) =
+        TestFramework::init(None, HashMap::new(), None, ConnectionType::CLEARNET, None).await;
+
+    let mut taker_write = taker.write().unwrap();
+    let wallet = taker_write.get_wallet_mut();
+
+    test_framework.fund_wallet(wallet, &[Amount::from_btc(0.05).unwrap()]);
+
+    // ---- Create a bond ----
+    let bond_amount = Amount::from_sat(1_000_000);
+    let locktime = LockTime::from_height(test_framework.get_block_count() as u32 + 1000).unwrap();
+    let index = wallet.create_fidelity(bond_amount, locktime).unwrap();
+
+    let bonds = wallet.list_fidelity_bonds().unwrap();
+    assert_eq!(bonds.len(), 1);
+    let (outpoint, bond, matured) = &bonds[0];
+    assert_eq!(*outpoint, wallet.get_fidelity_bonds()[&index].0.outpoint);
+    assert_eq!(bond.amount, bond_amount);
+    assert!(!matured);
+    let remaining_blocks = wallet.fidelity_bond_maturity_blocks(bond).unwrap();
+    assert!(remaining_blocks > 0 && remaining_blocks <= 1000);
+
+    // ---- An unmatured bond can't be redeemed nor renewed ----
+    assert!(matches!(
+        wallet.redeem_fidelity(index),
+        Err(WalletError::Fidelity(FidelityError::BondNotMatured { .. }))
+    ));
+    assert!(matches!(
+        wallet.renew_fidelity_bond(*outpoint, locktime),
+        Err(WalletError::Fidelity(FidelityError::BondNotMatured { .. }))
+    ));
+    assert!(matches!(
+        wallet.renew_fidelity_bond(OutPoint::null(), locktime),
+        Err(WalletError::Fidelity(FidelityError::BondDoesNotExist))
+    ));
+
+    // ---- Renew the bond once matured ----
+    let outpoint = *outpoint;
+    let bond = bond.clone();
+    test_framework.generate_blocks(remaining_blocks as u64);
+    assert!(wallet.fidelity_bond_maturity_blocks(&bond).unwrap() <= 0);
+    assert!(wallet.list_fidelity_bonds().unwrap()[0].2);
+
+    let new_locktime =
+        LockTime::from_height(test_framework.get_block_count() as u32 + 1000).unwrap();
+    let new_outpoint = wallet.renew_fidelity_bond(outpoint, new_locktime).unwrap();
+    assert_ne!(new_outpoint, outpoint);
+    assert!(wallet.get_fidelity_bonds().get(&index).unwrap().2);
+
+    // The old bond is spent, only the new one is listed.
+    let bonds = wallet.list_fidelity_bonds().unwrap();
+    assert_eq!(bonds.len(), 1);
+    let (listed_outpoint, new_bond, matured) = &bonds[0];
+    assert_eq!(*listed_outpoint, new_outpoint);
+    assert_eq!(new_bond.amount, bond_amount - Amount::from_sat(1000));
+    assert_eq!(new_bond.lock_time, new_locktime);
+    assert!(!matured);
+
+    // The old bond is spent straight into the new one.
+    let renewal_tx = test_framework
+        .get_client()
+        .get_raw_transaction(&new_bond.outpoint.txid, None)
+        .unwrap();
+    assert_eq!(renewal_tx.input.len(), 1);
+    assert_eq!(renewal_tx.input[0].previous_output, bond.outpoint);
+    assert_eq!(renewal_tx.output.len(), 1);
+
+    // ---- A bond too small to pay the renewal fee ----
+    wallet.sync().unwrap();
+    let small_locktime =
+        LockTime::from_height(test_framework.get_block_count() as u32 + 1).unwrap();
+    let small_index = wallet
+        .create_fidelity(Amount::from_sat(1000), small_locktime)
+        .unwrap();
+    test_framework.generate_blocks(1);
+    let small_outpoint = wallet.get_fidelity_bonds()[&small_index].0.outpoint;
+    assert!(matches!(
+        wallet.renew_fidelity_bond(small_outpoint, new_locktime),
+        Err(WalletError::Fidelity(FidelityError::InsufficientFund {
+            available: 1000,
+            ..
+        }))
+    ));
+    assert!(!wallet.get_fidelity_bonds().get(&small_index).unwrap().2);
+
+    // ---- Cleanup ----
+    drop(taker_write);
+    directory_server_instance.shutdown().unwrap();
+    test_framework.stop();
+}
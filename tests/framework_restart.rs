@@ -0,0 +1,25 @@
+#![cfg(feature = "integration-test")]
+use std::{collections::HashMap, thread, time::Duration};
+
+use coinswap::utill::ConnectionType;
+
+mod test_framework;
+use test_framework::*;
+
+/// Test starting and stopping the framework in a row. Stopping waits for the block generation
+/// thread, wherever it's at, so the node never goes away under it.
+#[tokio::test]
+async fn test_framework_restart() {
+    for i in 0..5 {
+        let (test_framework, _, _, directory_server_instance) =
+            TestFramework::init(None, HashMap::new(), None, ConnectionType::CLEARNET, None).await;
+
+        // Stop at different points of the block generation interval.
+        thread::sleep(Duration::from_millis(800 * i));
+        let block_count = test_framework.get_block_count();
+        assert!(block_count >= 101);
+
+        directory_server_instance.shutdown().unwrap();
+        test_framework.stop();
+    }
+}
@@ -0,0 +1,74 @@
+#![cfg(feature = "integration-test")]
+use std::collections::HashMap;
+
+use bitcoin::Amount;
+use bitcoind::bitcoincore_rpc::RpcApi;
+use coinswap::{protocol::contract::funding_tx_fee, utill::ConnectionType};
+
+mod test_framework;
+use test_framework::*;
+
+/// Test the funding txes pay the fee of their sats/kvbyte fee rate, the same Makers are paid for
+/// theirs, and get relayed.
+#[tokio::test]
+async fn test_funding_tx_fee() {
+    // ---- Setup ----
+    let (test_framework, taker, _, directory_server_instance) =
+        TestFramework::init(None, HashMap::new(), None, ConnectionType::CLEARNET, None).await;
+
+    let mut taker_write = taker.write().unwrap();
+    let wallet = taker_write.get_wallet_mut();
+
+    test_framework.fund_wallet(wallet, &[Amount::from_btc(0.05).unwrap(); 3]);
+
+    // ---- Build the funding txes at 2 sats/vbyte ----
+    let fee_rate = Amount::from_sat(2000);
+    let destinations = (0..3)
+        .map(|_| {
+            test_framework
+                .get_client()
+                .get_new_address(None, None)
+                .unwrap()
+                .assume_checked()
+        })
+        .collect::<Vec<_>>();
+    let result = wallet
+        .create_funding_txes(Amount::from_sat(500_000), &destinations, fee_rate)
+        .unwrap();
+
+    let fee = funding_tx_fee(fee_rate);
+    assert_eq!(fee, Amount::from_sat(744));
+    assert_eq!(result.funding_txes.len(), 3);
+    assert_eq!(result.total_miner_fee, 3 * fee.to_sat());
+
+    for tx in &result.funding_txes {
+        let input_value = tx
+            .input
+            .iter()
+            .map(|input| {
+                let prev_tx = test_framework
+                    .get_client()
+                    .get_raw_transaction(&input.previous_output.txid, None)
+                    .unwrap();
+                prev_tx.output[input.previous_output.vout as usize].value
+            })
+            .sum::<Amount>();
+        let output_value = tx.output.iter().map(|output| output.value).sum::<Amount>();
+        assert_eq!(input_value - output_value, fee);
+
+        let txid = test_framework
+            .get_client()
+            .send_raw_transaction(tx)
+            .unwrap();
+        let mempool_entry = test_framework
+            .get_client()
+            .get_mempool_entry(&txid)
+            .unwrap();
+        assert_eq!(mempool_entry.fees.base, fee);
+    }
+
+    // ---- Cleanup ----
+    drop(taker_write);
+    directory_server_instance.shutdown().unwrap();
+    test_framework.stop();
+}
@@ -0,0 +1,91 @@
+#![cfg(feature = "integration-test")]
+use std::collections::HashMap;
+
+use bitcoin::Amount;
+use coinswap::{
+    utill::ConnectionType,
+    wallet::{Destination, SendAmount, Wallet, WalletError},
+};
+
+mod test_framework;
+use test_framework::*;
+
+/// Test that coin selection enforces the maximum number of inputs per transaction, on a wallet
+/// holding many small coins.
+#[tokio::test]
+async fn test_max_inputs_per_tx() {
+    // ---- Setup ----
+    let (test_framework, taker, _, directory_server_instance) =
+        TestFramework::init(None, HashMap::new(), None, ConnectionType::CLEARNET, None).await;
+
+    let mut taker_write = taker.write().unwrap();
+    let wallet = taker_write.get_wallet_mut();
+
+    test_framework.fund_wallet(wallet, &[Amount::from_sat(10_000); 6]);
+
+    // ---- A send needing all the coins ----
+    let amount = Amount::from_sat(55_000);
+    assert_eq!(wallet.coin_select(amount).unwrap().len(), 6);
+
+    match wallet.coin_select_with_limit(amount, 4) {
+        Err(WalletError::TooManyInputs { required, max }) => {
+            assert_eq!(required, 6);
+            assert_eq!(max, 4);
+        }
+        other => panic!("expected TooManyInputs, got {:?}", other),
+    }
+    assert_eq!(wallet.coin_select_with_limit(amount, 6).unwrap().len(), 6);
+
+    // A smaller amount stays within the limit.
+    assert_eq!(
+        wallet
+            .coin_select_with_limit(Amount::from_sat(25_000), 4)
+            .unwrap()
+            .len(),
+        3
+    );
+
+    // ---- Sends and funding over the wallet limit ----
+    wallet.set_max_inputs_per_tx(2).unwrap();
+    assert!(matches!(
+        wallet.spend_from_wallet(
+            Amount::from_sat(1000),
+            SendAmount::Amount(amount),
+            Destination::Wallet,
+            &[],
+        ),
+        Err(WalletError::TooManyInputs {
+            required: 6,
+            max: 2
+        })
+    ));
+    let destinations = wallet.get_next_internal_addresses(2).unwrap();
+    assert!(matches!(
+        wallet.create_funding_txes(amount, &destinations, Amount::from_sat(1000)),
+        Err(WalletError::TooManyInputs { max: 2, .. })
+    ));
+
+    wallet.set_max_inputs_per_tx(6).unwrap();
+    let tx = wallet
+        .spend_from_wallet(
+            Amount::from_sat(1000),
+            SendAmount::Amount(amount),
+            Destination::Wallet,
+            &[],
+        )
+        .unwrap();
+    assert_eq!(tx.input.len(), 6);
+
+    // ---- Consolidation splits across transactions ----
+    let coins = wallet.spendable_utxos().unwrap();
+    let batches = Wallet::split_by_input_limit(&coins, 4);
+    assert_eq!(
+        batches.iter().map(|batch| batch.len()).collect::<Vec<_>>(),
+        vec![4, 2]
+    );
+
+    // ---- Cleanup ----
+    drop(taker_write);
+    directory_server_instance.shutdown().unwrap();
+    test_framework.stop();
+}
@@ -0,0 +1,154 @@
+#![cfg(feature = "integration-test")]
+use bitcoin::Amount;
+use coinswap::{
+    maker::{start_maker_server, MakerBehavior},
+    taker::{SwapParams, Taker, TakerBehavior},
+    utill::ConnectionType,
+    wallet::RPCConfig,
+};
+
+mod test_framework;
+use test_framework::*;
+
+use log::{info, warn};
+use std::{
+    sync::{Arc, RwLock},
+    thread,
+    time::Duration,
+};
+
+/// Test a Maker turning malicious between two swaps. The first swap completes normally. Maker 6102
+/// then closes after the proof of funding on the second swap, and the Taker recovers from it.
+///
+/// A Taker doesn't swap twice with the same makers, so the second swap is done by a second Taker.
+#[tokio::test]
+async fn test_maker_behavior_change_between_swaps() {
+    // ---- Setup ----
+    let makers_config_map = [
+        ((6102, None), MakerBehavior::Normal),
+        ((16102, None), MakerBehavior::Normal),
+    ];
+
+    let (test_framework, taker, makers, directory_server_instance) = TestFramework::init(
+        None,
+        makers_config_map.into(),
+        None,
+        ConnectionType::CLEARNET,
+        None,
+    )
+    .await;
+
+    warn!("Running Test: Maker 6102 turns malicious on the second swap");
+
+    let second_taker = Arc::new(RwLock::new(
+        Taker::init(
+            taker
+                .read()
+                .unwrap()
+                .get_wallet()
+                .get_file_path()
+                .ancestors()
+                .nth(3)
+                .map(|temp_dir| temp_dir.join("second-taker")),
+            None,
+            Some(RPCConfig::from(test_framework.as_ref())),
+            TakerBehavior::Normal,
+            Some(ConnectionType::CLEARNET),
+            false,
+        )
+        .unwrap(),
+    ));
+
+    // Fund the Takers and Makers with 3 utxos of 0.05 btc each, plus the Makers' fidelity coins.
+    for taker in [&taker, &second_taker] {
+        test_framework.fund_wallet(
+            taker.write().unwrap().get_wallet_mut(),
+            &[Amount::from_btc(0.05).unwrap(); 3],
+        );
+    }
+    makers.iter().for_each(|maker| {
+        test_framework.fund_wallet(
+            &mut maker.get_wallet().write().unwrap(),
+            &[Amount::from_btc(0.05).unwrap(); 4],
+        );
+    });
+
+    // ---- Start Servers ----
+    let maker_threads = makers
+        .iter()
+        .map(|maker| {
+            let maker_clone = maker.clone();
+            thread::spawn(move || {
+                start_maker_server(maker_clone).unwrap();
+            })
+        })
+        .collect::<Vec<_>>();
+
+    makers.iter().for_each(|maker| {
+        while !*maker.is_setup_complete.read().unwrap() {
+            info!("Waiting for maker setup completion");
+            thread::sleep(Duration::from_secs(10));
+        }
+    });
+
+    let swap_params = SwapParams {
+        send_amount: Amount::from_sat(500000),
+        maker_count: 2,
+        tx_count: 3,
+        required_confirms: 1,
+        fee_rate: Amount::from_sat(1000),
+        wait_for_confirms: 0,
+        refund_locktime: None,
+        refund_locktime_step: None,
+    };
+
+    let run_swap = |taker: &Arc<RwLock<Taker>>| {
+        let taker_clone = taker.clone();
+        thread::spawn(move || {
+            taker_clone
+                .write()
+                .unwrap()
+                .do_coinswap(swap_params)
+                .unwrap();
+        })
+        .join()
+        .unwrap();
+    };
+
+    // ---- First swap, all Makers behave ----
+    run_swap(&taker);
+    assert_eq!(taker.read().unwrap().get_wallet().get_swapcoins_count(), 6);
+    assert!(taker.read().unwrap().get_bad_makers().is_empty());
+
+    // ---- Second swap, 6102 closes after funding ----
+    // Wait for the Makers to finish processing the first swap.
+    let naughty_maker = makers
+        .iter()
+        .find(|maker| maker.config.port == 6102)
+        .unwrap();
+    while naughty_maker
+        .set_behavior(MakerBehavior::CloseAtProofOfFunding)
+        .is_err()
+    {
+        info!("Waiting for the first swap to conclude on the maker side");
+        thread::sleep(Duration::from_secs(1));
+    }
+    run_swap(&second_taker);
+
+    // The Taker recovered, and banned 6102.
+    assert!(second_taker.read().unwrap().list_active_swaps().is_empty());
+    assert_eq!(
+        second_taker.read().unwrap().get_bad_makers()[0]
+            .address
+            .to_string(),
+        format!("127.0.0.1:{}", 6102)
+    );
+
+    // ---- Cleanup ----
+    makers.iter().for_each(|maker| maker.shutdown().unwrap());
+    maker_threads
+        .into_iter()
+        .for_each(|thread| thread.join().unwrap());
+    directory_server_instance.shutdown().unwrap();
+    test_framework.stop();
+}
@@ -0,0 +1,77 @@
+#![cfg(feature = "integration-test")]
+use bitcoin::Amount;
+use coinswap::{
+    maker::{start_maker_server, MakerBehavior},
+    utill::ConnectionType,
+};
+
+mod test_framework;
+use log::info;
+use std::{thread, time::Duration};
+use test_framework::*;
+
+/// Test that a Maker started with an empty wallet waits for funds, and creates its fidelity bond
+/// once they arrive.
+#[tokio::test]
+async fn test_unfunded_maker_waits_for_funds() {
+    // ---- Setup ----
+    let makers_config_map = [((6102, None), MakerBehavior::Normal)];
+
+    let (test_framework, _, makers, directory_server_instance) = TestFramework::init(
+        None,
+        makers_config_map.into(),
+        None,
+        ConnectionType::CLEARNET,
+        None,
+    )
+    .await;
+    let maker = makers[0].clone();
+
+    // ---- Start the unfunded Maker ----
+    let maker_clone = maker.clone();
+    let maker_thread = thread::spawn(move || {
+        start_maker_server(maker_clone).unwrap();
+    });
+
+    // The Maker keeps waiting, without a bond.
+    thread::sleep(Duration::from_secs(20));
+    assert!(!*maker.is_setup_complete.read().unwrap());
+    assert_eq!(
+        maker
+            .get_wallet()
+            .read()
+            .unwrap()
+            .get_highest_fidelity_index()
+            .unwrap(),
+        None
+    );
+
+    // ---- Fund the Maker ----
+    let maker_address = maker
+        .get_wallet()
+        .write()
+        .unwrap()
+        .get_next_external_address()
+        .unwrap();
+    test_framework.send_to_address(&maker_address, Amount::from_btc(0.1).unwrap());
+    test_framework.generate_blocks(1);
+
+    // The bond gets created, and the setup completes.
+    while !*maker.is_setup_complete.read().unwrap() {
+        info!("Waiting for maker setup completion");
+        thread::sleep(Duration::from_secs(10));
+    }
+    assert!(maker
+        .get_wallet()
+        .read()
+        .unwrap()
+        .get_highest_fidelity_index()
+        .unwrap()
+        .is_some());
+
+    // ---- Cleanup ----
+    maker.shutdown().unwrap();
+    maker_thread.join().unwrap();
+    directory_server_instance.shutdown().unwrap();
+    test_framework.stop();
+}
@@ -0,0 +1,236 @@
+#![cfg(feature = "integration-test")]
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::Path,
+    sync::Arc,
+    thread,
+    time::{Duration, Instant},
+};
+
+use bitcoin::{Amount, OutPoint};
+use coinswap::{
+    maker::{start_maker_server, Maker, MakerBehavior},
+    protocol::messages::{
+        MakerToTakerMessage, TakerHello, TakerToMakerMessage, PROTOCOL_VERSION_MAX,
+        PROTOCOL_VERSION_MIN,
+    },
+    taker::{SwapParams, TakerBehavior},
+    utill::{read_maker_message, send_message, ConnectionType},
+    wallet::RPCConfig,
+};
+use tokio::{
+    io::{AsyncReadExt, BufReader},
+    net::TcpStream,
+};
+
+mod test_framework;
+use log::info;
+use test_framework::*;
+
+const IDLE_CONNECTION_TIMEOUT: u64 = 10;
+
+/// Exchange the hellos with the Maker.
+async fn say_hello(stream: &mut TcpStream) {
+    let (read_half, mut write_half) = stream.split();
+    let mut reader = BufReader::new(read_half);
+    assert!(matches!(
+        read_maker_message(&mut reader).await.unwrap(),
+        MakerToTakerMessage::MakerHello(_)
+    ));
+    send_message(
+        &mut write_half,
+        &TakerToMakerMessage::TakerHello(TakerHello {
+            protocol_version_min: PROTOCOL_VERSION_MIN,
+            protocol_version_max: PROTOCOL_VERSION_MAX,
+        }),
+    )
+    .await
+    .unwrap();
+}
+
+/// Init a Maker with a short idle connection timeout, funded for its fidelity bond and a swap.
+fn init_maker(
+    test_framework: &TestFramework,
+    data_dir: &Path,
+    port: u16,
+    rpc_port: u16,
+) -> Arc<Maker> {
+    let maker_dir = data_dir.join("maker").join(port.to_string());
+    fs::create_dir_all(&maker_dir).unwrap();
+    fs::write(
+        maker_dir.join("config.toml"),
+        format!(
+            "[maker_config]\nidle_connection_timeout = {}\n",
+            IDLE_CONNECTION_TIMEOUT
+        ),
+    )
+    .unwrap();
+    let maker = Arc::new(
+        Maker::init(
+            Some(data_dir.to_path_buf()),
+            Some(format!("maker{}", port)),
+            Some(RPCConfig::from(test_framework)),
+            Some(port),
+            Some(rpc_port),
+            None,
+            Some(ConnectionType::CLEARNET),
+            MakerBehavior::Normal,
+            true,
+        )
+        .unwrap(),
+    );
+    assert_eq!(
+        maker.config.idle_connection_timeout,
+        IDLE_CONNECTION_TIMEOUT
+    );
+
+    test_framework.fund_wallet(
+        &mut maker.get_wallet().write().unwrap(),
+        &[Amount::from_btc(0.05).unwrap(); 4],
+    );
+    maker
+}
+
+/// The coins a Maker can spend, the locked ones left out.
+fn spendable_outpoints(maker: &Maker) -> HashSet<OutPoint> {
+    maker
+        .get_wallet()
+        .read()
+        .unwrap()
+        .spendable_utxos()
+        .unwrap()
+        .iter()
+        .map(|(utxo, _)| OutPoint::new(utxo.txid, utxo.vout))
+        .collect()
+}
+
+/// A Taker connects, sends its hello, then goes silent. The Maker closes the connection after its
+/// idle connection timeout, and keeps serving new Takers.
+///
+/// Another Taker stalls after the proof of funding, when the Maker has reserved the inputs of its
+/// funding transactions. The Maker closes the connection too, and releases the inputs and the swapcoins.
+#[tokio::test]
+async fn test_maker_idle_connection_timeout() {
+    // ---- Setup ----
+    let (test_framework, taker, _, directory_server_instance) = TestFramework::init(
+        None,
+        HashMap::new(),
+        Some(TakerBehavior::StallAfterProofOfFunding),
+        ConnectionType::CLEARNET,
+        None,
+    )
+    .await;
+
+    // Makers with a short idle connection timeout.
+    let data_dir = std::env::temp_dir().join(".coinswap-idle-timeout");
+    let _ = fs::remove_dir_all(&data_dir);
+    let makers = [(6102, 3501), (6104, 3503)]
+        .map(|(port, rpc_port)| init_maker(&test_framework, &data_dir, port, rpc_port));
+
+    // Fund the Taker with 3 utxos of 0.05 btc.
+    test_framework.fund_wallet(
+        taker.write().unwrap().get_wallet_mut(),
+        &[Amount::from_btc(0.05).unwrap(); 3],
+    );
+
+    let maker_threads = makers
+        .iter()
+        .map(|maker| {
+            let maker_clone = maker.clone();
+            thread::spawn(move || {
+                start_maker_server(maker_clone).unwrap();
+            })
+        })
+        .collect::<Vec<_>>();
+    for maker in &makers {
+        while !*maker.is_setup_complete.read().unwrap() {
+            info!("Waiting for maker setup completion");
+            thread::sleep(Duration::from_secs(10));
+        }
+    }
+
+    // ---- A silent Taker ----
+    let port = makers[0].config.port;
+    let mut stream = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+    say_hello(&mut stream).await;
+    let silent_since = Instant::now();
+
+    // The Maker closes the connection, within the configured window.
+    let mut buf = [0u8; 1];
+    assert_eq!(stream.read(&mut buf).await.unwrap(), 0);
+    let closed_after = silent_since.elapsed();
+    assert!(closed_after >= Duration::from_secs(IDLE_CONNECTION_TIMEOUT - 1));
+    assert!(closed_after < Duration::from_secs(IDLE_CONNECTION_TIMEOUT + 5));
+
+    // Nothing is held for the silent Taker, and new Takers are served.
+    assert!(makers[0].list_active_swaps().unwrap().is_empty());
+    let mut stream = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+    say_hello(&mut stream).await;
+    drop(stream);
+
+    // ---- A Taker stalling after the proof of funding ----
+    let org_spendable = makers
+        .iter()
+        .map(|m| spendable_outpoints(m))
+        .collect::<Vec<_>>();
+
+    let swap_params = SwapParams {
+        send_amount: Amount::from_sat(500000),
+        maker_count: 2,
+        tx_count: 3,
+        required_confirms: 1,
+        fee_rate: Amount::from_sat(1000),
+        wait_for_confirms: 0,
+        refund_locktime: None,
+        refund_locktime_step: None,
+    };
+    let taker_clone = taker.clone();
+    let taker_thread = thread::spawn(move || {
+        taker_clone
+            .write()
+            .unwrap()
+            .do_coinswap(swap_params)
+            .unwrap();
+    });
+
+    // The first Maker reserves the inputs of its funding transactions, for as long as the Taker stalls.
+    // The inputs are reserved just before the swapcoins are saved, so wait for both.
+    loop {
+        if (0..makers.len()).any(|i| {
+            spendable_outpoints(&makers[i]) != org_spendable[i]
+                && makers[i].get_wallet().read().unwrap().get_swapcoins_count() > 0
+        }) {
+            break;
+        }
+        assert!(
+            !taker_thread.is_finished(),
+            "No Maker reserved its funding inputs and saved the swapcoins"
+        );
+        thread::sleep(Duration::from_millis(500));
+    }
+
+    // Once the connection is closed, the Taker recovers its funding with its contracts.
+    taker_thread.join().unwrap();
+
+    // The funding inputs are unlocked, and the swap is forgotten.
+    for (maker, org_spendable) in makers.iter().zip(org_spendable) {
+        assert_eq!(spendable_outpoints(maker), org_spendable);
+        let wallet = maker.get_wallet().read().unwrap();
+        assert!(wallet.get_outgoing_swapcoin_list().unwrap().is_empty());
+        assert!(wallet.get_incoming_swapcoin_list().unwrap().is_empty());
+        drop(wallet);
+        assert!(maker.list_active_swaps().unwrap().is_empty());
+    }
+
+    // ---- Cleanup ----
+    for maker in &makers {
+        maker.shutdown().unwrap();
+    }
+    maker_threads
+        .into_iter()
+        .for_each(|thread| thread.join().unwrap());
+    directory_server_instance.shutdown().unwrap();
+    test_framework.stop();
+    let _ = fs::remove_dir_all(&data_dir);
+}
@@ -0,0 +1,88 @@
+#![cfg(feature = "integration-test")]
+use bitcoin::Amount;
+use coinswap::{
+    maker::{
+        rpc::{read_rpc_message, RpcMsgReq, RpcMsgResp},
+        start_maker_server, MakerBehavior,
+    },
+    utill::{send_message, ConnectionType},
+};
+
+mod test_framework;
+use log::info;
+use std::{thread, time::Duration};
+use test_framework::*;
+use tokio::{io::BufReader, net::TcpStream};
+
+/// Query the status and the active swaps of a running Maker over its RPC socket, then shut it down.
+#[tokio::test]
+async fn test_maker_status() {
+    // ---- Setup ----
+    let makers_config_map = [((6102, None), MakerBehavior::Normal)];
+
+    let (test_framework, _, makers, directory_server_instance) = TestFramework::init(
+        None,
+        makers_config_map.into(),
+        None,
+        ConnectionType::CLEARNET,
+        None,
+    )
+    .await;
+    let maker = makers[0].clone();
+
+    // Fund the Maker with 2 utxos of 0.05 btc, enough for its fidelity bond.
+    test_framework.fund_wallet(
+        &mut maker.get_wallet().write().unwrap(),
+        &[Amount::from_btc(0.05).unwrap(); 2],
+    );
+
+    // ---- Start the Server ----
+    let maker_clone = maker.clone();
+    let maker_thread = thread::spawn(move || {
+        start_maker_server(maker_clone).unwrap();
+    });
+
+    while !*maker.is_setup_complete.read().unwrap() {
+        info!("Waiting for maker setup completion");
+        thread::sleep(Duration::from_secs(10));
+    }
+
+    // ---- Query the Status ----
+    let mut stream = TcpStream::connect(("127.0.0.1", maker.config.rpc_port))
+        .await
+        .unwrap();
+    let (read_half, mut write_half) = stream.split();
+    send_message(&mut write_half, &RpcMsgReq::Status)
+        .await
+        .unwrap();
+    let resp = read_rpc_message::<RpcMsgResp>(&mut BufReader::new(read_half))
+        .await
+        .unwrap();
+    let status = match resp {
+        Some(RpcMsgResp::StatusResp(status)) => status,
+        resp => panic!("Unexpected response: {:?}", resp),
+    };
+    assert!(status.fidelity_bond.is_some());
+    assert_eq!(status.active_swaps, 0);
+    assert!(status.balance.fidelity_locked > Amount::ZERO);
+    assert!(status.uptime_secs > 0);
+
+    // ---- List the Swaps ----
+    let mut stream = TcpStream::connect(("127.0.0.1", maker.config.rpc_port))
+        .await
+        .unwrap();
+    let (read_half, mut write_half) = stream.split();
+    send_message(&mut write_half, &RpcMsgReq::ListSwaps)
+        .await
+        .unwrap();
+    let resp = read_rpc_message::<RpcMsgResp>(&mut BufReader::new(read_half))
+        .await
+        .unwrap();
+    assert!(matches!(resp, Some(RpcMsgResp::ListSwapsResp(swaps)) if swaps.is_empty()));
+
+    // ---- Cleanup ----
+    maker.shutdown().unwrap();
+    maker_thread.join().unwrap();
+    directory_server_instance.shutdown().unwrap();
+    test_framework.stop();
+}
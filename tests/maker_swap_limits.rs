@@ -0,0 +1,175 @@
+#![cfg(feature = "integration-test")]
+use std::{collections::HashMap, fs, sync::Arc, thread, time::Duration};
+
+use bitcoin::{
+    absolute::LockTime,
+    hashes::{hash160::Hash as Hash160, Hash},
+    secp256k1::{Secp256k1, SecretKey},
+    transaction::Version,
+    Amount, PublicKey, ScriptBuf, Transaction,
+};
+use coinswap::{
+    maker::{start_maker_server, Maker, MakerBehavior},
+    protocol::messages::{
+        ContractTxInfoForSender, GiveOffer, MakerToTakerMessage, ReqContractSigsForSender,
+        SwapRejection, TakerHello, TakerToMakerMessage, PROTOCOL_VERSION_MAX, PROTOCOL_VERSION_MIN,
+    },
+    utill::{read_maker_message, send_message, ConnectionType},
+    wallet::RPCConfig,
+};
+use tokio::{io::BufReader, net::TcpStream};
+
+mod test_framework;
+use log::info;
+use test_framework::*;
+
+const MAX_SIZE: u64 = 100_000;
+
+/// Send a message to the Maker, and read its reply if `expect_reply`.
+async fn exchange(
+    stream: &mut TcpStream,
+    message: TakerToMakerMessage,
+    expect_reply: bool,
+) -> Option<MakerToTakerMessage> {
+    let (read_half, mut write_half) = stream.split();
+    send_message(&mut write_half, &message).await.unwrap();
+    if expect_reply {
+        let mut reader = BufReader::new(read_half);
+        Some(read_maker_message(&mut reader).await.unwrap())
+    } else {
+        None
+    }
+}
+
+/// A request for the sender's contract sigs, funded with `funding_input_values`. The contracts aren't
+/// valid, so the Maker only gets to check its swap limits.
+fn req_contract_sigs(funding_input_values: &[u64]) -> TakerToMakerMessage {
+    let secret_key = SecretKey::from_slice(&[1; 32]).unwrap();
+    let pubkey = PublicKey {
+        compressed: true,
+        inner: secret_key.public_key(&Secp256k1::new()),
+    };
+    TakerToMakerMessage::ReqContractSigsForSender(ReqContractSigsForSender {
+        txs_info: funding_input_values
+            .iter()
+            .map(|value| ContractTxInfoForSender {
+                multisig_nonce: secret_key,
+                hashlock_nonce: secret_key,
+                timelock_pubkey: pubkey,
+                senders_contract_tx: Transaction {
+                    version: Version::TWO,
+                    lock_time: LockTime::ZERO,
+                    input: vec![],
+                    output: vec![],
+                },
+                multisig_redeemscript: ScriptBuf::new(),
+                funding_input_value: Amount::from_sat(*value),
+            })
+            .collect(),
+        hashvalue: Hash160::all_zeros(),
+        locktime: 20,
+    })
+}
+
+/// A Taker asks a Maker with a `max_size` for a larger swap. The Maker refuses it and stays ready for
+/// another request, without holding anything for the Taker. A request whose amount overflows is dropped.
+#[tokio::test]
+async fn test_maker_swap_limits() {
+    // ---- Setup ----
+    let (test_framework, _, _, directory_server_instance) =
+        TestFramework::init(None, HashMap::new(), None, ConnectionType::CLEARNET, None).await;
+
+    // A Maker with a small max swap size.
+    let port = 6103;
+    let data_dir = std::env::temp_dir().join(".coinswap-swap-limits");
+    let _ = fs::remove_dir_all(&data_dir);
+    let maker_dir = data_dir.join("maker").join(port.to_string());
+    fs::create_dir_all(&maker_dir).unwrap();
+    fs::write(
+        maker_dir.join("config.toml"),
+        format!("[maker_config]\nmax_size = {}\n", MAX_SIZE),
+    )
+    .unwrap();
+    let maker = Arc::new(
+        Maker::init(
+            Some(data_dir.clone()),
+            Some("maker6103".to_string()),
+            Some(RPCConfig::from(test_framework.as_ref())),
+            Some(port),
+            Some(3502),
+            None,
+            Some(ConnectionType::CLEARNET),
+            MakerBehavior::Normal,
+            true,
+        )
+        .unwrap(),
+    );
+    assert_eq!(maker.config.max_size, MAX_SIZE);
+
+    // Fund the Maker with 2 utxos of 0.05 btc, enough for its fidelity bond.
+    test_framework.fund_wallet(
+        &mut maker.get_wallet().write().unwrap(),
+        &[Amount::from_btc(0.05).unwrap(); 2],
+    );
+
+    let maker_clone = maker.clone();
+    let maker_thread = thread::spawn(move || {
+        start_maker_server(maker_clone).unwrap();
+    });
+    while !*maker.is_setup_complete.read().unwrap() {
+        info!("Waiting for maker setup completion");
+        thread::sleep(Duration::from_secs(10));
+    }
+
+    // ---- Get the offer ----
+    let mut stream = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+    {
+        let (read_half, _) = stream.split();
+        let mut reader = BufReader::new(read_half);
+        assert!(matches!(
+            read_maker_message(&mut reader).await.unwrap(),
+            MakerToTakerMessage::MakerHello(_)
+        ));
+    }
+    let hello = TakerToMakerMessage::TakerHello(TakerHello {
+        protocol_version_min: PROTOCOL_VERSION_MIN,
+        protocol_version_max: PROTOCOL_VERSION_MAX,
+    });
+    exchange(&mut stream, hello, false).await;
+    match exchange(
+        &mut stream,
+        TakerToMakerMessage::ReqGiveOffer(GiveOffer),
+        true,
+    )
+    .await
+    {
+        Some(MakerToTakerMessage::RespOffer(offer)) => assert!(offer.max_size <= MAX_SIZE),
+        other => panic!("Expected an offer, got {:?}", other),
+    }
+
+    // ---- Too large swaps are refused, and can be asked again ----
+    for _ in 0..2 {
+        let reply = exchange(&mut stream, req_contract_sigs(&[MAX_SIZE, 1]), true).await;
+        assert!(matches!(
+            reply,
+            Some(MakerToTakerMessage::RespSwapRejected(
+                SwapRejection::AmountTooLarge { amount, max_size }
+            )) if amount == MAX_SIZE + 1 && max_size == MAX_SIZE
+        ));
+    }
+    assert!(maker.list_active_swaps().unwrap().is_empty());
+
+    // ---- An overflowing amount closes the connection ----
+    exchange(&mut stream, req_contract_sigs(&[u64::MAX, 1]), false).await;
+    let (read_half, _) = stream.split();
+    let mut reader = BufReader::new(read_half);
+    assert!(read_maker_message(&mut reader).await.is_err());
+    drop(stream);
+
+    // ---- Cleanup ----
+    maker.shutdown().unwrap();
+    maker_thread.join().unwrap();
+    directory_server_instance.shutdown().unwrap();
+    test_framework.stop();
+    let _ = fs::remove_dir_all(&data_dir);
+}
@@ -32,6 +32,7 @@ async fn malice1_taker_broadcast_contract_prematurely() {
         makers_config_map.into(),
         Some(TakerBehavior::BroadcastContractAfterFullSetup),
         ConnectionType::CLEARNET,
+        None,
     )
     .await;
 
@@ -135,6 +136,9 @@ async fn malice1_taker_broadcast_contract_prematurely() {
         tx_count: 3,
         required_confirms: 1,
         fee_rate: Amount::from_sat(1000),
+        wait_for_confirms: 0,
+        refund_locktime: None,
+        refund_locktime_step: None,
     };
 
     // Calculate Original balance excluding fidelity bonds.
@@ -237,7 +241,7 @@ async fn malice1_taker_broadcast_contract_prematurely() {
             assert_eq!(maker_balance_fidelity, Amount::from_btc(0.05).unwrap());
             assert_eq!(
                 maker_balance_descriptor_utxo,
-                Amount::from_btc(0.14992232).unwrap()
+                Amount::from_btc(0.14991044).unwrap()
             );
             assert_eq!(maker_balance_swap_coins, Amount::from_btc(0.0).unwrap());
             assert_eq!(maker_balance_live_contract, Amount::from_btc(0.0).unwrap());
@@ -279,14 +283,14 @@ async fn malice1_taker_broadcast_contract_prematurely() {
     assert!(maker_balances.len() == 1); // The set only contains one element,
                                         // assert_eq!(maker_balances.first().unwrap(), &Amount::from_sat(14994773));
 
-    // Everybody looses 4227 sats for contract transactions.
+    // Everybody looses 7956 sats for contract transactions.
     assert_eq!(
         org_maker_balances
             .first()
             .unwrap()
             .checked_sub(*maker_balances.first().unwrap())
             .unwrap(),
-        Amount::from_sat(6768)
+        Amount::from_sat(7956)
     );
 
     assert_eq!(org_taker_balance_fidelity, Amount::from_btc(0.0).unwrap());
@@ -303,14 +307,14 @@ async fn malice1_taker_broadcast_contract_prematurely() {
     assert_eq!(taker_balance_fidelity, Amount::from_btc(0.0).unwrap());
     assert_eq!(
         taker_balance_descriptor_utxo,
-        Amount::from_btc(0.14993232).unwrap()
+        Amount::from_btc(0.14992044).unwrap()
     );
     assert_eq!(taker_balance_live_contract, Amount::from_btc(0.0).unwrap());
     assert_eq!(taker_balance_swap_coins, Amount::from_btc(0.0).unwrap());
 
     assert_eq!(
         org_taker_balance.checked_sub(taker_balance).unwrap(),
-        Amount::from_sat(6768)
+        Amount::from_sat(7956)
     );
 
     test_framework.stop();
@@ -0,0 +1,118 @@
+#![cfg(feature = "integration-test")]
+use std::{collections::HashMap, convert::TryFrom};
+
+use bip39::Mnemonic;
+use bitcoin::Amount;
+use bitcoind::bitcoincore_rpc::{Client, RpcApi};
+use coinswap::{
+    utill::ConnectionType,
+    wallet::{RPCConfig, Wallet, WalletError},
+};
+
+mod test_framework;
+use test_framework::*;
+
+/// Test running two wallets against the same node: their coins and addresses don't bleed into each
+/// other, and each can be unloaded and loaded again by name.
+#[tokio::test]
+async fn test_multiple_wallets() {
+    // ---- Setup ----
+    let (test_framework, taker, _, directory_server_instance) =
+        TestFramework::init(None, HashMap::new(), None, ConnectionType::CLEARNET, None).await;
+
+    let wallet_dir = taker
+        .read()
+        .unwrap()
+        .get_wallet()
+        .get_file_path()
+        .parent()
+        .unwrap()
+        .to_path_buf();
+    let rpc_config = RPCConfig::from(test_framework.as_ref());
+    let names = ["maker-wallet", "personal-wallet"];
+    let mut wallets = names.map(|name| {
+        let mut wallet = Wallet::init(
+            &wallet_dir.join(name),
+            &RPCConfig {
+                wallet_name: name.to_string(),
+                ..rpc_config.clone()
+            },
+            Mnemonic::generate(12).unwrap().to_string(),
+            "".to_string(),
+            None,
+        )
+        .unwrap();
+        wallet.sync().unwrap();
+        wallet
+    });
+    let loaded = Wallet::list_loaded_wallets(&rpc_config).unwrap();
+    assert!(names.iter().all(|name| loaded.contains(&name.to_string())));
+
+    // ---- Funds and addresses are independent ----
+    let maker_address = wallets[0].get_next_external_address().unwrap();
+    test_framework.send_to_address(&maker_address, Amount::from_btc(0.05).unwrap());
+    test_framework.generate_blocks(1);
+    for wallet in wallets.iter_mut() {
+        wallet.sync().unwrap();
+    }
+
+    assert_eq!(
+        wallets[0].balance().unwrap(),
+        Amount::from_btc(0.05).unwrap()
+    );
+    assert_eq!(wallets[1].balance().unwrap(), Amount::ZERO);
+    let personal_address = wallets[1].get_next_external_address().unwrap();
+    assert_ne!(personal_address, maker_address);
+    assert_eq!(wallets[1].get_address_info(&maker_address).unwrap(), None);
+    assert_eq!(
+        wallets[0].get_address_info(&personal_address).unwrap(),
+        None
+    );
+
+    // ---- Switch back to an unloaded wallet ----
+    let [maker_wallet, personal_wallet] = wallets;
+    drop(personal_wallet);
+    Client::try_from(&RPCConfig {
+        wallet_name: names[1].to_string(),
+        ..rpc_config.clone()
+    })
+    .unwrap()
+    .unload_wallet(None)
+    .unwrap();
+    assert!(!Wallet::list_loaded_wallets(&rpc_config)
+        .unwrap()
+        .contains(&names[1].to_string()));
+
+    let personal_wallet =
+        Wallet::load_by_name(&wallet_dir.join(names[1]), &rpc_config, names[1], None).unwrap();
+    assert!(Wallet::list_loaded_wallets(&rpc_config)
+        .unwrap()
+        .contains(&names[1].to_string()));
+    assert_eq!(personal_wallet.balance().unwrap(), Amount::ZERO);
+    assert_eq!(
+        maker_wallet.balance().unwrap(),
+        Amount::from_btc(0.05).unwrap()
+    );
+
+    // ---- A wallet the node doesn't have ----
+    let ghost_path = wallet_dir.join("ghost-wallet");
+    Wallet::init(
+        &ghost_path,
+        &RPCConfig {
+            wallet_name: "ghost-wallet".to_string(),
+            ..rpc_config.clone()
+        },
+        Mnemonic::generate(12).unwrap().to_string(),
+        "".to_string(),
+        None,
+    )
+    .unwrap();
+    assert!(matches!(
+        Wallet::load_by_name(&ghost_path, &rpc_config, "ghost-wallet", None),
+        Err(WalletError::CoreWalletNotFound(name)) if name == "ghost-wallet"
+    ));
+
+    // ---- Cleanup ----
+    directory_server_instance.shutdown().unwrap();
+    test_framework.stop();
+}
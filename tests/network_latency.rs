@@ -0,0 +1,108 @@
+#![cfg(feature = "integration-test")]
+use bitcoin::Amount;
+use coinswap::{
+    maker::{start_maker_server, MakerBehavior},
+    taker::SwapParams,
+    utill::ConnectionType,
+};
+
+mod test_framework;
+use test_framework::*;
+
+use log::{info, warn};
+use std::{thread, time::Duration};
+
+/// A coinswap between a Taker and 2 Makers, through links delaying every message by 2 seconds. The
+/// swap is slow, but completes.
+#[tokio::test]
+async fn test_coinswap_with_network_latency() {
+    // ---- Setup ----
+    let makers_config_map = [
+        ((6102, None), MakerBehavior::Normal),
+        ((16102, None), MakerBehavior::Normal),
+    ];
+    let network_fault = NetworkFault {
+        latency: Duration::from_secs(2),
+        jitter: Duration::from_millis(200),
+        drop_after_bytes: None,
+    };
+
+    let (test_framework, taker, makers, directory_server_instance) = TestFramework::init(
+        None,
+        makers_config_map.into(),
+        None,
+        ConnectionType::CLEARNET,
+        Some(network_fault),
+    )
+    .await;
+
+    warn!("Running Test: Coinswap with network latency");
+
+    // Fund the Taker with 3 utxos, and the Makers with 4 utxos of 0.05 btc each.
+    test_framework.fund_wallet(
+        taker.write().unwrap().get_wallet_mut(),
+        &[Amount::from_btc(0.05).unwrap(); 3],
+    );
+    makers.iter().for_each(|maker| {
+        test_framework.fund_wallet(
+            &mut maker.get_wallet().write().unwrap(),
+            &[Amount::from_btc(0.05).unwrap(); 4],
+        );
+    });
+
+    // ---- Start Servers and attempt Swap ----
+    let maker_threads = makers
+        .iter()
+        .map(|maker| {
+            let maker_clone = maker.clone();
+            thread::spawn(move || {
+                start_maker_server(maker_clone).unwrap();
+            })
+        })
+        .collect::<Vec<_>>();
+
+    makers.iter().for_each(|maker| {
+        while !*maker.is_setup_complete.read().unwrap() {
+            log::info!("Waiting for maker setup completion");
+            thread::sleep(Duration::from_secs(10));
+        }
+    });
+
+    let swap_params = SwapParams {
+        send_amount: Amount::from_sat(500000),
+        maker_count: 2,
+        tx_count: 3,
+        required_confirms: 1,
+        fee_rate: Amount::from_sat(1000),
+        wait_for_confirms: 0,
+        refund_locktime: None,
+        refund_locktime_step: None,
+    };
+
+    info!("Initiating coinswap protocol");
+    let taker_clone = taker.clone();
+    let taker_thread = thread::spawn(move || {
+        taker_clone
+            .write()
+            .unwrap()
+            .do_coinswap(swap_params)
+            .unwrap();
+    });
+    taker_thread.join().unwrap();
+
+    makers.iter().for_each(|maker| maker.shutdown().unwrap());
+    maker_threads
+        .into_iter()
+        .for_each(|thread| thread.join().unwrap());
+
+    // ---- After Swap Asserts ----
+    assert_eq!(test_framework.dropped_connections(), 0);
+    assert_eq!(taker.read().unwrap().get_wallet().get_swapcoins_count(), 6);
+    makers.iter().for_each(|maker| {
+        assert_eq!(maker.get_wallet().read().unwrap().get_swapcoins_count(), 6);
+    });
+
+    // ---- Cleanup ----
+    directory_server_instance.shutdown().unwrap();
+    test_framework.stop();
+}
@@ -0,0 +1,133 @@
+#![cfg(feature = "integration-test")]
+use std::collections::HashMap;
+
+use bitcoin::{Amount, Sequence};
+use bitcoind::bitcoincore_rpc::RpcApi;
+use coinswap::{
+    utill::ConnectionType,
+    wallet::{Destination, SendAmount, SpendOptions, WalletError},
+};
+
+mod test_framework;
+use test_framework::*;
+
+/// Test signaling replaceability on direct sends, and replacing a low fee send with a higher fee one.
+/// Only the change of a send pays the bump, even when the destination is a wallet address too. Sends
+/// without the signal can't be bumped.
+#[tokio::test]
+async fn test_rbf_fee_bump() {
+    // ---- Setup ----
+    let (test_framework, taker, _, directory_server_instance) =
+        TestFramework::init(None, HashMap::new(), None, ConnectionType::CLEARNET, None).await;
+
+    let mut taker_write = taker.write().unwrap();
+    let wallet = taker_write.get_wallet_mut();
+
+    test_framework.fund_wallet(wallet, &[Amount::from_sat(100_000); 3]);
+
+    let client = test_framework.get_client();
+    let destination = client.get_new_address(None, None).unwrap().assume_checked();
+    let coins = wallet.spendable_utxos().unwrap();
+    let signer = wallet.signer_handle();
+    let rbf = SpendOptions {
+        enable_rbf: true,
+        ..SpendOptions::default()
+    };
+
+    // ---- A replaceable send, at a low fee ----
+    let tx = wallet
+        .spend_from_wallet_with(
+            Amount::from_sat(200),
+            SendAmount::Amount(Amount::from_sat(50_000)),
+            Destination::Address(destination.clone()),
+            &coins[..1],
+            signer.as_ref(),
+            rbf,
+        )
+        .unwrap();
+    assert!(tx
+        .input
+        .iter()
+        .all(|txin| txin.sequence == Sequence::ENABLE_RBF_NO_LOCKTIME));
+    let txid = client.send_raw_transaction(&tx).unwrap();
+
+    // ---- Bump it ----
+    let replacement = wallet.bump_fee(txid, 10).unwrap();
+    assert_eq!(
+        replacement
+            .input
+            .iter()
+            .map(|txin| txin.previous_output)
+            .collect::<Vec<_>>(),
+        tx.input
+            .iter()
+            .map(|txin| txin.previous_output)
+            .collect::<Vec<_>>()
+    );
+    // The payment is untouched, the change pays the fee.
+    assert_eq!(replacement.output[0], tx.output[0]);
+    assert!(replacement.output[1].value < tx.output[1].value);
+
+    let replacement_txid = client.send_raw_transaction(&replacement).unwrap();
+    assert!(client.get_mempool_entry(&txid).is_err());
+    let entry = client.get_mempool_entry(&replacement_txid).unwrap();
+    assert!(entry.fees.base.to_sat() >= 10 * entry.vsize);
+    assert!(entry.fees.base.to_sat() < 11 * entry.vsize);
+
+    // ---- A send to the wallet's own change address ----
+    let own_address = wallet.get_next_internal_addresses(1).unwrap().remove(0);
+    let tx = wallet
+        .spend_from_wallet_with(
+            Amount::from_sat(200),
+            SendAmount::Amount(Amount::from_sat(50_000)),
+            Destination::Address(own_address.clone()),
+            &coins[2..],
+            signer.as_ref(),
+            rbf,
+        )
+        .unwrap();
+    assert_eq!(tx.output[0].script_pubkey, own_address.script_pubkey());
+    assert_eq!(tx.output[1].script_pubkey, own_address.script_pubkey());
+    let own_txid = client.send_raw_transaction(&tx).unwrap();
+
+    let own_replacement = wallet.bump_fee(own_txid, 10).unwrap();
+    assert_eq!(own_replacement.output[0], tx.output[0]);
+    assert!(own_replacement.output[1].value < tx.output[1].value);
+    let own_replacement_txid = client.send_raw_transaction(&own_replacement).unwrap();
+
+    // The replacement can be bumped again, still from its change.
+    let own_replacement = wallet.bump_fee(own_replacement_txid, 20).unwrap();
+    assert_eq!(own_replacement.output[0], tx.output[0]);
+    client.send_raw_transaction(&own_replacement).unwrap();
+
+    // ---- A non replaceable send can't be bumped ----
+    let tx = wallet
+        .spend_from_wallet(
+            Amount::from_sat(200),
+            SendAmount::Amount(Amount::from_sat(50_000)),
+            Destination::Address(destination),
+            &coins[1..2],
+        )
+        .unwrap();
+    assert!(tx
+        .input
+        .iter()
+        .all(|txin| txin.sequence == Sequence::ENABLE_LOCKTIME_NO_RBF));
+    let txid = client.send_raw_transaction(&tx).unwrap();
+    assert!(matches!(
+        wallet.bump_fee(txid, 10),
+        Err(WalletError::NotReplaceable(non_rbf)) if non_rbf == txid
+    ));
+
+    // ---- A confirmed send can't be bumped ----
+    test_framework.generate_blocks(1);
+    assert!(matches!(
+        wallet.bump_fee(replacement_txid, 20),
+        Err(WalletError::ParentNotInMempool(_))
+    ));
+
+    // ---- Cleanup ----
+    drop(taker_write);
+    directory_server_instance.shutdown().unwrap();
+    test_framework.stop();
+}
@@ -0,0 +1,108 @@
+#![cfg(feature = "integration-test")]
+use bitcoin::Amount;
+use coinswap::{
+    maker::{start_maker_server, MakerBehavior},
+    protocol::contract::read_contract_locktime,
+    taker::SwapParams,
+    utill::ConnectionType,
+};
+
+mod test_framework;
+use test_framework::*;
+
+use log::info;
+use std::{thread, time::Duration};
+
+/// Test a coinswap overriding the configured refund locktimes.
+#[tokio::test]
+async fn test_refund_locktime_override() {
+    // ---- Setup ----
+    let makers_config_map = [
+        ((6102, None), MakerBehavior::Normal),
+        ((16102, None), MakerBehavior::Normal),
+    ];
+
+    let (test_framework, taker, makers, directory_server_instance) = TestFramework::init(
+        None,
+        makers_config_map.into(),
+        None,
+        ConnectionType::CLEARNET,
+        None,
+    )
+    .await;
+
+    // Fund the Taker and Makers with 3 utxos of 0.05 btc each, plus the Makers' fidelity coins.
+    test_framework.fund_wallet(
+        taker.write().unwrap().get_wallet_mut(),
+        &[Amount::from_btc(0.05).unwrap(); 3],
+    );
+    makers.iter().for_each(|maker| {
+        test_framework.fund_wallet(
+            &mut maker.get_wallet().write().unwrap(),
+            &[Amount::from_btc(0.05).unwrap(); 4],
+        );
+    });
+
+    // ---- Start Servers and attempt Swap ----
+    let maker_threads = makers
+        .iter()
+        .map(|maker| {
+            let maker_clone = maker.clone();
+            thread::spawn(move || {
+                start_maker_server(maker_clone).unwrap();
+            })
+        })
+        .collect::<Vec<_>>();
+
+    makers.iter().for_each(|maker| {
+        while !*maker.is_setup_complete.read().unwrap() {
+            info!("Waiting for maker setup completion");
+            thread::sleep(Duration::from_secs(10));
+        }
+    });
+
+    let swap_params = SwapParams {
+        send_amount: Amount::from_sat(500000),
+        maker_count: 2,
+        tx_count: 3,
+        required_confirms: 1,
+        fee_rate: Amount::from_sat(1000),
+        wait_for_confirms: 0,
+        refund_locktime: Some(60),
+        refund_locktime_step: Some(50),
+    };
+    let taker_clone = taker.clone();
+    thread::spawn(move || {
+        taker_clone
+            .write()
+            .unwrap()
+            .do_coinswap(swap_params)
+            .unwrap();
+    })
+    .join()
+    .unwrap();
+
+    // ---- The contracts follow the overridden locktimes ----
+    // The Taker pays the first Maker with a locktime of 60 + 2 * 50, and is paid back with 60.
+    let taker_read = taker.read().unwrap();
+    let wallet = taker_read.get_wallet();
+    let outgoing = wallet.get_outgoing_swapcoin_list().unwrap();
+    let incoming = wallet.get_incoming_swapcoin_list().unwrap();
+    assert_eq!(outgoing.len(), 3);
+    assert_eq!(incoming.len(), 3);
+    assert!(outgoing
+        .values()
+        .all(|coin| read_contract_locktime(&coin.contract_redeemscript).unwrap() == 160));
+    assert!(incoming
+        .values()
+        .all(|coin| read_contract_locktime(&coin.contract_redeemscript).unwrap() == 60));
+    drop(taker_read);
+
+    // ---- Cleanup ----
+    makers.iter().for_each(|maker| maker.shutdown().unwrap());
+    maker_threads
+        .into_iter()
+        .for_each(|thread| thread.join().unwrap());
+    directory_server_instance.shutdown().unwrap();
+    test_framework.stop();
+}
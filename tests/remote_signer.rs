@@ -0,0 +1,159 @@
+#![cfg(feature = "integration-test")]
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use bip39::Mnemonic;
+use bitcoin::{bip32::Xpriv, secp256k1::SecretKey, Amount, Network, PublicKey};
+use bitcoind::bitcoincore_rpc::RpcApi;
+use coinswap::{
+    utill::ConnectionType,
+    wallet::{
+        Destination, LocalSigner, RPCConfig, RemoteSigner, SendAmount, SignRequest, SignResponse,
+        SignerKey, Wallet, WalletError, WalletPubkeys,
+    },
+};
+
+mod test_framework;
+use test_framework::*;
+
+const MNEMONIC: &str =
+    "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+
+/// A signer living on another machine. Requests and responses go through their serialized form,
+/// and the signer only knows the seed, not the wallet.
+struct MockRemoteSigner {
+    signer: LocalSigner,
+    requests: Arc<Mutex<Vec<SignRequest>>>,
+}
+
+impl MockRemoteSigner {
+    fn new(mnemonic: &str, requests: &Arc<Mutex<Vec<SignRequest>>>) -> Box<Self> {
+        let seed = Mnemonic::parse(mnemonic).unwrap().to_seed("");
+        Box::new(Self {
+            signer: LocalSigner::new(Xpriv::new_master(Network::Regtest, &seed).unwrap()),
+            requests: requests.clone(),
+        })
+    }
+}
+
+impl RemoteSigner for MockRemoteSigner {
+    fn public_key(&self, key: &SignerKey) -> Result<PublicKey, WalletError> {
+        self.signer.public_key(key)
+    }
+
+    fn wallet_pubkeys(&self) -> Result<WalletPubkeys, WalletError> {
+        self.signer.wallet_pubkeys()
+    }
+
+    fn handover_key(&self, key: &SignerKey) -> Result<SecretKey, WalletError> {
+        self.signer.handover_key(key)
+    }
+
+    fn sign(&self, request: &SignRequest) -> Result<SignResponse, WalletError> {
+        let received: SignRequest =
+            serde_json::from_str(&serde_json::to_string(request).unwrap()).unwrap();
+        let response = self.signer.sign(&received)?;
+        self.requests.lock().unwrap().push(received);
+        Ok(serde_json::from_str(&serde_json::to_string(&response).unwrap()).unwrap())
+    }
+}
+
+/// Test a wallet whose keys are all held by a remote signer: its file holds no private key, and a
+/// direct send completes with all signatures made by the signer.
+#[tokio::test]
+async fn test_direct_send_with_remote_signer() {
+    // ---- Setup ----
+    let (test_framework, taker, _, directory_server_instance) =
+        TestFramework::init(None, HashMap::new(), None, ConnectionType::CLEARNET, None).await;
+
+    let wallet_name = "remote-signed-wallet".to_string();
+    let wallet_path = taker
+        .read()
+        .unwrap()
+        .get_wallet()
+        .get_file_path()
+        .parent()
+        .unwrap()
+        .join(&wallet_name);
+    let rpc_config = RPCConfig {
+        wallet_name,
+        ..RPCConfig::from(test_framework.as_ref())
+    };
+    let requests = Arc::new(Mutex::new(Vec::new()));
+    let mut wallet = Wallet::init_with_signer(
+        &wallet_path,
+        &rpc_config,
+        MockRemoteSigner::new(MNEMONIC, &requests),
+        None,
+    )
+    .unwrap();
+    wallet.sync().unwrap();
+
+    test_framework.fund_wallet(&mut wallet, &[Amount::from_btc(0.05).unwrap(); 2]);
+
+    // ---- Direct send, signed remotely ----
+    let coins = wallet.spendable_utxos().unwrap();
+    assert_eq!(coins.len(), 2);
+    let destination = test_framework
+        .get_client()
+        .get_new_address(None, None)
+        .unwrap()
+        .assume_checked();
+    let tx = wallet
+        .spend_from_wallet(
+            Amount::from_sat(1000),
+            SendAmount::Amount(Amount::from_btc(0.06).unwrap()),
+            Destination::Address(destination),
+            &coins,
+        )
+        .unwrap();
+
+    // One request per input.
+    assert_eq!(requests.lock().unwrap().len(), tx.input.len());
+
+    let txid = test_framework
+        .get_client()
+        .send_raw_transaction(&tx)
+        .unwrap();
+    test_framework.generate_blocks(1);
+    let tx_info = test_framework
+        .get_client()
+        .get_raw_transaction_info(&txid, None)
+        .unwrap();
+    assert!(tx_info.confirmations.unwrap() >= 1);
+
+    // ---- The wallet file holds no private key ----
+    wallet.save_to_disk().unwrap();
+    drop(wallet);
+    assert!(matches!(
+        Wallet::load(&rpc_config, &wallet_path, None),
+        Err(WalletError::Protocol(_))
+    ));
+    // Nor loads with the keys of another seed.
+    const OTHER_MNEMONIC: &str = "zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo wrong";
+    assert!(matches!(
+        Wallet::load_with_signer(
+            &rpc_config,
+            &wallet_path,
+            MockRemoteSigner::new(OTHER_MNEMONIC, &requests),
+            None,
+        ),
+        Err(WalletError::Protocol(_))
+    ));
+    let mut wallet = Wallet::load_with_signer(
+        &rpc_config,
+        &wallet_path,
+        MockRemoteSigner::new(MNEMONIC, &requests),
+        None,
+    )
+    .unwrap();
+    wallet.sync().unwrap();
+    // The change of the send.
+    assert_eq!(wallet.spendable_utxos().unwrap().len(), 1);
+
+    // ---- Cleanup ----
+    directory_server_instance.shutdown().unwrap();
+    test_framework.stop();
+}
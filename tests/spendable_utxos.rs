@@ -0,0 +1,223 @@
+#![cfg(feature = "integration-test")]
+use std::{collections::HashMap, str::FromStr};
+
+use bitcoin::{absolute::LockTime, hashes::Hash, Address, Amount, Network, OutPoint, PublicKey};
+use bitcoind::bitcoincore_rpc::RpcApi;
+use coinswap::{
+    protocol::contract::create_contract_redeemscript,
+    utill::{redeemscript_to_scriptpubkey, ConnectionType},
+    wallet::UTXOSpendInfo,
+};
+
+mod test_framework;
+use test_framework::*;
+
+/// Test that [Wallet::spendable_utxos] excludes every category of coins not spendable right away.
+///
+///  - fidelity bonds
+///  - watch-only coins
+///  - unconfirmed coins from others
+///  - frozen coins
+///  - the fee reserve
+///  - coins locked by in-flight funding transactions
+#[tokio::test]
+async fn test_spendable_utxos() {
+    // ---- Setup ----
+    let (test_framework, taker, _, directory_server_instance) =
+        TestFramework::init(None, HashMap::new(), None, ConnectionType::CLEARNET, None).await;
+
+    let mut taker_write = taker.write().unwrap();
+    let wallet = taker_write.get_wallet_mut();
+
+    test_framework.fund_wallet(wallet, &[Amount::from_btc(0.05).unwrap(); 3]);
+
+    let outpoint = |utxo: &bitcoind::bitcoincore_rpc::json::ListUnspentResultEntry| OutPoint {
+        txid: utxo.txid,
+        vout: utxo.vout,
+    };
+
+    // ---- Confirmed seed coins are spendable ----
+    let spendable = wallet.spendable_utxos().unwrap();
+    assert_eq!(spendable.len(), 3);
+    assert!(spendable
+        .iter()
+        .all(|(_, info)| matches!(info, UTXOSpendInfo::SeedCoin { .. })));
+
+    // ---- Unconfirmed coins from others are not spendable ----
+    // The block generation thread might confirm the coin anytime. The check only counts if it's still
+    // in the mempool after listing the spendable coins, so retry until it is.
+    let mut checked_unconfirmed = false;
+    for _ in 0..5 {
+        let address = wallet.get_next_external_address().unwrap();
+        let unconfirmed_txid = test_framework
+            .get_client()
+            .send_to_address(
+                &address,
+                Amount::from_btc(0.01).unwrap(),
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+            )
+            .unwrap();
+        let spendable = wallet.spendable_utxos().unwrap();
+        if test_framework
+            .get_client()
+            .get_mempool_entry(&unconfirmed_txid)
+            .is_ok()
+        {
+            assert!(spendable
+                .iter()
+                .all(|(utxo, _)| utxo.txid != unconfirmed_txid));
+            checked_unconfirmed = true;
+            break;
+        }
+    }
+    assert!(checked_unconfirmed);
+    test_framework.generate_blocks(1);
+    wallet.sync().unwrap();
+
+    // ---- Frozen coins are not spendable ----
+    let spendable = wallet.spendable_utxos().unwrap();
+    let frozen = outpoint(&spendable[0].0);
+    wallet.freeze_utxo(frozen).unwrap();
+    let spendable_frozen = wallet.spendable_utxos().unwrap();
+    assert_eq!(spendable_frozen.len(), spendable.len() - 1);
+    assert!(spendable_frozen
+        .iter()
+        .all(|(utxo, _)| outpoint(utxo) != frozen));
+    wallet.unfreeze_utxo(&frozen).unwrap();
+    assert_eq!(wallet.spendable_utxos().unwrap().len(), spendable.len());
+
+    // ---- The smallest coins covering the fee reserve are not spendable ----
+    let smallest = spendable
+        .iter()
+        .min_by_key(|(utxo, _)| utxo.amount)
+        .map(|(utxo, _)| utxo.clone())
+        .unwrap();
+    wallet.set_fee_reserve(smallest.amount).unwrap();
+    let spendable_reserved = wallet.spendable_utxos().unwrap();
+    assert_eq!(spendable_reserved.len(), spendable.len() - 1);
+    assert!(spendable_reserved
+        .iter()
+        .all(|(utxo, _)| outpoint(utxo) != outpoint(&smallest)));
+    // A larger reserve holds back the next smallest coin too.
+    wallet
+        .set_fee_reserve(smallest.amount + Amount::from_sat(1))
+        .unwrap();
+    assert_eq!(wallet.spendable_utxos().unwrap().len(), spendable.len() - 2);
+    wallet.set_fee_reserve(Amount::ZERO).unwrap();
+    assert_eq!(wallet.spendable_utxos().unwrap().len(), spendable.len());
+
+    // ---- Watch-only coins are not spendable ----
+    let pubkey =
+        PublicKey::from_str("032e58afe51f9ed8ad3cc7897f634d881fdbe49a81564629ded8156bebd2ffd1af")
+            .unwrap();
+    let watchonly_redeemscript =
+        create_contract_redeemscript(&pubkey, &pubkey, &Hash::all_zeros(), &20);
+    wallet
+        .import_watchonly_redeemscript(&watchonly_redeemscript)
+        .unwrap();
+    let watchonly_address = Address::from_script(
+        &redeemscript_to_scriptpubkey(&watchonly_redeemscript),
+        Network::Regtest,
+    )
+    .unwrap();
+    let watchonly_txid = test_framework
+        .get_client()
+        .send_to_address(
+            &watchonly_address,
+            Amount::from_btc(0.01).unwrap(),
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+    test_framework.generate_blocks(1);
+    wallet.sync().unwrap();
+
+    assert!(wallet
+        .get_all_utxo()
+        .unwrap()
+        .iter()
+        .any(|utxo| utxo.txid == watchonly_txid));
+    assert!(wallet
+        .spendable_utxos()
+        .unwrap()
+        .iter()
+        .all(|(utxo, _)| utxo.txid != watchonly_txid));
+
+    // ---- Fidelity bonds are not spendable ----
+    let current_height = test_framework.get_client().get_block_count().unwrap() as u32;
+    wallet
+        .create_fidelity(
+            Amount::from_btc(0.02).unwrap(),
+            LockTime::from_height(current_height + 100).unwrap(),
+        )
+        .unwrap();
+    wallet.sync().unwrap();
+
+    let fidelity_utxos = wallet.list_fidelity_spend_info(None).unwrap();
+    assert_eq!(fidelity_utxos.len(), 1);
+    let fidelity_outpoint = outpoint(&fidelity_utxos[0].0);
+    assert!(wallet
+        .spendable_utxos()
+        .unwrap()
+        .iter()
+        .all(|(utxo, _)| outpoint(utxo) != fidelity_outpoint));
+
+    // ---- Inputs of in-flight funding transactions are not spendable ----
+    let balance = wallet.balance().unwrap();
+    let destination = wallet.get_next_external_address().unwrap();
+    let funding = wallet
+        .create_funding_txes(
+            Amount::from_btc(0.01).unwrap(),
+            &[destination],
+            Amount::from_sat(1000),
+        )
+        .unwrap();
+    let in_flight = funding
+        .funding_txes
+        .iter()
+        .flat_map(|tx| tx.input.iter().map(|input| input.previous_output))
+        .collect::<Vec<_>>();
+    assert!(!in_flight.is_empty());
+    let spendable = wallet.spendable_utxos().unwrap();
+    assert!(!spendable.is_empty());
+    assert!(spendable
+        .iter()
+        .all(|(utxo, _)| !in_flight.contains(&outpoint(utxo))));
+
+    // Locking the unspendable coins, as the next coin selection does, keeps them locked.
+    wallet.lock_unspendable_utxos().unwrap();
+    assert!(wallet
+        .spendable_utxos()
+        .unwrap()
+        .iter()
+        .all(|(utxo, _)| !in_flight.contains(&outpoint(utxo))));
+
+    // The balance and recovery queries list them, and keep them locked.
+    assert_eq!(wallet.balance().unwrap(), balance);
+    wallet.balance_breakdown().unwrap();
+    wallet.find_incomplete_coinswaps().unwrap();
+    wallet.find_live_contract_unspents().unwrap();
+    let all_utxos = wallet.get_all_utxo().unwrap();
+    assert!(in_flight
+        .iter()
+        .all(|input| all_utxos.iter().any(|utxo| outpoint(utxo) == *input)));
+    assert!(wallet
+        .spendable_utxos()
+        .unwrap()
+        .iter()
+        .all(|(utxo, _)| !in_flight.contains(&outpoint(utxo))));
+
+    // ---- Cleanup ----
+    drop(taker_write);
+    directory_server_instance.shutdown().unwrap();
+    test_framework.stop();
+}
@@ -33,6 +33,7 @@ async fn test_standard_coinswap() {
         makers_config_map.into(),
         None,
         ConnectionType::CLEARNET,
+        None,
     )
     .await;
 
@@ -212,6 +213,9 @@ async fn test_standard_coinswap() {
         tx_count: 3,
         required_confirms: 1,
         fee_rate: Amount::from_sat(1000),
+        wait_for_confirms: 0,
+        refund_locktime: None,
+        refund_locktime_step: None,
     };
 
     info!("Initiating coinswap protocol");
@@ -267,7 +271,7 @@ async fn test_standard_coinswap() {
             .balance_swap_coins(Some(&all_utxos))
             .unwrap();
 
-    assert_eq!(taker_spendable_bal, Amount::from_btc(0.1498284).unwrap());
+    assert_eq!(taker_spendable_bal, Amount::from_btc(0.14984724).unwrap());
 
     let taker_balance_fidelity = taker
         .read()
@@ -298,11 +302,11 @@ async fn test_standard_coinswap() {
             + taker_balance_descriptor_utxo
             + taker_balance_swap_coins
             + taker_balance_live_contract,
-        Amount::from_btc(0.1498284).unwrap()
+        Amount::from_btc(0.14984724).unwrap()
     );
     assert_eq!(
         taker_balance_descriptor_utxo,
-        Amount::from_btc(0.14497).unwrap()
+        Amount::from_btc(0.14498884).unwrap()
     );
     assert_eq!(
         taker_balance_swap_coins,
@@ -343,14 +347,14 @@ async fn test_standard_coinswap() {
         let maker_total_balance = maker.get_wallet().read().unwrap().balance().unwrap();
 
         assert!(
-            maker_total_balance == Amount::from_btc(0.20003044).unwrap()
-                || maker_total_balance == Amount::from_btc(0.20003116).unwrap(),
+            maker_total_balance == Amount::from_btc(0.20004928).unwrap()
+                || maker_total_balance == Amount::from_btc(0.20005).unwrap(),
             "maker total balance didn't match any of the expected values"
         );
 
         assert!(
-            maker_balance_descriptor_utxo == Amount::from_btc(0.14503116).unwrap()
-                || maker_balance_descriptor_utxo == Amount::from_btc(0.1451016).unwrap(),
+            maker_balance_descriptor_utxo == Amount::from_btc(0.14505).unwrap()
+                || maker_balance_descriptor_utxo == Amount::from_btc(0.14512044).unwrap(),
             "maker_balance_descriptor_utxo does not match any of the expected values"
         );
 
@@ -365,8 +369,8 @@ async fn test_standard_coinswap() {
         let maker_spendable_balance = maker_balance_descriptor_utxo + maker_balance_swap_coins;
 
         assert!(
-            maker_spendable_balance == Amount::from_btc(0.15003116).unwrap()
-                || maker_spendable_balance == Amount::from_btc(0.15003044).unwrap(),
+            maker_spendable_balance == Amount::from_btc(0.15005).unwrap()
+                || maker_spendable_balance == Amount::from_btc(0.15004928).unwrap(),
             "maker spendable balance didn't match any of the expected values"
         );
     });
@@ -418,7 +422,7 @@ async fn test_standard_coinswap() {
         .unwrap();
 
     assert_eq!(swap_coin_bal, Amount::ZERO);
-    assert_eq!(descriptor_bal, Amount::from_btc(0.1498184).unwrap());
+    assert_eq!(descriptor_bal, Amount::from_btc(0.14983724).unwrap());
 
     info!("All checks successful. Terminating integration test case");
 
@@ -0,0 +1,149 @@
+#![cfg(feature = "integration-test")]
+use bitcoin::Amount;
+use coinswap::{
+    maker::{start_maker_server, MakerBehavior},
+    taker::{RecoveredSwap, SwapParams, Taker, TakerBehavior},
+    utill::ConnectionType,
+    wallet::RPCConfig,
+};
+
+mod test_framework;
+use log::{info, warn};
+use std::{thread, time::Duration};
+use test_framework::*;
+
+/// Recover after a Taker crash.
+/// The Taker stops right after the full swap setup, leaving the swap's checkpoint in its wallet.
+/// The Makers give up on the Taker and recover their coins. A new Taker, started from the same data
+/// directory, can't settle the swap anymore, and takes the refund path through the outgoing contracts.
+#[tokio::test]
+async fn test_resume_swap_refund() {
+    // ---- Setup ----
+
+    let makers_config_map = [
+        ((6102, None), MakerBehavior::Normal),
+        ((16102, None), MakerBehavior::Normal),
+    ];
+
+    // The Taker stops after the full setup, like a crash before settlement.
+    let (test_framework, taker, makers, directory_server_instance) = TestFramework::init(
+        None,
+        makers_config_map.into(),
+        Some(TakerBehavior::DropConnectionAfterFullSetup),
+        ConnectionType::CLEARNET,
+        None,
+    )
+    .await;
+
+    warn!("Running Test: Taker refunds a crashed swap.");
+
+    // Fund the Taker and Makers with 3 utxos of 0.05 btc each, and the Makers' fidelity bonds.
+    test_framework.fund_wallet(
+        taker.write().unwrap().get_wallet_mut(),
+        &[Amount::from_btc(0.05).unwrap(); 3],
+    );
+    makers.iter().for_each(|maker| {
+        test_framework.fund_wallet(
+            &mut maker.get_wallet().write().unwrap(),
+            &[Amount::from_btc(0.05).unwrap(); 4],
+        );
+    });
+
+    // Nothing to resume yet.
+    assert_eq!(
+        taker.read().unwrap().get_wallet().load_swap_checkpoint(),
+        None
+    );
+
+    // ---- Start Servers and attempt Swap ----
+
+    let maker_threads = makers
+        .iter()
+        .map(|maker| {
+            let maker_clone = maker.clone();
+            thread::spawn(move || {
+                start_maker_server(maker_clone).unwrap();
+            })
+        })
+        .collect::<Vec<_>>();
+
+    makers.iter().for_each(|maker| {
+        while !*maker.is_setup_complete.read().unwrap() {
+            log::info!("Waiting for maker setup completion");
+            thread::sleep(Duration::from_secs(10));
+        }
+    });
+
+    let swap_params = SwapParams {
+        send_amount: Amount::from_sat(500000),
+        maker_count: 2,
+        tx_count: 3,
+        required_confirms: 1,
+        fee_rate: Amount::from_sat(1000),
+        wait_for_confirms: 0,
+        refund_locktime: None,
+        refund_locktime_step: None,
+    };
+
+    info!("Initiating coinswap protocol");
+    let taker_clone = taker.clone();
+    thread::spawn(move || {
+        taker_clone
+            .write()
+            .unwrap()
+            .do_coinswap(swap_params)
+            .unwrap();
+    })
+    .join()
+    .unwrap();
+
+    maker_threads
+        .into_iter()
+        .for_each(|thread| thread.join().unwrap());
+
+    // ---- Restart the Taker ----
+
+    let wallet_path = taker.read().unwrap().get_wallet().get_file_path().clone();
+    drop(taker);
+    // The wallet is at `<data_dir>/wallets/<wallet_name>`.
+    let data_dir = wallet_path.parent().unwrap().parent().unwrap();
+    let wallet_name = wallet_path.file_name().unwrap().to_str().unwrap();
+    let mut taker = Taker::init(
+        Some(data_dir.to_path_buf()),
+        Some(wallet_name.to_string()),
+        Some(RPCConfig::from(test_framework.as_ref())),
+        TakerBehavior::Normal,
+        Some(ConnectionType::CLEARNET),
+        false,
+    )
+    .unwrap();
+
+    // ---- Refund the Swap ----
+
+    // The checkpoint covers the whole swap.
+    let checkpoint = taker
+        .get_wallet()
+        .load_swap_checkpoint()
+        .expect("checkpoint expected")
+        .clone();
+    assert_eq!(checkpoint.outgoing_swapcoins.len(), 3);
+    assert_eq!(checkpoint.incoming_swapcoins.len(), 3);
+    assert_eq!(checkpoint.contract_redeemscripts.len(), 6);
+    assert!(checkpoint.incoming_locktime.unwrap() < checkpoint.outgoing_locktime);
+    assert_eq!(taker.get_wallet().get_swapcoins_count(), 6);
+
+    warn!("Resuming the swap");
+    assert_eq!(
+        taker.resume_swap().await.unwrap(),
+        Some(RecoveredSwap::Refunded)
+    );
+
+    // Everything is refunded and the checkpoint is gone.
+    assert_eq!(taker.get_wallet().get_swapcoins_count(), 0);
+    assert_eq!(taker.get_wallet().load_swap_checkpoint(), None);
+    assert_eq!(taker.resume_swap().await.unwrap(), None);
+
+    // ---- Cleanup ----
+    directory_server_instance.shutdown().unwrap();
+    test_framework.stop();
+}
@@ -0,0 +1,153 @@
+#![cfg(feature = "integration-test")]
+use bitcoin::Amount;
+use coinswap::{
+    maker::{start_maker_server, MakerBehavior},
+    taker::{RecoveredSwap, SwapParams, Taker, TakerBehavior},
+    utill::ConnectionType,
+    wallet::RPCConfig,
+};
+
+mod test_framework;
+use log::{info, warn};
+use std::{thread, time::Duration};
+use test_framework::*;
+
+/// Resume after a Taker crash.
+/// The Taker stops right after the full swap setup, leaving the swap's checkpoint in its wallet.
+/// A new Taker, started from the same data directory before the Makers give up on it, settles the swap
+/// with them from the checkpointed stage.
+#[tokio::test]
+async fn test_resume_swap_settle() {
+    // ---- Setup ----
+
+    let makers_config_map = [
+        ((6102, None), MakerBehavior::Normal),
+        ((16102, None), MakerBehavior::Normal),
+    ];
+
+    // The Taker stops after the full setup, like a crash before settlement.
+    let (test_framework, taker, makers, directory_server_instance) = TestFramework::init(
+        None,
+        makers_config_map.into(),
+        Some(TakerBehavior::DropConnectionAfterFullSetup),
+        ConnectionType::CLEARNET,
+        None,
+    )
+    .await;
+
+    warn!("Running Test: Taker settles a crashed swap.");
+
+    // Fund the Taker and Makers with 3 utxos of 0.05 btc each, and the Makers' fidelity bonds.
+    test_framework.fund_wallet(
+        taker.write().unwrap().get_wallet_mut(),
+        &[Amount::from_btc(0.05).unwrap(); 3],
+    );
+    makers.iter().for_each(|maker| {
+        test_framework.fund_wallet(
+            &mut maker.get_wallet().write().unwrap(),
+            &[Amount::from_btc(0.05).unwrap(); 4],
+        );
+    });
+
+    // ---- Start Servers and attempt Swap ----
+
+    let maker_threads = makers
+        .iter()
+        .map(|maker| {
+            let maker_clone = maker.clone();
+            thread::spawn(move || {
+                start_maker_server(maker_clone).unwrap();
+            })
+        })
+        .collect::<Vec<_>>();
+
+    makers.iter().for_each(|maker| {
+        while !*maker.is_setup_complete.read().unwrap() {
+            log::info!("Waiting for maker setup completion");
+            thread::sleep(Duration::from_secs(10));
+        }
+    });
+
+    let swap_params = SwapParams {
+        send_amount: Amount::from_sat(500000),
+        maker_count: 2,
+        tx_count: 3,
+        required_confirms: 1,
+        fee_rate: Amount::from_sat(1000),
+        wait_for_confirms: 0,
+        refund_locktime: None,
+        refund_locktime_step: None,
+    };
+
+    info!("Initiating coinswap protocol");
+    let taker_clone = taker.clone();
+    thread::spawn(move || {
+        taker_clone
+            .write()
+            .unwrap()
+            .do_coinswap(swap_params)
+            .unwrap();
+    })
+    .join()
+    .unwrap();
+
+    // ---- Restart the Taker, while the Makers still wait for it ----
+
+    let wallet_path = taker.read().unwrap().get_wallet().get_file_path().clone();
+    drop(taker);
+    // The wallet is at `<data_dir>/wallets/<wallet_name>`.
+    let data_dir = wallet_path.parent().unwrap().parent().unwrap();
+    let wallet_name = wallet_path.file_name().unwrap().to_str().unwrap();
+    let mut taker = Taker::init(
+        Some(data_dir.to_path_buf()),
+        Some(wallet_name.to_string()),
+        Some(RPCConfig::from(test_framework.as_ref())),
+        TakerBehavior::Normal,
+        Some(ConnectionType::CLEARNET),
+        false,
+    )
+    .unwrap();
+
+    // ---- Settle the Swap ----
+
+    let checkpoint = taker
+        .get_wallet()
+        .load_swap_checkpoint()
+        .expect("checkpoint expected")
+        .clone();
+    assert_eq!(checkpoint.makers.len(), 2);
+    assert_eq!(checkpoint.watchonly_swapcoins.len(), 1);
+
+    // The checkpointed swap is listed as active after the restart.
+    let active_swaps = taker.list_active_swaps();
+    assert_eq!(active_swaps.len(), 1);
+    assert_eq!(active_swaps[0].id, checkpoint.hashvalue);
+    assert_eq!(active_swaps[0].counterparties, checkpoint.makers);
+    assert!(active_swaps[0].committed_amount > Amount::ZERO);
+
+    warn!("Resuming the swap");
+    assert_eq!(
+        taker.resume_swap().await.unwrap(),
+        Some(RecoveredSwap::Settled)
+    );
+
+    // The Taker got the privkeys of all its incoming swapcoins, and the checkpoint is gone.
+    assert!(checkpoint
+        .incoming_swapcoins
+        .iter()
+        .all(|redeemscript| taker
+            .get_wallet()
+            .find_incoming_swapcoin(redeemscript)
+            .is_some_and(|swapcoin| swapcoin.other_privkey.is_some())));
+    assert_eq!(taker.get_wallet().load_swap_checkpoint(), None);
+    assert!(taker.list_active_swaps().is_empty());
+    assert_eq!(taker.resume_swap().await.unwrap(), None);
+
+    // ---- Cleanup ----
+    makers.iter().for_each(|maker| maker.shutdown().unwrap());
+    maker_threads
+        .into_iter()
+        .for_each(|thread| thread.join().unwrap());
+    directory_server_instance.shutdown().unwrap();
+    test_framework.stop();
+}
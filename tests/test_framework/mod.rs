@@ -15,9 +15,15 @@ use bitcoin::secp256k1::rand::{distributions::Alphanumeric, thread_rng, Rng};
 use std::{
     collections::HashMap,
     fs,
+    io::{Read, Write},
+    net::{Shutdown, TcpListener, TcpStream},
     path::PathBuf,
-    sync::{Arc, RwLock},
-    thread,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc::{self, RecvTimeoutError},
+        Arc, Mutex, RwLock,
+    },
+    thread::{self, JoinHandle},
     time::Duration,
 };
 
@@ -32,7 +38,7 @@ use coinswap::{
     market::directory::{start_directory_server, DirectoryServer},
     taker::{Taker, TakerBehavior},
     utill::{setup_logger, ConnectionType},
-    wallet::RPCConfig,
+    wallet::{RPCConfig, Wallet},
 };
 
 fn get_random_tmp_dir() -> PathBuf {
@@ -45,6 +51,96 @@ fn get_random_tmp_dir() -> PathBuf {
     PathBuf::from(path)
 }
 
+/// Faults injected on the connections between the Taker and the Makers.
+///
+/// Each Maker is put behind a proxy applying them, and announces the proxy to the directory.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NetworkFault {
+    /// Delay of every chunk of relayed bytes, in both directions. The bytes are relayed in reads of up
+    /// to 4 KiB, so a message larger than that is delayed once per chunk.
+    pub latency: Duration,
+    /// Random extra delay of every chunk, up to this much.
+    pub jitter: Duration,
+    /// Cut the connection after relaying this many bytes from the Taker to a Maker, usually in the
+    /// middle of a message. This happens once for each Maker, the next connections are left alone.
+    pub drop_after_bytes: Option<usize>,
+}
+
+/// Relay the bytes from `from` to `to` with the fault delays, until either side closes.
+///
+/// With a `drop_budget`, the connection is cut in both directions once the budget is spent, and the
+/// budget is cleared.
+fn relay(
+    mut from: TcpStream,
+    mut to: TcpStream,
+    fault: NetworkFault,
+    drop_budget: Option<Arc<Mutex<Option<usize>>>>,
+    dropped_connections: Arc<AtomicUsize>,
+) {
+    let mut buf = [0u8; 4096];
+    loop {
+        let n = match from.read(&mut buf) {
+            Ok(0) | Err(_) => break,
+            Ok(n) => n,
+        };
+        let jitter = fault.jitter.as_millis() as u64;
+        thread::sleep(fault.latency + Duration::from_millis(thread_rng().gen_range(0..=jitter)));
+
+        if let Some(budget) = &drop_budget {
+            let mut budget = budget.lock().unwrap();
+            if let Some(remaining) = *budget {
+                if remaining <= n {
+                    let _ = to.write_all(&buf[..remaining]);
+                    *budget = None;
+                    dropped_connections.fetch_add(1, Ordering::SeqCst);
+                    log::warn!(
+                        "Network fault: dropping connection after {} bytes",
+                        remaining
+                    );
+                    let _ = from.shutdown(Shutdown::Both);
+                    let _ = to.shutdown(Shutdown::Both);
+                    return;
+                }
+                *budget = Some(remaining - n);
+            }
+        }
+        if to.write_all(&buf[..n]).is_err() {
+            break;
+        }
+    }
+    let _ = to.shutdown(Shutdown::Write);
+}
+
+/// Spawn a proxy to the Maker listening on `maker_port`, applying `fault`. Returns the proxy port.
+fn spawn_fault_proxy(
+    maker_port: u16,
+    fault: NetworkFault,
+    dropped_connections: Arc<AtomicUsize>,
+) -> u16 {
+    let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+    let proxy_port = listener.local_addr().unwrap().port();
+    let drop_budget = Arc::new(Mutex::new(fault.drop_after_bytes));
+    thread::spawn(move || {
+        for taker_stream in listener.incoming() {
+            let Ok(taker_stream) = taker_stream else {
+                continue;
+            };
+            let Ok(maker_stream) = TcpStream::connect(("127.0.0.1", maker_port)) else {
+                continue;
+            };
+            let (taker_read, maker_read) = (
+                taker_stream.try_clone().unwrap(),
+                maker_stream.try_clone().unwrap(),
+            );
+            let (budget, dropped) = (drop_budget.clone(), dropped_connections.clone());
+            thread::spawn(move || relay(taker_read, maker_stream, fault, Some(budget), dropped));
+            let dropped = dropped_connections.clone();
+            thread::spawn(move || relay(maker_read, taker_stream, fault, None, dropped));
+        }
+    });
+    proxy_port
+}
+
 /// The Test Framework.
 ///
 /// Handles initializing, operating and cleaning up of all backend processes. Bitcoind, Taker and Makers.
@@ -52,7 +148,9 @@ fn get_random_tmp_dir() -> PathBuf {
 pub struct TestFramework {
     bitcoind: BitcoinD,
     temp_dir: PathBuf,
-    shutdown: Arc<RwLock<bool>>,
+    /// Stops the block generation thread once dropped, and its handle to wait for it.
+    block_generator: Mutex<Option<(mpsc::Sender<()>, JoinHandle<()>)>>,
+    dropped_connections: Arc<AtomicUsize>,
 }
 
 impl TestFramework {
@@ -61,6 +159,8 @@ impl TestFramework {
     /// - bitcoind conf.
     /// - a map of [port, [MakerBehavior]]
     /// - optional taker behavior.
+    /// - optional [NetworkFault] injected on the Taker to Maker connections. Only clearnet connections
+    ///   can be faulted.
     ///
     /// Returns ([TestFramework], [Taker], [`Vec<Maker>`]).
     /// Maker's config will follow the pattern given the input HashMap.
@@ -70,6 +170,7 @@ impl TestFramework {
         makers_config_map: HashMap<(u16, Option<u16>), MakerBehavior>,
         taker_behavior: Option<TakerBehavior>,
         connection_type: ConnectionType,
+        network_fault: Option<NetworkFault>,
     ) -> (
         Arc<Self>,
         Arc<RwLock<Taker>>,
@@ -116,11 +217,11 @@ impl TestFramework {
             .generate_to_address(101, &mining_address)
             .unwrap();
         log::info!("bitcoind initiated!!");
-        let shutdown = Arc::new(RwLock::new(false));
         let test_framework = Arc::new(Self {
             bitcoind,
             temp_dir: temp_dir.clone(),
-            shutdown,
+            block_generator: Mutex::new(None),
+            dropped_connections: Arc::new(AtomicUsize::new(0)),
         });
 
         log::info!("Initiating Directory Server .....");
@@ -145,6 +246,7 @@ impl TestFramework {
                 Some(taker_rpc_config),
                 taker_behavior.unwrap_or_default(),
                 Some(connection_type),
+                false,
             )
             .unwrap(),
         ));
@@ -157,33 +259,53 @@ impl TestFramework {
                 let maker_id = "maker".to_string() + &port.0.to_string(); // ex: "maker6102"
                 let maker_rpc_config = rpc_config.clone();
                 thread::sleep(Duration::from_secs(5)); // Sleep for some time avoid resource unavailable error.
-                Arc::new(
-                    Maker::init(
-                        Some(temp_dir.clone()),
-                        Some(maker_id),
-                        Some(maker_rpc_config),
-                        Some(port.0),
-                        Some(base_rpc_port),
-                        port.1,
-                        Some(connection_type),
-                        *behavior,
-                    )
-                    .unwrap(),
+                let maker = Maker::init(
+                    Some(temp_dir.clone()),
+                    Some(maker_id),
+                    Some(maker_rpc_config),
+                    Some(port.0),
+                    Some(base_rpc_port),
+                    port.1,
+                    Some(connection_type),
+                    *behavior,
+                    false,
                 )
+                .unwrap();
+                if let Some(fault) = network_fault {
+                    let proxy_port = spawn_fault_proxy(
+                        port.0,
+                        fault,
+                        test_framework.dropped_connections.clone(),
+                    );
+                    maker.set_advertised_port(proxy_port).unwrap();
+                }
+                Arc::new(maker)
             })
             .collect::<Vec<_>>();
 
-        // start the block generation thread
+        // start the block generation thread, with its own rpc client so it doesn't keep the framework alive.
         log::info!("spawning block generation thread");
-        let tf_clone = test_framework.clone();
-        thread::spawn(move || loop {
-            thread::sleep(Duration::from_secs(3));
-            tf_clone.generate_blocks(10);
-            if *tf_clone.shutdown.read().unwrap() {
-                log::info!("ending block generation thread");
-                return;
+        let client = Client::new(
+            &test_framework.bitcoind.rpc_url_with_wallet("default"),
+            Auth::CookieFile(test_framework.bitcoind.params.cookie_file.clone()),
+        )
+        .unwrap();
+        let (shutdown_sender, shutdown_receiver) = mpsc::channel::<()>();
+        let handle = thread::spawn(move || loop {
+            // Checked right before mining, the node isn't called once the framework is stopping.
+            match shutdown_receiver.recv_timeout(Duration::from_secs(3)) {
+                Err(RecvTimeoutError::Timeout) => {
+                    if let Err(e) = try_generate_blocks(&client, 10) {
+                        log::warn!("block generation failed: {:?}", e);
+                    }
+                }
+                _ => {
+                    log::info!("ending block generation thread");
+                    return;
+                }
             }
         });
+        *test_framework.block_generator.lock().unwrap() = Some((shutdown_sender, handle));
 
         (test_framework, taker, makers, directory_server_instance)
     }
@@ -195,17 +317,7 @@ impl TestFramework {
 
     /// Generate Blocks in regtest node.
     pub fn generate_blocks(&self, n: u64) {
-        let mining_address = self
-            .bitcoind
-            .client
-            .get_new_address(None, None)
-            .unwrap()
-            .require_network(bitcoind::bitcoincore_rpc::bitcoin::Network::Regtest)
-            .unwrap();
-        self.bitcoind
-            .client
-            .generate_to_address(n, &mining_address)
-            .unwrap();
+        try_generate_blocks(&self.bitcoind.client, n).unwrap();
     }
 
     /// Send coins to a bitcoin address.
@@ -216,11 +328,24 @@ impl TestFramework {
             .unwrap();
     }
 
+    /// Fund `wallet` with one utxo of each amount, confirm them in a block, and sync the wallet.
+    pub fn fund_wallet(&self, wallet: &mut Wallet, amounts: &[Amount]) {
+        for amount in amounts {
+            let address = wallet.get_next_external_address().unwrap();
+            self.send_to_address(&address, *amount);
+        }
+        self.generate_blocks(1);
+        wallet.sync().unwrap();
+    }
+
     /// Stop bitcoind and clean up all test data.
     pub fn stop(&self) {
         log::info!("Stopping Test Framework");
-        // stop all framework threads.
-        *self.shutdown.write().unwrap() = true;
+        // stop all framework threads, and wait for them before the node goes away.
+        if let Some((shutdown_sender, handle)) = self.block_generator.lock().unwrap().take() {
+            drop(shutdown_sender);
+            handle.join().unwrap();
+        }
         // stop bitcoind
         let _ = self.bitcoind.client.stop().unwrap();
     }
@@ -228,6 +353,21 @@ impl TestFramework {
     pub fn get_block_count(&self) -> u64 {
         self.bitcoind.client.get_block_count().unwrap()
     }
+
+    /// The number of connections cut by the [NetworkFault].
+    pub fn dropped_connections(&self) -> usize {
+        self.dropped_connections.load(Ordering::SeqCst)
+    }
+}
+
+/// Generate `n` blocks in the regtest node behind `client`.
+fn try_generate_blocks(client: &Client, n: u64) -> Result<(), bitcoind::bitcoincore_rpc::Error> {
+    let mining_address = client
+        .get_new_address(None, None)?
+        .require_network(bitcoind::bitcoincore_rpc::bitcoin::Network::Regtest)
+        .expect("regtest address");
+    client.generate_to_address(n, &mining_address)?;
+    Ok(())
 }
 
 /// Initializes a [TestFramework] given a [RPCConfig].
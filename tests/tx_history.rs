@@ -0,0 +1,93 @@
+#![cfg(feature = "integration-test")]
+use std::collections::HashMap;
+
+use bitcoin::{absolute::LockTime, Amount, SignedAmount};
+use bitcoind::bitcoincore_rpc::RpcApi;
+use coinswap::{
+    utill::ConnectionType,
+    wallet::{Destination, SendAmount, TxCategory},
+};
+
+mod test_framework;
+use test_framework::*;
+
+/// Test listing the wallet's receives, sends and fidelity bond creation, confirmed and unconfirmed.
+#[tokio::test]
+async fn test_list_transactions() {
+    // ---- Setup ----
+    let (test_framework, taker, _, directory_server_instance) =
+        TestFramework::init(None, HashMap::new(), None, ConnectionType::CLEARNET, None).await;
+
+    let mut taker_write = taker.write().unwrap();
+    let wallet = taker_write.get_wallet_mut();
+
+    assert!(wallet.list_transactions(10, 0).unwrap().is_empty());
+
+    test_framework.fund_wallet(wallet, &[Amount::from_btc(0.05).unwrap()]);
+
+    // ---- A confirmed receive ----
+    let history = wallet.list_transactions(10, 0).unwrap();
+    assert_eq!(history.len(), 1);
+    assert_eq!(history[0].category, TxCategory::Receive);
+    assert_eq!(history[0].amount, SignedAmount::from_sat(5_000_000));
+    assert!(history[0].confirmations >= 1);
+
+    // ---- An unconfirmed send ----
+    let destination = test_framework
+        .get_client()
+        .get_new_address(None, None)
+        .unwrap()
+        .assume_checked();
+    let coins = wallet.spendable_utxos().unwrap();
+    let send = wallet
+        .spend_from_wallet(
+            Amount::from_sat(1000),
+            SendAmount::Amount(Amount::from_sat(1_000_000)),
+            Destination::Address(destination),
+            &coins,
+        )
+        .unwrap();
+    let send_txid = test_framework
+        .get_client()
+        .send_raw_transaction(&send)
+        .unwrap();
+
+    let history = wallet.list_transactions(10, 0).unwrap();
+    assert_eq!(history.len(), 2);
+    let sent = history.last().unwrap();
+    assert_eq!(sent.txid, send_txid);
+    assert_eq!(sent.category, TxCategory::Send);
+    assert_eq!(sent.amount, SignedAmount::from_sat(-1_001_000));
+    // Blocks are mined in the background, only check it if it's still unconfirmed after the listing.
+    if test_framework
+        .get_client()
+        .get_mempool_entry(&send_txid)
+        .is_ok()
+    {
+        assert_eq!(sent.confirmations, 0);
+    }
+
+    // ---- A fidelity bond ----
+    test_framework.generate_blocks(1);
+    let locktime = LockTime::from_height(test_framework.get_block_count() as u32 + 1000).unwrap();
+    wallet
+        .create_fidelity(Amount::from_sat(1_000_000), locktime)
+        .unwrap();
+    test_framework.generate_blocks(1);
+    wallet.sync().unwrap();
+
+    let history = wallet.list_transactions(10, 0).unwrap();
+    assert_eq!(history.len(), 3);
+    assert_eq!(history[1].txid, send_txid);
+    assert!(history[1].confirmations >= 1);
+    assert_eq!(history[2].category, TxCategory::Fidelity);
+
+    // ---- Paging, from the most recent ----
+    let page = wallet.list_transactions(1, 1).unwrap();
+    assert_eq!(page, vec![history[1].clone()]);
+
+    // ---- Cleanup ----
+    drop(taker_write);
+    directory_server_instance.shutdown().unwrap();
+    test_framework.stop();
+}
@@ -0,0 +1,138 @@
+#![cfg(feature = "integration-test")]
+use std::{collections::HashMap, convert::TryFrom};
+
+use bitcoin::Amount;
+use bitcoind::bitcoincore_rpc::{Client, RpcApi};
+use coinswap::{
+    utill::ConnectionType,
+    wallet::{Destination, KeychainKind, RPCConfig, SendAmount, Wallet, WalletError},
+};
+
+mod test_framework;
+use test_framework::*;
+
+/// Test that the wallet descriptors are valid and imported in the core wallet before signing.
+///
+/// A fresh node wallet doesn't have any of our descriptors yet. The pre-sign check should create the
+/// core wallet, import all the descriptors and let the spend go through.
+#[tokio::test]
+async fn test_descriptors_imported_before_signing() {
+    // ---- Setup ----
+    let (test_framework, taker, _, directory_server_instance) =
+        TestFramework::init(None, HashMap::new(), None, ConnectionType::CLEARNET, None).await;
+
+    // ---- Fresh wallet, with no core wallet in the node ----
+    let wallet_name = "fresh-wallet".to_string();
+    let wallet_path = taker
+        .read()
+        .unwrap()
+        .get_wallet()
+        .get_file_path()
+        .parent()
+        .unwrap()
+        .join(&wallet_name);
+    let rpc_config = RPCConfig {
+        wallet_name: wallet_name.clone(),
+        ..RPCConfig::from(test_framework.as_ref())
+    };
+    let mut wallet = Wallet::init(
+        &wallet_path,
+        &rpc_config,
+        "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about"
+            .to_string(),
+        "".to_string(),
+        None,
+    )
+    .unwrap();
+
+    assert!(!test_framework
+        .get_client()
+        .list_wallets()
+        .unwrap()
+        .contains(&wallet_name));
+
+    wallet.ensure_descriptors_imported().unwrap();
+
+    // ---- Keychain descriptors are valid and match the wallet's addresses ----
+    let core_wallet = Client::try_from(&rpc_config).unwrap();
+    for kind in [KeychainKind::External, KeychainKind::Internal] {
+        let descriptor = wallet.get_descriptor(kind).unwrap();
+        let (descriptor_without_checksum, checksum) = descriptor.split_once('#').unwrap();
+        let info = core_wallet.get_descriptor_info(&descriptor).unwrap();
+        assert_eq!(info.checksum.as_deref(), Some(checksum));
+        assert!(descriptor_without_checksum.starts_with("wpkh("));
+    }
+    // Fidelity bonds have no ranged descriptor.
+    assert!(matches!(
+        wallet.get_descriptor(KeychainKind::Fidelity),
+        Err(WalletError::NoDescriptor(KeychainKind::Fidelity))
+    ));
+    let external_descriptor = wallet.get_descriptor(KeychainKind::External).unwrap();
+    let first_address = core_wallet
+        .derive_addresses(&external_descriptor, Some([0, 0]))
+        .unwrap()[0]
+        .clone()
+        .assume_checked();
+
+    // The core wallet now exists, and watches our addresses.
+    assert!(test_framework
+        .get_client()
+        .list_wallets()
+        .unwrap()
+        .contains(&wallet_name));
+    let address = wallet.get_next_external_address().unwrap();
+    assert_eq!(address, first_address);
+    assert_eq!(
+        core_wallet.get_address_info(&address).unwrap().is_mine,
+        Some(true)
+    );
+
+    // ---- Spend after the core wallet got unloaded ----
+    let taker_address = taker
+        .write()
+        .unwrap()
+        .get_wallet_mut()
+        .get_next_external_address()
+        .unwrap();
+    test_framework.send_to_address(&taker_address, Amount::from_btc(0.05).unwrap());
+    test_framework.generate_blocks(1);
+
+    let mut taker_write = taker.write().unwrap();
+    let taker_wallet = taker_write.get_wallet_mut();
+    taker_wallet.sync().unwrap();
+    let coins = taker_wallet.list_descriptor_utxo_spend_info(None).unwrap();
+    assert_eq!(coins.len(), 1);
+
+    let taker_wallet_name = taker_wallet
+        .get_file_path()
+        .file_name()
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .to_string();
+    let taker_core_wallet = Client::try_from(&RPCConfig {
+        wallet_name: taker_wallet_name.clone(),
+        ..RPCConfig::from(test_framework.as_ref())
+    })
+    .unwrap();
+    taker_core_wallet.unload_wallet(None).unwrap();
+
+    let tx = taker_wallet
+        .spend_from_wallet(
+            Amount::from_sat(1000),
+            SendAmount::Max,
+            Destination::Wallet,
+            &coins,
+        )
+        .unwrap();
+    assert_eq!(tx.input.len(), 1);
+    assert!(test_framework
+        .get_client()
+        .list_wallets()
+        .unwrap()
+        .contains(&taker_wallet_name));
+
+    // ---- Cleanup ----
+    directory_server_instance.shutdown().unwrap();
+    test_framework.stop();
+}
@@ -0,0 +1,130 @@
+#![cfg(feature = "integration-test")]
+use std::collections::HashMap;
+
+use bitcoin::{
+    absolute::LockTime, hashes::Hash, transaction::Version, Address, Amount, Network, Transaction,
+};
+use coinswap::{
+    protocol::contract::{create_contract_redeemscript, create_multisig_redeemscript},
+    utill::{generate_keypair, redeemscript_to_scriptpubkey, ConnectionType},
+    wallet::{
+        IncomingSwapCoin, SignerKey, SwapCoin, UTXOSpendInfo, WalletError, WatchOnlySwapCoin,
+    },
+};
+
+mod test_framework;
+use test_framework::*;
+
+/// Test importing a swapcoin without its keys: its coins are listed as watch-only, are never
+/// spendable, and swapcoins the wallet holds a key of are refused.
+#[tokio::test]
+async fn test_import_watchonly_swapcoin() {
+    // ---- Setup ----
+    let (test_framework, taker, _, directory_server_instance) =
+        TestFramework::init(None, HashMap::new(), None, ConnectionType::CLEARNET, None).await;
+
+    let mut taker_write = taker.write().unwrap();
+    let wallet = taker_write.get_wallet_mut();
+
+    test_framework.fund_wallet(wallet, &[Amount::from_btc(0.01).unwrap()]);
+
+    let contract_tx = Transaction {
+        version: Version::TWO,
+        lock_time: LockTime::ZERO,
+        input: Vec::new(),
+        output: Vec::new(),
+    };
+    let (sender_pubkey, _) = generate_keypair();
+    let (receiver_pubkey, _) = generate_keypair();
+    let (hashlock_pubkey, hashlock_privkey) = generate_keypair();
+    let (timelock_pubkey, _) = generate_keypair();
+    let contract_redeemscript =
+        create_contract_redeemscript(&hashlock_pubkey, &timelock_pubkey, &Hash::all_zeros(), &20);
+    let multisig_redeemscript = create_multisig_redeemscript(&sender_pubkey, &receiver_pubkey);
+    let swapcoin = WatchOnlySwapCoin::new(
+        &multisig_redeemscript,
+        receiver_pubkey,
+        contract_tx.clone(),
+        contract_redeemscript.clone(),
+        Amount::from_btc(0.02).unwrap(),
+    )
+    .unwrap();
+
+    // ---- Import and fund the watch-only swapcoin ----
+    wallet.import_watchonly_swapcoin(swapcoin.clone()).unwrap();
+    assert_eq!(
+        wallet.find_watchonly_swapcoin(&multisig_redeemscript),
+        Some(&swapcoin)
+    );
+
+    let multisig_address = Address::from_script(
+        &redeemscript_to_scriptpubkey(&multisig_redeemscript),
+        Network::Regtest,
+    )
+    .unwrap();
+    let balance_before = wallet.balance_breakdown().unwrap();
+    test_framework.send_to_address(&multisig_address, Amount::from_btc(0.02).unwrap());
+    test_framework.generate_blocks(1);
+    wallet.sync().unwrap();
+
+    let (utxo, spend_info) = wallet
+        .list_all_utxo_spend_info(None)
+        .unwrap()
+        .into_iter()
+        .find(|(utxo, _)| utxo.script_pub_key == multisig_address.script_pubkey())
+        .unwrap();
+    assert_eq!(utxo.amount, Amount::from_btc(0.02).unwrap());
+    assert!(matches!(
+        spend_info,
+        UTXOSpendInfo::WatchOnlySwapCoin { multisig_redeemscript: rs } if rs == multisig_redeemscript
+    ));
+
+    // Never spendable, nor counted in the balance.
+    assert!(wallet
+        .spendable_utxos()
+        .unwrap()
+        .iter()
+        .all(|(other, _)| other.txid != utxo.txid));
+    assert_eq!(wallet.balance_breakdown().unwrap(), balance_before);
+
+    // ---- Swapcoins with a wallet key are refused ----
+    let (_, my_privkey) = generate_keypair();
+    let (other_pubkey, _) = generate_keypair();
+    let incoming = IncomingSwapCoin::new(
+        SignerKey::Imported {
+            privkey: my_privkey,
+        },
+        other_pubkey,
+        contract_tx.clone(),
+        contract_redeemscript.clone(),
+        SignerKey::Imported {
+            privkey: hashlock_privkey,
+        },
+        Amount::from_btc(0.02).unwrap(),
+        wallet.signer(),
+    )
+    .unwrap();
+    wallet.add_incoming_swapcoin(&incoming);
+    let incoming_multisig_redeemscript = incoming.get_multisig_redeemscript();
+    let owned = WatchOnlySwapCoin::new(
+        &incoming_multisig_redeemscript,
+        other_pubkey,
+        contract_tx,
+        contract_redeemscript,
+        Amount::from_btc(0.02).unwrap(),
+    )
+    .unwrap();
+    assert!(matches!(
+        wallet.import_watchonly_swapcoin(owned),
+        Err(WalletError::Protocol(_))
+    ));
+    assert_eq!(
+        wallet.find_watchonly_swapcoin(&incoming_multisig_redeemscript),
+        None
+    );
+
+    // ---- Cleanup ----
+    drop(taker_write);
+    directory_server_instance.shutdown().unwrap();
+    test_framework.stop();
+}